@@ -1,10 +1,15 @@
 use rusqlite::params;
 use rusqlite::Connection;
-use tauri::State;
+use tauri::{AppHandle, Emitter, Manager, State};
 use uuid::Uuid;
 
-use crate::db::DbState;
+use crate::db::{write_transaction, DbState};
+use crate::export::{self, Cell, Sheet};
 use crate::models::*;
+use crate::store::{
+    product_from_row, quantity_unit_from_row, translations_from_row, translations_json, Store,
+    PRODUCT_COLUMNS,
+};
 
 // ── Inner functions (testable without Tauri runtime) ────────────────────────
 
@@ -12,53 +17,183 @@ pub(crate) fn list_categories_inner(db: &DbState) -> Result<Vec<Category>, Strin
     let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
 
     let mut stmt = conn
-        .prepare("SELECT id, label, color FROM categories ORDER BY label")
+        .prepare(&format!(
+            "SELECT {CATEGORY_COLUMNS} FROM categories ORDER BY label"
+        ))
         .map_err(|e| format!("Query error: {e}"))?;
 
-    let categories = stmt
-        .query_map([], |row| {
-            Ok(Category {
-                id: row.get(0)?,
-                label: row.get(1)?,
-                color: row.get(2)?,
-            })
-        })
+    let mut categories = stmt
+        .query_map([], category_from_row)
         .map_err(|e| format!("Query error: {e}"))?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| format!("Row mapping error: {e}"))?;
 
+    for category in &mut categories {
+        category.path = category_path(&conn, &category.id)?;
+    }
+    // Sorting by path lists every category right after its parent.
+    categories.sort_by(|a, b| a.path.cmp(&b.path));
+
     Ok(categories)
 }
 
+/// Columns read by `category_from_row`, in order.
+const CATEGORY_COLUMNS: &str = "id, label, color, translations, icon, parent_id";
+
+/// Maps a category row; `path` is left for `category_path` to fill in.
+fn category_from_row(row: &rusqlite::Row) -> rusqlite::Result<Category> {
+    Ok(Category {
+        id: row.get(0)?,
+        label: row.get(1)?,
+        color: row.get(2)?,
+        translations: translations_from_row(row, 3)?,
+        icon: row.get(4)?,
+        parent_id: row.get(5)?,
+        path: String::new(),
+    })
+}
+
+/// Loads a category with its path.
+fn load_category(conn: &Connection, category_id: &str) -> Result<Category, String> {
+    let mut category = conn
+        .query_row(
+            &format!("SELECT {CATEGORY_COLUMNS} FROM categories WHERE id = ?1"),
+            params![category_id],
+            category_from_row,
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
+    category.path = category_path(conn, category_id)?;
+    Ok(category)
+}
+
+/// Deepest nesting followed when walking up the category tree; a guard
+/// against cycles in hand-edited databases.
+const MAX_CATEGORY_DEPTH: i64 = 32;
+
+/// Returns the (id, label) of `category_id` and its ancestors, top-level
+/// category first. Empty when the category does not exist.
+fn category_ancestors(
+    conn: &Connection,
+    category_id: &str,
+) -> Result<Vec<(String, String)>, String> {
+    let mut stmt = conn
+        .prepare(
+            "WITH RECURSIVE ancestors(id, label, parent_id, depth) AS (
+                 SELECT id, label, parent_id, 0 FROM categories WHERE id = ?1
+                 UNION ALL
+                 SELECT c.id, c.label, c.parent_id, a.depth + 1
+                 FROM categories c
+                 JOIN ancestors a ON c.id = a.parent_id
+                 WHERE a.depth < ?2
+             )
+             SELECT id, label FROM ancestors ORDER BY depth DESC",
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let ancestors = stmt
+        .query_map(params![category_id, MAX_CATEGORY_DEPTH], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    Ok(ancestors)
+}
+
+fn category_path(conn: &Connection, category_id: &str) -> Result<String, String> {
+    let labels: Vec<String> = category_ancestors(conn, category_id)?
+        .into_iter()
+        .map(|(_, label)| label)
+        .collect();
+    Ok(labels.join(" > "))
+}
+
+/// Checks that `parent_id` exists and that making it the parent of
+/// `category_id` does not create a cycle.
+fn validate_category_parent(
+    conn: &Connection,
+    category_id: &str,
+    parent_id: &str,
+) -> Result<(), String> {
+    let ancestors = category_ancestors(conn, parent_id)?;
+    if ancestors.is_empty() {
+        return Err(format!("Parent category not found: {parent_id}"));
+    }
+    if ancestors.iter().any(|(id, _)| id == category_id) {
+        return Err(format!(
+            "Category '{category_id}' cannot be nested under itself or one of its subcategories"
+        ));
+    }
+    Ok(())
+}
+
 pub(crate) fn create_category_inner(
     db: &DbState,
     payload: CreateCategoryPayload,
+    admin_pin: Option<&str>,
 ) -> Result<Category, String> {
+    verify_catalog_admin(db, admin_pin)?;
+    let translations = normalize_translations(payload.translations)?;
+    let icon = payload.icon.as_deref().map(normalize_icon).transpose()?;
+    let parent_id = payload.parent_id.filter(|p| !p.is_empty());
     let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
 
+    check_category_color(&conn, &payload.color, payload.allow_custom_color)?;
+    if let Some(parent_id) = &parent_id {
+        validate_category_parent(&conn, &payload.id, parent_id)?;
+    }
+
     conn.execute(
-        "INSERT INTO categories (id, label, color) VALUES (?1, ?2, ?3)",
-        params![payload.id, payload.label, payload.color],
+        "INSERT INTO categories (id, label, color, translations, icon, parent_id)
+         VALUES (?1, ?2, ?3, ?4, NULLIF(?5, ''), ?6)",
+        params![
+            payload.id,
+            payload.label,
+            payload.color,
+            translations_json(&translations),
+            icon,
+            parent_id
+        ],
     )
     .map_err(|e| format!("Insert error: {e}"))?;
 
-    Ok(Category {
-        id: payload.id,
-        label: payload.label,
-        color: payload.color,
-    })
+    load_category(&conn, &payload.id)
 }
 
 pub(crate) fn update_category_inner(
     db: &DbState,
     payload: UpdateCategoryPayload,
+    admin_pin: Option<&str>,
 ) -> Result<Category, String> {
+    verify_catalog_admin(db, admin_pin)?;
+    let translations = payload
+        .translations
+        .map(normalize_translations)
+        .transpose()?;
+    let icon = payload.icon.as_deref().map(normalize_icon).transpose()?;
     let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
 
+    check_category_color(&conn, &payload.color, payload.allow_custom_color)?;
+    if let Some(parent_id) = payload.parent_id.as_deref().filter(|p| !p.is_empty()) {
+        validate_category_parent(&conn, &payload.id, parent_id)?;
+    }
+
     let rows_affected = conn
         .execute(
-            "UPDATE categories SET label = ?1, color = ?2 WHERE id = ?3",
-            params![payload.label, payload.color, payload.id],
+            "UPDATE categories
+             SET label = ?1, color = ?2, translations = COALESCE(?3, translations),
+                 icon = CASE WHEN ?4 IS NULL THEN icon ELSE NULLIF(?4, '') END,
+                 parent_id = CASE WHEN ?5 IS NULL THEN parent_id ELSE NULLIF(?5, '') END
+             WHERE id = ?6",
+            params![
+                payload.label,
+                payload.color,
+                translations.as_ref().map(translations_json),
+                icon,
+                payload.parent_id,
+                payload.id
+            ],
         )
         .map_err(|e| format!("Update error: {e}"))?;
 
@@ -66,118 +201,457 @@ pub(crate) fn update_category_inner(
         return Err(format!("Category not found: {}", payload.id));
     }
 
-    Ok(Category {
-        id: payload.id,
-        label: payload.label,
-        color: payload.color,
-    })
+    load_category(&conn, &payload.id)
 }
 
-pub(crate) fn list_products_inner(db: &DbState) -> Result<Vec<Product>, String> {
-    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+/// Checks that a category color is in the palette, unless the palette is
+/// empty or a custom color is explicitly allowed.
+fn check_category_color(conn: &Connection, color: &str, allow_custom: bool) -> Result<(), String> {
+    if allow_custom {
+        return Ok(());
+    }
+    let palette = load_palette(conn)?;
+    if palette.is_empty() || palette.iter().any(|p| p.color.eq_ignore_ascii_case(color)) {
+        Ok(())
+    } else {
+        Err(format!("Color {color} is not in the palette"))
+    }
+}
 
+fn load_palette(conn: &Connection) -> Result<Vec<PaletteColor>, String> {
     let mut stmt = conn
-        .prepare("SELECT id, name, price, category_id, available FROM products ORDER BY category_id, name")
+        .prepare("SELECT color, name FROM palette_colors ORDER BY position")
         .map_err(|e| format!("Query error: {e}"))?;
-
-    let products = stmt
+    let palette = stmt
         .query_map([], |row| {
-            Ok(Product {
-                id: row.get(0)?,
+            Ok(PaletteColor {
+                color: row.get(0)?,
                 name: row.get(1)?,
-                price: row.get(2)?,
-                category_id: row.get(3)?,
-                available: row.get::<_, i64>(4)? != 0,
             })
         })
         .map_err(|e| format!("Query error: {e}"))?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| format!("Row mapping error: {e}"))?;
+    Ok(palette)
+}
 
-    Ok(products)
+/// The approved category colors, in display order.
+pub(crate) fn list_palette_inner(db: &DbState) -> Result<Vec<PaletteColor>, String> {
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    load_palette(&conn)
 }
 
-pub(crate) fn create_product_inner(
+/// Trims the names and lowercases the colors of a palette, checking that
+/// each color is a distinct `#rrggbb`.
+fn normalize_palette(colors: Vec<PaletteColor>) -> Result<Vec<PaletteColor>, String> {
+    let mut palette: Vec<PaletteColor> = Vec::with_capacity(colors.len());
+    for entry in colors {
+        let color = entry.color.trim().to_ascii_lowercase();
+        let is_hex = color.len() == 7
+            && color.starts_with('#')
+            && color[1..].bytes().all(|b| b.is_ascii_hexdigit());
+        if !is_hex {
+            return Err(format!("Invalid color '{}', expected #rrggbb", entry.color));
+        }
+        if palette.iter().any(|p| p.color == color) {
+            return Err(format!("Color {color} is listed twice"));
+        }
+        palette.push(PaletteColor {
+            color,
+            name: entry.name.trim().to_string(),
+        });
+    }
+    Ok(palette)
+}
+
+/// Replaces the palette. Categories already using a removed color keep it;
+/// an empty palette lifts the restriction.
+pub(crate) fn update_palette_inner(
     db: &DbState,
-    payload: CreateProductPayload,
-) -> Result<Product, String> {
-    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    colors: Vec<PaletteColor>,
+) -> Result<Vec<PaletteColor>, String> {
+    let palette = normalize_palette(colors)?;
 
-    let id = Uuid::new_v4().to_string();
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Transaction begin error: {e}"))?;
+    tx.execute("DELETE FROM palette_colors", [])
+        .map_err(|e| format!("Delete error: {e}"))?;
+    for (position, entry) in palette.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO palette_colors (color, name, position) VALUES (?1, ?2, ?3)",
+            params![entry.color, entry.name, position as i64],
+        )
+        .map_err(|e| format!("Insert error: {e}"))?;
+    }
+    tx.commit()
+        .map_err(|e| format!("Transaction commit error: {e}"))?;
+    Ok(palette)
+}
 
-    conn.execute(
-        "INSERT INTO products (id, name, price, category_id, available) VALUES (?1, ?2, ?3, ?4, 1)",
-        params![id, payload.name, payload.price, payload.category_id],
-    )
-    .map_err(|e| format!("Insert error: {e}"))?;
+/// Trims a category icon and checks that it is either a short emoji or an
+/// icon name made of lowercase letters, digits and dashes. An empty icon is
+/// returned as is and means "no icon".
+fn normalize_icon(icon: &str) -> Result<String, String> {
+    let icon = icon.trim();
+    let is_name = icon.len() <= 32
+        && icon
+            .bytes()
+            .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-');
+    // Emoji sequences (flags, skin tones, ZWJ families) span several chars.
+    let is_emoji = icon.chars().count() <= 8
+        && icon
+            .chars()
+            .all(|c| !c.is_ascii() && !c.is_alphanumeric() && !c.is_whitespace());
+    if is_name || is_emoji {
+        Ok(icon.to_string())
+    } else {
+        Err(format!(
+            "Invalid icon '{icon}', expected an emoji or an icon name such as 'beer-mug'"
+        ))
+    }
+}
+
+/// Lists the products matching `filter`. With `active_catalog_only` (the
+/// sales screen), only the products of the active catalog are returned, if
+/// one is active, only those shown by the current register, if one is set,
+/// and only those offered today unless `filter` gives another date.
+pub(crate) fn list_products_inner(
+    store: &impl Store,
+    active_catalog_only: bool,
+    filter: &ProductFilter,
+) -> Result<Vec<Product>, String> {
+    if let Some(date) = &filter.available_on {
+        validate_date(date)?;
+    }
+    let mut filter = filter.clone();
+    let (catalog_id, register_id) = if active_catalog_only {
+        if filter.available_on.is_none() {
+            filter.available_on = Some(chrono::Local::now().format("%Y-%m-%d").to_string());
+        }
+        (active_catalog_id(store)?, current_register_id(store)?)
+    } else {
+        (None, None)
+    };
+
+    store.list_products(catalog_id.as_deref(), register_id.as_deref(), &filter)
+}
 
-    Ok(Product {
-        id,
+pub(crate) fn create_product_inner(
+    store: &impl Store,
+    payload: CreateProductPayload,
+    admin_pin: Option<&str>,
+) -> Result<Product, String> {
+    verify_catalog_admin(store, admin_pin)?;
+    let product = Product {
+        id: Uuid::new_v4().to_string(),
         name: payload.name,
         price: payload.price,
         category_id: payload.category_id,
         available: true,
-    })
+        age_restricted: payload.age_restricted,
+        unit: payload.unit,
+        translations: normalize_translations(payload.translations)?,
+        barcode: None,
+        available_from: None,
+        available_until: None,
+    };
+
+    store.insert_product(&product)?;
+
+    Ok(product)
 }
 
+/// Edits a product. While catalog review is on, only an admin can: others
+/// propose their edits with `propose_product_change_inner`.
 pub(crate) fn update_product_inner(
-    db: &DbState,
+    store: &impl Store,
+    payload: UpdateProductPayload,
+    admin_pin: Option<&str>,
+) -> Result<Product, String> {
+    verify_catalog_admin(store, admin_pin)?;
+    apply_product_update(store, payload)
+}
+
+fn apply_product_update(
+    store: &impl Store,
     payload: UpdateProductPayload,
 ) -> Result<Product, String> {
+    let translations = payload
+        .translations
+        .map(normalize_translations)
+        .transpose()?;
+
+    let mut product = store
+        .get_product(&payload.id)?
+        .ok_or_else(|| format!("Product not found: {}", payload.id))?;
+
+    product.name = payload.name;
+    product.price = payload.price;
+    product.category_id = payload.category_id;
+    product.available = payload.available;
+    if let Some(age_restricted) = payload.age_restricted {
+        product.age_restricted = age_restricted;
+    }
+    if let Some(unit) = payload.unit {
+        product.unit = unit;
+    }
+    if let Some(translations) = translations {
+        product.translations = translations;
+    }
+
+    store.update_product(&product)?;
+
+    Ok(product)
+}
+
+/// Settings key turning catalog review on ("1") or off.
+const CATALOG_REVIEW_KEY: &str = "catalog.review";
+
+fn catalog_review_enabled(store: &impl Store) -> Result<bool, String> {
+    Ok(store.get_setting(CATALOG_REVIEW_KEY)?.as_deref() == Some("1"))
+}
+
+/// While catalog review is on, only an admin can change the catalog.
+fn verify_catalog_admin(store: &impl Store, admin_pin: Option<&str>) -> Result<(), String> {
+    if catalog_review_enabled(store)? {
+        verify_admin_pin(store, admin_pin)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn get_catalog_review_inner(store: &impl Store) -> Result<bool, String> {
+    catalog_review_enabled(store)
+}
+
+/// Turns catalog review on or off. It needs an admin PIN, which tells
+/// admins from volunteers: while review is on, any change to the products
+/// and categories needs it, and volunteers propose product edits instead.
+pub(crate) fn set_catalog_review_inner(
+    store: &impl Store,
+    enabled: bool,
+    admin_pin: Option<String>,
+) -> Result<bool, String> {
+    if enabled && admin_pin_hash(store)?.is_none() {
+        return Err("Set an admin PIN before turning catalog review on".to_string());
+    }
+    verify_admin_pin(store, admin_pin.as_deref())?;
+    store.set_settings(&[(
+        CATALOG_REVIEW_KEY,
+        if enabled { "1" } else { "0" }.to_string(),
+    )])?;
+    Ok(enabled)
+}
+
+const PENDING_PRODUCT_CHANGE_COLUMNS: &str = "id, product_id, payload, proposed_by, created_at";
+
+fn query_pending_product_changes(
+    conn: &Connection,
+    filter: &str,
+    params: impl rusqlite::Params,
+) -> Result<Vec<PendingProductChange>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {PENDING_PRODUCT_CHANGE_COLUMNS} FROM pending_product_changes
+             WHERE {filter} ORDER BY created_at, id"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+    let rows = stmt
+        .query_map(params, |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    rows.into_iter()
+        .map(|(id, product_id, payload, proposed_by, created_at)| {
+            Ok(PendingProductChange {
+                current: conn
+                    .get_product(&product_id)?
+                    .ok_or_else(|| format!("Product not found: {product_id}"))?,
+                proposed: serde_json::from_str(&payload)
+                    .map_err(|e| format!("Invalid pending change {id}: {e}"))?,
+                id,
+                proposed_by,
+                created_at,
+            })
+        })
+        .collect()
+}
+
+fn load_pending_product_change(
+    conn: &Connection,
+    change_id: &str,
+) -> Result<PendingProductChange, String> {
+    query_pending_product_changes(conn, "id = ?1", params![change_id])?
+        .pop()
+        .ok_or_else(|| format!("Pending change not found: {change_id}"))
+}
+
+/// Stores a product edit for an admin to approve, without touching the
+/// product.
+pub(crate) fn propose_product_change_inner(
+    db: &DbState,
+    payload: UpdateProductPayload,
+    proposed_by: String,
+) -> Result<PendingProductChange, String> {
+    if let Some(translations) = &payload.translations {
+        normalize_translations(translations.clone())?;
+    }
     let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    if conn.get_product(&payload.id)?.is_none() {
+        return Err(format!("Product not found: {}", payload.id));
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let json = serde_json::to_string(&payload)
+        .map_err(|e| format!("Failed to serialize the change: {e}"))?;
+    conn.execute(
+        "INSERT INTO pending_product_changes (id, product_id, payload, proposed_by, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            id,
+            payload.id,
+            json,
+            proposed_by.trim(),
+            chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        ],
+    )
+    .map_err(|e| format!("Insert error: {e}"))?;
+
+    load_pending_product_change(&conn, &id)
+}
+
+/// Pending product edits, oldest first.
+pub(crate) fn list_pending_product_changes_inner(
+    db: &DbState,
+) -> Result<Vec<PendingProductChange>, String> {
+    let conn = db.read_conn()?;
+    query_pending_product_changes(&conn, "1", [])
+}
+
+/// Applies a pending product edit and removes it from the queue.
+pub(crate) fn approve_product_change_inner(
+    db: &DbState,
+    change_id: String,
+    admin_pin: Option<String>,
+) -> Result<Product, String> {
+    verify_admin_pin(db, admin_pin.as_deref())?;
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let change = load_pending_product_change(&conn, &change_id)?;
 
-    let available_int: i64 = if payload.available { 1 } else { 0 };
+    write_transaction(&mut conn, |tx| {
+        let product = apply_product_update(&**tx, change.proposed.clone())?;
+        tx.execute(
+            "DELETE FROM pending_product_changes WHERE id = ?1",
+            params![change_id],
+        )
+        .map_err(|e| format!("Delete error: {e}"))?;
+        Ok(product)
+    })
+}
 
+/// Drops a pending product edit without applying it.
+pub(crate) fn reject_product_change_inner(
+    db: &DbState,
+    change_id: String,
+    admin_pin: Option<String>,
+) -> Result<(), String> {
+    verify_admin_pin(db, admin_pin.as_deref())?;
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
     let rows_affected = conn
         .execute(
-            "UPDATE products SET name = ?1, price = ?2, category_id = ?3, available = ?4 WHERE id = ?5",
-            params![
-                payload.name,
-                payload.price,
-                payload.category_id,
-                available_int,
-                payload.id
-            ],
+            "DELETE FROM pending_product_changes WHERE id = ?1",
+            params![change_id],
         )
-        .map_err(|e| format!("Update error: {e}"))?;
-
+        .map_err(|e| format!("Delete error: {e}"))?;
     if rows_affected == 0 {
-        return Err(format!("Product not found: {}", payload.id));
+        return Err(format!("Pending change not found: {change_id}"));
     }
+    Ok(())
+}
 
-    Ok(Product {
-        id: payload.id,
-        name: payload.name,
-        price: payload.price,
-        category_id: payload.category_id,
-        available: payload.available,
-    })
+/// Checks that `locale` looks like `fr` or `en-GB`.
+fn validate_locale(locale: &str) -> Result<(), String> {
+    let valid = match locale.split_once('-') {
+        Some((lang, region)) => {
+            lang.len() == 2
+                && lang.bytes().all(|b| b.is_ascii_lowercase())
+                && region.len() == 2
+                && region.bytes().all(|b| b.is_ascii_uppercase())
+        }
+        None => locale.len() == 2 && locale.bytes().all(|b| b.is_ascii_lowercase()),
+    };
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "Invalid locale '{locale}', expected e.g. 'en' or 'en-GB'"
+        ))
+    }
+}
+
+/// Trims labels and drops empty ones, so that a blank field in the form
+/// falls back to the default label.
+fn normalize_translations(translations: Translations) -> Result<Translations, String> {
+    let mut normalized = Translations::new();
+    for (locale, label) in translations {
+        validate_locale(&locale)?;
+        let label = label.trim();
+        if !label.is_empty() {
+            normalized.insert(locale, label.to_string());
+        }
+    }
+    Ok(normalized)
 }
 
 pub(crate) fn toggle_product_availability_inner(
-    db: &DbState,
+    store: &impl Store,
     product_id: String,
+    admin_pin: Option<&str>,
 ) -> Result<bool, String> {
-    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
-
-    // Read current availability.
-    let current: i64 = conn
-        .query_row(
-            "SELECT available FROM products WHERE id = ?1",
-            params![product_id],
-            |row| row.get(0),
-        )
-        .map_err(|e| format!("Product not found ({}): {e}", product_id))?;
+    verify_catalog_admin(store, admin_pin)?;
+    let mut product = store
+        .get_product(&product_id)?
+        .ok_or_else(|| format!("Product not found: {product_id}"))?;
 
-    let new_value: i64 = if current != 0 { 0 } else { 1 };
+    product.available = !product.available;
+    store.update_product(&product)?;
 
-    conn.execute(
-        "UPDATE products SET available = ?1 WHERE id = ?2",
-        params![new_value, product_id],
-    )
-    .map_err(|e| format!("Update error: {e}"))?;
+    Ok(product.available)
+}
 
-    Ok(new_value != 0)
+/// Limits a product to the dates from `from` to `until` (inclusive); a
+/// `None` bound leaves that side open.
+pub(crate) fn set_product_dates_inner(
+    store: &impl Store,
+    product_id: String,
+    from: Option<String>,
+    until: Option<String>,
+    admin_pin: Option<&str>,
+) -> Result<Product, String> {
+    verify_catalog_admin(store, admin_pin)?;
+    validate_range(&DateRange {
+        from: from.clone(),
+        to: until.clone(),
+    })?;
+    let mut product = store
+        .get_product(&product_id)?
+        .ok_or_else(|| format!("Product not found: {product_id}"))?;
+
+    product.available_from = from;
+    product.available_until = until;
+    store.update_product(&product)?;
+
+    Ok(product)
 }
 
 pub(crate) fn create_order_inner(
@@ -185,739 +659,13673 @@ pub(crate) fn create_order_inner(
     payload: CreateOrderPayload,
 ) -> Result<OrderWithItems, String> {
     let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let mut new_order = prepare_order(&conn, payload)?;
 
-    if payload.items.is_empty() {
-        return Err("Cannot create an order with no items".to_string());
+    // Execute inside a database transaction for atomicity.
+    write_transaction(&mut conn, |tx| save_order(tx, &mut new_order))?;
+
+    Ok(new_order.order)
+}
+
+/// Splits a cart into one order per part, created in a single transaction:
+/// either every part is recorded or none is. Every cart line must be
+/// assigned in full.
+pub(crate) fn split_order_inner(
+    db: &DbState,
+    payload: SplitOrderPayload,
+) -> Result<Vec<OrderWithItems>, String> {
+    if payload.parts.len() < 2 {
+        return Err("A split needs at least two parts".to_string());
     }
+    let parts = split_items(
+        &payload.items,
+        payload.parts,
+        payload.age_confirmed,
+        payload.limits_confirmed,
+    )?;
 
-    // Compute totals.
-    let mut order_items: Vec<OrderItem> = Vec::with_capacity(payload.items.len());
-    let order_id = Uuid::new_v4().to_string();
-    let mut order_total: i64 = 0;
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let mut new_orders = prepare_orders(&conn, parts)?;
+    write_transaction(&mut conn, |tx| save_orders(tx, &mut new_orders))?;
 
-    for item in &payload.items {
-        if item.quantity <= 0 {
+    Ok(new_orders.into_iter().map(|o| o.order).collect())
+}
+
+/// One order payload per part, checking that every line of `items` is
+/// assigned in full.
+fn split_items(
+    items: &[CreateOrderItemPayload],
+    parts: Vec<SplitOrderPart>,
+    age_confirmed: bool,
+    limits_confirmed: bool,
+) -> Result<Vec<CreateOrderPayload>, String> {
+    let mut assigned = vec![0.0; items.len()];
+    let mut payloads = Vec::with_capacity(parts.len());
+    for part in parts {
+        let mut part_items = Vec::with_capacity(part.items.len());
+        for assignment in part.items {
+            let item = items
+                .get(assignment.item_index)
+                .ok_or_else(|| format!("Unknown cart line: {}", assignment.item_index))?;
+            assigned[assignment.item_index] += assignment.quantity;
+            part_items.push(CreateOrderItemPayload {
+                product_id: item.product_id.clone(),
+                product_name: item.product_name.clone(),
+                unit_price: item.unit_price,
+                quantity: assignment.quantity,
+            });
+        }
+        payloads.push(CreateOrderPayload {
+            items: part_items,
+            payment_method: part.payment_method,
+            age_confirmed,
+            limits_confirmed,
+            adjustments: Vec::new(),
+            source: OrderSource::Counter,
+        });
+    }
+    for (item, assigned) in items.iter().zip(&assigned) {
+        if ((item.quantity - assigned) * 1000.0).round() != 0.0 {
             return Err(format!(
-                "Invalid quantity {} for product {}",
-                item.quantity, item.product_id
+                "{} is assigned {assigned} times instead of {}",
+                item.product_name, item.quantity
             ));
         }
-        let line_total = item.unit_price * item.quantity;
-        order_total += line_total;
-        order_items.push(OrderItem {
-            id: Uuid::new_v4().to_string(),
-            order_id: order_id.clone(),
-            product_id: item.product_id.clone(),
-            product_name: item.product_name.clone(),
-            unit_price: item.unit_price,
-            quantity: item.quantity,
-            total: line_total,
-        });
     }
+    Ok(payloads)
+}
 
-    let created_at = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
+fn prepare_orders(
+    conn: &Connection,
+    payloads: Vec<CreateOrderPayload>,
+) -> Result<Vec<NewOrder>, String> {
+    payloads
+        .into_iter()
+        .map(|payload| prepare_order(conn, payload))
+        .collect()
+}
 
-    // Execute inside a database transaction for atomicity.
-    let tx = conn
-        .transaction()
-        .map_err(|e| format!("Transaction begin error: {e}"))?;
+/// Stores several prepared orders, chained in turn. Must run inside the
+/// caller's transaction.
+fn save_orders(conn: &Connection, new_orders: &mut [NewOrder]) -> Result<(), String> {
+    new_orders
+        .iter_mut()
+        .try_for_each(|new_order| save_order(conn, new_order))
+}
 
-    tx.execute(
-        "INSERT INTO orders (id, created_at, total, payment_method) VALUES (?1, ?2, ?3, ?4)",
-        params![
-            order_id,
-            created_at,
-            order_total,
-            payload.payment_method.as_db_str()
-        ],
-    )
-    .map_err(|e| format!("Insert order error: {e}"))?;
+// ── Tabs ─────────────────────────────────────────────────────────────────────
 
-    for oi in &order_items {
-        tx.execute(
-            "INSERT INTO order_items (id, order_id, product_id, product_name, unit_price, quantity, total)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            params![
-                oi.id,
-                oi.order_id,
-                oi.product_id,
-                oi.product_name,
-                oi.unit_price,
-                oi.quantity,
-                oi.total
-            ],
-        )
-        .map_err(|e| format!("Insert order item error: {e}"))?;
+/// Opens an empty tab.
+pub(crate) fn open_tab_inner(db: &DbState, name: String) -> Result<Tab, String> {
+    let name = name.trim().to_string();
+    if name.is_empty() {
+        return Err("Tab name cannot be empty".to_string());
     }
+    let tab = Tab {
+        id: Uuid::new_v4().to_string(),
+        name,
+        opened_at: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        items: Vec::new(),
+        total: 0,
+    };
 
-    tx.commit()
-        .map_err(|e| format!("Transaction commit error: {e}"))?;
-
-    Ok(OrderWithItems {
-        order: Order {
-            id: order_id,
-            created_at,
-            total: order_total,
-            payment_method: payload.payment_method,
-        },
-        items: order_items,
-    })
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    conn.execute(
+        "INSERT INTO tabs (id, name, opened_at) VALUES (?1, ?2, ?3)",
+        params![tab.id, tab.name, tab.opened_at],
+    )
+    .map_err(|e| format!("Insert error: {e}"))?;
+    Ok(tab)
 }
 
-pub(crate) fn list_orders_inner(db: &DbState) -> Result<Vec<OrderWithItems>, String> {
+/// Tabs not settled yet, oldest first.
+pub(crate) fn list_open_tabs_inner(db: &DbState) -> Result<Vec<Tab>, String> {
     let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
-
-    // Fetch all orders.
-    let mut order_stmt = conn
-        .prepare(
-            "SELECT id, created_at, total, payment_method FROM orders ORDER BY created_at DESC",
-        )
+    let mut stmt = conn
+        .prepare("SELECT id FROM tabs WHERE settled_at IS NULL ORDER BY opened_at, name")
         .map_err(|e| format!("Query error: {e}"))?;
-
-    let orders: Vec<Order> = order_stmt
-        .query_map([], |row| {
-            let pm_str: String = row.get(3)?;
-            let payment_method = PaymentMethod::from_db_str(&pm_str).map_err(|e| {
-                rusqlite::Error::FromSqlConversionFailure(
-                    3,
-                    rusqlite::types::Type::Text,
-                    Box::from(e),
-                )
-            })?;
-            Ok(Order {
-                id: row.get(0)?,
-                created_at: row.get(1)?,
-                total: row.get(2)?,
-                payment_method,
-            })
-        })
+    let ids = stmt
+        .query_map([], |row| row.get::<_, String>(0))
         .map_err(|e| format!("Query error: {e}"))?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| format!("Row mapping error: {e}"))?;
+    ids.iter().map(|id| load_open_tab(&conn, id)).collect()
+}
 
-    // Fetch all items and group by order_id.
-    let mut item_stmt = conn
-        .prepare(
-            "SELECT id, order_id, product_id, product_name, unit_price, quantity, total
-             FROM order_items
-             ORDER BY order_id",
+/// Puts items on an open tab.
+pub(crate) fn add_to_tab_inner(
+    db: &DbState,
+    tab_id: String,
+    payload: AddToTabPayload,
+) -> Result<Tab, String> {
+    if payload.items.is_empty() {
+        return Err("No items to add to the tab".to_string());
+    }
+
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    load_open_tab(&conn, &tab_id)?;
+    let restricted_products = restricted_product_names(&conn, &payload.items)?;
+    if !restricted_products.is_empty() && !payload.age_confirmed {
+        return Err(format!(
+            "Age confirmation required for: {}",
+            restricted_products.join(", ")
+        ));
+    }
+
+    let added_at = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Transaction begin error: {e}"))?;
+    for item in &payload.items {
+        let (quantity, _) = line_quantity(&tx, item)?;
+        tx.execute(
+            "INSERT INTO tab_items
+                 (id, tab_id, product_id, product_name, unit_price, quantity, added_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                Uuid::new_v4().to_string(),
+                tab_id,
+                item.product_id,
+                item.product_name,
+                item.unit_price,
+                quantity,
+                added_at
+            ],
         )
-        .map_err(|e| format!("Query error: {e}"))?;
+        .map_err(|e| format!("Insert error: {e}"))?;
+    }
+    tx.commit()
+        .map_err(|e| format!("Transaction commit error: {e}"))?;
 
-    let all_items: Vec<OrderItem> = item_stmt
-        .query_map([], |row| {
-            Ok(OrderItem {
-                id: row.get(0)?,
-                order_id: row.get(1)?,
-                product_id: row.get(2)?,
-                product_name: row.get(3)?,
-                unit_price: row.get(4)?,
-                quantity: row.get(5)?,
-                total: row.get(6)?,
-            })
-        })
-        .map_err(|e| format!("Query error: {e}"))?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Row mapping error: {e}"))?;
+    load_open_tab(&conn, &tab_id)
+}
 
-    // Build a map of order_id -> items.
-    let mut items_map: std::collections::HashMap<String, Vec<OrderItem>> =
-        std::collections::HashMap::new();
-    for item in all_items {
-        items_map
-            .entry(item.order_id.clone())
-            .or_default()
-            .push(item);
+/// Takes a line off an open tab, e.g. an item put on the wrong tab.
+pub(crate) fn remove_from_tab_inner(
+    db: &DbState,
+    tab_id: String,
+    item_id: String,
+    admin_pin: Option<String>,
+) -> Result<Tab, String> {
+    verify_admin_pin(db, admin_pin.as_deref())?;
+
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    load_open_tab(&conn, &tab_id)?;
+    let removed = conn
+        .execute(
+            "DELETE FROM tab_items WHERE id = ?1 AND tab_id = ?2",
+            params![item_id, tab_id],
+        )
+        .map_err(|e| format!("Delete error: {e}"))?;
+    if removed == 0 {
+        return Err(format!("No item {item_id} on tab {tab_id}"));
     }
 
-    let result: Vec<OrderWithItems> = orders
+    load_open_tab(&conn, &tab_id)
+}
+
+/// Deletes an open tab and its items without recording any sale.
+pub(crate) fn cancel_tab_inner(
+    db: &DbState,
+    tab_id: String,
+    admin_pin: Option<String>,
+) -> Result<(), String> {
+    verify_admin_pin(db, admin_pin.as_deref())?;
+
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    load_open_tab(&conn, &tab_id)?;
+    write_transaction(&mut conn, |tx| {
+        tx.execute("DELETE FROM tab_items WHERE tab_id = ?1", params![tab_id])
+            .map_err(|e| format!("Delete error: {e}"))?;
+        tx.execute("DELETE FROM tabs WHERE id = ?1", params![tab_id])
+            .map_err(|e| format!("Delete error: {e}"))?;
+        Ok(())
+    })
+}
+
+/// Closes a tab by recording what it holds as paid orders, one per payment,
+/// split as in `split_order`. Either every order is recorded and the tab is
+/// closed, or nothing changes. Payments over the `OrderLimits` need
+/// `limits_confirmed`.
+pub(crate) fn settle_tab_inner(
+    db: &DbState,
+    tab_id: String,
+    payments: Vec<SplitOrderPart>,
+    limits_confirmed: bool,
+) -> Result<Vec<OrderWithItems>, String> {
+    if payments.is_empty() {
+        return Err("A tab is settled with at least one payment".to_string());
+    }
+
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let tab = load_open_tab(&conn, &tab_id)?;
+    if tab.items.is_empty() {
+        return Err(format!("Tab {} is empty", tab.name));
+    }
+    let items: Vec<CreateOrderItemPayload> = tab
+        .items
         .into_iter()
-        .map(|order| {
-            let items = items_map.remove(&order.id).unwrap_or_default();
-            OrderWithItems { order, items }
+        .map(|item| CreateOrderItemPayload {
+            product_id: item.product_id,
+            product_name: item.product_name,
+            unit_price: item.unit_price,
+            quantity: item.quantity,
         })
         .collect();
+    // Ages were confirmed when the items were served.
+    let parts = split_items(&items, payments, true, limits_confirmed)?;
+    let mut new_orders = prepare_orders(&conn, parts)?;
 
-    Ok(result)
+    write_transaction(&mut conn, |tx| {
+        save_orders(tx, &mut new_orders)?;
+        tx.execute(
+            "UPDATE tabs SET settled_at = ?1 WHERE id = ?2",
+            params![
+                chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+                tab_id
+            ],
+        )
+        .map_err(|e| format!("Update error: {e}"))?;
+        Ok(())
+    })?;
+
+    Ok(new_orders.into_iter().map(|o| o.order).collect())
 }
 
-pub(crate) fn get_dashboard_summary_inner(db: &DbState) -> Result<DashboardSummary, String> {
-    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+/// Loads a tab with its items, failing if it does not exist or is settled.
+fn load_open_tab(conn: &Connection, tab_id: &str) -> Result<Tab, String> {
+    use rusqlite::OptionalExtension;
 
-    // Grand totals.
-    let (total_revenue, total_transactions): (i64, i64) = conn
+    let (name, opened_at) = conn
         .query_row(
-            "SELECT COALESCE(SUM(total), 0), COUNT(*) FROM orders",
-            [],
-            |row| Ok((row.get(0)?, row.get(1)?)),
-        )
-        .map_err(|e| format!("Query error: {e}"))?;
-
-    // Per-product summary.
-    let mut prod_stmt = conn
-        .prepare(
-            "SELECT product_id, product_name,
-                    SUM(quantity) AS total_qty,
-                    SUM(total) AS total_rev
-             FROM order_items
-             GROUP BY product_id
-             ORDER BY total_rev DESC",
+            "SELECT name, opened_at FROM tabs WHERE id = ?1 AND settled_at IS NULL",
+            params![tab_id],
+            |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)),
         )
-        .map_err(|e| format!("Query error: {e}"))?;
-
-    let per_product: Vec<ProductSalesSummary> = prod_stmt
-        .query_map([], |row| {
-            Ok(ProductSalesSummary {
-                product_id: row.get(0)?,
-                product_name: row.get(1)?,
-                total_quantity: row.get(2)?,
-                total_revenue: row.get(3)?,
-            })
-        })
+        .optional()
         .map_err(|e| format!("Query error: {e}"))?
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| format!("Row mapping error: {e}"))?;
+        .ok_or_else(|| format!("No open tab with id: {tab_id}"))?;
 
-    // Per-payment-method breakdown.
-    let mut pm_stmt = conn
+    let mut stmt = conn
         .prepare(
-            "SELECT payment_method,
-                    SUM(total) AS total_rev,
-                    COUNT(*) AS tx_count
-             FROM orders
-             GROUP BY payment_method
-             ORDER BY payment_method",
+            "SELECT id, product_id, product_name, unit_price, quantity, added_at
+             FROM tab_items WHERE tab_id = ?1 ORDER BY added_at, rowid",
         )
         .map_err(|e| format!("Query error: {e}"))?;
-
-    let per_payment_method: Vec<PaymentMethodBreakdown> = pm_stmt
-        .query_map([], |row| {
-            let pm_str: String = row.get(0)?;
-            let payment_method = PaymentMethod::from_db_str(&pm_str).map_err(|e| {
-                rusqlite::Error::FromSqlConversionFailure(
-                    0,
-                    rusqlite::types::Type::Text,
-                    Box::from(e),
-                )
-            })?;
-            Ok(PaymentMethodBreakdown {
-                payment_method,
-                total_revenue: row.get(1)?,
-                transaction_count: row.get(2)?,
+    let items = stmt
+        .query_map(params![tab_id], |row| {
+            Ok(TabItem {
+                id: row.get(0)?,
+                product_id: row.get(1)?,
+                product_name: row.get(2)?,
+                unit_price: row.get(3)?,
+                quantity: row.get(4)?,
+                added_at: row.get(5)?,
             })
         })
         .map_err(|e| format!("Query error: {e}"))?
         .collect::<Result<Vec<_>, _>>()
         .map_err(|e| format!("Row mapping error: {e}"))?;
 
-    Ok(DashboardSummary {
-        total_revenue,
-        total_transactions,
-        per_product,
-        per_payment_method,
+    Ok(Tab {
+        id: tab_id.to_string(),
+        name,
+        opened_at,
+        total: items
+            .iter()
+            .map(|item| line_total(item.unit_price, item.quantity))
+            .sum(),
+        items,
     })
 }
 
-pub(crate) fn delete_product_inner(db: &DbState, product_id: String) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+/// A validated order, not stored yet.
+struct NewOrder {
+    order: OrderWithItems,
+    /// Names of the age-restricted products it contains.
+    restricted_products: Vec<String>,
+}
 
-    let rows_affected = conn
-        .execute("DELETE FROM products WHERE id = ?1", params![product_id])
-        .map_err(|e| format!("Delete error: {e}"))?;
+/// Validates the quantity of a cart line and rounds it: weights and volumes
+/// are kept to the gram / millilitre, pieces are whole numbers.
+fn line_quantity(
+    conn: &Connection,
+    item: &CreateOrderItemPayload,
+) -> Result<(f64, QuantityUnit), String> {
+    if !item.quantity.is_finite() || item.quantity <= 0.0 {
+        return Err(format!(
+            "Invalid quantity {} for product {}",
+            item.quantity, item.product_id
+        ));
+    }
+    let unit = product_unit(conn, &item.product_id)?;
+    let quantity = (item.quantity * 1000.0).round() / 1000.0;
+    if unit == QuantityUnit::Piece && quantity.fract() != 0.0 {
+        return Err(format!(
+            "Quantity {} for product {} must be a whole number",
+            item.quantity, item.product_id
+        ));
+    }
+    Ok((quantity, unit))
+}
 
-    if rows_affected == 0 {
-        return Err(format!("Product not found: {}", product_id));
+/// Validates `payload` and computes the order lines and total, rounded as
+/// set by the `RoundingSettings`. Orders over the `OrderLimits` need
+/// `limits_confirmed`.
+fn prepare_order(conn: &Connection, payload: CreateOrderPayload) -> Result<NewOrder, String> {
+    if payload.items.is_empty() {
+        return Err("Cannot create an order with no items".to_string());
+    }
+    if !payload.limits_confirmed {
+        check_order_limits(&load_order_limits(conn)?, &payload.items)?;
     }
+    let rounding = load_rounding_settings(conn)?;
 
-    Ok(())
-}
+    // Compute totals.
+    let mut order_items: Vec<OrderItem> = Vec::with_capacity(payload.items.len());
+    let order_id = Uuid::new_v4().to_string();
+    let mut order_total: i64 = 0;
 
-pub(crate) fn delete_category_inner(db: &DbState, category_id: String) -> Result<(), String> {
-    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    for item in &payload.items {
+        let (quantity, unit) = line_quantity(conn, item)?;
+        let mut line_total = line_total(item.unit_price, quantity);
+        if rounding.mode == RoundingMode::PerLine {
+            line_total = round_to_increment(line_total, rounding.increment);
+        }
+        order_total += line_total;
+        order_items.push(OrderItem {
+            id: Uuid::new_v4().to_string(),
+            order_id: order_id.clone(),
+            product_id: item.product_id.clone(),
+            product_name: item.product_name.clone(),
+            unit_price: item.unit_price,
+            quantity,
+            unit,
+            total: line_total,
+            refunded_item_id: None,
+        });
+    }
 
-    // Check whether any products reference this category.
-    let product_count: i64 = conn
-        .query_row(
-            "SELECT COUNT(*) FROM products WHERE category_id = ?1",
-            params![category_id],
-            |row| row.get(0),
-        )
-        .map_err(|e| format!("Query error: {e}"))?;
+    for adjustment in &payload.adjustments {
+        let label = adjustment.label.trim();
+        if label.is_empty() {
+            return Err("Adjustment label cannot be empty".to_string());
+        }
+        if adjustment.amount >= 0 {
+            return Err(format!("Adjustment {label} must be a negative amount"));
+        }
+        order_total += adjustment.amount;
+        order_items.push(OrderItem {
+            id: Uuid::new_v4().to_string(),
+            order_id: order_id.clone(),
+            product_id: ADJUSTMENT_PRODUCT_ID.to_string(),
+            product_name: label.to_string(),
+            unit_price: adjustment.amount,
+            quantity: 1.0,
+            unit: QuantityUnit::Piece,
+            total: adjustment.amount,
+            refunded_item_id: None,
+        });
+    }
+    if order_total < 0 {
+        return Err(format!(
+            "Adjustments exceed the order by {} EUR",
+            export::format_cents(-order_total)
+        ));
+    }
 
-    if product_count > 0 {
+    if let Some(rule) = surcharge_rule(conn, &payload.payment_method)? {
+        if !rule.below.is_some_and(|below| order_total >= below) {
+            order_total += rule.amount;
+            order_items.push(OrderItem {
+                id: Uuid::new_v4().to_string(),
+                order_id: order_id.clone(),
+                product_id: SURCHARGE_PRODUCT_ID.to_string(),
+                product_name: format!("Surcharge ({})", payload.payment_method),
+                unit_price: rule.amount,
+                quantity: 1.0,
+                unit: QuantityUnit::Piece,
+                total: rule.amount,
+                refunded_item_id: None,
+            });
+        }
+    }
+
+    if rounding.mode == RoundingMode::OrderTotal {
+        let adjustment = round_to_increment(order_total, rounding.increment) - order_total;
+        if adjustment != 0 {
+            order_total += adjustment;
+            order_items.push(OrderItem {
+                id: Uuid::new_v4().to_string(),
+                order_id: order_id.clone(),
+                product_id: ROUNDING_PRODUCT_ID.to_string(),
+                product_name: "Rounding".to_string(),
+                unit_price: adjustment,
+                quantity: 1.0,
+                unit: QuantityUnit::Piece,
+                total: adjustment,
+                refunded_item_id: None,
+            });
+        }
+    }
+
+    // Age-restricted products may only be sold after an explicit confirmation.
+    let restricted_products = restricted_product_names(conn, &payload.items)?;
+    if !restricted_products.is_empty() && !payload.age_confirmed {
         return Err(format!(
-            "Cannot delete category '{}': it is referenced by {} product(s)",
-            category_id, product_count
+            "Age confirmation required for: {}",
+            restricted_products.join(", ")
         ));
     }
 
-    let rows_affected = conn
-        .execute("DELETE FROM categories WHERE id = ?1", params![category_id])
-        .map_err(|e| format!("Delete error: {e}"))?;
+    let created_at = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
 
-    if rows_affected == 0 {
-        return Err(format!("Category not found: {}", category_id));
+    let order = Order {
+        id: order_id,
+        created_at,
+        total: order_total,
+        payment_method: payload.payment_method,
+        hash: None,
+        voided_at: None,
+        refund_of: None,
+        tags: Vec::new(),
+        is_test: false,
+        source: payload.source,
+    };
+
+    Ok(NewOrder {
+        order: OrderWithItems {
+            order,
+            items: order_items,
+        },
+        restricted_products,
+    })
+}
+
+/// Stores a prepared order with its age confirmation, if any. Must run
+/// inside the caller's transaction.
+fn save_order(conn: &Connection, new_order: &mut NewOrder) -> Result<(), String> {
+    let OrderWithItems { order, items } = &mut new_order.order;
+    insert_chained_order(conn, order, items)?;
+
+    if !new_order.restricted_products.is_empty() {
+        conn.execute(
+            "INSERT INTO age_confirmations (order_id, confirmed_at, restricted_products)
+             VALUES (?1, ?2, ?3)",
+            params![
+                order.id,
+                order.created_at,
+                new_order.restricted_products.join(", ")
+            ],
+        )
+        .map_err(|e| format!("Insert age confirmation error: {e}"))?;
     }
 
     Ok(())
 }
 
-pub(crate) fn reset_database_inner(db: &DbState) -> Result<(), String> {
-    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+/// Inserts `order` and its items, chained to the last order, and sets
+/// `order.hash`. Must run inside the caller's transaction so that two
+/// orders can never claim the same predecessor.
+fn insert_chained_order(
+    conn: &Connection,
+    order: &mut Order,
+    items: &[OrderItem],
+) -> Result<(), String> {
+    let previous_hash = last_order_hash(conn)?;
+    let hash = order_hash(&previous_hash, order, items);
 
-    let db_path = &db.db_path;
-    let is_file_db = db_path != ":memory:";
+    conn.execute(
+        "INSERT INTO orders
+            (id, created_at, total, payment_method, previous_hash, hash, refund_of, source)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        params![
+            order.id,
+            order.created_at,
+            order.total,
+            order.payment_method.as_db_str(),
+            previous_hash,
+            hash,
+            order.refund_of,
+            order.source.as_db_str()
+        ],
+    )
+    .map_err(|e| format!("Insert order error: {e}"))?;
 
-    // Close the current connection by replacing it with a temporary in-memory one.
-    // This ensures the file handle is released before we delete the file.
-    if is_file_db {
-        *conn = Connection::open_in_memory()
-            .map_err(|e| format!("Failed to create temporary connection: {e}"))?;
+    insert_order_items(conn, items)?;
+    record_ingredient_consumption(conn, &order.created_at, items)?;
 
-        std::fs::remove_file(db_path)
-            .map_err(|e| format!("Failed to delete database file: {e}"))?;
-        // Also remove WAL/SHM sidecar files if they exist.
-        let _ = std::fs::remove_file(format!("{db_path}-wal"));
-        let _ = std::fs::remove_file(format!("{db_path}-shm"));
+    order.hash = Some(hash);
+    Ok(())
+}
 
-        *conn = Connection::open(db_path).map_err(|e| format!("Failed to reopen database: {e}"))?;
+/// Reason of the ingredient movements written for sold lines.
+const INGREDIENT_SALE_REASON: &str = "sale";
+/// Reason of the ingredient movements that give a voided order's back.
+const INGREDIENT_VOID_REASON: &str = "void";
+
+/// Records what `items` consume according to the recipes at the time of
+/// sale, so that editing a recipe later leaves past consumption alone.
+/// Refund lines have negative quantities and put the ingredients back.
+fn record_ingredient_consumption(
+    conn: &Connection,
+    created_at: &str,
+    items: &[OrderItem],
+) -> Result<(), String> {
+    let mut recipe = conn
+        .prepare("SELECT ingredient_id, quantity FROM recipe_items WHERE product_id = ?1")
+        .map_err(|e| format!("Query error: {e}"))?;
+    for item in items {
+        let uses = recipe
+            .query_map(params![item.product_id], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?))
+            })
+            .map_err(|e| format!("Query error: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Row mapping error: {e}"))?;
+        for (ingredient_id, per_unit) in uses {
+            conn.execute(
+                "INSERT INTO ingredient_movements
+                    (id, ingredient_id, quantity, reason, created_at, order_item_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    Uuid::new_v4().to_string(),
+                    ingredient_id,
+                    round_stock(-item.quantity * per_unit),
+                    INGREDIENT_SALE_REASON,
+                    created_at,
+                    item.id
+                ],
+            )
+            .map_err(|e| format!("Insert error: {e}"))?;
+        }
+    }
+    Ok(())
+}
 
-        conn.execute_batch("PRAGMA journal_mode=WAL;")
-            .map_err(|e| format!("Failed to set WAL mode: {e}"))?;
-    } else {
-        *conn = Connection::open_in_memory()
-            .map_err(|e| format!("Failed to open in-memory database: {e}"))?;
+/// Lowest bound SQLite may be compiled with for the number of parameters
+/// of a statement.
+const MAX_SQL_PARAMS: usize = 999;
+const ORDER_ITEM_PARAMS: usize = 9;
+
+/// Inserts `items` with one multi-row INSERT per chunk of items, so that a
+/// large group order does not cost one statement per line.
+fn insert_order_items(conn: &Connection, items: &[OrderItem]) -> Result<(), String> {
+    for chunk in items.chunks(MAX_SQL_PARAMS / ORDER_ITEM_PARAMS) {
+        let placeholders = vec!["(?, ?, ?, ?, ?, ?, ?, ?, ?)"; chunk.len()].join(", ");
+        let units: Vec<&str> = chunk.iter().map(|oi| oi.unit.as_db_str()).collect();
+        let mut values: Vec<&dyn rusqlite::ToSql> =
+            Vec::with_capacity(chunk.len() * ORDER_ITEM_PARAMS);
+        for (oi, unit) in chunk.iter().zip(&units) {
+            values.extend([
+                &oi.id as &dyn rusqlite::ToSql,
+                &oi.order_id,
+                &oi.product_id,
+                &oi.product_name,
+                &oi.unit_price,
+                &oi.quantity,
+                unit,
+                &oi.total,
+                &oi.refunded_item_id,
+            ]);
+        }
+        conn.execute(
+            &format!(
+                "INSERT INTO order_items
+                    (id, order_id, product_id, product_name, unit_price, quantity, unit, total,
+                     refunded_item_id)
+                 VALUES {placeholders}"
+            ),
+            rusqlite::params_from_iter(values),
+        )
+        .map_err(|e| format!("Insert order item error: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Builds the refund order of `items` of the order `original`, checking that
+/// no line gets refunded more than was sold. Refunded lines are valued at
+/// their original price; refunding what is left of a line gives back exactly
+/// what is left of its total.
+fn build_refund(
+    conn: &Connection,
+    original: &OrderWithItems,
+    refunds: &[ItemRefund],
+) -> Result<OrderWithItems, String> {
+    if original.order.voided_at.is_some() {
+        return Err("A voided order cannot be refunded".to_string());
+    }
+    if original.order.refund_of.is_some() {
+        return Err("A refund cannot be refunded".to_string());
+    }
+    if refunds.is_empty() {
+        return Err("No items to refund".to_string());
     }
 
-    conn.execute_batch("PRAGMA foreign_keys=ON;")
-        .map_err(|e| format!("Failed to enable foreign keys: {e}"))?;
+    // Merge repeated lines so that the cap applies to their sum.
+    let mut requested: Vec<(&OrderItem, f64)> = Vec::new();
+    for refund in refunds {
+        let item = original
+            .items
+            .iter()
+            .find(|i| i.id == refund.item_id)
+            .ok_or_else(|| format!("Item {} is not part of this order", refund.item_id))?;
+        if !refund.quantity.is_finite() || refund.quantity <= 0.0 {
+            return Err(format!(
+                "Invalid refund quantity {} for {}",
+                refund.quantity, item.product_name
+            ));
+        }
+        match requested.iter_mut().find(|(i, _)| i.id == item.id) {
+            Some((_, quantity)) => *quantity += refund.quantity,
+            None => requested.push((item, refund.quantity)),
+        }
+    }
 
-    let migrations = rusqlite_migration::Migrations::new(crate::db::migrations());
-    migrations
-        .to_latest(&mut *conn)
-        .map_err(|e| format!("Migration error: {e}"))?;
+    let mut refunded_stmt = conn
+        .prepare(
+            "SELECT COALESCE(-SUM(quantity), 0), COALESCE(-SUM(total), 0)
+             FROM order_items
+             WHERE refunded_item_id = ?1
+               AND order_id IN (SELECT id FROM orders WHERE voided_at IS NULL)",
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
 
-    crate::db::create_default_data(&conn);
+    let order_id = Uuid::new_v4().to_string();
+    let mut items = Vec::with_capacity(requested.len());
+    for (item, quantity) in requested {
+        let quantity = (quantity * 1000.0).round() / 1000.0;
+        if item.unit == QuantityUnit::Piece && quantity.fract() != 0.0 {
+            return Err(format!(
+                "Refund quantity {quantity} for {} must be a whole number",
+                item.product_name
+            ));
+        }
 
-    Ok(())
+        let (refunded_quantity, refunded_total): (f64, i64) = refunded_stmt
+            .query_row(params![item.id], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| format!("Query error: {e}"))?;
+        let remaining = ((item.quantity - refunded_quantity) * 1000.0).round() / 1000.0;
+        if quantity > remaining {
+            return Err(format!(
+                "Cannot refund {quantity} of {}: only {remaining} left to refund",
+                item.product_name
+            ));
+        }
+
+        let total = if quantity == remaining {
+            item.total - refunded_total
+        } else {
+            line_total(item.unit_price, quantity)
+        };
+        items.push(OrderItem {
+            id: Uuid::new_v4().to_string(),
+            order_id: order_id.clone(),
+            product_id: item.product_id.clone(),
+            product_name: item.product_name.clone(),
+            unit_price: item.unit_price,
+            quantity: -quantity,
+            unit: item.unit,
+            total: -total,
+            refunded_item_id: Some(item.id.clone()),
+        });
+    }
+
+    Ok(OrderWithItems {
+        order: Order {
+            id: order_id,
+            created_at: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            total: items.iter().map(|i| i.total).sum(),
+            payment_method: original.order.payment_method.clone(),
+            hash: None,
+            voided_at: None,
+            refund_of: Some(original.order.id.clone()),
+            tags: Vec::new(),
+            is_test: false,
+            source: original.order.source,
+        },
+        items,
+    })
 }
 
-// ── Tauri command wrappers ──────────────────────────────────────────────────
+/// Refunds the given quantities of lines of `order_id`, as a new chained
+/// order with negative lines linked to the refunded ones.
+pub(crate) fn refund_items_inner(
+    db: &DbState,
+    order_id: String,
+    item_refunds: Vec<ItemRefund>,
+) -> Result<OrderWithItems, String> {
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    write_transaction(&mut conn, |tx| {
+        let original = load_order(tx, &order_id)?;
+        let mut refund = build_refund(tx, &original, &item_refunds)?;
+        insert_chained_order(tx, &mut refund.order, &refund.items)?;
+        Ok(refund)
+    })
+}
 
-#[tauri::command]
-pub fn list_categories(state: State<'_, DbState>) -> Result<Vec<Category>, String> {
-    list_categories_inner(&state)
+/// Refunds lines of `order_id` and sells the `replacement` items in one
+/// transaction, so the customer only settles the difference.
+pub(crate) fn exchange_order_inner(
+    db: &DbState,
+    order_id: String,
+    item_refunds: Vec<ItemRefund>,
+    replacement: CreateOrderPayload,
+) -> Result<Exchange, String> {
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let mut new_order = prepare_order(&conn, replacement)?;
+    let refund = write_transaction(&mut conn, |tx| {
+        let original = load_order(tx, &order_id)?;
+        let mut refund = build_refund(tx, &original, &item_refunds)?;
+        insert_chained_order(tx, &mut refund.order, &refund.items)?;
+        save_order(tx, &mut new_order)?;
+        Ok(refund)
+    })?;
+
+    Ok(Exchange {
+        balance: new_order.order.order.total + refund.order.total,
+        refund,
+        replacement: new_order.order,
+    })
 }
 
-#[tauri::command]
-pub fn create_category(
-    state: State<'_, DbState>,
-    payload: CreateCategoryPayload,
-) -> Result<Category, String> {
-    create_category_inner(&state, payload)
+/// Price of `quantity` units at `unit_price` cents, rounded to the nearest
+/// cent (half away from zero).
+fn line_total(unit_price: i64, quantity: f64) -> i64 {
+    (unit_price as f64 * quantity).round() as i64
 }
 
-#[tauri::command]
-pub fn update_category(
-    state: State<'_, DbState>,
-    payload: UpdateCategoryPayload,
-) -> Result<Category, String> {
-    update_category_inner(&state, payload)
+/// `product_id` of the line holding the rounding adjustment of an order; no
+/// product has this id.
+const ROUNDING_PRODUCT_ID: &str = "rounding";
+
+/// `product_id` of the line holding the payment surcharge of an order; no
+/// product has this id.
+const SURCHARGE_PRODUCT_ID: &str = "surcharge";
+
+/// `product_id` of the lines holding the `OrderAdjustment`s of an order; no
+/// product has this id.
+const ADJUSTMENT_PRODUCT_ID: &str = "adjustment";
+
+fn surcharge_rule_from_row(row: &rusqlite::Row) -> rusqlite::Result<SurchargeRule> {
+    let pm_str: String = row.get(0)?;
+    Ok(SurchargeRule {
+        payment_method: PaymentMethod::from_db_str(&pm_str).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::from(e))
+        })?,
+        amount: row.get(1)?,
+        below: row.get(2)?,
+    })
 }
 
-#[tauri::command]
-pub fn list_products(state: State<'_, DbState>) -> Result<Vec<Product>, String> {
-    list_products_inner(&state)
+fn surcharge_rule(
+    conn: &Connection,
+    payment_method: &PaymentMethod,
+) -> Result<Option<SurchargeRule>, String> {
+    use rusqlite::OptionalExtension;
+
+    conn.query_row(
+        "SELECT payment_method, amount, below FROM surcharge_rules WHERE payment_method = ?1",
+        params![payment_method.as_db_str()],
+        surcharge_rule_from_row,
+    )
+    .optional()
+    .map_err(|e| format!("Query error: {e}"))
 }
 
-#[tauri::command]
-pub fn create_product(
-    state: State<'_, DbState>,
-    payload: CreateProductPayload,
-) -> Result<Product, String> {
-    create_product_inner(&state, payload)
+pub(crate) fn list_surcharge_rules_inner(db: &DbState) -> Result<Vec<SurchargeRule>, String> {
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT payment_method, amount, below FROM surcharge_rules ORDER BY payment_method",
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
+    let rules = stmt
+        .query_map([], surcharge_rule_from_row)
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+    Ok(rules)
 }
 
-#[tauri::command]
-pub fn update_product(
-    state: State<'_, DbState>,
-    payload: UpdateProductPayload,
-) -> Result<Product, String> {
-    update_product_inner(&state, payload)
+fn validate_surcharge_rule(rule: &SurchargeRule) -> Result<(), String> {
+    if rule.amount <= 0 {
+        return Err("Surcharge amount must be greater than 0".to_string());
+    }
+    if rule.below.is_some_and(|below| below <= 0) {
+        return Err("Surcharge threshold must be greater than 0".to_string());
+    }
+    Ok(())
 }
 
-#[tauri::command]
-pub fn toggle_product_availability(
-    state: State<'_, DbState>,
-    product_id: String,
-) -> Result<bool, String> {
-    toggle_product_availability_inner(&state, product_id)
+/// Stores `rule`, replacing the previous rule of its payment method.
+fn save_surcharge_rule(conn: &Connection, rule: &SurchargeRule) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO surcharge_rules (payment_method, amount, below) VALUES (?1, ?2, ?3)
+         ON CONFLICT(payment_method) DO UPDATE SET
+             amount = excluded.amount,
+             below = excluded.below",
+        params![rule.payment_method.as_db_str(), rule.amount, rule.below],
+    )
+    .map_err(|e| format!("Update error: {e}"))?;
+    Ok(())
 }
 
-#[tauri::command]
-pub fn delete_product(state: State<'_, DbState>, product_id: String) -> Result<(), String> {
-    delete_product_inner(&state, product_id)
+/// Sets the surcharge of a payment method, replacing the previous one.
+pub(crate) fn set_surcharge_rule_inner(
+    db: &DbState,
+    rule: SurchargeRule,
+) -> Result<SurchargeRule, String> {
+    validate_surcharge_rule(&rule)?;
+
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    save_surcharge_rule(&conn, &rule)?;
+    Ok(rule)
 }
 
-#[tauri::command]
-pub fn delete_category(state: State<'_, DbState>, category_id: String) -> Result<(), String> {
-    delete_category_inner(&state, category_id)
+/// Stops charging a surcharge for `payment_method`.
+pub(crate) fn delete_surcharge_rule_inner(
+    db: &DbState,
+    payment_method: PaymentMethod,
+) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    conn.execute(
+        "DELETE FROM surcharge_rules WHERE payment_method = ?1",
+        params![payment_method.as_db_str()],
+    )
+    .map_err(|e| format!("Delete error: {e}"))?;
+    Ok(())
 }
 
-#[tauri::command]
-pub fn create_order(
-    state: State<'_, DbState>,
-    payload: CreateOrderPayload,
-) -> Result<OrderWithItems, String> {
-    create_order_inner(&state, payload)
+/// Rounds `cents` to the nearest multiple of `increment`, halves away from
+/// zero.
+fn round_to_increment(cents: i64, increment: i64) -> i64 {
+    if increment <= 1 {
+        return cents;
+    }
+    let rounded = (cents.abs() + increment / 2) / increment * increment;
+    rounded * cents.signum()
 }
 
-#[tauri::command]
-pub fn list_orders(state: State<'_, DbState>) -> Result<Vec<OrderWithItems>, String> {
-    list_orders_inner(&state)
+/// Settings keys holding the `RoundingSettings`.
+const ROUNDING_MODE_KEY: &str = "rounding.mode";
+const ROUNDING_INCREMENT_KEY: &str = "rounding.increment";
+
+fn load_rounding_settings(store: &impl Store) -> Result<RoundingSettings, String> {
+    let defaults = RoundingSettings::default();
+    Ok(RoundingSettings {
+        mode: match store.get_setting(ROUNDING_MODE_KEY)? {
+            Some(mode) => RoundingMode::from_db_str(&mode)?,
+            None => defaults.mode,
+        },
+        increment: parse_number(store, ROUNDING_INCREMENT_KEY, defaults.increment)?,
+    })
 }
 
-#[tauri::command]
-pub fn get_dashboard_summary(state: State<'_, DbState>) -> Result<DashboardSummary, String> {
-    get_dashboard_summary_inner(&state)
+pub(crate) fn get_rounding_settings_inner(store: &impl Store) -> Result<RoundingSettings, String> {
+    load_rounding_settings(store)
 }
 
-#[tauri::command]
-pub fn reset_database(state: State<'_, DbState>) -> Result<(), String> {
-    reset_database_inner(&state)
+pub(crate) fn update_rounding_settings_inner(
+    store: &impl Store,
+    settings: RoundingSettings,
+) -> Result<RoundingSettings, String> {
+    if !(1..=100).contains(&settings.increment) {
+        return Err("Rounding increment must be between 1 and 100 cents".to_string());
+    }
+    store.set_settings(&[
+        (ROUNDING_MODE_KEY, settings.mode.as_db_str().to_string()),
+        (ROUNDING_INCREMENT_KEY, settings.increment.to_string()),
+    ])?;
+    load_rounding_settings(store)
 }
 
-#[tauri::command]
-pub fn get_db_path(state: State<'_, DbState>) -> String {
-    state.db_path.clone()
+/// Settings keys holding the `OrderLimits`.
+const ORDER_MAX_TOTAL_KEY: &str = "orders.max_total";
+const ORDER_MAX_QUANTITY_KEY: &str = "orders.max_quantity";
+
+fn load_order_limits(store: &impl Store) -> Result<OrderLimits, String> {
+    let defaults = OrderLimits::default();
+    Ok(OrderLimits {
+        max_total: parse_number(store, ORDER_MAX_TOTAL_KEY, defaults.max_total)?,
+        max_quantity: parse_number(store, ORDER_MAX_QUANTITY_KEY, defaults.max_quantity)?,
+    })
 }
 
-#[tauri::command]
-pub fn get_app_version() -> AppVersion {
-    AppVersion {
-        version: env!("APP_VERSION").to_string(),
-        os: env!("APP_OS").to_string(),
-        arch: env!("APP_ARCH").to_string(),
+pub(crate) fn get_order_limits_inner(store: &impl Store) -> Result<OrderLimits, String> {
+    load_order_limits(store)
+}
+
+pub(crate) fn update_order_limits_inner(
+    store: &impl Store,
+    limits: OrderLimits,
+) -> Result<OrderLimits, String> {
+    if limits.max_total < 0 {
+        return Err("Maximum order total cannot be negative".to_string());
+    }
+    if !limits.max_quantity.is_finite() || limits.max_quantity < 0.0 {
+        return Err("Maximum quantity per line cannot be negative".to_string());
     }
+    store.set_settings(&[
+        (ORDER_MAX_TOTAL_KEY, limits.max_total.to_string()),
+        (ORDER_MAX_QUANTITY_KEY, limits.max_quantity.to_string()),
+    ])?;
+    load_order_limits(store)
 }
 
-// ── Tests ───────────────────────────────────────────────────────────────────
+/// Fails when a line or the product lines total exceed `limits`, so that
+/// the cashier confirms the order before it is recorded.
+fn check_order_limits(
+    limits: &OrderLimits,
+    items: &[CreateOrderItemPayload],
+) -> Result<(), String> {
+    if limits.max_quantity > 0.0 {
+        if let Some(item) = items.iter().find(|i| i.quantity > limits.max_quantity) {
+            return Err(format!(
+                "Confirmation required: quantity {} of {} is over the limit of {}",
+                item.quantity, item.product_name, limits.max_quantity
+            ));
+        }
+    }
+    let total: i64 = items
+        .iter()
+        .map(|i| line_total(i.unit_price, i.quantity))
+        .sum();
+    if limits.max_total > 0 && total > limits.max_total {
+        return Err(format!(
+            "Confirmation required: total {} EUR is over the limit of {} EUR",
+            export::format_cents(total),
+            export::format_cents(limits.max_total)
+        ));
+    }
+    Ok(())
+}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::db::init_db_in_memory;
+/// Returns the unit `product_id` is sold by. Products that no longer exist
+/// are treated as sold by the piece.
+fn product_unit(conn: &Connection, product_id: &str) -> Result<QuantityUnit, String> {
+    use rusqlite::OptionalExtension;
 
-    fn make_product(db: &DbState, name: &str, price: i64, category_id: &str) -> Product {
-        create_product_inner(
-            db,
-            CreateProductPayload {
-                name: name.to_string(),
-                price,
-                category_id: category_id.to_string(),
-            },
+    let unit: Option<String> = conn
+        .query_row(
+            "SELECT unit FROM products WHERE id = ?1",
+            params![product_id],
+            |row| row.get(0),
         )
-        .expect("create_product_inner failed")
-    }
+        .optional()
+        .map_err(|e| format!("Query error: {e}"))?;
 
-    #[test]
-    fn list_products_returns_defaults() {
-        let db = init_db_in_memory();
-        let products = list_products_inner(&db).unwrap();
-        assert_eq!(products.len(), 20);
+    match unit {
+        Some(unit) => QuantityUnit::from_db_str(&unit),
+        None => Ok(QuantityUnit::Piece),
     }
+}
 
-    #[test]
-    fn create_and_list_products() {
-        let db = init_db_in_memory();
-        let created = make_product(&db, "Cola", 150, "boisson-sans-alcool");
-        assert_eq!(created.name, "Cola");
-        assert_eq!(created.price, 150);
-        assert_eq!(created.category_id, "boisson-sans-alcool");
-        assert!(created.available);
+/// Returns the names of the age-restricted products among `items`.
+/// Items whose product no longer exists are not considered restricted.
+fn restricted_product_names(
+    conn: &Connection,
+    items: &[CreateOrderItemPayload],
+) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare("SELECT name FROM products WHERE id = ?1 AND age_restricted = 1")
+        .map_err(|e| format!("Query error: {e}"))?;
 
-        let products = list_products_inner(&db).unwrap();
-        assert_eq!(products.len(), 21);
-        let cola = products
-            .iter()
-            .find(|p| p.id == created.id)
-            .expect("Cola should be in the list");
-        assert_eq!(cola.name, "Cola");
+    let mut names = Vec::new();
+    for item in items {
+        let mut rows = stmt
+            .query(params![item.product_id])
+            .map_err(|e| format!("Query error: {e}"))?;
+        if let Some(row) = rows.next().map_err(|e| format!("Query error: {e}"))? {
+            let name: String = row.get(0).map_err(|e| format!("Row mapping error: {e}"))?;
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
     }
 
-    #[test]
-    fn update_product_changes_fields() {
-        let db = init_db_in_memory();
-        let p = make_product(&db, "Chips", 200, "snack");
+    Ok(names)
+}
 
-        let updated = update_product_inner(
-            &db,
-            UpdateProductPayload {
-                id: p.id.clone(),
-                name: "Crisps".to_string(),
-                price: 250,
-                category_id: "snack".to_string(),
-                available: false,
-            },
+/// Predecessor hash used for the very first chained order.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Settings key holding the hash of the last purged chained order, which the
+/// oldest remaining order links to.
+const CHAIN_ANCHOR_KEY: &str = "orders.purged_chain_hash";
+
+/// Predecessor hash of the oldest chained order in the database.
+fn chain_start_hash(conn: &Connection) -> Result<String, String> {
+    Ok(crate::db::get_setting(conn, CHAIN_ANCHOR_KEY)?.unwrap_or_else(|| GENESIS_HASH.to_string()))
+}
+
+/// Returns the hash of the most recently chained order, or the hash the chain
+/// starts from.
+fn last_order_hash(conn: &Connection) -> Result<String, String> {
+    use rusqlite::OptionalExtension;
+
+    let hash: Option<String> = conn
+        .query_row(
+            "SELECT hash FROM orders WHERE hash IS NOT NULL ORDER BY rowid DESC LIMIT 1",
+            [],
+            |row| row.get(0),
         )
-        .unwrap();
+        .optional()
+        .map_err(|e| format!("Query error: {e}"))?;
 
-        assert_eq!(updated.id, p.id);
-        assert_eq!(updated.name, "Crisps");
-        assert_eq!(updated.price, 250);
-        assert!(!updated.available);
+    match hash {
+        Some(hash) => Ok(hash),
+        None => chain_start_hash(conn),
+    }
+}
 
-        // Verify via list
-        let products = list_products_inner(&db).unwrap();
-        let crisps = products
-            .iter()
-            .find(|p| p.id == updated.id)
-            .expect("Crisps should be in the list");
-        assert_eq!(crisps.name, "Crisps");
-        assert!(!crisps.available);
+/// SHA-256 over the previous hash and the immutable content of an order and
+/// its items, hex-encoded.
+fn order_hash(previous_hash: &str, order: &Order, items: &[OrderItem]) -> String {
+    use sha2::{Digest, Sha256};
+
+    // Refund links are only hashed when set, so that orders recorded before
+    // refunds existed still verify.
+    let items: Vec<serde_json::Value> = items
+        .iter()
+        .map(|oi| {
+            let mut fields = vec![
+                serde_json::json!(oi.id),
+                serde_json::json!(oi.product_id),
+                serde_json::json!(oi.product_name),
+                serde_json::json!(oi.unit_price),
+                hashed_quantity(oi.quantity),
+                serde_json::json!(oi.total),
+            ];
+            if let Some(refunded_item_id) = &oi.refunded_item_id {
+                fields.push(serde_json::json!(refunded_item_id));
+            }
+            serde_json::Value::Array(fields)
+        })
+        .collect();
+    let mut content = vec![
+        serde_json::json!(previous_hash),
+        serde_json::json!(order.id),
+        serde_json::json!(order.created_at),
+        serde_json::json!(order.total),
+        serde_json::json!(order.payment_method.as_db_str()),
+        serde_json::Value::Array(items),
+    ];
+    if let Some(refund_of) = &order.refund_of {
+        content.push(serde_json::json!(refund_of));
+    }
+    let content = serde_json::Value::Array(content);
+
+    format!("{:x}", Sha256::digest(content.to_string().as_bytes()))
+}
+
+/// Whole quantities are hashed as integers, as they were before decimal
+/// quantities existed, so that older orders still verify.
+fn hashed_quantity(quantity: f64) -> serde_json::Value {
+    if quantity.fract() == 0.0 {
+        serde_json::json!(quantity as i64)
+    } else {
+        serde_json::json!(quantity)
+    }
+}
+
+/// A chained order with its items and the predecessor hash it was linked to.
+struct ChainedOrder {
+    order: OrderWithItems,
+    previous_hash: Option<String>,
+}
+
+/// Loads the chained orders placed within `range`, in chain order.
+fn chained_orders(conn: &Connection, range: &DateRange) -> Result<Vec<ChainedOrder>, String> {
+    let mut order_stmt = conn
+        .prepare(&format!(
+            "SELECT id, created_at, total, payment_method, previous_hash, hash, voided_at,
+                    refund_of, is_test, source
+             FROM orders
+             WHERE hash IS NOT NULL AND {DATE_RANGE_FILTER}
+             ORDER BY rowid"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let orders: Vec<(Order, Option<String>)> = order_stmt
+        .query_map(params![range.from, range.to], |row| {
+            let pm_str: String = row.get(3)?;
+            let payment_method = PaymentMethod::from_db_str(&pm_str).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    3,
+                    rusqlite::types::Type::Text,
+                    Box::from(e),
+                )
+            })?;
+            Ok((
+                Order {
+                    id: row.get(0)?,
+                    created_at: row.get(1)?,
+                    total: row.get(2)?,
+                    payment_method,
+                    hash: row.get(5)?,
+                    voided_at: row.get(6)?,
+                    refund_of: row.get(7)?,
+                    tags: Vec::new(),
+                    is_test: row.get(8)?,
+                    source: order_source_from_row(row, 9)?,
+                },
+                row.get(4)?,
+            ))
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    let mut item_stmt = conn
+        .prepare(
+            "SELECT id, order_id, product_id, product_name, unit_price, quantity, unit, total,
+                    refunded_item_id
+             FROM order_items
+             WHERE order_id = ?1
+             ORDER BY rowid",
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let mut chained = Vec::with_capacity(orders.len());
+    for (mut order, previous_hash) in orders {
+        order.tags = order_tags(conn, &order.id)?;
+        let items: Vec<OrderItem> = item_stmt
+            .query_map(params![order.id], |row| {
+                Ok(OrderItem {
+                    id: row.get(0)?,
+                    order_id: row.get(1)?,
+                    product_id: row.get(2)?,
+                    product_name: row.get(3)?,
+                    unit_price: row.get(4)?,
+                    quantity: row.get(5)?,
+                    unit: quantity_unit_from_row(row, 6)?,
+                    total: row.get(7)?,
+                    refunded_item_id: row.get(8)?,
+                })
+            })
+            .map_err(|e| format!("Query error: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Row mapping error: {e}"))?;
+
+        chained.push(ChainedOrder {
+            order: OrderWithItems { order, items },
+            previous_hash,
+        });
+    }
+
+    Ok(chained)
+}
+
+/// Returns the hash of the most recently chained void, or `GENESIS_HASH`.
+fn last_void_hash(conn: &Connection) -> Result<String, String> {
+    use rusqlite::OptionalExtension;
+
+    let hash: Option<String> = conn
+        .query_row(
+            "SELECT hash FROM order_voids WHERE hash IS NOT NULL ORDER BY rowid DESC LIMIT 1",
+            [],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("Query error: {e}"))?;
+    Ok(hash.unwrap_or_else(|| GENESIS_HASH.to_string()))
+}
+
+/// SHA-256 over the previous void hash and the void, hex-encoded.
+fn void_hash(previous_hash: &str, order_id: &str, voided_at: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    let content = serde_json::json!([previous_hash, order_id, voided_at]);
+    format!("{:x}", Sha256::digest(content.to_string().as_bytes()))
+}
+
+/// Voids `order_id` and chains the void after the last one. Call within the
+/// transaction writing the order's other changes, if any.
+fn record_void(conn: &Connection, order_id: &str, voided_at: &str) -> Result<(), String> {
+    let previous_hash = last_void_hash(conn)?;
+    let hash = void_hash(&previous_hash, order_id, voided_at);
+
+    conn.execute(
+        "UPDATE orders SET voided_at = ?1 WHERE id = ?2",
+        params![voided_at, order_id],
+    )
+    .map_err(|e| format!("Update error: {e}"))?;
+    conn.execute(
+        "INSERT INTO order_voids (order_id, voided_at, previous_hash, hash)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![order_id, voided_at, previous_hash, hash],
+    )
+    .map_err(|e| format!("Insert void error: {e}"))?;
+
+    // Give back what the order consumed, whatever the recipes are now.
+    conn.execute(
+        "INSERT INTO ingredient_movements
+            (id, ingredient_id, quantity, reason, created_at, order_item_id)
+         SELECT lower(hex(randomblob(16))), m.ingredient_id, -m.quantity, ?2, ?3, m.order_item_id
+         FROM ingredient_movements m
+         JOIN order_items oi ON oi.id = m.order_item_id
+         WHERE oi.order_id = ?1 AND m.reason = ?4",
+        params![
+            order_id,
+            INGREDIENT_VOID_REASON,
+            voided_at,
+            INGREDIENT_SALE_REASON
+        ],
+    )
+    .map_err(|e| format!("Insert error: {e}"))?;
+    Ok(())
+}
+
+/// Loads the voids recorded within `range`, in chain order.
+fn order_voids(conn: &Connection, range: &DateRange) -> Result<Vec<OrderVoid>, String> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT order_id, voided_at, previous_hash, hash FROM order_voids
+             WHERE (?1 IS NULL OR date(voided_at, 'localtime') >= ?1)
+               AND (?2 IS NULL OR date(voided_at, 'localtime') <= ?2)
+             ORDER BY rowid",
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
+    let voids = stmt
+        .query_map(params![range.from, range.to], |row| {
+            Ok(OrderVoid {
+                order_id: row.get(0)?,
+                voided_at: row.get(1)?,
+                previous_hash: row.get(2)?,
+                hash: row.get(3)?,
+            })
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+    Ok(voids)
+}
+
+pub(crate) fn verify_order_chain_inner(db: &DbState) -> Result<ChainVerification, String> {
+    use rusqlite::OptionalExtension;
+
+    let conn = db.read_conn()?;
+
+    let mut expected_previous = chain_start_hash(&conn)?;
+    let mut checked_orders = 0;
+    let invalid = |checked_orders, checked_voids, order_id| ChainVerification {
+        valid: false,
+        checked_orders,
+        checked_voids,
+        first_invalid_order_id: Some(order_id),
+    };
+    for chained in chained_orders(&conn, &DateRange::default())? {
+        let OrderWithItems { order, items } = chained.order;
+
+        checked_orders += 1;
+        let stored_hash = order.hash.clone().unwrap_or_default();
+        let linked = chained.previous_hash.as_deref() == Some(expected_previous.as_str());
+        if !linked || order_hash(&expected_previous, &order, &items) != stored_hash {
+            return Ok(invalid(checked_orders, 0, order.id));
+        }
+        expected_previous = stored_hash;
+    }
+
+    let mut expected_previous = GENESIS_HASH.to_string();
+    let mut checked_voids = 0;
+    for void in order_voids(&conn, &DateRange::default())? {
+        let Some(stored_hash) = void.hash else {
+            continue;
+        };
+
+        checked_voids += 1;
+        let linked = void.previous_hash.as_deref() == Some(expected_previous.as_str());
+        if !linked || void_hash(&expected_previous, &void.order_id, &void.voided_at) != stored_hash
+        {
+            return Ok(invalid(checked_orders, checked_voids, void.order_id));
+        }
+        expected_previous = stored_hash;
+    }
+
+    // Orders voided or un-voided without their void. Voids of purged orders
+    // have no order left to compare with.
+    let unmatched: Option<String> = conn
+        .query_row(
+            "SELECT o.id FROM orders o LEFT JOIN order_voids v ON v.order_id = o.id
+             WHERE o.voided_at IS NOT v.voided_at
+             ORDER BY o.rowid LIMIT 1",
+            [],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("Query error: {e}"))?;
+    if let Some(order_id) = unmatched {
+        return Ok(invalid(checked_orders, checked_voids, order_id));
+    }
+
+    Ok(ChainVerification {
+        valid: true,
+        checked_orders,
+        checked_voids,
+        first_invalid_order_id: None,
+    })
+}
+
+/// Identifies the journal export format; bump `JOURNAL_FORMAT_VERSION` on
+/// any incompatible change.
+const JOURNAL_FORMAT: &str = "pos-journal";
+const JOURNAL_FORMAT_VERSION: i64 = 2;
+
+pub(crate) fn export_journal_inner(
+    db: &DbState,
+    range: DateRange,
+    path: String,
+) -> Result<Journal, String> {
+    use sha2::{Digest, Sha256};
+
+    validate_range(&range)?;
+
+    let (chained, voids) = {
+        let conn = db.read_conn()?;
+        (chained_orders(&conn, &range)?, order_voids(&conn, &range)?)
+    };
+
+    // Orders and voids in time order; an order voided within the second it
+    // was placed still comes first, the sort being stable.
+    let mut events: Vec<(String, JournalEntry)> = chained
+        .into_iter()
+        .map(|chained| {
+            (
+                chained.order.order.created_at.clone(),
+                JournalEntry::Order {
+                    sequence: 0,
+                    previous_hash: chained.previous_hash,
+                    order: chained.order,
+                },
+            )
+        })
+        .chain(voids.into_iter().map(|void| {
+            (
+                void.voided_at.clone(),
+                JournalEntry::Void { sequence: 0, void },
+            )
+        }))
+        .collect();
+    events.sort_by(|a, b| a.0.cmp(&b.0));
+
+    // The journal hash covers the entry hashes in sequence, so removing,
+    // reordering or altering any entry changes it.
+    let mut journal_hasher = Sha256::new();
+    let entries: Vec<JournalEntry> = events
+        .into_iter()
+        .enumerate()
+        .map(|(i, (_, mut entry))| {
+            match &mut entry {
+                JournalEntry::Order {
+                    sequence, order, ..
+                } => {
+                    *sequence = i as i64 + 1;
+                    journal_hasher.update(order.order.hash.as_deref().unwrap_or_default());
+                }
+                JournalEntry::Void { sequence, void } => {
+                    *sequence = i as i64 + 1;
+                    journal_hasher.update(void.hash.as_deref().unwrap_or_default());
+                }
+            }
+            entry
+        })
+        .collect();
+
+    let journal = Journal {
+        format: JOURNAL_FORMAT.to_string(),
+        version: JOURNAL_FORMAT_VERSION,
+        app_version: env!("APP_VERSION").to_string(),
+        generated_at: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        range,
+        journal_hash: format!("{:x}", journal_hasher.finalize()),
+        entries,
+    };
+
+    let json = serde_json::to_string_pretty(&journal)
+        .map_err(|e| format!("Failed to serialize journal: {e}"))?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write {path}: {e}"))?;
+
+    Ok(journal)
+}
+
+/// Identifies the data dump format; bump `DUMP_FORMAT_VERSION` on any
+/// incompatible change.
+const DUMP_FORMAT: &str = "pos-dump";
+const DUMP_FORMAT_VERSION: i64 = 1;
+
+/// Settings left out of data dumps, so that a dump can be attached to a bug
+/// report without leaking credentials.
+const SECRET_SETTING_KEYS: [&str; 4] = [
+    SMTP_PASSWORD_KEY,
+    NOTIFY_SLACK_WEBHOOK_KEY,
+    NOTIFY_TELEGRAM_TOKEN_KEY,
+    ADMIN_PIN_KEY,
+];
+
+/// Settings describing the state of this database rather than its
+/// configuration, or pointing at catalogs and registers that exports do not
+/// carry, left out of settings exports and data dumps.
+const LOCAL_SETTING_KEYS: [&str; 6] = [
+    ADMIN_PIN_FAILURES_KEY,
+    ADMIN_PIN_LOCKED_UNTIL_KEY,
+    CHAIN_ANCHOR_KEY,
+    SCHEDULER_ERROR_KEY,
+    ACTIVE_CATALOG_KEY,
+    CURRENT_REGISTER_KEY,
+];
+
+/// Identifies the settings export format; bump `SETTINGS_FORMAT_VERSION` on
+/// any incompatible change.
+const SETTINGS_FORMAT: &str = "pos-settings";
+const SETTINGS_FORMAT_VERSION: i64 = 1;
+
+/// Reads everything a `DataDump` holds. Run inside a transaction so that the
+/// tables are read as of the same point in time.
+fn data_dump(conn: &Connection) -> Result<DataDump, String> {
+    use rusqlite::OptionalExtension;
+
+    let schema_version: i64 = conn
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {CATEGORY_COLUMNS} FROM categories ORDER BY rowid"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+    let mut categories = stmt
+        .query_map([], category_from_row)
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+    for category in &mut categories {
+        category.path = category_path(conn, &category.id)?;
+    }
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {PRODUCT_COLUMNS} FROM products ORDER BY rowid"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+    let products = stmt
+        .query_map([], product_from_row)
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    // Unlike `chained_orders`, this includes the orders recorded before the
+    // chain was introduced.
+    let mut stmt = conn
+        .prepare("SELECT id, previous_hash FROM orders ORDER BY rowid")
+        .map_err(|e| format!("Query error: {e}"))?;
+    let links = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?))
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT confirmed_at, restricted_products FROM age_confirmations
+             WHERE order_id = ?1",
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
+    let mut orders = Vec::with_capacity(links.len());
+    for (order_id, previous_hash) in links {
+        let age_confirmation = stmt
+            .query_row(params![order_id], |row| {
+                Ok(AgeConfirmation {
+                    confirmed_at: row.get(0)?,
+                    restricted_products: row.get(1)?,
+                })
+            })
+            .optional()
+            .map_err(|e| format!("Query error: {e}"))?;
+        orders.push(DumpedOrder {
+            order: load_order(conn, &order_id)?,
+            previous_hash,
+            age_confirmation,
+        });
+    }
+    let voids = order_voids(conn, &DateRange::default())?;
+
+    let mut stmt = conn
+        .prepare("SELECT key, value FROM settings ORDER BY key")
+        .map_err(|e| format!("Query error: {e}"))?;
+    let settings: std::collections::BTreeMap<String, String> = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))
+        .map_err(|e| format!("Query error: {e}"))?
+        .filter(|setting| {
+            !matches!(setting, Ok((key, _))
+                if SECRET_SETTING_KEYS.contains(&key.as_str())
+                    || LOCAL_SETTING_KEYS.contains(&key.as_str()))
+        })
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    Ok(DataDump {
+        format: DUMP_FORMAT.to_string(),
+        version: DUMP_FORMAT_VERSION,
+        app_version: env!("APP_VERSION").to_string(),
+        schema_version,
+        generated_at: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        categories,
+        products,
+        orders,
+        voids,
+        settings,
+    })
+}
+
+/// Writes the categories, products, orders and settings to `path` as one
+/// JSON `DataDump`, to move the data to another till or attach it to a bug
+/// report.
+pub(crate) fn export_all_data_inner(db: &DbState, path: String) -> Result<(), String> {
+    let dump = {
+        let mut conn = db.read_conn()?;
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Transaction begin error: {e}"))?;
+        data_dump(&tx)?
+    };
+
+    let json = serde_json::to_string_pretty(&dump)
+        .map_err(|e| format!("Failed to serialize data dump: {e}"))?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write {path}: {e}"))
+}
+
+/// Whether a row with this `id` exists in `table`.
+fn row_exists(conn: &Connection, table: &str, id: &str) -> Result<bool, String> {
+    conn.query_row(
+        &format!("SELECT EXISTS(SELECT 1 FROM {table} WHERE id = ?1)"),
+        params![id],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("Query error: {e}"))
+}
+
+/// Deletes the rows of `table` whose id is not in `keep`, returning how many
+/// were deleted.
+fn delete_rows_not_in(
+    conn: &Connection,
+    table: &str,
+    column: &str,
+    keep: &[&str],
+) -> Result<u32, String> {
+    let mut stmt = conn
+        .prepare(&format!("SELECT {column} FROM {table}"))
+        .map_err(|e| format!("Query error: {e}"))?;
+    let ids = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    let mut deleted = 0;
+    for id in ids.iter().filter(|id| !keep.contains(&id.as_str())) {
+        conn.execute(
+            &format!("DELETE FROM {table} WHERE {column} = ?1"),
+            params![id],
+        )
+        .map_err(|e| format!("Delete error: {e}"))?;
+        deleted += 1;
+    }
+    Ok(deleted)
+}
+
+fn import_categories(
+    conn: &Connection,
+    categories: &[Category],
+    strategy: ImportStrategy,
+) -> Result<ImportCounts, String> {
+    let mut counts = ImportCounts::default();
+    for category in categories {
+        let values = params![
+            category.label,
+            category.color,
+            translations_json(&category.translations),
+            category.icon,
+            category.parent_id,
+            category.id
+        ];
+        if !row_exists(conn, "categories", &category.id)? {
+            conn.execute(
+                "INSERT INTO categories (label, color, translations, icon, parent_id, id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                values,
+            )
+            .map_err(|e| format!("Insert error: {e}"))?;
+            counts.inserted += 1;
+        } else if strategy == ImportStrategy::Skip {
+            counts.skipped += 1;
+        } else {
+            conn.execute(
+                "UPDATE categories
+                 SET label = ?1, color = ?2, translations = ?3, icon = ?4, parent_id = ?5
+                 WHERE id = ?6",
+                values,
+            )
+            .map_err(|e| format!("Update error: {e}"))?;
+            counts.updated += 1;
+        }
+    }
+    Ok(counts)
+}
+
+fn import_products(
+    conn: &Connection,
+    products: &[Product],
+    strategy: ImportStrategy,
+) -> Result<ImportCounts, String> {
+    let mut counts = ImportCounts::default();
+    for product in products {
+        if conn.get_product(&product.id)?.is_none() {
+            conn.insert_product(product)?;
+            counts.inserted += 1;
+        } else if strategy == ImportStrategy::Skip {
+            counts.skipped += 1;
+        } else {
+            conn.update_product(product)?;
+            counts.updated += 1;
+        }
+    }
+    Ok(counts)
+}
+
+/// Orders are never updated or deleted, whatever the strategy: they are
+/// restored with their original chain links, and an order whose id is taken
+/// by a different order is left out with a warning. So are their voids.
+/// When this database holds chained orders or voids the dump does not, the
+/// two chains cannot be merged: no order is imported and a warning says so.
+fn import_orders(
+    conn: &Connection,
+    orders: &[DumpedOrder],
+    voids: &[OrderVoid],
+    warnings: &mut Vec<String>,
+) -> Result<ImportCounts, String> {
+    let mut counts = ImportCounts::default();
+
+    let local_ids = |sql: &str| -> Result<Vec<String>, String> {
+        let mut stmt = conn.prepare(sql).map_err(|e| format!("Query error: {e}"))?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(|e| format!("Query error: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Row mapping error: {e}"))?;
+        Ok(ids)
+    };
+    let diverged = local_ids("SELECT id FROM orders WHERE hash IS NOT NULL")?
+        .iter()
+        .any(|id| !orders.iter().any(|d| &d.order.order.id == id))
+        || local_ids("SELECT order_id FROM order_voids WHERE hash IS NOT NULL")?
+            .iter()
+            .any(|id| !voids.iter().any(|void| &void.order_id == id));
+    if diverged {
+        let mut new_orders = 0;
+        for dumped in orders {
+            if !row_exists(conn, "orders", &dumped.order.order.id)? {
+                new_orders += 1;
+            }
+        }
+        if new_orders > 0 {
+            warnings.push(format!(
+                "{new_orders} orders were not imported: this database already holds sales \
+                 that are not in the dump, and adding them would break the order chain"
+            ));
+            counts.skipped = orders.len() as u32;
+            return Ok(counts);
+        }
+    }
+
+    let mut inserted = Vec::new();
+    for dumped in orders {
+        let OrderWithItems { order, items } = &dumped.order;
+        if row_exists(conn, "orders", &order.id)? {
+            // Tags and test flags are not part of the sale, only compare the
+            // rest.
+            let mut existing = load_order(conn, &order.id)?;
+            existing.order.tags.clone_from(&order.tags);
+            existing.order.is_test = order.is_test;
+            if existing != dumped.order {
+                warnings.push(format!(
+                    "Order {} differs from the existing order with the same id",
+                    order.id
+                ));
+            }
+            counts.skipped += 1;
+            continue;
+        }
+
+        conn.execute(
+            "INSERT INTO orders
+                (id, created_at, total, payment_method, previous_hash, hash, voided_at, refund_of,
+                 is_test, source)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                order.id,
+                order.created_at,
+                order.total,
+                order.payment_method.as_db_str(),
+                dumped.previous_hash,
+                order.hash,
+                order.voided_at,
+                order.refund_of,
+                order.is_test,
+                order.source.as_db_str()
+            ],
+        )
+        .map_err(|e| format!("Insert order error: {e}"))?;
+        insert_order_items(conn, items)?;
+        // Dumps carry no recipes: the sale consumes what the local ones say.
+        if order.voided_at.is_none() {
+            record_ingredient_consumption(conn, &order.created_at, items)?;
+        }
+        if let Some(confirmation) = &dumped.age_confirmation {
+            conn.execute(
+                "INSERT INTO age_confirmations (order_id, confirmed_at, restricted_products)
+                 VALUES (?1, ?2, ?3)",
+                params![
+                    order.id,
+                    confirmation.confirmed_at,
+                    confirmation.restricted_products
+                ],
+            )
+            .map_err(|e| format!("Insert age confirmation error: {e}"))?;
+        }
+        for tag in &order.tags {
+            conn.execute(
+                "INSERT OR IGNORE INTO order_tags (order_id, tag) VALUES (?1, ?2)",
+                params![order.id, tag],
+            )
+            .map_err(|e| format!("Insert error: {e}"))?;
+        }
+        counts.inserted += 1;
+        inserted.push(dumped);
+    }
+
+    // In chain order, after the orders: the voids of a dump written before
+    // voids were chained are recorded unchained, as by 35-void-chain.
+    let dumped_voids = voids
+        .iter()
+        .filter(|void| inserted.iter().any(|d| d.order.order.id == void.order_id));
+    let unchained_voids = inserted.iter().filter_map(|d| {
+        let order = &d.order.order;
+        let voided_at = order.voided_at.clone()?;
+        (!voids.iter().any(|void| void.order_id == order.id)).then(|| OrderVoid {
+            order_id: order.id.clone(),
+            voided_at,
+            previous_hash: None,
+            hash: None,
+        })
+    });
+    for void in dumped_voids.cloned().chain(unchained_voids) {
+        conn.execute(
+            "INSERT INTO order_voids (order_id, voided_at, previous_hash, hash)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![void.order_id, void.voided_at, void.previous_hash, void.hash],
+        )
+        .map_err(|e| format!("Insert void error: {e}"))?;
+    }
+    Ok(counts)
+}
+
+/// Credentials and the state of this database are not part of dumps, so
+/// they are never imported nor deleted.
+fn import_setting_values(
+    conn: &Connection,
+    settings: &std::collections::BTreeMap<String, String>,
+    strategy: ImportStrategy,
+) -> Result<ImportCounts, String> {
+    let mut counts = ImportCounts::default();
+    for (key, value) in settings {
+        if SECRET_SETTING_KEYS.contains(&key.as_str()) || LOCAL_SETTING_KEYS.contains(&key.as_str())
+        {
+            continue;
+        }
+        match crate::db::get_setting(conn, key)? {
+            None => counts.inserted += 1,
+            Some(_) if strategy == ImportStrategy::Skip => {
+                counts.skipped += 1;
+                continue;
+            }
+            Some(_) => counts.updated += 1,
+        }
+        crate::db::set_setting(conn, key, value)?;
+    }
+
+    if strategy == ImportStrategy::Overwrite {
+        let keep: Vec<&str> = settings
+            .keys()
+            .map(String::as_str)
+            .chain(SECRET_SETTING_KEYS)
+            .chain(LOCAL_SETTING_KEYS)
+            .collect();
+        counts.deleted = delete_rows_not_in(conn, "settings", "key", &keep)?;
+    }
+    Ok(counts)
+}
+
+/// Restores a `DataDump` written by `export_all_data`, in a single
+/// transaction. See `ImportStrategy` for what happens to existing records;
+/// orders are only ever added.
+pub(crate) fn import_all_data_inner(
+    db: &DbState,
+    path: String,
+    strategy: ImportStrategy,
+    admin_pin: Option<String>,
+) -> Result<ImportReport, String> {
+    verify_admin_pin(db, admin_pin.as_deref())?;
+
+    let json = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {path}: {e}"))?;
+    let dump: DataDump =
+        serde_json::from_str(&json).map_err(|e| format!("Invalid data dump {path}: {e}"))?;
+    if dump.format != DUMP_FORMAT {
+        return Err(format!("Not a data dump: {path}"));
+    }
+    if dump.version > DUMP_FORMAT_VERSION {
+        return Err(format!(
+            "Data dump version {} is not supported by this version of the app",
+            dump.version
+        ));
+    }
+
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Transaction begin error: {e}"))?;
+    // Records reference each other (parent categories, refunds...): check
+    // the references once everything is in place.
+    tx.execute_batch("PRAGMA defer_foreign_keys = ON;")
+        .map_err(|e| format!("Import error: {e}"))?;
+
+    let mut report = ImportReport {
+        categories: import_categories(&tx, &dump.categories, strategy)?,
+        products: import_products(&tx, &dump.products, strategy)?,
+        settings: import_setting_values(&tx, &dump.settings, strategy)?,
+        ..Default::default()
+    };
+    report.orders = import_orders(&tx, &dump.orders, &dump.voids, &mut report.warnings)?;
+
+    if strategy == ImportStrategy::Overwrite {
+        let product_ids: Vec<&str> = dump.products.iter().map(|p| p.id.as_str()).collect();
+        report.products.deleted = delete_rows_not_in(&tx, "products", "id", &product_ids)?;
+        let category_ids: Vec<&str> = dump.categories.iter().map(|c| c.id.as_str()).collect();
+        report.categories.deleted = delete_rows_not_in(&tx, "categories", "id", &category_ids)?;
+    }
+
+    tx.commit()
+        .map_err(|e| format!("Transaction commit error: {e}"))?;
+
+    Ok(report)
+}
+
+/// Writes the configuration of this till to `path` (JSON), so that another
+/// till can be set up the same way with `import_settings`. Credentials and
+/// the admin PIN are left out.
+pub(crate) fn export_settings_inner(db: &DbState, path: String) -> Result<(), String> {
+    let (settings, report_presets) = {
+        let conn = db.read_conn()?;
+        let mut stmt = conn
+            .prepare("SELECT key, value FROM settings ORDER BY key")
+            .map_err(|e| format!("Query error: {e}"))?;
+        let settings: std::collections::BTreeMap<String, String> = stmt
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))
+            .map_err(|e| format!("Query error: {e}"))?
+            .filter(|setting| {
+                !matches!(setting, Ok((key, _))
+                    if SECRET_SETTING_KEYS.contains(&key.as_str())
+                        || LOCAL_SETTING_KEYS.contains(&key.as_str()))
+            })
+            .collect::<Result<_, _>>()
+            .map_err(|e| format!("Row mapping error: {e}"))?;
+
+        let mut stmt = conn
+            .prepare("SELECT id FROM report_presets ORDER BY name")
+            .map_err(|e| format!("Query error: {e}"))?;
+        let preset_ids = stmt
+            .query_map([], |row| row.get::<_, String>(0))
+            .map_err(|e| format!("Query error: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Row mapping error: {e}"))?;
+        let report_presets = preset_ids
+            .iter()
+            .map(|id| load_report_preset(&conn, id))
+            .collect::<Result<Vec<_>, _>>()?;
+        (settings, report_presets)
+    };
+
+    let export = SettingsExport {
+        format: SETTINGS_FORMAT.to_string(),
+        version: SETTINGS_FORMAT_VERSION,
+        app_version: env!("APP_VERSION").to_string(),
+        generated_at: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        settings,
+        report_presets,
+        scheduled_jobs: list_scheduled_jobs_inner(db)?
+            .into_iter()
+            .map(|job| ScheduledJob {
+                last_run_at: None,
+                last_error: None,
+                ..job
+            })
+            .collect(),
+        features: get_features_inner(db)?,
+        surcharge_rules: list_surcharge_rules_inner(db)?,
+        station_routes: list_station_routes_inner(db)?,
+        palette_colors: list_palette_inner(db)?,
+    };
+    let json = serde_json::to_string_pretty(&export)
+        .map_err(|e| format!("Failed to serialize settings: {e}"))?;
+    std::fs::write(&path, json).map_err(|e| format!("Failed to write {path}: {e}"))
+}
+
+/// Applies a `SettingsExport` written by `export_settings`, in a single
+/// transaction. Settings, schedules, surcharge rules and station routes are
+/// overwritten, report presets are matched by name and palette colors by
+/// color; nothing absent from the file is removed.
+pub(crate) fn import_settings_inner(
+    db: &DbState,
+    path: String,
+    admin_pin: Option<String>,
+) -> Result<ImportCounts, String> {
+    verify_admin_pin(db, admin_pin.as_deref())?;
+
+    let json = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read {path}: {e}"))?;
+    let export: SettingsExport =
+        serde_json::from_str(&json).map_err(|e| format!("Invalid settings file {path}: {e}"))?;
+    if export.format != SETTINGS_FORMAT {
+        return Err(format!("Not a settings file: {path}"));
+    }
+    if export.version > SETTINGS_FORMAT_VERSION {
+        return Err(format!(
+            "Settings file version {} is not supported by this version of the app",
+            export.version
+        ));
+    }
+
+    let settings: std::collections::BTreeMap<String, String> = export
+        .settings
+        .into_iter()
+        .filter(|(key, _)| {
+            !SECRET_SETTING_KEYS.contains(&key.as_str())
+                && !LOCAL_SETTING_KEYS.contains(&key.as_str())
+        })
+        .collect();
+    for preset in &export.report_presets {
+        validate_report_preset(&preset.name, &preset.config)?;
+    }
+    for job in &export.scheduled_jobs {
+        chrono::NaiveTime::parse_from_str(&job.time, "%H:%M")
+            .map_err(|e| format!("Invalid time '{}' (expected HH:MM): {e}", job.time))?;
+    }
+    for rule in &export.surcharge_rules {
+        validate_surcharge_rule(rule)?;
+    }
+    if export
+        .station_routes
+        .iter()
+        .any(|r| r.station.trim().is_empty())
+    {
+        return Err("Station name cannot be empty".to_string());
+    }
+    let palette = normalize_palette(export.palette_colors)?;
+
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Transaction begin error: {e}"))?;
+
+    let counts = import_setting_values(&tx, &settings, ImportStrategy::Merge)?;
+    for preset in &export.report_presets {
+        tx.execute(
+            "INSERT INTO report_presets (id, name, config) VALUES (?1, ?2, ?3)
+             ON CONFLICT(name) DO UPDATE SET config = excluded.config",
+            params![
+                Uuid::new_v4().to_string(),
+                preset.name.trim(),
+                report_preset_config_json(&preset.config)?
+            ],
+        )
+        .map_err(|e| format!("Import error: {e}"))?;
+    }
+    for job in &export.scheduled_jobs {
+        tx.execute(
+            "INSERT INTO scheduled_jobs (kind, enabled, time, recipients)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(kind) DO UPDATE SET
+                 enabled = excluded.enabled,
+                 time = excluded.time,
+                 recipients = excluded.recipients",
+            params![
+                job.kind.as_db_str(),
+                job.enabled,
+                job.time,
+                serde_json::to_string(&job.recipients)
+                    .map_err(|e| format!("Failed to serialize recipients: {e}"))?
+            ],
+        )
+        .map_err(|e| format!("Import error: {e}"))?;
+    }
+    for flag in &export.features {
+        save_feature(&tx, flag.feature, flag.enabled)?;
+    }
+    for rule in &export.surcharge_rules {
+        save_surcharge_rule(&tx, rule)?;
+    }
+    for route in &export.station_routes {
+        tx.execute(
+            "INSERT INTO station_routes (category_id, station)
+             SELECT id, ?2 FROM categories WHERE id = ?1
+             ON CONFLICT(category_id) DO UPDATE SET station = excluded.station",
+            params![route.category_id, route.station.trim()],
+        )
+        .map_err(|e| format!("Import error: {e}"))?;
+    }
+    for entry in &palette {
+        tx.execute(
+            "INSERT INTO palette_colors (color, name, position)
+             VALUES (?1, ?2, (SELECT COALESCE(MAX(position) + 1, 0) FROM palette_colors))
+             ON CONFLICT(color) DO UPDATE SET name = excluded.name",
+            params![entry.color, entry.name],
+        )
+        .map_err(|e| format!("Import error: {e}"))?;
+    }
+
+    tx.commit()
+        .map_err(|e| format!("Transaction commit error: {e}"))?;
+    Ok(counts)
+}
+
+/// How long after its creation the last order can still be undone.
+const UNDO_WINDOW_MINUTES: i64 = 5;
+
+/// Voids the most recent order, provided it was placed less than
+/// `UNDO_WINDOW_MINUTES` ago. The order stays in the hash chain and the
+/// journal, followed by its void; only the sales figures leave it out.
+pub(crate) fn undo_last_order_inner(
+    db: &DbState,
+    admin_pin: Option<String>,
+) -> Result<OrderWithItems, String> {
+    use rusqlite::OptionalExtension;
+
+    verify_admin_pin(db, admin_pin.as_deref())?;
+
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+
+    let (order_id, created_at, voided_at): (String, String, Option<String>) = conn
+        .query_row(
+            "SELECT id, created_at, voided_at FROM orders ORDER BY rowid DESC LIMIT 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()
+        .map_err(|e| format!("Query error: {e}"))?
+        .ok_or_else(|| "There is no order to undo".to_string())?;
+
+    if voided_at.is_some() {
+        return Err("The last order was already voided".to_string());
+    }
+
+    let created_at = chrono::DateTime::parse_from_rfc3339(&created_at)
+        .map_err(|e| format!("Invalid order date {created_at}: {e}"))?;
+    let now = chrono::Utc::now();
+    if now.signed_duration_since(created_at) > chrono::Duration::minutes(UNDO_WINDOW_MINUTES) {
+        return Err(format!(
+            "The last order is more than {UNDO_WINDOW_MINUTES} minutes old and can no longer be undone"
+        ));
+    }
+
+    let voided_at = now.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    write_transaction(&mut conn, |tx| record_void(tx, &order_id, &voided_at))?;
+
+    load_order(&conn, &order_id)
+}
+
+/// Tags of an order, sorted.
+fn order_tags(conn: &Connection, order_id: &str) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare("SELECT tag FROM order_tags WHERE order_id = ?1 ORDER BY tag")
+        .map_err(|e| format!("Query error: {e}"))?;
+    let tags = stmt
+        .query_map(params![order_id], |row| row.get(0))
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+    Ok(tags)
+}
+
+fn normalize_tag(tag: &str) -> Result<&str, String> {
+    let tag = tag.trim();
+    if tag.is_empty() {
+        return Err("Tag cannot be empty".to_string());
+    }
+    Ok(tag)
+}
+
+/// Adds a free-form tag to an order, e.g. "VIP". Tagging an order twice
+/// with the same tag has no effect.
+pub(crate) fn tag_order_inner(
+    db: &DbState,
+    order_id: String,
+    tag: String,
+) -> Result<OrderWithItems, String> {
+    let tag = normalize_tag(&tag)?;
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    // Fails when the order does not exist.
+    load_order(&conn, &order_id)?;
+
+    conn.execute(
+        "INSERT OR IGNORE INTO order_tags (order_id, tag) VALUES (?1, ?2)",
+        params![order_id, tag],
+    )
+    .map_err(|e| format!("Insert error: {e}"))?;
+
+    load_order(&conn, &order_id)
+}
+
+pub(crate) fn untag_order_inner(
+    db: &DbState,
+    order_id: String,
+    tag: String,
+) -> Result<OrderWithItems, String> {
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    conn.execute(
+        "DELETE FROM order_tags WHERE order_id = ?1 AND tag = ?2",
+        params![order_id, tag.trim()],
+    )
+    .map_err(|e| format!("Delete error: {e}"))?;
+
+    load_order(&conn, &order_id)
+}
+
+/// Flags an order as a test sale, or clears the flag.
+pub(crate) fn set_order_test_flag_inner(
+    db: &DbState,
+    order_id: String,
+    is_test: bool,
+) -> Result<OrderWithItems, String> {
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    // Fails when the order does not exist.
+    load_order(&conn, &order_id)?;
+
+    conn.execute(
+        "UPDATE orders SET is_test = ?1 WHERE id = ?2",
+        params![is_test, order_id],
+    )
+    .map_err(|e| format!("Update error: {e}"))?;
+
+    load_order(&conn, &order_id)
+}
+
+/// Voids every order flagged as a test sale, in one transaction, so that
+/// they no longer count in sales figures. They stay in the order chain, as
+/// any voided order. Returns the orders voided.
+pub(crate) fn purge_test_orders_inner(
+    db: &DbState,
+    admin_pin: Option<String>,
+) -> Result<Vec<OrderWithItems>, String> {
+    verify_admin_pin(db, admin_pin.as_deref())?;
+
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Transaction begin error: {e}"))?;
+    let mut stmt = tx
+        .prepare("SELECT id FROM orders WHERE is_test = 1 AND voided_at IS NULL")
+        .map_err(|e| format!("Query error: {e}"))?;
+    let ids: Vec<String> = stmt
+        .query_map([], |row| row.get(0))
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+    drop(stmt);
+    let voided_at = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    for id in &ids {
+        record_void(&tx, id, &voided_at)?;
+    }
+    let voided = ids
+        .iter()
+        .map(|id| load_order(&tx, id))
+        .collect::<Result<Vec<_>, _>>()?;
+    tx.commit()
+        .map_err(|e| format!("Transaction commit error: {e}"))?;
+    Ok(voided)
+}
+
+/// Every tag in use, sorted, e.g. to suggest existing tags.
+pub(crate) fn list_order_tags_inner(db: &DbState) -> Result<Vec<String>, String> {
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let mut stmt = conn
+        .prepare("SELECT DISTINCT tag FROM order_tags ORDER BY tag")
+        .map_err(|e| format!("Query error: {e}"))?;
+    let tags = stmt
+        .query_map([], |row| row.get(0))
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+    Ok(tags)
+}
+
+/// Lists the orders, most recent first. With a `tag`, only the orders
+/// carrying it are returned.
+pub(crate) fn list_orders_inner(
+    db: &DbState,
+    tag: Option<String>,
+) -> Result<Vec<OrderWithItems>, String> {
+    list_orders_page_inner(db, tag, OrderPageQuery::default())
+}
+
+/// Event emitted with each order recorded, refunds included, so that the
+/// order history stays current without reloading its pages.
+pub const ORDER_CREATED_EVENT: &str = "order-created";
+
+/// Event emitted with each order voided.
+pub const ORDER_VOIDED_EVENT: &str = "order-voided";
+
+/// Event emitted with a `SalesTick` after orders are recorded.
+pub const SALES_TICK_EVENT: &str = "sales-tick";
+
+/// Event emitted with the `DashboardSummary` of all products and sources
+/// whenever the data it summarizes changes (orders, categories, products,
+/// display currency), so that the dashboard never polls.
+pub const DASHBOARD_UPDATED_EVENT: &str = "dashboard-updated";
+
+/// Emits `event` once per order, then `DASHBOARD_UPDATED_EVENT`. Failures
+/// are ignored: a screen that missed an event can still reload the history.
+fn emit_orders(app: &AppHandle, event: &str, orders: &[OrderWithItems]) {
+    for order in orders {
+        let _ = app.emit(event, order);
+    }
+    emit_dashboard_update(app);
+}
+
+/// Recomputes the cached dashboard and emits it with
+/// `DASHBOARD_UPDATED_EVENT`.
+fn emit_dashboard_update(app: &AppHandle) {
+    let db = app.state::<DbState>();
+    let cache = app.state::<DashboardCache>();
+    if let Ok(summary) = refresh_dashboard_inner(&db, &cache) {
+        let _ = app.emit(DASHBOARD_UPDATED_EVENT, summary);
+    }
+}
+
+/// Emits `ORDER_CREATED_EVENT` for each of the `orders` just recorded, then
+/// one `SALES_TICK_EVENT`, and writes their preparation tickets.
+fn emit_created_orders(app: &AppHandle, db: &DbState, orders: &[OrderWithItems]) {
+    emit_orders(app, ORDER_CREATED_EVENT, orders);
+    if let Ok(tick) = sales_tick_inner(db, orders) {
+        let _ = app.emit(SALES_TICK_EVENT, tick);
+    }
+    print_created_orders(app, db, orders);
+}
+
+/// Writes the preparation tickets of `orders` to the `TICKETS_FOLDER` of the
+/// app data dir. Failures are ignored: the orders are recorded all the same,
+/// and a missing ticket can be written again with `generate_station_tickets`
+/// or `generate_kitchen_ticket`.
+fn print_created_orders(app: &AppHandle, db: &DbState, orders: &[OrderWithItems]) {
+    let Ok(data_dir) = app.path().app_data_dir() else {
+        return;
+    };
+    let dir = data_dir.join(TICKETS_FOLDER);
+    for order in orders {
+        let _ = write_order_tickets_inner(db, &order.order.id, &dir);
+    }
+}
+
+/// The day's figures after recording `orders`.
+pub(crate) fn sales_tick_inner(
+    db: &DbState,
+    orders: &[OrderWithItems],
+) -> Result<SalesTick, String> {
+    let today = get_today_stats_inner(db)?;
+    Ok(SalesTick {
+        daily_total: today.total_revenue,
+        order_count: today.order_count,
+        last_order_total: orders.iter().map(|o| o.order.total).sum(),
+    })
+}
+
+/// Ids of the orders of a page, most recent first. Parameters: ?1 tag,
+/// ?2 id of the order the page starts after, ?3 page size (-1: no limit).
+const ORDER_PAGE_IDS: &str = "SELECT id FROM orders
+     WHERE (?1 IS NULL OR id IN (SELECT order_id FROM order_tags WHERE tag = ?1))
+       AND (?2 IS NULL
+            OR (created_at, rowid) < (SELECT created_at, rowid FROM orders WHERE id = ?2))
+     ORDER BY created_at DESC, rowid DESC
+     LIMIT ?3";
+
+/// A page of `list_orders_inner`. The next page starts after the last order
+/// of this one; orders created meanwhile are delivered by the
+/// `ORDER_CREATED_EVENT` instead.
+pub(crate) fn list_orders_page_inner(
+    db: &DbState,
+    tag: Option<String>,
+    page: OrderPageQuery,
+) -> Result<Vec<OrderWithItems>, String> {
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let limit = page
+        .limit
+        .map_or(-1, |limit| i64::try_from(limit).unwrap_or(i64::MAX));
+    let page_params = params![tag, page.before, limit];
+
+    let mut order_stmt = conn
+        .prepare(&format!(
+            "SELECT id, created_at, total, payment_method, hash, voided_at, refund_of,
+                    is_test, source
+             FROM orders
+             WHERE id IN ({ORDER_PAGE_IDS})
+             ORDER BY created_at DESC, rowid DESC"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let orders: Vec<Order> = order_stmt
+        .query_map(page_params, |row| {
+            let pm_str: String = row.get(3)?;
+            let payment_method = PaymentMethod::from_db_str(&pm_str).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    3,
+                    rusqlite::types::Type::Text,
+                    Box::from(e),
+                )
+            })?;
+            Ok(Order {
+                id: row.get(0)?,
+                created_at: row.get(1)?,
+                total: row.get(2)?,
+                payment_method,
+                hash: row.get(4)?,
+                voided_at: row.get(5)?,
+                refund_of: row.get(6)?,
+                tags: Vec::new(),
+                is_test: row.get(7)?,
+                source: order_source_from_row(row, 8)?,
+            })
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    // Fetch the items of the page and group by order_id.
+    let mut item_stmt = conn
+        .prepare(&format!(
+            "SELECT id, order_id, product_id, product_name, unit_price, quantity, unit, total,
+                    refunded_item_id
+             FROM order_items
+             WHERE order_id IN ({ORDER_PAGE_IDS})
+             ORDER BY order_id"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let all_items: Vec<OrderItem> = item_stmt
+        .query_map(page_params, |row| {
+            Ok(OrderItem {
+                id: row.get(0)?,
+                order_id: row.get(1)?,
+                product_id: row.get(2)?,
+                product_name: row.get(3)?,
+                unit_price: row.get(4)?,
+                quantity: row.get(5)?,
+                unit: quantity_unit_from_row(row, 6)?,
+                total: row.get(7)?,
+                refunded_item_id: row.get(8)?,
+            })
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    // Build a map of order_id -> items.
+    let mut items_map: std::collections::HashMap<String, Vec<OrderItem>> =
+        std::collections::HashMap::new();
+    for item in all_items {
+        items_map
+            .entry(item.order_id.clone())
+            .or_default()
+            .push(item);
+    }
+
+    let mut tag_stmt = conn
+        .prepare(&format!(
+            "SELECT order_id, tag FROM order_tags
+             WHERE order_id IN ({ORDER_PAGE_IDS})
+             ORDER BY tag"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+    let mut tags_map: std::collections::HashMap<String, Vec<String>> =
+        std::collections::HashMap::new();
+    for row in tag_stmt
+        .query_map(page_params, |row| {
+            Ok((row.get::<_, String>(0)?, row.get(1)?))
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+    {
+        let (order_id, tag) = row.map_err(|e| format!("Row mapping error: {e}"))?;
+        tags_map.entry(order_id).or_default().push(tag);
+    }
+
+    let result: Vec<OrderWithItems> = orders
+        .into_iter()
+        .map(|mut order| {
+            let items = items_map.remove(&order.id).unwrap_or_default();
+            order.tags = tags_map.remove(&order.id).unwrap_or_default();
+            OrderWithItems { order, items }
+        })
+        .collect();
+
+    Ok(result)
+}
+
+/// With a `source`, only the orders from that channel are counted.
+pub(crate) fn get_dashboard_summary_inner(
+    db: &DbState,
+    products: ProductSummaryQuery,
+    source: Option<OrderSource>,
+) -> Result<DashboardSummary, String> {
+    let conn = db.read_conn()?;
+    dashboard_summary(&conn, &DateRange::default(), &products, source)
+}
+
+/// Dashboard summaries already computed since their data last changed, by
+/// query, so that screens reading the dashboard do not recompute it each
+/// time. Managed by Tauri alongside the `DbState`.
+#[derive(Default)]
+pub struct DashboardCache {
+    summaries: std::sync::Mutex<
+        std::collections::HashMap<(ProductSummaryQuery, Option<OrderSource>), DashboardSummary>,
+    >,
+    /// Bumped by each refresh, so that a summary computed from data that
+    /// changed meanwhile is not cached.
+    generation: std::sync::atomic::AtomicU64,
+}
+
+/// `get_dashboard_summary_inner`, computed only on the first read after its
+/// data changed.
+pub(crate) fn cached_dashboard_summary_inner(
+    db: &DbState,
+    cache: &DashboardCache,
+    products: ProductSummaryQuery,
+    source: Option<OrderSource>,
+) -> Result<DashboardSummary, String> {
+    let key = (products, source);
+    if let Some(summary) = cache
+        .summaries
+        .lock()
+        .map_err(|e| format!("Dashboard cache lock error: {e}"))?
+        .get(&key)
+    {
+        return Ok(summary.clone());
+    }
+
+    let generation = cache.generation.load(std::sync::atomic::Ordering::SeqCst);
+    let summary = get_dashboard_summary_inner(db, key.0.clone(), key.1)?;
+    let mut summaries = cache
+        .summaries
+        .lock()
+        .map_err(|e| format!("Dashboard cache lock error: {e}"))?;
+    if cache.generation.load(std::sync::atomic::Ordering::SeqCst) == generation {
+        summaries.insert(key, summary.clone());
+    }
+    Ok(summary)
+}
+
+/// Drops the cached summaries after their data changed and recomputes the
+/// one of all products and sources, which is returned.
+pub(crate) fn refresh_dashboard_inner(
+    db: &DbState,
+    cache: &DashboardCache,
+) -> Result<DashboardSummary, String> {
+    {
+        let mut summaries = cache
+            .summaries
+            .lock()
+            .map_err(|e| format!("Dashboard cache lock error: {e}"))?;
+        cache
+            .generation
+            .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+        summaries.clear();
+    }
+    cached_dashboard_summary_inner(db, cache, ProductSummaryQuery::default(), None)
+}
+
+/// Settings keys holding the `RevenueGoal`.
+const GOAL_TARGET_KEY: &str = "goal.target";
+const GOAL_FROM_KEY: &str = "goal.from";
+const GOAL_TO_KEY: &str = "goal.to";
+
+fn load_revenue_goal(store: &impl Store) -> Result<RevenueGoal, String> {
+    Ok(RevenueGoal {
+        target: parse_number(store, GOAL_TARGET_KEY, 0)?,
+        from: store.get_setting(GOAL_FROM_KEY)?.unwrap_or_default(),
+        to: store.get_setting(GOAL_TO_KEY)?.unwrap_or_default(),
+    })
+}
+
+pub(crate) fn get_revenue_goal_inner(store: &impl Store) -> Result<RevenueGoal, String> {
+    load_revenue_goal(store)
+}
+
+pub(crate) fn update_revenue_goal_inner(
+    store: &impl Store,
+    goal: RevenueGoal,
+) -> Result<RevenueGoal, String> {
+    if goal.target < 0 {
+        return Err("Revenue goal cannot be negative".to_string());
+    }
+    let (from, to) = (goal.from.trim().to_string(), goal.to.trim().to_string());
+    if goal.target > 0 {
+        validate_range(&DateRange {
+            from: Some(from.clone()),
+            to: Some(to.clone()),
+        })?;
+    }
+    store.set_settings(&[
+        (GOAL_TARGET_KEY, goal.target.to_string()),
+        (GOAL_FROM_KEY, from),
+        (GOAL_TO_KEY, to),
+    ])?;
+    load_revenue_goal(store)
+}
+
+/// Progress of the sales towards the `RevenueGoal` at `now`. The projection
+/// extends the revenue per hour since the start of the event's first day
+/// to the end of its last day.
+pub(crate) fn get_goal_progress_inner(
+    db: &DbState,
+    now: chrono::NaiveDateTime,
+) -> Result<GoalProgress, String> {
+    let conn = db.read_conn()?;
+    let goal = load_revenue_goal(&*conn)?;
+    if goal.target == 0 {
+        return Err("No revenue goal is set".to_string());
+    }
+
+    let achieved: i64 = conn
+        .query_row(
+            &format!("SELECT COALESCE(SUM(total), 0) FROM orders WHERE {SALES_FILTER}"),
+            params![goal.from, goal.to],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let start = parse_date(&goal.from)?.and_time(chrono::NaiveTime::MIN);
+    let end = (parse_date(&goal.to)? + chrono::Duration::days(1)).and_time(chrono::NaiveTime::MIN);
+    let elapsed = (now - start).num_seconds();
+    let projected = if now >= end || elapsed <= 0 {
+        achieved
+    } else {
+        let duration = (end - start).num_seconds();
+        (achieved as f64 * duration as f64 / elapsed as f64).round() as i64
+    };
+
+    Ok(GoalProgress {
+        target: goal.target,
+        achieved,
+        projected,
+    })
+}
+
+/// UTC timestamp, in the `created_at` format, of the start of the local day.
+fn start_of_today() -> String {
+    let midnight = chrono::Local::now()
+        .date_naive()
+        .and_time(chrono::NaiveTime::MIN);
+    // On some DST changes the day starts at 01:00.
+    let start = midnight
+        .and_local_timezone(chrono::Local)
+        .earliest()
+        .or_else(|| {
+            (midnight + chrono::Duration::hours(1))
+                .and_local_timezone(chrono::Local)
+                .earliest()
+        })
+        .map_or_else(chrono::Utc::now, |start| start.with_timezone(&chrono::Utc));
+    start.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+/// Unlike the dashboard, compares the raw `created_at` so that only today's
+/// orders are read, through their index.
+pub(crate) fn get_today_stats_inner(db: &DbState) -> Result<TodayStats, String> {
+    let conn = db.read_conn()?;
+    conn.query_row(
+        "SELECT COALESCE(SUM(total), 0), COUNT(*), MAX(created_at),
+                COALESCE(SUM(CASE WHEN payment_method = 'cash' THEN total END), 0),
+                COALESCE(SUM(CASE WHEN payment_method = 'card' THEN total END), 0)
+         FROM orders
+         WHERE created_at >= ?1 AND voided_at IS NULL",
+        params![start_of_today()],
+        |row| {
+            Ok(TodayStats {
+                total_revenue: row.get(0)?,
+                order_count: row.get(1)?,
+                last_order_at: row.get(2)?,
+                cash_revenue: row.get(3)?,
+                card_revenue: row.get(4)?,
+            })
+        },
+    )
+    .map_err(|e| format!("Query error: {e}"))
+}
+
+/// Computes the dashboard aggregates over the orders placed within `range`.
+fn dashboard_summary(
+    conn: &Connection,
+    range: &DateRange,
+    products: &ProductSummaryQuery,
+    source: Option<OrderSource>,
+) -> Result<DashboardSummary, String> {
+    let filter = format!("{SALES_FILTER} AND (?3 IS NULL OR source = ?3)");
+    let source = source.map(|s| s.as_db_str());
+
+    // Grand totals.
+    let (total_revenue, total_transactions): (i64, i64) = conn
+        .query_row(
+            &format!("SELECT COALESCE(SUM(total), 0), COUNT(*) FROM orders WHERE {filter}"),
+            params![range.from, range.to, source],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    // Per-product summary.
+    let order_by = match products.sort_by {
+        ProductSortKey::Revenue => "total_rev DESC, total_qty DESC",
+        ProductSortKey::Quantity => "total_qty DESC, total_rev DESC",
+    };
+    // A negative LIMIT means "no limit" in SQLite.
+    let limit: i64 = products.limit.map(i64::from).unwrap_or(-1);
+
+    let mut prod_stmt = conn
+        .prepare(&format!(
+            "SELECT product_id, product_name,
+                    ROUND(SUM(quantity), 3) AS total_qty,
+                    SUM(total) AS total_rev
+             FROM order_items
+             WHERE order_id IN (SELECT id FROM orders WHERE {filter})
+             GROUP BY product_id
+             ORDER BY {order_by}
+             LIMIT ?4"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let per_product: Vec<ProductSalesSummary> = prod_stmt
+        .query_map(params![range.from, range.to, source, limit], |row| {
+            Ok(ProductSalesSummary {
+                product_id: row.get(0)?,
+                product_name: row.get(1)?,
+                total_quantity: row.get(2)?,
+                total_revenue: row.get(3)?,
+            })
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    // Per-payment-method breakdown.
+    let mut pm_stmt = conn
+        .prepare(&format!(
+            "SELECT payment_method,
+                    SUM(total) AS total_rev,
+                    COUNT(*) AS tx_count,
+                    COALESCE(SUM((SELECT SUM(oi.total) FROM order_items oi
+                                  WHERE oi.order_id = orders.id
+                                    AND oi.product_id = '{SURCHARGE_PRODUCT_ID}')), 0)
+             FROM orders
+             WHERE {filter}
+             GROUP BY payment_method
+             ORDER BY payment_method"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let per_payment_method: Vec<PaymentMethodBreakdown> = pm_stmt
+        .query_map(params![range.from, range.to, source], |row| {
+            let pm_str: String = row.get(0)?;
+            let payment_method = PaymentMethod::from_db_str(&pm_str).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    0,
+                    rusqlite::types::Type::Text,
+                    Box::from(e),
+                )
+            })?;
+            Ok(PaymentMethodBreakdown {
+                payment_method,
+                total_revenue: row.get(1)?,
+                transaction_count: row.get(2)?,
+                surcharge_revenue: row.get(3)?,
+            })
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    // Per-source breakdown.
+    let mut source_stmt = conn
+        .prepare(&format!(
+            "SELECT source, SUM(total), COUNT(*)
+             FROM orders
+             WHERE {filter}
+             GROUP BY source
+             ORDER BY source"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let per_source: Vec<SourceBreakdown> = source_stmt
+        .query_map(params![range.from, range.to, source], |row| {
+            Ok(SourceBreakdown {
+                source: order_source_from_row(row, 0)?,
+                total_revenue: row.get(1)?,
+                transaction_count: row.get(2)?,
+            })
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    // Per-category summary.
+    let mut cat_stmt = conn
+        .prepare(&format!(
+            "SELECT c.id, c.label,
+                    ROUND(SUM(oi.quantity), 3) AS total_qty,
+                    SUM(oi.total) AS total_rev
+             FROM order_items oi
+             LEFT JOIN products p ON p.id = oi.product_id
+             LEFT JOIN categories c ON c.id = p.category_id
+             WHERE oi.order_id IN (SELECT id FROM orders WHERE {filter})
+             GROUP BY c.id
+             ORDER BY total_rev DESC"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let per_category: Vec<CategorySalesSummary> = cat_stmt
+        .query_map(params![range.from, range.to, source], |row| {
+            Ok(CategorySalesSummary {
+                category_id: row.get(0)?,
+                category_label: row.get(1)?,
+                total_quantity: row.get(2)?,
+                total_revenue: row.get(3)?,
+            })
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    // Basket statistics need every order total, so they are computed here
+    // rather than in SQL. A weighed line counts as a single item.
+    let mut basket_stmt = conn
+        .prepare(&format!(
+            "SELECT o.total,
+                    COALESCE(SUM(CASE WHEN oi.unit = 'piece' THEN oi.quantity ELSE 1 END), 0)
+             FROM orders o
+             LEFT JOIN order_items oi ON oi.order_id = o.id
+             WHERE {filter}
+             GROUP BY o.id"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let baskets: Vec<(i64, i64)> = basket_stmt
+        .query_map(params![range.from, range.to, source], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    Ok(DashboardSummary {
+        total_revenue,
+        total_transactions,
+        per_product,
+        per_payment_method,
+        per_category,
+        per_source,
+        basket: basket_stats(&baskets),
+        converted_total: load_display_currency(conn)?.convert(total_revenue),
+    })
+}
+
+/// Upper bounds (exclusive, in cents) of the basket histogram buckets.
+const BASKET_HISTOGRAM_BOUNDS: [i64; 5] = [100, 200, 500, 1000, 2000];
+
+/// Computes basket statistics from `(order_total, item_count)` pairs.
+fn basket_stats(baskets: &[(i64, i64)]) -> BasketStats {
+    let mut histogram: Vec<BasketHistogramBucket> = Vec::new();
+    let mut min = 0;
+    for max in BASKET_HISTOGRAM_BOUNDS {
+        histogram.push(BasketHistogramBucket {
+            min,
+            max: Some(max),
+            order_count: 0,
+        });
+        min = max;
+    }
+    histogram.push(BasketHistogramBucket {
+        min,
+        max: None,
+        order_count: 0,
+    });
+
+    if baskets.is_empty() {
+        return BasketStats {
+            average_total: 0,
+            median_total: 0,
+            average_items_per_order: 0.0,
+            histogram,
+        };
+    }
+
+    let count = baskets.len() as i64;
+    let mut totals: Vec<i64> = baskets.iter().map(|(total, _)| *total).collect();
+    totals.sort_unstable();
+
+    let sum: i64 = totals.iter().sum();
+    let items: i64 = baskets.iter().map(|(_, items)| *items).sum();
+    let mid = totals.len() / 2;
+    let median_total = if totals.len().is_multiple_of(2) {
+        (totals[mid - 1] + totals[mid]) / 2
+    } else {
+        totals[mid]
+    };
+
+    for total in &totals {
+        let bucket = BASKET_HISTOGRAM_BOUNDS
+            .iter()
+            .position(|max| total < max)
+            .unwrap_or(BASKET_HISTOGRAM_BOUNDS.len());
+        histogram[bucket].order_count += 1;
+    }
+
+    BasketStats {
+        average_total: (sum as f64 / count as f64).round() as i64,
+        median_total,
+        average_items_per_order: items as f64 / count as f64,
+        histogram,
+    }
+}
+
+pub(crate) fn compare_periods_inner(
+    db: &DbState,
+    range_a: DateRange,
+    range_b: DateRange,
+) -> Result<PeriodComparison, String> {
+    validate_range(&range_a)?;
+    validate_range(&range_b)?;
+
+    let conn = db.read_conn()?;
+
+    let products = ProductSummaryQuery::default();
+    let period_a = dashboard_summary(&conn, &range_a, &products, None)?;
+    let period_b = dashboard_summary(&conn, &range_b, &products, None)?;
+
+    let revenue_delta = period_a.total_revenue - period_b.total_revenue;
+    let revenue_change_percent = if period_b.total_revenue != 0 {
+        Some(revenue_delta as f64 * 100.0 / period_b.total_revenue as f64)
+    } else {
+        None
+    };
+
+    Ok(PeriodComparison {
+        revenue_delta,
+        transactions_delta: period_a.total_transactions - period_b.total_transactions,
+        revenue_change_percent,
+        period_a,
+        period_b,
+    })
+}
+
+/// Parses a calendar date formatted as `YYYY-MM-DD`.
+fn parse_date(date: &str) -> Result<chrono::NaiveDate, String> {
+    chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|e| format!("Invalid date '{date}' (expected YYYY-MM-DD): {e}"))
+}
+
+/// Checks that `date` is a calendar date formatted as `YYYY-MM-DD`.
+fn validate_date(date: &str) -> Result<(), String> {
+    parse_date(date).map(|_| ())
+}
+
+/// Revenue and order count per tag over `range`. An order with several tags
+/// counts towards each of them.
+pub(crate) fn get_sales_by_tag_inner(
+    db: &DbState,
+    range: DateRange,
+) -> Result<Vec<TagSales>, String> {
+    validate_range(&range)?;
+    let conn = db.read_conn()?;
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT t.tag, SUM(orders.total) AS total_rev, COUNT(*)
+             FROM order_tags t
+             JOIN orders ON orders.id = t.order_id
+             WHERE {SALES_FILTER}
+             GROUP BY t.tag
+             ORDER BY total_rev DESC, t.tag"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let sales = stmt
+        .query_map(params![range.from, range.to], |row| {
+            Ok(TagSales {
+                tag: row.get(0)?,
+                total_revenue: row.get(1)?,
+                order_count: row.get(2)?,
+            })
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    Ok(sales)
+}
+
+pub(crate) fn get_hourly_breakdown_inner(
+    db: &DbState,
+    date: String,
+) -> Result<Vec<HourlySales>, String> {
+    validate_date(&date)?;
+
+    let conn = db.read_conn()?;
+
+    // Orders are stored in UTC; bucket them by the local hour at which the
+    // sale actually happened.
+    let mut stmt = conn
+        .prepare(
+            "SELECT CAST(strftime('%H', created_at, 'localtime') AS INTEGER) AS hour,
+                    SUM(total) AS total_rev,
+                    COUNT(*) AS order_count
+             FROM orders
+             WHERE date(created_at, 'localtime') = ?1 AND voided_at IS NULL
+             GROUP BY hour",
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let rows: Vec<(i64, i64, i64)> = stmt
+        .query_map(params![date], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    // Always return the 24 buckets so quiet hours show up as zeros.
+    let mut buckets: Vec<HourlySales> = (0..24)
+        .map(|hour| HourlySales {
+            hour,
+            total_revenue: 0,
+            order_count: 0,
+        })
+        .collect();
+    for (hour, total_revenue, order_count) in rows {
+        if let Some(bucket) = buckets.get_mut(hour as usize) {
+            bucket.total_revenue = total_revenue;
+            bucket.order_count = order_count;
+        }
+    }
+
+    Ok(buckets)
+}
+
+/// SQL predicate restricting `orders.created_at` to a `DateRange`.
+/// Expects the range bounds to be bound as `?1` (from) and `?2` (to).
+const DATE_RANGE_FILTER: &str = "(?1 IS NULL OR date(created_at, 'localtime') >= ?1)
+     AND (?2 IS NULL OR date(created_at, 'localtime') <= ?2)";
+
+/// `DATE_RANGE_FILTER` restricted to the orders that count as sales, i.e.
+/// leaving out voided ones.
+const SALES_FILTER: &str = "(?1 IS NULL OR date(created_at, 'localtime') >= ?1)
+     AND (?2 IS NULL OR date(created_at, 'localtime') <= ?2)
+     AND voided_at IS NULL";
+
+fn validate_range(range: &DateRange) -> Result<(), String> {
+    if let Some(from) = &range.from {
+        validate_date(from)?;
+    }
+    if let Some(to) = &range.to {
+        validate_date(to)?;
+    }
+    if let (Some(from), Some(to)) = (&range.from, &range.to) {
+        if from > to {
+            return Err(format!("Invalid date range: {from} is after {to}"));
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn get_weekday_breakdown_inner(
+    db: &DbState,
+    range: DateRange,
+) -> Result<Vec<WeekdaySales>, String> {
+    validate_range(&range)?;
+
+    let conn = db.read_conn()?;
+
+    // strftime('%w') counts from Sunday = 0; shift it to ISO Monday = 1.
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT (CAST(strftime('%w', created_at, 'localtime') AS INTEGER) + 6) % 7 + 1 AS weekday,
+                    SUM(total) AS total_rev,
+                    COUNT(*) AS order_count
+             FROM orders
+             WHERE {SALES_FILTER}
+             GROUP BY weekday"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let rows: Vec<(i64, i64, i64)> = stmt
+        .query_map(params![range.from, range.to], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    let mut buckets: Vec<WeekdaySales> = (1..=7)
+        .map(|weekday| WeekdaySales {
+            weekday,
+            total_revenue: 0,
+            order_count: 0,
+        })
+        .collect();
+    for (weekday, total_revenue, order_count) in rows {
+        if let Some(bucket) = buckets.get_mut((weekday - 1) as usize) {
+            bucket.total_revenue = total_revenue;
+            bucket.order_count = order_count;
+        }
+    }
+
+    Ok(buckets)
+}
+
+/// Most days a sales time series covers, by bucket, as every bucket of the
+/// range is returned.
+const MAX_DAILY_SERIES_DAYS: i64 = MAX_SPLIT_DAYS;
+const MAX_HOURLY_SERIES_DAYS: i64 = 31;
+
+pub(crate) fn get_sales_timeseries_inner(
+    db: &DbState,
+    from: String,
+    to: String,
+    bucket: TimeBucket,
+) -> Result<Vec<SalesPoint>, String> {
+    let first = parse_date(&from)?;
+    let last = parse_date(&to)?;
+    let range = DateRange {
+        from: Some(from),
+        to: Some(to),
+    };
+    validate_range(&range)?;
+    let max_days = match bucket {
+        TimeBucket::Hour => MAX_HOURLY_SERIES_DAYS,
+        TimeBucket::Day => MAX_DAILY_SERIES_DAYS,
+    };
+    if (last - first).num_days() >= max_days {
+        return Err(format!(
+            "A sales time series covers at most {max_days} days"
+        ));
+    }
+
+    let conn = db.read_conn()?;
+
+    let period_expr = match bucket {
+        TimeBucket::Hour => "strftime('%Y-%m-%d %H:00', created_at, 'localtime')",
+        TimeBucket::Day => "date(created_at, 'localtime')",
+    };
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {period_expr} AS period,
+                    SUM(total) AS total_rev,
+                    COUNT(*) AS order_count
+             FROM orders
+             WHERE {SALES_FILTER}
+             GROUP BY period"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let mut totals: std::collections::HashMap<String, (i64, i64)> = stmt
+        .query_map(params![range.from, range.to], |row| {
+            Ok((row.get::<_, String>(0)?, (row.get(1)?, row.get(2)?)))
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    // Emit every bucket of the range, including empty ones, so charts get a
+    // continuous axis.
+    let mut points = Vec::new();
+    for day in first.iter_days().take_while(|d| *d <= last) {
+        let day = day.format("%Y-%m-%d").to_string();
+        let periods: Vec<String> = match bucket {
+            TimeBucket::Hour => (0..24).map(|h| format!("{day} {h:02}:00")).collect(),
+            TimeBucket::Day => vec![day],
+        };
+        for period in periods {
+            let (total_revenue, order_count) = totals.remove(&period).unwrap_or((0, 0));
+            points.push(SalesPoint {
+                period,
+                total_revenue,
+                order_count,
+            });
+        }
+    }
+
+    Ok(points)
+}
+
+pub(crate) fn get_sales_heatmap_inner(
+    db: &DbState,
+    range: DateRange,
+) -> Result<SalesHeatmap, String> {
+    validate_range(&range)?;
+
+    let conn = db.read_conn()?;
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT (CAST(strftime('%w', created_at, 'localtime') AS INTEGER) + 6) % 7 AS weekday,
+                    CAST(strftime('%H', created_at, 'localtime') AS INTEGER) AS hour,
+                    SUM(total) AS total_rev,
+                    COUNT(*) AS order_count
+             FROM orders
+             WHERE {SALES_FILTER}
+             GROUP BY weekday, hour"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let rows: Vec<(i64, i64, i64, i64)> = stmt
+        .query_map(params![range.from, range.to], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    let mut heatmap = SalesHeatmap {
+        order_counts: vec![vec![0; 24]; 7],
+        revenue: vec![vec![0; 24]; 7],
+    };
+    for (weekday, hour, total_revenue, order_count) in rows {
+        let (w, h) = (weekday as usize, hour as usize);
+        if w < 7 && h < 24 {
+            heatmap.order_counts[w][h] = order_count;
+            heatmap.revenue[w][h] = total_revenue;
+        }
+    }
+
+    Ok(heatmap)
+}
+
+/// Number of pairs returned by `get_product_pairs` when no limit is given.
+const DEFAULT_PRODUCT_PAIRS_LIMIT: u32 = 10;
+
+pub(crate) fn get_product_pairs_inner(
+    db: &DbState,
+    range: DateRange,
+    limit: Option<u32>,
+) -> Result<Vec<ProductPair>, String> {
+    validate_range(&range)?;
+
+    let conn = db.read_conn()?;
+
+    // Each unordered pair is counted once thanks to `a.product_id < b.product_id`.
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT a.product_id, MAX(a.product_name),
+                    b.product_id, MAX(b.product_name),
+                    COUNT(DISTINCT a.order_id) AS together
+             FROM order_items a
+             JOIN order_items b ON b.order_id = a.order_id AND a.product_id < b.product_id
+             WHERE a.order_id IN (SELECT id FROM orders WHERE {SALES_FILTER})
+             GROUP BY a.product_id, b.product_id
+             ORDER BY together DESC, a.product_id, b.product_id
+             LIMIT ?3"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let limit = limit.unwrap_or(DEFAULT_PRODUCT_PAIRS_LIMIT);
+    let pairs = stmt
+        .query_map(params![range.from, range.to, limit], |row| {
+            Ok(ProductPair {
+                product_a_id: row.get(0)?,
+                product_a_name: row.get(1)?,
+                product_b_id: row.get(2)?,
+                product_b_name: row.get(3)?,
+                order_count: row.get(4)?,
+            })
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    Ok(pairs)
+}
+
+/// Lays out a dashboard summary as export sheets.
+fn dashboard_sheets(summary: &DashboardSummary) -> Vec<Sheet> {
+    let mut totals = Sheet::new("Summary", &["Metric", "Value"]);
+    totals.rows = vec![
+        vec![
+            Cell::Text("Total revenue".to_string()),
+            Cell::Money(summary.total_revenue),
+        ],
+        vec![
+            Cell::Text("Transactions".to_string()),
+            Cell::Integer(summary.total_transactions),
+        ],
+        vec![
+            Cell::Text("Average basket".to_string()),
+            Cell::Money(summary.basket.average_total),
+        ],
+        vec![
+            Cell::Text("Median basket".to_string()),
+            Cell::Money(summary.basket.median_total),
+        ],
+    ];
+
+    let mut products = Sheet::new("Products", &["Product", "Quantity", "Revenue"]);
+    products.rows = summary
+        .per_product
+        .iter()
+        .map(|p| {
+            vec![
+                Cell::Text(p.product_name.clone()),
+                Cell::Number(p.total_quantity),
+                Cell::Money(p.total_revenue),
+            ]
+        })
+        .collect();
+
+    let mut payments = Sheet::new(
+        "Payment methods",
+        &["Payment method", "Transactions", "Revenue"],
+    );
+    payments.rows = summary
+        .per_payment_method
+        .iter()
+        .map(|pm| {
+            vec![
+                Cell::Text(pm.payment_method.to_string()),
+                Cell::Integer(pm.transaction_count),
+                Cell::Money(pm.total_revenue),
+            ]
+        })
+        .collect();
+
+    let mut categories = Sheet::new("Categories", &["Category", "Quantity", "Revenue"]);
+    categories.rows = summary
+        .per_category
+        .iter()
+        .map(|c| {
+            vec![
+                Cell::Text(c.category_label.clone().unwrap_or_default()),
+                Cell::Number(c.total_quantity),
+                Cell::Money(c.total_revenue),
+            ]
+        })
+        .collect();
+
+    vec![totals, products, payments, categories]
+}
+
+/// With a `source`, only the orders from that channel are counted. With
+/// `split_by_day`, each day of `range` is exported on its own, see
+/// `export::write_sheets_by_day`.
+pub(crate) fn export_dashboard_inner(
+    db: &DbState,
+    range: DateRange,
+    products: ProductSummaryQuery,
+    source: Option<OrderSource>,
+    format: ExportFormat,
+    split_by_day: bool,
+    path: String,
+) -> Result<(), String> {
+    validate_range(&range)?;
+    let path = std::path::Path::new(&path);
+
+    let conn = db.read_conn()?;
+    if split_by_day {
+        let days = range_days(&range)?
+            .into_iter()
+            .map(|day| {
+                let summary = dashboard_summary(&conn, &day_range(&day), &products, source)?;
+                Ok((day, dashboard_sheets(&summary)))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        drop(conn);
+        return export::write_sheets_by_day(days, format, path);
+    }
+    let summary = dashboard_summary(&conn, &range, &products, source)?;
+    drop(conn);
+
+    export::write_sheets(&dashboard_sheets(&summary), format, path)
+}
+
+/// Most days an export can be split into.
+const MAX_SPLIT_DAYS: i64 = 366;
+
+/// Every date of `range`, for the exports split by day. Both bounds are
+/// required.
+fn range_days(range: &DateRange) -> Result<Vec<String>, String> {
+    let (Some(from), Some(to)) = (&range.from, &range.to) else {
+        return Err("Splitting by day needs a start and an end date".to_string());
+    };
+    let (from, to) = (parse_date(from)?, parse_date(to)?);
+    if (to - from).num_days() >= MAX_SPLIT_DAYS {
+        return Err(format!(
+            "Cannot split an export into more than {MAX_SPLIT_DAYS} days"
+        ));
+    }
+    Ok(from
+        .iter_days()
+        .take_while(|day| *day <= to)
+        .map(|day| day.format("%Y-%m-%d").to_string())
+        .collect())
+}
+
+/// The range covering `day` alone.
+fn day_range(day: &str) -> DateRange {
+    DateRange {
+        from: Some(day.to_string()),
+        to: Some(day.to_string()),
+    }
+}
+
+/// Writes the orders of `range` to an XLSX workbook with three sheets: the
+/// orders, their items and a per-day summary of the sales. Voided orders
+/// are listed, marked as such, but left out of the summary. With
+/// `split_by_day`, each day gets its own three sheets.
+pub(crate) fn export_orders_xlsx_inner(
+    db: &DbState,
+    range: DateRange,
+    split_by_day: bool,
+    path: String,
+) -> Result<(), String> {
+    validate_range(&range)?;
+    let path = std::path::Path::new(&path);
+    let conn = db.read_conn()?;
+
+    if split_by_day {
+        let days = range_days(&range)?
+            .into_iter()
+            .map(|day| Ok((day.clone(), order_sheets(&conn, &day_range(&day))?)))
+            .collect::<Result<Vec<_>, String>>()?;
+        drop(conn);
+        return export::write_sheets_by_day(days, ExportFormat::Xlsx, path);
+    }
+    let sheets = order_sheets(&conn, &range)?;
+    drop(conn);
+
+    export::write_xlsx(&sheets, path)
+}
+
+/// The orders, items and per-day sheets of `export_orders_xlsx`.
+fn order_sheets(conn: &Connection, range: &DateRange) -> Result<Vec<Sheet>, String> {
+    let range_params = params![range.from, range.to];
+
+    let mut orders = Sheet::new(
+        "Orders",
+        &["Order", "Date", "Payment", "Total", "Status", "Tags"],
+    );
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT id, strftime('%Y-%m-%dT%H:%M:%S', created_at, 'localtime'), payment_method,
+                    total,
+                    CASE WHEN voided_at IS NOT NULL THEN 'voided'
+                         WHEN refund_of IS NOT NULL THEN 'refund'
+                         ELSE '' END,
+                    (SELECT COALESCE(GROUP_CONCAT(tag, ', '), '')
+                     FROM order_tags WHERE order_id = orders.id)
+             FROM orders
+             WHERE {DATE_RANGE_FILTER}
+             ORDER BY created_at, rowid"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+    orders.rows = stmt
+        .query_map(range_params, |row| {
+            Ok(vec![
+                Cell::Text(row.get(0)?),
+                Cell::Date(row.get(1)?),
+                Cell::Text(row.get(2)?),
+                Cell::Money(row.get(3)?),
+                Cell::Text(row.get(4)?),
+                Cell::Text(row.get(5)?),
+            ])
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    let mut items = Sheet::new(
+        "Items",
+        &[
+            "Order",
+            "Date",
+            "Product",
+            "Unit price",
+            "Quantity",
+            "Unit",
+            "Total",
+        ],
+    );
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT oi.order_id, strftime('%Y-%m-%dT%H:%M:%S', o.created_at, 'localtime'),
+                    oi.product_name, oi.unit_price, oi.quantity, oi.unit, oi.total
+             FROM order_items oi
+             JOIN orders o ON o.id = oi.order_id
+             WHERE oi.order_id IN (SELECT id FROM orders WHERE {DATE_RANGE_FILTER})
+             ORDER BY o.created_at, o.rowid, oi.rowid"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+    items.rows = stmt
+        .query_map(range_params, |row| {
+            Ok(vec![
+                Cell::Text(row.get(0)?),
+                Cell::Date(row.get(1)?),
+                Cell::Text(row.get(2)?),
+                Cell::Money(row.get(3)?),
+                Cell::Number(row.get(4)?),
+                Cell::Text(quantity_unit_from_row(row, 5)?.as_db_str().to_string()),
+                Cell::Money(row.get(6)?),
+            ])
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    let mut days = Sheet::new("Per day", &["Date", "Orders", "Cash", "Card", "Total"]);
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT date(created_at, 'localtime') AS day, COUNT(*),
+                    COALESCE(SUM(CASE WHEN payment_method = 'cash' THEN total END), 0),
+                    COALESCE(SUM(CASE WHEN payment_method = 'card' THEN total END), 0),
+                    SUM(total)
+             FROM orders
+             WHERE {SALES_FILTER}
+             GROUP BY day
+             ORDER BY day"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+    days.rows = stmt
+        .query_map(range_params, |row| {
+            Ok(vec![
+                Cell::Date(row.get(0)?),
+                Cell::Integer(row.get(1)?),
+                Cell::Money(row.get(2)?),
+                Cell::Money(row.get(3)?),
+                Cell::Money(row.get(4)?),
+            ])
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    Ok(vec![orders, items, days])
+}
+
+/// Settings keys holding the `LedgerAccounts` codes.
+const LEDGER_JOURNAL_CODE_KEY: &str = "ledger.journal_code";
+const LEDGER_SALES_ACCOUNT_KEY: &str = "ledger.sales_account";
+const LEDGER_CASH_ACCOUNT_KEY: &str = "ledger.cash_account";
+const LEDGER_CARD_ACCOUNT_KEY: &str = "ledger.card_account";
+
+/// Loads the configured account codes, falling back to the defaults for any
+/// code that was never set.
+fn load_ledger_accounts(store: &impl Store) -> Result<LedgerAccounts, String> {
+    let defaults = LedgerAccounts::default();
+    let get = |key: &str, default: String| -> Result<String, String> {
+        Ok(store.get_setting(key)?.unwrap_or(default))
+    };
+    Ok(LedgerAccounts {
+        journal_code: get(LEDGER_JOURNAL_CODE_KEY, defaults.journal_code)?,
+        sales_account: get(LEDGER_SALES_ACCOUNT_KEY, defaults.sales_account)?,
+        cash_account: get(LEDGER_CASH_ACCOUNT_KEY, defaults.cash_account)?,
+        card_account: get(LEDGER_CARD_ACCOUNT_KEY, defaults.card_account)?,
+    })
+}
+
+pub(crate) fn get_ledger_accounts_inner(store: &impl Store) -> Result<LedgerAccounts, String> {
+    load_ledger_accounts(store)
+}
+
+pub(crate) fn update_ledger_accounts_inner(
+    store: &impl Store,
+    accounts: LedgerAccounts,
+) -> Result<LedgerAccounts, String> {
+    let codes = [
+        (LEDGER_JOURNAL_CODE_KEY, &accounts.journal_code),
+        (LEDGER_SALES_ACCOUNT_KEY, &accounts.sales_account),
+        (LEDGER_CASH_ACCOUNT_KEY, &accounts.cash_account),
+        (LEDGER_CARD_ACCOUNT_KEY, &accounts.card_account),
+    ];
+    for (key, code) in &codes {
+        if code.trim().is_empty() {
+            return Err(format!("Account code for {key} cannot be empty"));
+        }
+    }
+
+    let values: Vec<(&str, String)> = codes
+        .iter()
+        .map(|(key, code)| (*key, code.trim().to_string()))
+        .collect();
+    store.set_settings(&values)?;
+    load_ledger_accounts(store)
+}
+
+/// An order as seen by the accounting exports: (id, local date as
+/// `YYYY-MM-DD`, total, payment method, whether it is a refund), in
+/// chronological order.
+type AccountingOrder = (String, String, i64, PaymentMethod, bool);
+
+fn accounting_orders(conn: &Connection, range: &DateRange) -> Result<Vec<AccountingOrder>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT id, date(created_at, 'localtime'), total, payment_method,
+                    refund_of IS NOT NULL OR total < 0
+             FROM orders
+             WHERE {SALES_FILTER}
+             ORDER BY created_at, id"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let orders = stmt
+        .query_map(params![range.from, range.to], |row| {
+            let pm_str: String = row.get(3)?;
+            let payment_method = PaymentMethod::from_db_str(&pm_str).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    3,
+                    rusqlite::types::Type::Text,
+                    Box::from(e),
+                )
+            })?;
+            Ok((
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                payment_method,
+                row.get(4)?,
+            ))
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    Ok(orders)
+}
+
+/// Returns the account debited for an order paid with `payment_method`.
+fn payment_account(accounts: &LedgerAccounts, payment_method: &PaymentMethod) -> String {
+    match payment_method {
+        PaymentMethod::Cash => accounts.cash_account.clone(),
+        PaymentMethod::Card => accounts.card_account.clone(),
+    }
+}
+
+pub(crate) fn export_fec_inner(db: &DbState, range: DateRange, path: String) -> Result<(), String> {
+    validate_range(&range)?;
+
+    let (accounts, orders) = {
+        let conn = db.read_conn()?;
+        (
+            load_ledger_accounts(&*conn)?,
+            accounting_orders(&conn, &range)?,
+        )
+    };
+
+    // Each order is one balanced entry: the payment account is debited and
+    // the sales account credited with the order total. Refunds go the other
+    // way round, with the amount given back.
+    let mut lines = Vec::with_capacity(orders.len() * 2);
+    for (number, (id, date, total, payment_method, refund)) in orders.into_iter().enumerate() {
+        let payment_label = match payment_method {
+            PaymentMethod::Cash => "Caisse",
+            PaymentMethod::Card => "Cartes bancaires à encaisser",
+        };
+        let (debit, credit) = if refund { (0, total.abs()) } else { (total, 0) };
+        let payment = export::FecLine {
+            journal_code: accounts.journal_code.clone(),
+            journal_label: "Ventes".to_string(),
+            entry_number: number as i64 + 1,
+            entry_date: date.replace('-', ""),
+            account_number: payment_account(&accounts, &payment_method),
+            account_label: payment_label.to_string(),
+            piece_ref: id.clone(),
+            label: if refund {
+                format!("Remboursement {id}")
+            } else {
+                format!("Vente {id}")
+            },
+            debit,
+            credit,
+        };
+        let sales = export::FecLine {
+            account_number: accounts.sales_account.clone(),
+            account_label: "Ventes de marchandises".to_string(),
+            debit: credit,
+            credit: debit,
+            ..payment.clone()
+        };
+        lines.push(payment);
+        lines.push(sales);
+    }
+
+    std::fs::write(&path, export::to_fec(&lines))
+        .map_err(|e| format!("Failed to write {path}: {e}"))
+}
+
+pub(crate) fn export_ledger_csv_inner(
+    db: &DbState,
+    range: DateRange,
+    path: String,
+) -> Result<(), String> {
+    validate_range(&range)?;
+
+    let (accounts, orders) = {
+        let conn = db.read_conn()?;
+        (
+            load_ledger_accounts(&*conn)?,
+            accounting_orders(&conn, &range)?,
+        )
+    };
+
+    let mut ledger = Sheet::new(
+        "Ledger",
+        &[
+            "Date",
+            "Journal",
+            "Account",
+            "Reference",
+            "Label",
+            "Debit",
+            "Credit",
+        ],
+    );
+    for (id, date, total, payment_method, refund) in orders {
+        let (label, debit, credit) = if refund {
+            (format!("Refund ({payment_method})"), 0, total.abs())
+        } else {
+            (format!("Sale ({payment_method})"), total, 0)
+        };
+        ledger.rows.push(vec![
+            Cell::Text(date.clone()),
+            Cell::Text(accounts.journal_code.clone()),
+            Cell::Text(payment_account(&accounts, &payment_method)),
+            Cell::Text(id.clone()),
+            Cell::Text(label.clone()),
+            Cell::Money(debit),
+            Cell::Money(credit),
+        ]);
+        ledger.rows.push(vec![
+            Cell::Text(date),
+            Cell::Text(accounts.journal_code.clone()),
+            Cell::Text(accounts.sales_account.clone()),
+            Cell::Text(id),
+            Cell::Text(label),
+            Cell::Money(credit),
+            Cell::Money(debit),
+        ]);
+    }
+
+    export::write_sheets(&[ledger], ExportFormat::Csv, std::path::Path::new(&path))
+}
+
+pub(crate) fn list_custom_reports_inner() -> Vec<CustomReport> {
+    crate::custom_reports::list()
+}
+
+/// Runs one of the curated report queries on the read-only connection and
+/// writes its rows to `path` as CSV.
+pub(crate) fn run_custom_report_inner(
+    db: &DbState,
+    template_id: String,
+    params: std::collections::HashMap<String, String>,
+    path: String,
+) -> Result<(), String> {
+    let sheet = {
+        let conn = db.read_conn()?;
+        crate::custom_reports::run(&conn, &template_id, &params)?
+    };
+
+    export::write_sheets(&[sheet], ExportFormat::Csv, std::path::Path::new(&path))
+}
+
+/// The dates `mode` stands for when run on `today`.
+fn resolve_date_range(mode: &DateRangeMode, today: chrono::NaiveDate) -> DateRange {
+    use chrono::{Datelike, Days, Months};
+
+    let monday = today - Days::new(u64::from(today.weekday().num_days_from_monday()));
+    let first_of_month = today.with_day(1).unwrap_or(today);
+    let (from, to) = match mode {
+        DateRangeMode::Today => (today, today),
+        DateRangeMode::Yesterday => (today - Days::new(1), today - Days::new(1)),
+        DateRangeMode::LastDays { days } => {
+            (today - Days::new(u64::from(days.saturating_sub(1))), today)
+        }
+        DateRangeMode::ThisWeek => (monday, today),
+        DateRangeMode::LastWeek => (monday - Days::new(7), monday - Days::new(1)),
+        DateRangeMode::ThisMonth => (first_of_month, today),
+        DateRangeMode::LastMonth => (
+            first_of_month - Months::new(1),
+            first_of_month - Days::new(1),
+        ),
+        DateRangeMode::Fixed { from, to } => {
+            return DateRange {
+                from: from.clone(),
+                to: to.clone(),
+            }
+        }
+    };
+    DateRange {
+        from: Some(from.format("%Y-%m-%d").to_string()),
+        to: Some(to.format("%Y-%m-%d").to_string()),
+    }
+}
+
+fn validate_report_preset(name: &str, config: &ReportPresetConfig) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Report preset name is required".to_string());
+    }
+    match &config.range {
+        DateRangeMode::LastDays { days: 0 } => {
+            return Err("A preset must cover at least one day".to_string())
+        }
+        DateRangeMode::Fixed { from, to } => validate_range(&DateRange {
+            from: from.clone(),
+            to: to.clone(),
+        })?,
+        _ => {}
+    }
+    Ok(name.to_string())
+}
+
+fn load_report_preset(conn: &Connection, preset_id: &str) -> Result<ReportPreset, String> {
+    use rusqlite::OptionalExtension;
+
+    let (id, name, config): (String, String, String) = conn
+        .query_row(
+            "SELECT id, name, config FROM report_presets WHERE id = ?1",
+            params![preset_id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+        .optional()
+        .map_err(|e| format!("Query error: {e}"))?
+        .ok_or_else(|| format!("Report preset not found: {preset_id}"))?;
+    let config =
+        serde_json::from_str(&config).map_err(|e| format!("Invalid report preset {name}: {e}"))?;
+    Ok(ReportPreset { id, name, config })
+}
+
+fn report_preset_config_json(config: &ReportPresetConfig) -> Result<String, String> {
+    serde_json::to_string(config).map_err(|e| format!("Failed to serialize report preset: {e}"))
+}
+
+pub(crate) fn list_report_presets_inner(db: &DbState) -> Result<Vec<ReportPreset>, String> {
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+
+    let mut stmt = conn
+        .prepare("SELECT id FROM report_presets ORDER BY name")
+        .map_err(|e| format!("Query error: {e}"))?;
+    let ids = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    ids.iter().map(|id| load_report_preset(&conn, id)).collect()
+}
+
+pub(crate) fn create_report_preset_inner(
+    db: &DbState,
+    payload: CreateReportPresetPayload,
+) -> Result<ReportPreset, String> {
+    let name = validate_report_preset(&payload.name, &payload.config)?;
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+
+    let id = Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO report_presets (id, name, config) VALUES (?1, ?2, ?3)",
+        params![id, name, report_preset_config_json(&payload.config)?],
+    )
+    .map_err(|e| format!("Insert error: {e}"))?;
+
+    load_report_preset(&conn, &id)
+}
+
+pub(crate) fn update_report_preset_inner(
+    db: &DbState,
+    payload: UpdateReportPresetPayload,
+) -> Result<ReportPreset, String> {
+    let name = validate_report_preset(&payload.name, &payload.config)?;
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+
+    let rows_affected = conn
+        .execute(
+            "UPDATE report_presets SET name = ?1, config = ?2 WHERE id = ?3",
+            params![
+                name,
+                report_preset_config_json(&payload.config)?,
+                payload.id
+            ],
+        )
+        .map_err(|e| format!("Update error: {e}"))?;
+    if rows_affected == 0 {
+        return Err(format!("Report preset not found: {}", payload.id));
+    }
+
+    load_report_preset(&conn, &payload.id)
+}
+
+pub(crate) fn delete_report_preset_inner(db: &DbState, preset_id: String) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let rows_affected = conn
+        .execute(
+            "DELETE FROM report_presets WHERE id = ?1",
+            params![preset_id],
+        )
+        .map_err(|e| format!("Delete error: {e}"))?;
+    if rows_affected == 0 {
+        return Err(format!("Report preset not found: {preset_id}"));
+    }
+    Ok(())
+}
+
+/// Runs a preset over its range as of today, writing the export to `path`.
+/// Returns the dates the export covers.
+pub(crate) fn run_report_preset_inner(
+    db: &DbState,
+    preset_id: String,
+    path: String,
+) -> Result<DateRange, String> {
+    let preset = {
+        let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+        load_report_preset(&conn, &preset_id)?
+    };
+    let config = preset.config;
+    let range = resolve_date_range(&config.range, chrono::Local::now().date_naive());
+
+    match config.report {
+        ReportKind::Dashboard => export_dashboard_inner(
+            db,
+            range.clone(),
+            config.products,
+            None,
+            config.format.unwrap_or(ExportFormat::Csv),
+            false,
+            path,
+        )?,
+        ReportKind::Fec => export_fec_inner(db, range.clone(), path)?,
+        ReportKind::LedgerCsv => export_ledger_csv_inner(db, range.clone(), path)?,
+        ReportKind::Journal => {
+            export_journal_inner(db, range.clone(), path)?;
+        }
+    }
+    Ok(range)
+}
+
+/// Time of day of a job that was never scheduled.
+const DEFAULT_JOB_TIME: &str = "23:00";
+
+fn scheduled_job_from_row(row: &rusqlite::Row) -> rusqlite::Result<ScheduledJob> {
+    let kind: String = row.get(0)?;
+    let recipients: String = row.get(3)?;
+    Ok(ScheduledJob {
+        kind: ScheduledJobKind::from_db_str(&kind).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, Box::from(e))
+        })?,
+        enabled: row.get(1)?,
+        time: row.get(2)?,
+        recipients: serde_json::from_str(&recipients).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(e))
+        })?,
+        last_run_at: row.get(4)?,
+        last_error: row.get(5)?,
+    })
+}
+
+/// Every job kind, disabled at `DEFAULT_JOB_TIME` until it is scheduled.
+pub(crate) fn list_scheduled_jobs_inner(db: &DbState) -> Result<Vec<ScheduledJob>, String> {
+    use rusqlite::OptionalExtension;
+
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    ScheduledJobKind::ALL
+        .iter()
+        .map(|kind| {
+            let job = conn
+                .query_row(
+                    "SELECT kind, enabled, time, recipients, last_run_at, last_error
+                     FROM scheduled_jobs WHERE kind = ?1",
+                    params![kind.as_db_str()],
+                    scheduled_job_from_row,
+                )
+                .optional()
+                .map_err(|e| format!("Query error: {e}"))?;
+            Ok(job.unwrap_or_else(|| ScheduledJob {
+                kind: *kind,
+                enabled: false,
+                time: DEFAULT_JOB_TIME.to_string(),
+                recipients: Vec::new(),
+                last_run_at: None,
+                last_error: None,
+            }))
+        })
+        .collect()
+}
+
+/// Changes when a job runs. Its last-run status is kept.
+pub(crate) fn update_schedule_inner(
+    db: &DbState,
+    payload: UpdateSchedulePayload,
+) -> Result<ScheduledJob, String> {
+    let time = chrono::NaiveTime::parse_from_str(payload.time.trim(), "%H:%M")
+        .map_err(|e| format!("Invalid time '{}' (expected HH:MM): {e}", payload.time))?
+        .format("%H:%M")
+        .to_string();
+    let recipients: Vec<String> = payload
+        .recipients
+        .iter()
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty())
+        .collect();
+    if payload.enabled && payload.kind == ScheduledJobKind::ReportEmail && recipients.is_empty() {
+        return Err("The report email needs at least one recipient".to_string());
+    }
+
+    {
+        let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+        conn.execute(
+            "INSERT INTO scheduled_jobs (kind, enabled, time, recipients)
+             VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT(kind) DO UPDATE SET
+                 enabled = excluded.enabled,
+                 time = excluded.time,
+                 recipients = excluded.recipients",
+            params![
+                payload.kind.as_db_str(),
+                payload.enabled,
+                time,
+                serde_json::to_string(&recipients)
+                    .map_err(|e| format!("Failed to serialize recipients: {e}"))?
+            ],
+        )
+        .map_err(|e| format!("Update error: {e}"))?;
+    }
+
+    list_scheduled_jobs_inner(db)?
+        .into_iter()
+        .find(|job| job.kind == payload.kind)
+        .ok_or_else(|| format!("Scheduled job not found: {}", payload.kind.as_db_str()))
+}
+
+/// Records the outcome of a scheduled run.
+pub(crate) fn record_job_run(
+    db: &DbState,
+    kind: ScheduledJobKind,
+    ran_at: &str,
+    error: Option<&str>,
+) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    conn.execute(
+        "UPDATE scheduled_jobs SET last_run_at = ?1, last_error = ?2 WHERE kind = ?3",
+        params![ran_at, error, kind.as_db_str()],
+    )
+    .map_err(|e| format!("Update error: {e}"))?;
+    Ok(())
+}
+
+/// Settings key holding why the scheduler could not look for due jobs, empty
+/// once it can again. Errors of the jobs themselves go to their `last_error`.
+const SCHEDULER_ERROR_KEY: &str = "scheduler.last_error";
+
+/// Records the outcome of a scheduler tick, writing only when it changed.
+pub(crate) fn record_scheduler_error(
+    store: &impl Store,
+    error: Option<&str>,
+) -> Result<(), String> {
+    let error = error.unwrap_or_default();
+    if store.get_setting(SCHEDULER_ERROR_KEY)?.unwrap_or_default() != error {
+        store.set_settings(&[(SCHEDULER_ERROR_KEY, error.to_string())])?;
+    }
+    Ok(())
+}
+
+pub(crate) fn get_scheduler_error_inner(store: &impl Store) -> Result<Option<String>, String> {
+    Ok(store
+        .get_setting(SCHEDULER_ERROR_KEY)?
+        .filter(|error| !error.is_empty()))
+}
+
+/// Settings keys holding the `SmtpSettings`.
+const SMTP_HOST_KEY: &str = "smtp.host";
+const SMTP_PORT_KEY: &str = "smtp.port";
+const SMTP_SECURITY_KEY: &str = "smtp.security";
+const SMTP_USERNAME_KEY: &str = "smtp.username";
+const SMTP_PASSWORD_KEY: &str = "smtp.password";
+const SMTP_FROM_KEY: &str = "smtp.from";
+
+fn load_smtp_settings(store: &impl Store) -> Result<SmtpSettings, String> {
+    let defaults = SmtpSettings::default();
+    let port = match store.get_setting(SMTP_PORT_KEY)? {
+        Some(port) => port
+            .parse()
+            .map_err(|e| format!("Invalid SMTP port '{port}': {e}"))?,
+        None => defaults.port,
+    };
+    let security = match store.get_setting(SMTP_SECURITY_KEY)? {
+        Some(security) => SmtpSecurity::from_db_str(&security)?,
+        None => defaults.security,
+    };
+
+    Ok(SmtpSettings {
+        host: store.get_setting(SMTP_HOST_KEY)?.unwrap_or(defaults.host),
+        port,
+        security,
+        username: store
+            .get_setting(SMTP_USERNAME_KEY)?
+            .unwrap_or(defaults.username),
+        password: store
+            .get_setting(SMTP_PASSWORD_KEY)?
+            .unwrap_or(defaults.password),
+        from: store.get_setting(SMTP_FROM_KEY)?.unwrap_or(defaults.from),
+    })
+}
+
+pub(crate) fn get_smtp_settings_inner(store: &impl Store) -> Result<SmtpSettings, String> {
+    load_smtp_settings(store)
+}
+
+pub(crate) fn update_smtp_settings_inner(
+    store: &impl Store,
+    settings: SmtpSettings,
+) -> Result<SmtpSettings, String> {
+    if settings.port == 0 {
+        return Err("SMTP port must be between 1 and 65535".to_string());
+    }
+
+    store.set_settings(&[
+        (SMTP_HOST_KEY, settings.host.trim().to_string()),
+        (SMTP_PORT_KEY, settings.port.to_string()),
+        (SMTP_SECURITY_KEY, settings.security.as_db_str().to_string()),
+        (SMTP_USERNAME_KEY, settings.username),
+        (SMTP_PASSWORD_KEY, settings.password),
+        (SMTP_FROM_KEY, settings.from.trim().to_string()),
+    ])?;
+
+    load_smtp_settings(store)
+}
+
+/// Human-readable label for a date range, used in report subjects.
+fn describe_range(range: &DateRange) -> String {
+    match (&range.from, &range.to) {
+        (Some(from), Some(to)) if from == to => from.clone(),
+        (Some(from), Some(to)) => format!("{from} to {to}"),
+        (Some(from), None) => format!("since {from}"),
+        (None, Some(to)) => format!("until {to}"),
+        (None, None) => "all time".to_string(),
+    }
+}
+
+/// Renders the plain-text body of a sales report email.
+fn report_email_body(summary: &DashboardSummary, period: &str) -> String {
+    let mut body = format!(
+        "Sales report ({period})\n\nRevenue: {} EUR\nOrders: {}\n",
+        export::format_cents(summary.total_revenue),
+        summary.total_transactions
+    );
+
+    if !summary.per_payment_method.is_empty() {
+        body.push_str("\nBy payment method:\n");
+        for pm in &summary.per_payment_method {
+            body.push_str(&format!(
+                "- {}: {} EUR ({} orders)\n",
+                pm.payment_method,
+                export::format_cents(pm.total_revenue),
+                pm.transaction_count
+            ));
+        }
+    }
+
+    if !summary.per_product.is_empty() {
+        body.push_str("\nBy product:\n");
+        for p in &summary.per_product {
+            body.push_str(&format!(
+                "- {}: {} sold, {} EUR\n",
+                p.product_name,
+                p.total_quantity,
+                export::format_cents(p.total_revenue)
+            ));
+        }
+    }
+
+    body.push_str("\nThe full summary is attached as CSV.\n");
+    body
+}
+
+/// Builds the report email for `range` without sending it, together with the
+/// SMTP settings needed to deliver it.
+pub(crate) fn build_report_email_inner(
+    db: &DbState,
+    range: DateRange,
+    recipients: Vec<String>,
+) -> Result<(SmtpSettings, lettre::Message), String> {
+    validate_range(&range)?;
+
+    let (settings, summary) = {
+        let conn = db.read_conn()?;
+        (
+            load_smtp_settings(&*conn)?,
+            dashboard_summary(&conn, &range, &ProductSummaryQuery::default(), None)?,
+        )
+    };
+
+    let period = describe_range(&range);
+    let csv = export::to_csv(&dashboard_sheets(&summary));
+    let message = crate::mail::build_message(
+        &settings,
+        &recipients,
+        &format!("Sales report ({period})"),
+        report_email_body(&summary, &period),
+        vec![crate::mail::MailAttachment {
+            filename: "sales-report.csv".to_string(),
+            content_type: "text/csv; charset=utf-8".to_string(),
+            content: csv.into_bytes(),
+        }],
+    )?;
+
+    Ok((settings, message))
+}
+
+/// Settings keys holding the `NotificationSettings`.
+const NOTIFY_CHANNEL_KEY: &str = "notify.channel";
+const NOTIFY_SLACK_WEBHOOK_KEY: &str = "notify.slack_webhook_url";
+const NOTIFY_TELEGRAM_TOKEN_KEY: &str = "notify.telegram_bot_token";
+const NOTIFY_TELEGRAM_CHAT_KEY: &str = "notify.telegram_chat_id";
+
+fn load_notification_settings(store: &impl Store) -> Result<NotificationSettings, String> {
+    let channel = match store.get_setting(NOTIFY_CHANNEL_KEY)? {
+        Some(channel) => NotificationChannel::from_db_str(&channel)?,
+        None => NotificationChannel::default(),
+    };
+
+    Ok(NotificationSettings {
+        channel,
+        slack_webhook_url: store
+            .get_setting(NOTIFY_SLACK_WEBHOOK_KEY)?
+            .unwrap_or_default(),
+        telegram_bot_token: store
+            .get_setting(NOTIFY_TELEGRAM_TOKEN_KEY)?
+            .unwrap_or_default(),
+        telegram_chat_id: store
+            .get_setting(NOTIFY_TELEGRAM_CHAT_KEY)?
+            .unwrap_or_default(),
+    })
+}
+
+pub(crate) fn get_notification_settings_inner(
+    store: &impl Store,
+) -> Result<NotificationSettings, String> {
+    load_notification_settings(store)
+}
+
+pub(crate) fn update_notification_settings_inner(
+    store: &impl Store,
+    settings: NotificationSettings,
+) -> Result<NotificationSettings, String> {
+    if settings.channel == NotificationChannel::Slack
+        && !settings.slack_webhook_url.starts_with("https://")
+    {
+        return Err("Slack webhook URL must start with https://".to_string());
+    }
+
+    store.set_settings(&[
+        (NOTIFY_CHANNEL_KEY, settings.channel.as_db_str().to_string()),
+        (
+            NOTIFY_SLACK_WEBHOOK_KEY,
+            settings.slack_webhook_url.trim().to_string(),
+        ),
+        (
+            NOTIFY_TELEGRAM_TOKEN_KEY,
+            settings.telegram_bot_token.trim().to_string(),
+        ),
+        (
+            NOTIFY_TELEGRAM_CHAT_KEY,
+            settings.telegram_chat_id.trim().to_string(),
+        ),
+    ])?;
+
+    load_notification_settings(store)
+}
+
+/// Number of products listed in the summary notification.
+const NOTIFICATION_TOP_PRODUCTS: u32 = 3;
+
+/// Builds the short summary message for `date` (today when absent), together
+/// with the settings needed to deliver it.
+pub(crate) fn build_summary_notification_inner(
+    db: &DbState,
+    date: Option<String>,
+) -> Result<(NotificationSettings, String), String> {
+    let date = date.unwrap_or_else(|| chrono::Local::now().format("%Y-%m-%d").to_string());
+    validate_date(&date)?;
+
+    let conn = db.read_conn()?;
+    let settings = load_notification_settings(&*conn)?;
+    let summary = dashboard_summary(
+        &conn,
+        &DateRange {
+            from: Some(date.clone()),
+            to: Some(date.clone()),
+        },
+        &ProductSummaryQuery {
+            sort_by: ProductSortKey::Quantity,
+            limit: Some(NOTIFICATION_TOP_PRODUCTS),
+        },
+        None,
+    )?;
+
+    let mut text = format!(
+        "Sales {date}: {} EUR, {} orders",
+        export::format_cents(summary.total_revenue),
+        summary.total_transactions
+    );
+    if !summary.per_product.is_empty() {
+        let top: Vec<String> = summary
+            .per_product
+            .iter()
+            .map(|p| format!("{} ({})", p.product_name, p.total_quantity))
+            .collect();
+        text.push_str(&format!("\nTop: {}", top.join(", ")));
+    }
+
+    Ok((settings, text))
+}
+
+/// Settings keys holding the `ScaleSettings`.
+const SCALE_CONNECTION_KEY: &str = "scale.connection";
+const SCALE_SERIAL_PORT_KEY: &str = "scale.serial_port";
+const SCALE_BAUD_RATE_KEY: &str = "scale.baud_rate";
+const SCALE_HID_VENDOR_KEY: &str = "scale.hid_vendor_id";
+const SCALE_HID_PRODUCT_KEY: &str = "scale.hid_product_id";
+
+/// Reads a numeric setting, falling back to `default` when it is not set.
+fn parse_number<T: std::str::FromStr>(
+    store: &impl Store,
+    key: &str,
+    default: T,
+) -> Result<T, String>
+where
+    T::Err: std::fmt::Display,
+{
+    match store.get_setting(key)? {
+        Some(value) => value
+            .parse()
+            .map_err(|e| format!("Invalid {key} '{value}': {e}")),
+        None => Ok(default),
+    }
+}
+
+fn load_scale_settings(store: &impl Store) -> Result<ScaleSettings, String> {
+    let defaults = ScaleSettings::default();
+    let connection = match store.get_setting(SCALE_CONNECTION_KEY)? {
+        Some(connection) => ScaleConnection::from_db_str(&connection)?,
+        None => defaults.connection,
+    };
+
+    Ok(ScaleSettings {
+        connection,
+        serial_port: store
+            .get_setting(SCALE_SERIAL_PORT_KEY)?
+            .unwrap_or(defaults.serial_port),
+        baud_rate: parse_number(store, SCALE_BAUD_RATE_KEY, defaults.baud_rate)?,
+        hid_vendor_id: parse_number(store, SCALE_HID_VENDOR_KEY, defaults.hid_vendor_id)?,
+        hid_product_id: parse_number(store, SCALE_HID_PRODUCT_KEY, defaults.hid_product_id)?,
+    })
+}
+
+pub(crate) fn get_scale_settings_inner(store: &impl Store) -> Result<ScaleSettings, String> {
+    load_scale_settings(store)
+}
+
+pub(crate) fn update_scale_settings_inner(
+    store: &impl Store,
+    settings: ScaleSettings,
+) -> Result<ScaleSettings, String> {
+    match settings.connection {
+        ScaleConnection::Serial if settings.serial_port.trim().is_empty() => {
+            return Err("A serial port is required".to_string());
+        }
+        ScaleConnection::Serial if settings.baud_rate == 0 => {
+            return Err("Baud rate must be greater than 0".to_string());
+        }
+        ScaleConnection::Hid if settings.hid_vendor_id == 0 => {
+            return Err("A USB vendor id is required".to_string());
+        }
+        _ => {}
+    }
+
+    store.set_settings(&[
+        (
+            SCALE_CONNECTION_KEY,
+            settings.connection.as_db_str().to_string(),
+        ),
+        (
+            SCALE_SERIAL_PORT_KEY,
+            settings.serial_port.trim().to_string(),
+        ),
+        (SCALE_BAUD_RATE_KEY, settings.baud_rate.to_string()),
+        (SCALE_HID_VENDOR_KEY, settings.hid_vendor_id.to_string()),
+        (SCALE_HID_PRODUCT_KEY, settings.hid_product_id.to_string()),
+    ])?;
+
+    load_scale_settings(store)
+}
+
+/// Settings keys holding the `LocaleSettings`.
+const LOCALE_SALES_KEY: &str = "locale.sales";
+const LOCALE_CUSTOMER_DISPLAY_KEY: &str = "locale.customer_display";
+
+fn load_locale_settings(store: &impl Store) -> Result<LocaleSettings, String> {
+    let defaults = LocaleSettings::default();
+    Ok(LocaleSettings {
+        sales_locale: store
+            .get_setting(LOCALE_SALES_KEY)?
+            .unwrap_or(defaults.sales_locale),
+        customer_display_locale: store
+            .get_setting(LOCALE_CUSTOMER_DISPLAY_KEY)?
+            .unwrap_or(defaults.customer_display_locale),
+    })
+}
+
+pub(crate) fn get_locale_settings_inner(store: &impl Store) -> Result<LocaleSettings, String> {
+    load_locale_settings(store)
+}
+
+pub(crate) fn update_locale_settings_inner(
+    store: &impl Store,
+    settings: LocaleSettings,
+) -> Result<LocaleSettings, String> {
+    validate_locale(&settings.sales_locale)?;
+    validate_locale(&settings.customer_display_locale)?;
+
+    store.set_settings(&[
+        (LOCALE_SALES_KEY, settings.sales_locale),
+        (
+            LOCALE_CUSTOMER_DISPLAY_KEY,
+            settings.customer_display_locale,
+        ),
+    ])?;
+
+    load_locale_settings(store)
+}
+
+/// Reads an `OrderSource` stored as text in column `idx`.
+fn order_source_from_row(row: &rusqlite::Row, idx: usize) -> rusqlite::Result<OrderSource> {
+    let source: String = row.get(idx)?;
+    OrderSource::from_db_str(&source).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::from(e))
+    })
+}
+
+/// Loads a single order with its items.
+fn load_order(conn: &Connection, order_id: &str) -> Result<OrderWithItems, String> {
+    use rusqlite::OptionalExtension;
+
+    let order = conn
+        .query_row(
+            "SELECT id, created_at, total, payment_method, hash, voided_at, refund_of,
+                    is_test, source
+             FROM orders
+             WHERE id = ?1",
+            params![order_id],
+            |row| {
+                let pm_str: String = row.get(3)?;
+                let payment_method = PaymentMethod::from_db_str(&pm_str).map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        3,
+                        rusqlite::types::Type::Text,
+                        Box::from(e),
+                    )
+                })?;
+                Ok(Order {
+                    id: row.get(0)?,
+                    created_at: row.get(1)?,
+                    total: row.get(2)?,
+                    payment_method,
+                    hash: row.get(4)?,
+                    voided_at: row.get(5)?,
+                    refund_of: row.get(6)?,
+                    tags: Vec::new(),
+                    is_test: row.get(7)?,
+                    source: order_source_from_row(row, 8)?,
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| format!("Query error: {e}"))?
+        .ok_or_else(|| format!("Order not found: {order_id}"))?;
+    let order = Order {
+        tags: order_tags(conn, order_id)?,
+        ..order
+    };
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, order_id, product_id, product_name, unit_price, quantity, unit, total,
+                    refunded_item_id
+             FROM order_items
+             WHERE order_id = ?1
+             ORDER BY rowid",
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let items = stmt
+        .query_map(params![order_id], |row| {
+            Ok(OrderItem {
+                id: row.get(0)?,
+                order_id: row.get(1)?,
+                product_id: row.get(2)?,
+                product_name: row.get(3)?,
+                unit_price: row.get(4)?,
+                quantity: row.get(5)?,
+                unit: quantity_unit_from_row(row, 6)?,
+                total: row.get(7)?,
+                refunded_item_id: row.get(8)?,
+            })
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    Ok(OrderWithItems { order, items })
+}
+
+/// Settings keys holding the `BusinessSettings`.
+const BUSINESS_NAME_KEY: &str = "business.name";
+const BUSINESS_ADDRESS_KEY: &str = "business.address";
+const BUSINESS_SIRET_KEY: &str = "business.siret";
+const BUSINESS_VAT_NUMBER_KEY: &str = "business.vat_number";
+const BUSINESS_VAT_ENABLED_KEY: &str = "business.vat_enabled";
+const BUSINESS_VAT_RATE_KEY: &str = "business.vat_rate";
+
+fn load_business_settings(store: &impl Store) -> Result<BusinessSettings, String> {
+    let defaults = BusinessSettings::default();
+    let vat_rate = match store.get_setting(BUSINESS_VAT_RATE_KEY)? {
+        Some(rate) => rate
+            .parse()
+            .map_err(|e| format!("Invalid VAT rate '{rate}': {e}"))?,
+        None => defaults.vat_rate,
+    };
+
+    Ok(BusinessSettings {
+        name: store
+            .get_setting(BUSINESS_NAME_KEY)?
+            .unwrap_or(defaults.name),
+        address: store
+            .get_setting(BUSINESS_ADDRESS_KEY)?
+            .unwrap_or(defaults.address),
+        siret: store
+            .get_setting(BUSINESS_SIRET_KEY)?
+            .unwrap_or(defaults.siret),
+        vat_number: store
+            .get_setting(BUSINESS_VAT_NUMBER_KEY)?
+            .unwrap_or(defaults.vat_number),
+        vat_enabled: store
+            .get_setting(BUSINESS_VAT_ENABLED_KEY)?
+            .map(|v| v == "1")
+            .unwrap_or(defaults.vat_enabled),
+        vat_rate,
+    })
+}
+
+pub(crate) fn get_business_settings_inner(store: &impl Store) -> Result<BusinessSettings, String> {
+    load_business_settings(store)
+}
+
+pub(crate) fn update_business_settings_inner(
+    store: &impl Store,
+    settings: BusinessSettings,
+) -> Result<BusinessSettings, String> {
+    if !(0.0..100.0).contains(&settings.vat_rate) {
+        return Err("VAT rate must be between 0 and 100".to_string());
+    }
+
+    store.set_settings(&[
+        (BUSINESS_NAME_KEY, settings.name.trim().to_string()),
+        (BUSINESS_ADDRESS_KEY, settings.address.trim().to_string()),
+        (BUSINESS_SIRET_KEY, settings.siret.trim().to_string()),
+        (
+            BUSINESS_VAT_NUMBER_KEY,
+            settings.vat_number.trim().to_string(),
+        ),
+        (
+            BUSINESS_VAT_ENABLED_KEY,
+            if settings.vat_enabled { "1" } else { "0" }.to_string(),
+        ),
+        (BUSINESS_VAT_RATE_KEY, settings.vat_rate.to_string()),
+    ])?;
+
+    load_business_settings(store)
+}
+
+/// Settings keys holding the `DisplayCurrencySettings`.
+const DISPLAY_CURRENCY_CODE_KEY: &str = "display_currency.code";
+const DISPLAY_CURRENCY_RATE_KEY: &str = "display_currency.rate";
+
+fn load_display_currency(store: &impl Store) -> Result<DisplayCurrencySettings, String> {
+    let defaults = DisplayCurrencySettings::default();
+    Ok(DisplayCurrencySettings {
+        code: store
+            .get_setting(DISPLAY_CURRENCY_CODE_KEY)?
+            .unwrap_or(defaults.code),
+        rate: parse_number(store, DISPLAY_CURRENCY_RATE_KEY, defaults.rate)?,
+    })
+}
+
+pub(crate) fn get_display_currency_inner(
+    store: &impl Store,
+) -> Result<DisplayCurrencySettings, String> {
+    load_display_currency(store)
+}
+
+pub(crate) fn update_display_currency_inner(
+    store: &impl Store,
+    settings: DisplayCurrencySettings,
+) -> Result<DisplayCurrencySettings, String> {
+    let code = settings.code.trim().to_uppercase();
+    if !code.is_empty() && (code.len() != 3 || !code.chars().all(|c| c.is_ascii_uppercase())) {
+        return Err(format!(
+            "Invalid currency code '{code}' (expected e.g. CHF)"
+        ));
+    }
+    if !settings.rate.is_finite() || settings.rate <= 0.0 {
+        return Err("Exchange rate must be greater than 0".to_string());
+    }
+
+    store.set_settings(&[
+        (DISPLAY_CURRENCY_CODE_KEY, code),
+        (DISPLAY_CURRENCY_RATE_KEY, settings.rate.to_string()),
+    ])?;
+    load_display_currency(store)
+}
+
+/// Formats a sequential invoice number.
+fn invoice_number(number: i64) -> String {
+    format!("INV-{number:06}")
+}
+
+/// Issues the invoice of `order_id` (or reprints it if one was already
+/// issued) and writes the PDF to `path`. Reprints use the seller details and
+/// amounts recorded at issue time. Voided orders and refunds are refused.
+pub(crate) fn generate_invoice_inner(
+    db: &DbState,
+    order_id: String,
+    customer: CustomerDetails,
+    path: String,
+) -> Result<Invoice, String> {
+    use rusqlite::OptionalExtension;
+
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let business = load_business_settings(&*conn)?;
+    if business.name.is_empty() {
+        return Err("Business details must be configured before issuing invoices".to_string());
+    }
+    let order = load_order(&conn, &order_id)?;
+    if order.order.voided_at.is_some() {
+        return Err("Voided orders cannot be invoiced".to_string());
+    }
+    if order.order.refund_of.is_some() || order.order.total < 0 {
+        return Err("Refunds cannot be invoiced".to_string());
+    }
+
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Transaction begin error: {e}"))?;
+
+    let existing = tx
+        .query_row(
+            "SELECT number, issued_at, customer_name, customer_address, customer_vat_number,
+                    seller_name, seller_address, seller_siret, seller_vat_number, vat_enabled,
+                    vat_rate, total, total_excl_vat
+             FROM invoices
+             WHERE order_id = ?1",
+            params![order_id],
+            |row| {
+                Ok(Invoice {
+                    number: invoice_number(row.get(0)?),
+                    order_id: order_id.clone(),
+                    issued_at: row.get(1)?,
+                    customer: CustomerDetails {
+                        name: row.get(2)?,
+                        address: row.get(3)?,
+                        vat_number: row.get(4)?,
+                    },
+                    seller: BusinessSettings {
+                        name: row.get(5)?,
+                        address: row.get(6)?,
+                        siret: row.get(7)?,
+                        vat_number: row.get(8)?,
+                        vat_enabled: row.get(9)?,
+                        vat_rate: row.get(10)?,
+                    },
+                    total: row.get(11)?,
+                    total_excl_vat: row.get(12)?,
+                })
+            },
+        )
+        .optional()
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let invoice = match existing {
+        Some(invoice) => invoice,
+        None => {
+            if customer.name.trim().is_empty() {
+                return Err("Customer name is required".to_string());
+            }
+            let number: i64 = tx
+                .query_row(
+                    "SELECT COALESCE(MAX(number), 0) + 1 FROM invoices",
+                    [],
+                    |row| row.get(0),
+                )
+                .map_err(|e| format!("Query error: {e}"))?;
+            let total = order.order.total;
+            let total_excl_vat = if business.vat_enabled {
+                crate::invoice::vat_breakdown(total, business.vat_rate).0
+            } else {
+                total
+            };
+            let invoice = Invoice {
+                number: invoice_number(number),
+                order_id: order_id.clone(),
+                issued_at: chrono::Local::now().format("%Y-%m-%d").to_string(),
+                customer: CustomerDetails {
+                    name: customer.name.trim().to_string(),
+                    address: customer.address.trim().to_string(),
+                    vat_number: customer.vat_number.trim().to_string(),
+                },
+                seller: business,
+                total,
+                total_excl_vat,
+            };
+            tx.execute(
+                "INSERT INTO invoices
+                    (number, order_id, issued_at, customer_name, customer_address, customer_vat_number,
+                     seller_name, seller_address, seller_siret, seller_vat_number, vat_enabled,
+                     vat_rate, total, total_excl_vat)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+                params![
+                    number,
+                    invoice.order_id,
+                    invoice.issued_at,
+                    invoice.customer.name,
+                    invoice.customer.address,
+                    invoice.customer.vat_number,
+                    invoice.seller.name,
+                    invoice.seller.address,
+                    invoice.seller.siret,
+                    invoice.seller.vat_number,
+                    invoice.seller.vat_enabled,
+                    invoice.seller.vat_rate,
+                    invoice.total,
+                    invoice.total_excl_vat
+                ],
+            )
+            .map_err(|e| format!("Insert invoice error: {e}"))?;
+            invoice
+        }
+    };
+
+    tx.commit()
+        .map_err(|e| format!("Transaction commit error: {e}"))?;
+
+    std::fs::write(&path, crate::invoice::render(&invoice, &order))
+        .map_err(|e| format!("Failed to write {path}: {e}"))?;
+
+    Ok(invoice)
+}
+
+/// Writes the PDF receipt of `order_id` to `path`. The receipt carries a QR
+/// code with the order id and chain hash, checked by `verify_receipt_code`.
+pub(crate) fn generate_receipt_inner(
+    db: &DbState,
+    order_id: String,
+    path: String,
+) -> Result<(), String> {
+    require_feature(db, Feature::Printing)?;
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let business = load_business_settings(&*conn)?;
+    let currency = load_display_currency(&*conn)?;
+    let order = load_order(&conn, &order_id)?;
+
+    std::fs::write(&path, crate::receipt::render(&business, &currency, &order)?)
+        .map_err(|e| format!("Failed to write {path}: {e}"))
+}
+
+/// Writes the price list of the products on sale (those of the active
+/// catalog, if any) to `path` as an A4 PDF, grouped by category, so that
+/// printed menus match the prices charged.
+pub(crate) fn export_menu_pdf_inner(db: &DbState, path: String) -> Result<(), String> {
+    require_feature(db, Feature::Printing)?;
+    let filter = ProductFilter {
+        available: Some(true),
+        ..ProductFilter::default()
+    };
+    let products = list_products_inner(db, true, &filter)?;
+    let categories = list_categories_inner(db)?;
+    let business = load_business_settings(db)?;
+    let title = if business.name.is_empty() {
+        "Menu"
+    } else {
+        business.name.as_str()
+    };
+
+    std::fs::write(&path, crate::menu::render(title, &categories, &products))
+        .map_err(|e| format!("Failed to write {path}: {e}"))
+}
+
+/// Folder of the app data dir receiving the preparation tickets of new
+/// orders, with one subfolder per station for its printer to pick them up.
+pub(crate) const TICKETS_FOLDER: &str = "tickets";
+
+/// Station receiving the kitchen tickets.
+const KITCHEN_STATION: &str = "Kitchen";
+
+/// Leaves out the lines nobody prepares: surcharges, rounding and
+/// adjustments.
+fn retain_prepared_items(order: &mut OrderWithItems) {
+    order.items.retain(|item| {
+        ![
+            SURCHARGE_PRODUCT_ID,
+            ROUNDING_PRODUCT_ID,
+            ADJUSTMENT_PRODUCT_ID,
+        ]
+        .contains(&item.product_id.as_str())
+    });
+}
+
+/// Writes the kitchen preparation ticket of `order_id` to `path`: its
+/// products and quantities, without prices.
+pub(crate) fn generate_kitchen_ticket_inner(
+    db: &DbState,
+    order_id: String,
+    path: String,
+) -> Result<(), String> {
+    require_feature(db, Feature::Printing)?;
+    let mut order = {
+        let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+        load_order(&conn, &order_id)?
+    };
+    if order.order.refund_of.is_some() {
+        return Err("Refunds have no kitchen ticket".to_string());
+    }
+    retain_prepared_items(&mut order);
+
+    std::fs::write(&path, crate::receipt::render_kitchen_ticket(None, &order))
+        .map_err(|e| format!("Failed to write {path}: {e}"))
+}
+
+/// Writes the preparation tickets of a new order to the station folders of
+/// `dir`, named after the order's kitchen reference: one per station as in
+/// `generate_station_tickets` once stations are routed, else the kitchen
+/// ticket. Nothing is written for refunds, nor while printing is disabled.
+pub(crate) fn write_order_tickets_inner(
+    db: &DbState,
+    order_id: &str,
+    dir: &std::path::Path,
+) -> Result<Vec<StationTicket>, String> {
+    if !feature_enabled(db, Feature::Printing)? {
+        return Ok(Vec::new());
+    }
+    let (mut order, stations) = {
+        let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+        let order = load_order(&conn, order_id)?;
+        let routed: bool = conn
+            .query_row("SELECT EXISTS (SELECT 1 FROM station_routes)", [], |row| {
+                row.get(0)
+            })
+            .map_err(|e| format!("Query error: {e}"))?;
+        let stations = if routed {
+            Some(items_by_station(&conn, &order)?)
+        } else {
+            None
+        };
+        (order, stations)
+    };
+    if order.order.refund_of.is_some() {
+        return Ok(Vec::new());
+    }
+    if let Some(stations) = stations {
+        return write_station_tickets(dir, &order.order, stations);
+    }
+    retain_prepared_items(&mut order);
+
+    let path = station_ticket_path(dir, KITCHEN_STATION, &order.order)?;
+    std::fs::write(&path, crate::receipt::render_kitchen_ticket(None, &order))
+        .map_err(|e| format!("Failed to write {path}: {e}"))?;
+    Ok(vec![StationTicket {
+        station: KITCHEN_STATION.to_string(),
+        path,
+    }])
+}
+
+/// `dir/station/reference.pdf` for the ticket of `order`, creating the
+/// station folder if needed.
+fn station_ticket_path(
+    dir: &std::path::Path,
+    station: &str,
+    order: &Order,
+) -> Result<String, String> {
+    let folder: String = station
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    let dir = dir.join(folder);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    Ok(dir
+        .join(format!("{}.pdf", crate::receipt::kitchen_reference(order)))
+        .to_string_lossy()
+        .into_owned())
+}
+
+/// Writes one preparation ticket per station to its folder in `dir`, each
+/// with the items of `order_id` routed to it by their category (see
+/// `set_station_route`), so that drinks go to the bar and food to the
+/// kitchen. Items whose category is not routed get no ticket.
+pub(crate) fn generate_station_tickets_inner(
+    db: &DbState,
+    order_id: String,
+    dir: String,
+) -> Result<Vec<StationTicket>, String> {
+    require_feature(db, Feature::Printing)?;
+    let (order, stations) = {
+        let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+        let order = load_order(&conn, &order_id)?;
+        let stations = items_by_station(&conn, &order)?;
+        (order, stations)
+    };
+    if order.order.refund_of.is_some() {
+        return Err("Refunds have no kitchen ticket".to_string());
+    }
+    write_station_tickets(std::path::Path::new(&dir), &order.order, stations)
+}
+
+/// The items of `order` grouped by the station they are routed to, in order
+/// of first appearance. Unrouted items are left out.
+fn items_by_station(
+    conn: &Connection,
+    order: &OrderWithItems,
+) -> Result<Vec<(String, Vec<OrderItem>)>, String> {
+    use rusqlite::OptionalExtension;
+
+    let mut stations: Vec<(String, Vec<OrderItem>)> = Vec::new();
+    for item in &order.items {
+        let category_id: Option<String> = conn
+            .query_row(
+                "SELECT category_id FROM products WHERE id = ?1",
+                params![item.product_id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Query error: {e}"))?;
+        let Some(station) = category_id
+            .map(|id| station_for(conn, &id))
+            .transpose()?
+            .flatten()
+        else {
+            continue;
+        };
+        match stations.iter_mut().find(|(s, _)| *s == station) {
+            Some((_, items)) => items.push(item.clone()),
+            None => stations.push((station, vec![item.clone()])),
+        }
+    }
+    Ok(stations)
+}
+
+fn write_station_tickets(
+    dir: &std::path::Path,
+    order: &Order,
+    stations: Vec<(String, Vec<OrderItem>)>,
+) -> Result<Vec<StationTicket>, String> {
+    let mut tickets = Vec::with_capacity(stations.len());
+    for (station, items) in stations {
+        let path = station_ticket_path(dir, &station, order)?;
+        let ticket = OrderWithItems {
+            order: order.clone(),
+            items,
+        };
+        std::fs::write(
+            &path,
+            crate::receipt::render_kitchen_ticket(Some(&station), &ticket),
+        )
+        .map_err(|e| format!("Failed to write {path}: {e}"))?;
+        tickets.push(StationTicket { station, path });
+    }
+    Ok(tickets)
+}
+
+/// Station of a category: its own route, else the route of its closest
+/// routed ancestor.
+fn station_for(conn: &Connection, category_id: &str) -> Result<Option<String>, String> {
+    use rusqlite::OptionalExtension;
+
+    for (id, _) in category_ancestors(conn, category_id)?.iter().rev() {
+        let station = conn
+            .query_row(
+                "SELECT station FROM station_routes WHERE category_id = ?1",
+                params![id],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(|e| format!("Query error: {e}"))?;
+        if station.is_some() {
+            return Ok(station);
+        }
+    }
+    Ok(None)
+}
+
+pub(crate) fn list_station_routes_inner(db: &DbState) -> Result<Vec<StationRoute>, String> {
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let mut stmt = conn
+        .prepare("SELECT category_id, station FROM station_routes ORDER BY station, category_id")
+        .map_err(|e| format!("Query error: {e}"))?;
+    let routes = stmt
+        .query_map([], |row| {
+            Ok(StationRoute {
+                category_id: row.get(0)?,
+                station: row.get(1)?,
+            })
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+    Ok(routes)
+}
+
+/// Routes the tickets of a category, and of its subcategories without a
+/// route of their own, to a station.
+pub(crate) fn set_station_route_inner(
+    db: &DbState,
+    route: StationRoute,
+) -> Result<StationRoute, String> {
+    let route = StationRoute {
+        station: route.station.trim().to_string(),
+        ..route
+    };
+    if route.station.is_empty() {
+        return Err("Station name cannot be empty".to_string());
+    }
+
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    if category_ancestors(&conn, &route.category_id)?.is_empty() {
+        return Err(format!("Category not found: {}", route.category_id));
+    }
+    conn.execute(
+        "INSERT INTO station_routes (category_id, station) VALUES (?1, ?2)
+         ON CONFLICT(category_id) DO UPDATE SET station = excluded.station",
+        params![route.category_id, route.station],
+    )
+    .map_err(|e| format!("Update error: {e}"))?;
+    Ok(route)
+}
+
+pub(crate) fn delete_station_route_inner(db: &DbState, category_id: String) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    conn.execute(
+        "DELETE FROM station_routes WHERE category_id = ?1",
+        params![category_id],
+    )
+    .map_err(|e| format!("Delete error: {e}"))?;
+    Ok(())
+}
+
+/// Builds the email carrying the PDF receipt of `order_id` to `address`,
+/// without sending it, together with the SMTP settings needed to deliver it.
+pub(crate) fn build_receipt_email_inner(
+    db: &DbState,
+    order_id: String,
+    address: String,
+) -> Result<(SmtpSettings, lettre::Message), String> {
+    require_feature(db, Feature::Printing)?;
+    let (settings, business, currency, order) = {
+        let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+        (
+            load_smtp_settings(&*conn)?,
+            load_business_settings(&*conn)?,
+            load_display_currency(&*conn)?,
+            load_order(&conn, &order_id)?,
+        )
+    };
+
+    let subject = if business.name.is_empty() {
+        "Your receipt".to_string()
+    } else {
+        format!("Your receipt from {}", business.name)
+    };
+    let converted = currency
+        .convert(order.order.total)
+        .map(|c| format!(" (about {} {})", export::format_cents(c.amount), c.currency))
+        .unwrap_or_default();
+    let body = format!(
+        "Thank you for your purchase.\n\nTotal: {} EUR{converted}, paid by {}.\nYour receipt is attached as PDF.\n",
+        export::format_cents(order.order.total),
+        order.order.payment_method
+    );
+    let message = crate::mail::build_message(
+        &settings,
+        &[address.trim().to_string()],
+        &subject,
+        body,
+        vec![crate::mail::MailAttachment {
+            filename: "receipt.pdf".to_string(),
+            content_type: "application/pdf".to_string(),
+            content: crate::receipt::render(&business, &currency, &order)?,
+        }],
+    )?;
+
+    Ok((settings, message))
+}
+
+/// Checks a scanned receipt code: the order must exist, its content must
+/// still hash to the stored chain hash, and that hash must be the one
+/// printed on the receipt.
+pub(crate) fn verify_receipt_code_inner(
+    db: &DbState,
+    code: String,
+) -> Result<ReceiptVerification, String> {
+    use rusqlite::OptionalExtension;
+
+    let (order_id, code_hash) = crate::receipt::parse_qr_payload(&code)?;
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+
+    let previous_hash: Option<Option<String>> = conn
+        .query_row(
+            "SELECT previous_hash FROM orders WHERE id = ?1",
+            params![order_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("Query error: {e}"))?;
+    let Some(previous_hash) = previous_hash else {
+        return Ok(ReceiptVerification {
+            authentic: false,
+            order: None,
+        });
+    };
+
+    let order = load_order(&conn, &order_id)?;
+    let authentic = match (&previous_hash, &order.order.hash) {
+        (Some(previous_hash), Some(stored_hash)) => {
+            *stored_hash == code_hash
+                && order_hash(previous_hash, &order.order, &order.items) == *stored_hash
+        }
+        _ => false,
+    };
+
+    Ok(ReceiptVerification {
+        authentic,
+        order: Some(order),
+    })
+}
+
+/// Prefix of the in-store barcodes; EAN-13 codes starting with 20 are
+/// reserved for restricted in-store circulation.
+const IN_STORE_BARCODE_PREFIX: &str = "20";
+
+/// Writes a PDF sheet with one barcode and price label per entry of
+/// `product_ids` (repeat an id to get several labels). Products printed for
+/// the first time get an in-store barcode. Returns the labelled products.
+pub(crate) fn print_product_labels_inner(
+    db: &DbState,
+    product_ids: Vec<String>,
+    path: String,
+) -> Result<Vec<Product>, String> {
+    use rusqlite::OptionalExtension;
+
+    require_feature(db, Feature::Printing)?;
+    if product_ids.is_empty() {
+        return Err("No products selected".to_string());
+    }
+
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Transaction begin error: {e}"))?;
+
+    let mut products: Vec<Product> = Vec::with_capacity(product_ids.len());
+    for product_id in &product_ids {
+        if let Some(product) = products.iter().find(|p| &p.id == product_id) {
+            products.push(product.clone());
+            continue;
+        }
+
+        let mut product = tx
+            .query_row(
+                &format!("SELECT {PRODUCT_COLUMNS} FROM products WHERE id = ?1"),
+                params![product_id],
+                product_from_row,
+            )
+            .optional()
+            .map_err(|e| format!("Query error: {e}"))?
+            .ok_or_else(|| format!("Product not found: {product_id}"))?;
+
+        if product.barcode.is_none() {
+            let next: i64 = tx
+                .query_row(
+                    "SELECT COALESCE(MAX(CAST(SUBSTR(barcode, 3, 10) AS INTEGER)), 0) + 1
+                     FROM products
+                     WHERE barcode LIKE ?1 || '%'",
+                    params![IN_STORE_BARCODE_PREFIX],
+                    |row| row.get(0),
+                )
+                .map_err(|e| format!("Query error: {e}"))?;
+            let barcode = crate::labels::ean13(&format!("{IN_STORE_BARCODE_PREFIX}{next:010}"));
+            tx.execute(
+                "UPDATE products SET barcode = ?1 WHERE id = ?2",
+                params![barcode, product.id],
+            )
+            .map_err(|e| format!("Update error: {e}"))?;
+            product.barcode = Some(barcode);
+        }
+        products.push(product);
+    }
+
+    std::fs::write(&path, crate::labels::render(&products)?)
+        .map_err(|e| format!("Failed to write {path}: {e}"))?;
+
+    tx.commit()
+        .map_err(|e| format!("Transaction commit error: {e}"))?;
+
+    Ok(products)
+}
+
+/// Settings key holding the id of the active catalog; empty when none is.
+const ACTIVE_CATALOG_KEY: &str = "catalog.active";
+
+fn active_catalog_id(store: &impl Store) -> Result<Option<String>, String> {
+    Ok(store
+        .get_setting(ACTIVE_CATALOG_KEY)?
+        .filter(|id| !id.is_empty()))
+}
+
+fn load_catalog(conn: &Connection, catalog_id: &str) -> Result<Catalog, String> {
+    use rusqlite::OptionalExtension;
+
+    let name: String = conn
+        .query_row(
+            "SELECT name FROM catalogs WHERE id = ?1",
+            params![catalog_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("Query error: {e}"))?
+        .ok_or_else(|| format!("Catalog not found: {catalog_id}"))?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT cp.product_id
+             FROM catalog_products cp
+             JOIN products p ON p.id = cp.product_id
+             WHERE cp.catalog_id = ?1
+             ORDER BY p.category_id, p.name",
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
+    let product_ids = stmt
+        .query_map(params![catalog_id], |row| row.get(0))
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    Ok(Catalog {
+        id: catalog_id.to_string(),
+        name,
+        product_ids,
+        active: active_catalog_id(conn)?.as_deref() == Some(catalog_id),
+    })
+}
+
+/// Replaces the products of a catalog.
+fn set_catalog_products(
+    conn: &Connection,
+    catalog_id: &str,
+    product_ids: &[String],
+) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM catalog_products WHERE catalog_id = ?1",
+        params![catalog_id],
+    )
+    .map_err(|e| format!("Delete error: {e}"))?;
+
+    for product_id in product_ids {
+        conn.execute(
+            "INSERT OR IGNORE INTO catalog_products (catalog_id, product_id) VALUES (?1, ?2)",
+            params![catalog_id, product_id],
+        )
+        .map_err(|e| format!("Product not found ({product_id}): {e}"))?;
+    }
+    Ok(())
+}
+
+fn validate_catalog_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Catalog name is required".to_string());
+    }
+    Ok(name.to_string())
+}
+
+pub(crate) fn list_catalogs_inner(db: &DbState) -> Result<Vec<Catalog>, String> {
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+
+    let mut stmt = conn
+        .prepare("SELECT id FROM catalogs ORDER BY name")
+        .map_err(|e| format!("Query error: {e}"))?;
+    let ids = stmt
+        .query_map([], |row| row.get(0))
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    ids.iter().map(|id| load_catalog(&conn, id)).collect()
+}
+
+pub(crate) fn create_catalog_inner(
+    db: &DbState,
+    payload: CreateCatalogPayload,
+) -> Result<Catalog, String> {
+    let name = validate_catalog_name(&payload.name)?;
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Transaction begin error: {e}"))?;
+
+    let id = Uuid::new_v4().to_string();
+    tx.execute(
+        "INSERT INTO catalogs (id, name) VALUES (?1, ?2)",
+        params![id, name],
+    )
+    .map_err(|e| format!("Insert error: {e}"))?;
+    set_catalog_products(&tx, &id, &payload.product_ids)?;
+
+    let catalog = load_catalog(&tx, &id)?;
+    tx.commit()
+        .map_err(|e| format!("Transaction commit error: {e}"))?;
+    Ok(catalog)
+}
+
+pub(crate) fn update_catalog_inner(
+    db: &DbState,
+    payload: UpdateCatalogPayload,
+) -> Result<Catalog, String> {
+    let name = validate_catalog_name(&payload.name)?;
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Transaction begin error: {e}"))?;
+
+    let rows_affected = tx
+        .execute(
+            "UPDATE catalogs SET name = ?1 WHERE id = ?2",
+            params![name, payload.id],
+        )
+        .map_err(|e| format!("Update error: {e}"))?;
+    if rows_affected == 0 {
+        return Err(format!("Catalog not found: {}", payload.id));
+    }
+    if let Some(product_ids) = &payload.product_ids {
+        set_catalog_products(&tx, &payload.id, product_ids)?;
+    }
+
+    let catalog = load_catalog(&tx, &payload.id)?;
+    tx.commit()
+        .map_err(|e| format!("Transaction commit error: {e}"))?;
+    Ok(catalog)
+}
+
+pub(crate) fn delete_catalog_inner(db: &DbState, catalog_id: String) -> Result<(), String> {
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Transaction begin error: {e}"))?;
+
+    let rows_affected = tx
+        .execute("DELETE FROM catalogs WHERE id = ?1", params![catalog_id])
+        .map_err(|e| format!("Delete error: {e}"))?;
+    if rows_affected == 0 {
+        return Err(format!("Catalog not found: {catalog_id}"));
+    }
+    if active_catalog_id(&*tx)?.as_deref() == Some(catalog_id.as_str()) {
+        crate::db::set_setting(&tx, ACTIVE_CATALOG_KEY, "")?;
+    }
+
+    tx.commit()
+        .map_err(|e| format!("Transaction commit error: {e}"))
+}
+
+/// Makes `catalog_id` the catalog of the sales screen; `None` offers every
+/// product again.
+pub(crate) fn set_active_catalog_inner(
+    db: &DbState,
+    catalog_id: Option<String>,
+) -> Result<Option<Catalog>, String> {
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+
+    match catalog_id {
+        Some(catalog_id) => {
+            // Fails on unknown catalogs before anything is written.
+            load_catalog(&conn, &catalog_id)?;
+            crate::db::set_setting(&conn, ACTIVE_CATALOG_KEY, &catalog_id)?;
+            load_catalog(&conn, &catalog_id).map(Some)
+        }
+        None => {
+            crate::db::set_setting(&conn, ACTIVE_CATALOG_KEY, "")?;
+            Ok(None)
+        }
+    }
+}
+
+/// Settings key holding the id of the register this till is; empty when
+/// none is.
+const CURRENT_REGISTER_KEY: &str = "register.current";
+
+fn current_register_id(store: &impl Store) -> Result<Option<String>, String> {
+    Ok(store
+        .get_setting(CURRENT_REGISTER_KEY)?
+        .filter(|id| !id.is_empty()))
+}
+
+/// Ids returned by `query`, with the register id bound to `?1`.
+fn register_members(
+    conn: &Connection,
+    register_id: &str,
+    query: &str,
+) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare(query)
+        .map_err(|e| format!("Query error: {e}"))?;
+    let ids = stmt
+        .query_map(params![register_id], |row| row.get(0))
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+    Ok(ids)
+}
+
+fn load_register(conn: &Connection, register_id: &str) -> Result<Register, String> {
+    use rusqlite::OptionalExtension;
+
+    let name: String = conn
+        .query_row(
+            "SELECT name FROM registers WHERE id = ?1",
+            params![register_id],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("Query error: {e}"))?
+        .ok_or_else(|| format!("Register not found: {register_id}"))?;
+
+    Ok(Register {
+        id: register_id.to_string(),
+        name,
+        product_ids: register_members(
+            conn,
+            register_id,
+            "SELECT rp.product_id
+             FROM register_products rp
+             JOIN products p ON p.id = rp.product_id
+             WHERE rp.register_id = ?1
+             ORDER BY p.category_id, p.name",
+        )?,
+        category_ids: register_members(
+            conn,
+            register_id,
+            "SELECT rc.category_id
+             FROM register_categories rc
+             JOIN categories c ON c.id = rc.category_id
+             WHERE rc.register_id = ?1
+             ORDER BY c.label",
+        )?,
+        current: current_register_id(conn)?.as_deref() == Some(register_id),
+    })
+}
+
+/// Replaces the products a register shows.
+fn set_register_products(
+    conn: &Connection,
+    register_id: &str,
+    product_ids: &[String],
+) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM register_products WHERE register_id = ?1",
+        params![register_id],
+    )
+    .map_err(|e| format!("Delete error: {e}"))?;
+
+    for product_id in product_ids {
+        conn.execute(
+            "INSERT OR IGNORE INTO register_products (register_id, product_id) VALUES (?1, ?2)",
+            params![register_id, product_id],
+        )
+        .map_err(|e| format!("Product not found ({product_id}): {e}"))?;
+    }
+    Ok(())
+}
+
+/// Replaces the categories a register shows.
+fn set_register_categories(
+    conn: &Connection,
+    register_id: &str,
+    category_ids: &[String],
+) -> Result<(), String> {
+    conn.execute(
+        "DELETE FROM register_categories WHERE register_id = ?1",
+        params![register_id],
+    )
+    .map_err(|e| format!("Delete error: {e}"))?;
+
+    for category_id in category_ids {
+        conn.execute(
+            "INSERT OR IGNORE INTO register_categories (register_id, category_id) VALUES (?1, ?2)",
+            params![register_id, category_id],
+        )
+        .map_err(|e| format!("Category not found ({category_id}): {e}"))?;
+    }
+    Ok(())
+}
+
+fn validate_register_name(name: &str) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Register name is required".to_string());
+    }
+    Ok(name.to_string())
+}
+
+pub(crate) fn list_registers_inner(db: &DbState) -> Result<Vec<Register>, String> {
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+
+    let mut stmt = conn
+        .prepare("SELECT id FROM registers ORDER BY name")
+        .map_err(|e| format!("Query error: {e}"))?;
+    let ids = stmt
+        .query_map([], |row| row.get(0))
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<String>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    ids.iter().map(|id| load_register(&conn, id)).collect()
+}
+
+pub(crate) fn create_register_inner(
+    db: &DbState,
+    payload: CreateRegisterPayload,
+) -> Result<Register, String> {
+    let name = validate_register_name(&payload.name)?;
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Transaction begin error: {e}"))?;
+
+    let id = Uuid::new_v4().to_string();
+    tx.execute(
+        "INSERT INTO registers (id, name) VALUES (?1, ?2)",
+        params![id, name],
+    )
+    .map_err(|e| format!("Insert error: {e}"))?;
+    set_register_products(&tx, &id, &payload.product_ids)?;
+    set_register_categories(&tx, &id, &payload.category_ids)?;
+
+    let register = load_register(&tx, &id)?;
+    tx.commit()
+        .map_err(|e| format!("Transaction commit error: {e}"))?;
+    Ok(register)
+}
+
+pub(crate) fn update_register_inner(
+    db: &DbState,
+    payload: UpdateRegisterPayload,
+) -> Result<Register, String> {
+    let name = validate_register_name(&payload.name)?;
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Transaction begin error: {e}"))?;
+
+    let rows_affected = tx
+        .execute(
+            "UPDATE registers SET name = ?1 WHERE id = ?2",
+            params![name, payload.id],
+        )
+        .map_err(|e| format!("Update error: {e}"))?;
+    if rows_affected == 0 {
+        return Err(format!("Register not found: {}", payload.id));
+    }
+    if let Some(product_ids) = &payload.product_ids {
+        set_register_products(&tx, &payload.id, product_ids)?;
+    }
+    if let Some(category_ids) = &payload.category_ids {
+        set_register_categories(&tx, &payload.id, category_ids)?;
+    }
+
+    let register = load_register(&tx, &payload.id)?;
+    tx.commit()
+        .map_err(|e| format!("Transaction commit error: {e}"))?;
+    Ok(register)
+}
+
+pub(crate) fn delete_register_inner(db: &DbState, register_id: String) -> Result<(), String> {
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Transaction begin error: {e}"))?;
+
+    let rows_affected = tx
+        .execute("DELETE FROM registers WHERE id = ?1", params![register_id])
+        .map_err(|e| format!("Delete error: {e}"))?;
+    if rows_affected == 0 {
+        return Err(format!("Register not found: {register_id}"));
+    }
+    if current_register_id(&*tx)?.as_deref() == Some(register_id.as_str()) {
+        crate::db::set_setting(&tx, CURRENT_REGISTER_KEY, "")?;
+    }
+
+    tx.commit()
+        .map_err(|e| format!("Transaction commit error: {e}"))
+}
+
+/// Makes this till `register_id`; `None` shows every product again.
+pub(crate) fn set_current_register_inner(
+    db: &DbState,
+    register_id: Option<String>,
+) -> Result<Option<Register>, String> {
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+
+    match register_id {
+        Some(register_id) => {
+            // Fails on unknown registers before anything is written.
+            load_register(&conn, &register_id)?;
+            crate::db::set_setting(&conn, CURRENT_REGISTER_KEY, &register_id)?;
+            load_register(&conn, &register_id).map(Some)
+        }
+        None => {
+            crate::db::set_setting(&conn, CURRENT_REGISTER_KEY, "")?;
+            Ok(None)
+        }
+    }
+}
+
+pub(crate) fn delete_product_inner(
+    store: &impl Store,
+    product_id: String,
+    admin_pin: Option<&str>,
+) -> Result<(), String> {
+    verify_catalog_admin(store, admin_pin)?;
+    store.delete_product(&product_id)
+}
+
+pub(crate) fn delete_category_inner(
+    db: &DbState,
+    category_id: String,
+    admin_pin: Option<&str>,
+) -> Result<(), String> {
+    verify_catalog_admin(db, admin_pin)?;
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+
+    // Check whether any products reference this category.
+    let product_count: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM products WHERE category_id = ?1",
+            params![category_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    if product_count > 0 {
+        return Err(format!(
+            "Cannot delete category '{}': it is referenced by {} product(s)",
+            category_id, product_count
+        ));
+    }
+
+    let subcategory_count: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM categories WHERE parent_id = ?1",
+            params![category_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    if subcategory_count > 0 {
+        return Err(format!(
+            "Cannot delete category '{category_id}': it has {subcategory_count} subcategory(ies)"
+        ));
+    }
+
+    let rows_affected = conn
+        .execute("DELETE FROM categories WHERE id = ?1", params![category_id])
+        .map_err(|e| format!("Delete error: {e}"))?;
+
+    if rows_affected == 0 {
+        return Err(format!("Category not found: {}", category_id));
+    }
+
+    Ok(())
+}
+
+/// SQL expression of the stock level of product `p`: its stock movements
+/// minus what was sold by non-voided orders.
+const STOCK_LEVEL: &str = "COALESCE((SELECT SUM(m.quantity) FROM stock_movements m
+                                      WHERE m.product_id = p.id), 0)
+     - COALESCE((SELECT SUM(oi.quantity) FROM order_items oi
+                 JOIN orders o ON o.id = oi.order_id
+                 WHERE oi.product_id = p.id AND o.voided_at IS NULL), 0)";
+
+/// Rounds a stock quantity to the gram / millilitre.
+fn round_stock(quantity: f64) -> f64 {
+    (quantity * 1000.0).round() / 1000.0
+}
+
+/// Lists the stock of the products that were counted at least once.
+pub(crate) fn get_stock_levels_inner(db: &DbState) -> Result<Vec<StockLevel>, String> {
+    require_feature(db, Feature::Stock)?;
+    let conn = db.read_conn()?;
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT p.id, p.name, p.unit, {STOCK_LEVEL}
+             FROM products p
+             WHERE EXISTS (SELECT 1 FROM stock_movements m WHERE m.product_id = p.id)
+                OR EXISTS (SELECT 1 FROM stocktake_counts c WHERE c.product_id = p.id)
+             ORDER BY p.category_id, p.name"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let levels = stmt
+        .query_map([], |row| {
+            Ok(StockLevel {
+                product_id: row.get(0)?,
+                product_name: row.get(1)?,
+                unit: quantity_unit_from_row(row, 2)?,
+                quantity: round_stock(row.get(3)?),
+            })
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    Ok(levels)
+}
+
+fn load_stocktake(conn: &Connection, stocktake_id: &str) -> Result<Stocktake, String> {
+    use rusqlite::OptionalExtension;
+
+    conn.query_row(
+        "SELECT id, started_at, committed_at FROM stocktakes WHERE id = ?1",
+        params![stocktake_id],
+        |row| {
+            Ok(Stocktake {
+                id: row.get(0)?,
+                started_at: row.get(1)?,
+                committed_at: row.get(2)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(|e| format!("Query error: {e}"))?
+    .ok_or_else(|| format!("Stocktake not found: {stocktake_id}"))
+}
+
+fn load_open_stocktake(conn: &Connection, stocktake_id: &str) -> Result<Stocktake, String> {
+    let stocktake = load_stocktake(conn, stocktake_id)?;
+    if stocktake.committed_at.is_some() {
+        return Err(format!("Stocktake {stocktake_id} is already committed"));
+    }
+    Ok(stocktake)
+}
+
+fn stocktake_report(conn: &Connection, stocktake_id: &str) -> Result<StocktakeReport, String> {
+    let stocktake = load_stocktake(conn, stocktake_id)?;
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT p.id, p.name, p.unit, p.price, c.counted, COALESCE(c.expected, {STOCK_LEVEL})
+             FROM stocktake_counts c
+             JOIN products p ON p.id = c.product_id
+             WHERE c.stocktake_id = ?1
+             ORDER BY p.category_id, p.name"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let lines = stmt
+        .query_map(params![stocktake_id], |row| {
+            let price: i64 = row.get(3)?;
+            let counted: f64 = row.get(4)?;
+            let expected = round_stock(row.get(5)?);
+            let variance = round_stock(counted - expected);
+            Ok(StocktakeLine {
+                product_id: row.get(0)?,
+                product_name: row.get(1)?,
+                unit: quantity_unit_from_row(row, 2)?,
+                expected,
+                counted,
+                variance,
+                variance_value: line_total(price, variance),
+            })
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    Ok(StocktakeReport {
+        stocktake,
+        total_variance_value: lines.iter().map(|l| l.variance_value).sum(),
+        lines,
+    })
+}
+
+/// Opens a stocktake; only one can be open at a time.
+pub(crate) fn start_stocktake_inner(db: &DbState) -> Result<Stocktake, String> {
+    require_feature(db, Feature::Stock)?;
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+
+    let open: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM stocktakes WHERE committed_at IS NULL",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
+    if open > 0 {
+        return Err("A stocktake is already in progress".to_string());
+    }
+
+    let stocktake = Stocktake {
+        id: Uuid::new_v4().to_string(),
+        started_at: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        committed_at: None,
+    };
+    conn.execute(
+        "INSERT INTO stocktakes (id, started_at) VALUES (?1, ?2)",
+        params![stocktake.id, stocktake.started_at],
+    )
+    .map_err(|e| format!("Insert error: {e}"))?;
+
+    Ok(stocktake)
+}
+
+/// Records the counted quantity of a product, replacing any previous count.
+pub(crate) fn count_stocktake_product_inner(
+    db: &DbState,
+    stocktake_id: String,
+    product_id: String,
+    counted: f64,
+) -> Result<(), String> {
+    use rusqlite::OptionalExtension;
+
+    require_feature(db, Feature::Stock)?;
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    load_open_stocktake(&conn, &stocktake_id)?;
+
+    let unit = conn
+        .query_row(
+            "SELECT unit FROM products WHERE id = ?1",
+            params![product_id],
+            |row| quantity_unit_from_row(row, 0),
+        )
+        .optional()
+        .map_err(|e| format!("Query error: {e}"))?
+        .ok_or_else(|| format!("Product not found: {product_id}"))?;
+
+    if !counted.is_finite() || counted < 0.0 {
+        return Err(format!("Invalid counted quantity {counted}"));
+    }
+    let counted = round_stock(counted);
+    if unit == QuantityUnit::Piece && counted.fract() != 0.0 {
+        return Err(format!("Counted quantity {counted} must be a whole number"));
+    }
+
+    conn.execute(
+        "INSERT INTO stocktake_counts (stocktake_id, product_id, counted) VALUES (?1, ?2, ?3)
+         ON CONFLICT (stocktake_id, product_id) DO UPDATE SET counted = excluded.counted",
+        params![stocktake_id, product_id, counted],
+    )
+    .map_err(|e| format!("Insert error: {e}"))?;
+
+    Ok(())
+}
+
+/// Variance report of a stocktake; a preview against the live stock while it
+/// is still open.
+pub(crate) fn get_stocktake_report_inner(
+    db: &DbState,
+    stocktake_id: String,
+) -> Result<StocktakeReport, String> {
+    require_feature(db, Feature::Stock)?;
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    stocktake_report(&conn, &stocktake_id)
+}
+
+/// Closes a stocktake: freezes the expected quantities and records an
+/// adjustment movement for every difference, so that the stock of the counted
+/// products matches the count.
+pub(crate) fn commit_stocktake_inner(
+    db: &DbState,
+    stocktake_id: String,
+) -> Result<StocktakeReport, String> {
+    require_feature(db, Feature::Stock)?;
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Transaction begin error: {e}"))?;
+    load_open_stocktake(&tx, &stocktake_id)?;
+
+    let now = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    let report = stocktake_report(&tx, &stocktake_id)?;
+    for line in &report.lines {
+        tx.execute(
+            "UPDATE stocktake_counts SET expected = ?1 WHERE stocktake_id = ?2 AND product_id = ?3",
+            params![line.expected, stocktake_id, line.product_id],
+        )
+        .map_err(|e| format!("Update error: {e}"))?;
+
+        if line.variance != 0.0 {
+            tx.execute(
+                "INSERT INTO stock_movements (id, product_id, kind, quantity, stocktake_id, created_at)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    Uuid::new_v4().to_string(),
+                    line.product_id,
+                    StockMovementKind::Adjustment.as_db_str(),
+                    line.variance,
+                    stocktake_id,
+                    now,
+                ],
+            )
+            .map_err(|e| format!("Insert error: {e}"))?;
+        }
+    }
+    tx.execute(
+        "UPDATE stocktakes SET committed_at = ?1 WHERE id = ?2",
+        params![now, stocktake_id],
+    )
+    .map_err(|e| format!("Update error: {e}"))?;
+
+    let report = stocktake_report(&tx, &stocktake_id)?;
+    tx.commit()
+        .map_err(|e| format!("Transaction commit error: {e}"))?;
+    Ok(report)
+}
+
+/// Records goods thrown away, taking them out of the stock.
+pub(crate) fn record_waste_inner(
+    db: &DbState,
+    product_id: String,
+    quantity: f64,
+    reason: String,
+) -> Result<StockMovement, String> {
+    use rusqlite::OptionalExtension;
+
+    require_feature(db, Feature::Stock)?;
+    let reason = reason.trim();
+    if reason.is_empty() {
+        return Err("A waste reason is required".to_string());
+    }
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+
+    let unit = conn
+        .query_row(
+            "SELECT unit FROM products WHERE id = ?1",
+            params![product_id],
+            |row| quantity_unit_from_row(row, 0),
+        )
+        .optional()
+        .map_err(|e| format!("Query error: {e}"))?
+        .ok_or_else(|| format!("Product not found: {product_id}"))?;
+
+    if !quantity.is_finite() || quantity <= 0.0 {
+        return Err(format!("Invalid wasted quantity {quantity}"));
+    }
+    let quantity = round_stock(quantity);
+    if unit == QuantityUnit::Piece && quantity.fract() != 0.0 {
+        return Err(format!("Wasted quantity {quantity} must be a whole number"));
+    }
+
+    let movement = StockMovement {
+        id: Uuid::new_v4().to_string(),
+        product_id,
+        kind: StockMovementKind::Waste,
+        quantity: -quantity,
+        reason: Some(reason.to_string()),
+        created_at: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+    };
+    conn.execute(
+        "INSERT INTO stock_movements (id, product_id, kind, quantity, reason, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        params![
+            movement.id,
+            movement.product_id,
+            movement.kind.as_db_str(),
+            movement.quantity,
+            movement.reason,
+            movement.created_at,
+        ],
+    )
+    .map_err(|e| format!("Insert error: {e}"))?;
+
+    Ok(movement)
+}
+
+/// Puts goods in stock as a batch, with the date they must be sold by.
+pub(crate) fn add_stock_batch_inner(
+    db: &DbState,
+    payload: AddStockBatchPayload,
+) -> Result<StockBatch, String> {
+    use rusqlite::OptionalExtension;
+
+    require_feature(db, Feature::Stock)?;
+    let expires_on = payload
+        .expires_on
+        .map(|date| date.trim().to_string())
+        .filter(|date| !date.is_empty());
+    if let Some(date) = &expires_on {
+        validate_date(date)?;
+    }
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+
+    let unit = conn
+        .query_row(
+            "SELECT unit FROM products WHERE id = ?1",
+            params![payload.product_id],
+            |row| quantity_unit_from_row(row, 0),
+        )
+        .optional()
+        .map_err(|e| format!("Query error: {e}"))?
+        .ok_or_else(|| format!("Product not found: {}", payload.product_id))?;
+
+    if !payload.quantity.is_finite() || payload.quantity <= 0.0 {
+        return Err(format!("Invalid batch quantity {}", payload.quantity));
+    }
+    let quantity = round_stock(payload.quantity);
+    if unit == QuantityUnit::Piece && quantity.fract() != 0.0 {
+        return Err(format!("Batch quantity {quantity} must be a whole number"));
+    }
+
+    let batch = StockBatch {
+        id: Uuid::new_v4().to_string(),
+        product_id: payload.product_id,
+        quantity,
+        expires_on,
+        created_at: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+    };
+    write_transaction(&mut conn, |tx| {
+        tx.execute(
+            "INSERT INTO stock_movements (id, product_id, kind, quantity, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                batch.id,
+                batch.product_id,
+                StockMovementKind::Receipt.as_db_str(),
+                batch.quantity,
+                batch.created_at,
+            ],
+        )
+        .map_err(|e| format!("Insert error: {e}"))?;
+        tx.execute(
+            "INSERT INTO stock_batches (movement_id, expires_on) VALUES (?1, ?2)",
+            params![batch.id, batch.expires_on],
+        )
+        .map_err(|e| format!("Insert error: {e}"))?;
+        Ok(())
+    })?;
+
+    Ok(batch)
+}
+
+/// Batches with stock left that expire within `days` of `today`, or have
+/// already expired, soonest first.
+pub(crate) fn get_expiring_batches_inner(
+    db: &DbState,
+    today: chrono::NaiveDate,
+    days: u32,
+) -> Result<Vec<ExpiringBatch>, String> {
+    require_feature(db, Feature::Stock)?;
+    let conn = db.read_conn()?;
+
+    // Newest batches first: they hold what is left of the stock.
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT m.id, p.id, p.name, p.unit, m.quantity, b.expires_on, {STOCK_LEVEL}
+             FROM stock_batches b
+             JOIN stock_movements m ON m.id = b.movement_id
+             JOIN products p ON p.id = m.product_id
+             ORDER BY p.id, m.created_at DESC, m.rowid DESC"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+    let rows = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                quantity_unit_from_row(row, 3)?,
+                row.get::<_, f64>(4)?,
+                row.get::<_, Option<String>>(5)?,
+                row.get::<_, f64>(6)?,
+            ))
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    let mut expiring = Vec::new();
+    let mut product_id = None;
+    let mut left = 0.0;
+    for (batch_id, id, name, unit, quantity, expires_on, level) in rows {
+        if product_id.as_ref() != Some(&id) {
+            left = level.max(0.0);
+            product_id = Some(id.clone());
+        }
+        let remaining = round_stock(quantity.min(left));
+        left -= remaining;
+        let Some(expires_on) = expires_on else {
+            continue;
+        };
+        let days_left = (parse_date(&expires_on)? - today).num_days();
+        if remaining > 0.0 && days_left <= i64::from(days) {
+            expiring.push(ExpiringBatch {
+                batch_id,
+                product_id: id,
+                product_name: name,
+                unit,
+                expires_on,
+                remaining,
+                days_left,
+            });
+        }
+    }
+
+    expiring.sort_by(|a, b| {
+        a.expires_on
+            .cmp(&b.expires_on)
+            .then_with(|| a.product_name.cmp(&b.product_name))
+    });
+    Ok(expiring)
+}
+
+/// How many days ahead the expiry alert looks.
+const EXPIRY_ALERT_DAYS: u32 = 1;
+
+/// Builds the message listing the batches expiring by the day after `today`,
+/// together with the settings needed to deliver it. `None` when nothing is
+/// about to expire.
+pub(crate) fn build_expiry_alert_inner(
+    db: &DbState,
+    today: chrono::NaiveDate,
+) -> Result<Option<(NotificationSettings, String)>, String> {
+    let batches = get_expiring_batches_inner(db, today, EXPIRY_ALERT_DAYS)?;
+    if batches.is_empty() {
+        return Ok(None);
+    }
+    let settings = load_notification_settings(db)?;
+
+    let mut text = "Stock expiring soon:".to_string();
+    for batch in &batches {
+        let when = match batch.days_left {
+            days if days < 0 => format!("expired {}", batch.expires_on),
+            0 => "today".to_string(),
+            _ => format!("by {}", batch.expires_on),
+        };
+        text.push_str(&format!(
+            "\n{} x{}: {when}",
+            batch.product_name, batch.remaining
+        ));
+    }
+    Ok(Some((settings, text)))
+}
+
+/// Wasted quantities per product and reason over `range`, most costly first.
+pub(crate) fn get_waste_report_inner(
+    db: &DbState,
+    range: DateRange,
+) -> Result<Vec<WasteSummary>, String> {
+    require_feature(db, Feature::Stock)?;
+    validate_range(&range)?;
+    let conn = db.read_conn()?;
+
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT p.id, p.name, p.unit, p.price, m.reason, -SUM(m.quantity)
+             FROM stock_movements m
+             JOIN products p ON p.id = m.product_id
+             WHERE m.kind = 'waste' AND {DATE_RANGE_FILTER}
+             GROUP BY p.id, m.reason"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let mut waste = stmt
+        .query_map(params![range.from, range.to], |row| {
+            let price: i64 = row.get(3)?;
+            let quantity = round_stock(row.get(5)?);
+            Ok(WasteSummary {
+                product_id: row.get(0)?,
+                product_name: row.get(1)?,
+                unit: quantity_unit_from_row(row, 2)?,
+                reason: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+                quantity,
+                value: line_total(price, quantity),
+            })
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    waste.sort_by(|a, b| {
+        b.value
+            .cmp(&a.value)
+            .then_with(|| a.product_name.cmp(&b.product_name))
+            .then_with(|| a.reason.cmp(&b.reason))
+    });
+    Ok(waste)
+}
+
+/// SQL expression of the stock level of ingredient `i`: the sum of its
+/// movements, sales and voids included.
+const INGREDIENT_STOCK: &str = "COALESCE((SELECT SUM(m.quantity) FROM ingredient_movements m
+                                           WHERE m.ingredient_id = i.id), 0)";
+
+fn query_ingredients(
+    conn: &Connection,
+    filter: &str,
+    params: impl rusqlite::Params,
+) -> Result<Vec<Ingredient>, String> {
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT id, name, unit, low_stock_threshold, stock
+             FROM (SELECT i.*, {INGREDIENT_STOCK} AS stock FROM ingredients i)
+             WHERE {filter}
+             ORDER BY name"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let ingredients = stmt
+        .query_map(params, |row| {
+            let low_stock_threshold: Option<f64> = row.get(3)?;
+            let stock = round_stock(row.get(4)?);
+            Ok(Ingredient {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                unit: quantity_unit_from_row(row, 2)?,
+                low_stock_threshold,
+                stock,
+                low_stock: low_stock_threshold.is_some_and(|t| stock <= t),
+            })
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    Ok(ingredients)
+}
+
+fn load_ingredient(conn: &Connection, ingredient_id: &str) -> Result<Ingredient, String> {
+    query_ingredients(conn, "id = ?1", params![ingredient_id])?
+        .pop()
+        .ok_or_else(|| format!("Ingredient not found: {ingredient_id}"))
+}
+
+fn validate_ingredient(name: &str, low_stock_threshold: Option<f64>) -> Result<String, String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("Ingredient name is required".to_string());
+    }
+    if let Some(threshold) = low_stock_threshold {
+        if !threshold.is_finite() || threshold < 0.0 {
+            return Err(format!("Invalid low stock threshold {threshold}"));
+        }
+    }
+    Ok(name.to_string())
+}
+
+pub(crate) fn list_ingredients_inner(db: &DbState) -> Result<Vec<Ingredient>, String> {
+    require_feature(db, Feature::Stock)?;
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    query_ingredients(&conn, "1", [])
+}
+
+/// Ingredients at or below their low stock threshold.
+pub(crate) fn get_low_stock_ingredients_inner(db: &DbState) -> Result<Vec<Ingredient>, String> {
+    require_feature(db, Feature::Stock)?;
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    query_ingredients(&conn, "stock <= low_stock_threshold", [])
+}
+
+pub(crate) fn create_ingredient_inner(
+    db: &DbState,
+    payload: CreateIngredientPayload,
+) -> Result<Ingredient, String> {
+    require_feature(db, Feature::Stock)?;
+    let name = validate_ingredient(&payload.name, payload.low_stock_threshold)?;
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+
+    let id = Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO ingredients (id, name, unit, low_stock_threshold) VALUES (?1, ?2, ?3, ?4)",
+        params![
+            id,
+            name,
+            payload.unit.as_db_str(),
+            payload.low_stock_threshold
+        ],
+    )
+    .map_err(|e| format!("Insert error: {e}"))?;
+
+    load_ingredient(&conn, &id)
+}
+
+pub(crate) fn update_ingredient_inner(
+    db: &DbState,
+    payload: UpdateIngredientPayload,
+) -> Result<Ingredient, String> {
+    require_feature(db, Feature::Stock)?;
+    let name = validate_ingredient(&payload.name, payload.low_stock_threshold)?;
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+
+    let rows_affected = conn
+        .execute(
+            "UPDATE ingredients SET name = ?1, unit = ?2, low_stock_threshold = ?3 WHERE id = ?4",
+            params![
+                name,
+                payload.unit.as_db_str(),
+                payload.low_stock_threshold,
+                payload.id
+            ],
+        )
+        .map_err(|e| format!("Update error: {e}"))?;
+    if rows_affected == 0 {
+        return Err(format!("Ingredient not found: {}", payload.id));
+    }
+
+    load_ingredient(&conn, &payload.id)
+}
+
+/// Deletes an ingredient; refused while a recipe still uses it.
+pub(crate) fn delete_ingredient_inner(db: &DbState, ingredient_id: String) -> Result<(), String> {
+    require_feature(db, Feature::Stock)?;
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+
+    let recipe_count: i64 = conn
+        .query_row(
+            "SELECT COUNT(*) FROM recipe_items WHERE ingredient_id = ?1",
+            params![ingredient_id],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
+    if recipe_count > 0 {
+        return Err(format!(
+            "Cannot delete ingredient '{ingredient_id}': {recipe_count} recipe(s) use it"
+        ));
+    }
+
+    let rows_affected = conn
+        .execute(
+            "DELETE FROM ingredients WHERE id = ?1",
+            params![ingredient_id],
+        )
+        .map_err(|e| format!("Delete error: {e}"))?;
+    if rows_affected == 0 {
+        return Err(format!("Ingredient not found: {ingredient_id}"));
+    }
+
+    Ok(())
+}
+
+/// Adds `quantity` (negative to remove) to the stock of an ingredient, e.g.
+/// for a delivery or a correction after counting.
+pub(crate) fn adjust_ingredient_stock_inner(
+    db: &DbState,
+    ingredient_id: String,
+    quantity: f64,
+    reason: Option<String>,
+) -> Result<Ingredient, String> {
+    require_feature(db, Feature::Stock)?;
+    if !quantity.is_finite() || quantity == 0.0 {
+        return Err(format!("Invalid stock adjustment {quantity}"));
+    }
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    load_ingredient(&conn, &ingredient_id)?;
+
+    let reason = reason
+        .map(|r| r.trim().to_string())
+        .filter(|r| !r.is_empty());
+    conn.execute(
+        "INSERT INTO ingredient_movements (id, ingredient_id, quantity, reason, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            Uuid::new_v4().to_string(),
+            ingredient_id,
+            round_stock(quantity),
+            reason,
+            chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        ],
+    )
+    .map_err(|e| format!("Insert error: {e}"))?;
+
+    load_ingredient(&conn, &ingredient_id)
+}
+
+pub(crate) fn get_recipe_inner(
+    db: &DbState,
+    product_id: String,
+) -> Result<Vec<RecipeItem>, String> {
+    require_feature(db, Feature::Stock)?;
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT r.ingredient_id, r.quantity
+             FROM recipe_items r
+             JOIN ingredients i ON i.id = r.ingredient_id
+             WHERE r.product_id = ?1
+             ORDER BY i.name",
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let recipe = stmt
+        .query_map(params![product_id], |row| {
+            Ok(RecipeItem {
+                ingredient_id: row.get(0)?,
+                quantity: row.get(1)?,
+            })
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    Ok(recipe)
+}
+
+/// Replaces the recipe of a product; an empty recipe stops the product from
+/// consuming ingredients.
+pub(crate) fn set_recipe_inner(
+    db: &DbState,
+    product_id: String,
+    items: Vec<RecipeItem>,
+) -> Result<(), String> {
+    require_feature(db, Feature::Stock)?;
+    for item in &items {
+        if !item.quantity.is_finite() || item.quantity <= 0.0 {
+            return Err(format!(
+                "Invalid quantity {} for ingredient {}",
+                item.quantity, item.ingredient_id
+            ));
+        }
+    }
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Transaction begin error: {e}"))?;
+
+    tx.execute(
+        "DELETE FROM recipe_items WHERE product_id = ?1",
+        params![product_id],
+    )
+    .map_err(|e| format!("Delete error: {e}"))?;
+    for item in &items {
+        tx.execute(
+            "INSERT INTO recipe_items (product_id, ingredient_id, quantity) VALUES (?1, ?2, ?3)
+             ON CONFLICT (product_id, ingredient_id) DO UPDATE SET quantity = excluded.quantity",
+            params![product_id, item.ingredient_id, item.quantity],
+        )
+        .map_err(|e| format!("Insert error ({}): {e}", item.ingredient_id))?;
+    }
+
+    tx.commit()
+        .map_err(|e| format!("Transaction commit error: {e}"))
+}
+
+/// Settings keys holding the `ReorderSettings`.
+const REORDER_LOOKBACK_DAYS_KEY: &str = "reorder.lookback_days";
+const REORDER_COVER_DAYS_KEY: &str = "reorder.cover_days";
+
+fn load_reorder_settings(store: &impl Store) -> Result<ReorderSettings, String> {
+    let defaults = ReorderSettings::default();
+    Ok(ReorderSettings {
+        lookback_days: parse_number(store, REORDER_LOOKBACK_DAYS_KEY, defaults.lookback_days)?,
+        cover_days: parse_number(store, REORDER_COVER_DAYS_KEY, defaults.cover_days)?,
+    })
+}
+
+pub(crate) fn get_reorder_settings_inner(store: &impl Store) -> Result<ReorderSettings, String> {
+    load_reorder_settings(store)
+}
+
+pub(crate) fn update_reorder_settings_inner(
+    store: &impl Store,
+    settings: ReorderSettings,
+) -> Result<ReorderSettings, String> {
+    if !(1..=365).contains(&settings.lookback_days) {
+        return Err("Lookback period must be between 1 and 365 days".to_string());
+    }
+    if !(1..=365).contains(&settings.cover_days) {
+        return Err("Cover period must be between 1 and 365 days".to_string());
+    }
+
+    store.set_settings(&[
+        (
+            REORDER_LOOKBACK_DAYS_KEY,
+            settings.lookback_days.to_string(),
+        ),
+        (REORDER_COVER_DAYS_KEY, settings.cover_days.to_string()),
+    ])?;
+
+    load_reorder_settings(store)
+}
+
+/// Proposes what to buy so that the stock lasts `cover_days` sales days at
+/// the average daily usage of the last `lookback_days`. Usage is averaged
+/// over the days with sales only, as the stall does not open every day.
+/// Covers the tracked products without a recipe and the ingredients.
+pub(crate) fn get_reorder_suggestions_inner(
+    db: &DbState,
+) -> Result<Vec<ReorderSuggestion>, String> {
+    require_feature(db, Feature::Stock)?;
+    let conn = db.read_conn()?;
+    let settings = load_reorder_settings(&*conn)?;
+
+    let since = (chrono::Local::now().date_naive()
+        - chrono::Duration::days(i64::from(settings.lookback_days) - 1))
+    .format("%Y-%m-%d")
+    .to_string();
+
+    let sales_days: i64 = conn
+        .query_row(
+            "SELECT COUNT(DISTINCT date(created_at, 'localtime'))
+             FROM orders
+             WHERE voided_at IS NULL AND date(created_at, 'localtime') >= ?1",
+            params![since],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
+    if sales_days == 0 {
+        return Ok(Vec::new());
+    }
+
+    // (id, name, ingredient, unit, stock, usage over the lookback period)
+    let sold_since = "o.voided_at IS NULL AND date(o.created_at, 'localtime') >= ?1";
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT p.id, p.name, 0, p.unit, {STOCK_LEVEL},
+                    COALESCE((SELECT SUM(oi.quantity) FROM order_items oi
+                              JOIN orders o ON o.id = oi.order_id
+                              WHERE oi.product_id = p.id AND {sold_since}), 0)
+             FROM products p
+             WHERE (EXISTS (SELECT 1 FROM stock_movements m WHERE m.product_id = p.id)
+                    OR EXISTS (SELECT 1 FROM stocktake_counts c WHERE c.product_id = p.id))
+               AND NOT EXISTS (SELECT 1 FROM recipe_items r WHERE r.product_id = p.id)
+             UNION ALL
+             SELECT i.id, i.name, 1, i.unit, {INGREDIENT_STOCK},
+                    COALESCE((SELECT -SUM(m.quantity) FROM ingredient_movements m
+                              JOIN order_items oi ON oi.id = m.order_item_id
+                              JOIN orders o ON o.id = oi.order_id
+                              WHERE m.ingredient_id = i.id AND {sold_since}), 0)
+             FROM ingredients i"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let rows = stmt
+        .query_map(params![since], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, bool>(2)?,
+                quantity_unit_from_row(row, 3)?,
+                row.get::<_, f64>(4)?,
+                row.get::<_, f64>(5)?,
+            ))
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    let mut suggestions: Vec<ReorderSuggestion> = rows
+        .into_iter()
+        .filter_map(|(id, name, ingredient, unit, stock, usage)| {
+            let daily_usage = usage / sales_days as f64;
+            let missing = daily_usage * f64::from(settings.cover_days) - stock;
+            let suggested_quantity = match unit {
+                QuantityUnit::Piece => missing.ceil(),
+                _ => round_stock(missing),
+            };
+            (suggested_quantity > 0.0).then(|| ReorderSuggestion {
+                id,
+                name,
+                ingredient,
+                unit,
+                stock: round_stock(stock),
+                daily_usage: round_stock(daily_usage),
+                suggested_quantity,
+            })
+        })
+        .collect();
+
+    suggestions.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(suggestions)
+}
+
+const EXPENSE_COLUMNS: &str = "id, spent_on, amount, category, note, receipt_path, created_at";
+
+fn expense_from_row(row: &rusqlite::Row) -> rusqlite::Result<Expense> {
+    Ok(Expense {
+        id: row.get(0)?,
+        spent_on: row.get(1)?,
+        amount: row.get(2)?,
+        category: row.get(3)?,
+        note: row.get(4)?,
+        receipt_path: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+fn load_expense(conn: &Connection, expense_id: &str) -> Result<Expense, String> {
+    conn.query_row(
+        &format!("SELECT {EXPENSE_COLUMNS} FROM expenses WHERE id = ?1"),
+        params![expense_id],
+        expense_from_row,
+    )
+    .map_err(|e| match e {
+        rusqlite::Error::QueryReturnedNoRows => format!("Expense not found: {expense_id}"),
+        e => format!("Query error: {e}"),
+    })
+}
+
+/// Checks an expense and returns its trimmed category, note and receipt
+/// path.
+fn validate_expense(
+    spent_on: &str,
+    amount: i64,
+    category: &str,
+    note: &str,
+    receipt_path: Option<&str>,
+) -> Result<(String, String, Option<String>), String> {
+    validate_date(spent_on)?;
+    if amount <= 0 {
+        return Err(format!("Invalid expense amount {amount}"));
+    }
+    let category = category.trim();
+    if category.is_empty() {
+        return Err("Expense category is required".to_string());
+    }
+    let receipt_path = receipt_path
+        .map(str::trim)
+        .filter(|path| !path.is_empty())
+        .map(str::to_string);
+    Ok((category.to_string(), note.trim().to_string(), receipt_path))
+}
+
+/// Expenses spent over `range`, most recent first.
+pub(crate) fn list_expenses_inner(db: &DbState, range: DateRange) -> Result<Vec<Expense>, String> {
+    validate_range(&range)?;
+    let conn = db.read_conn()?;
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {EXPENSE_COLUMNS} FROM expenses
+             WHERE (?1 IS NULL OR spent_on >= ?1) AND (?2 IS NULL OR spent_on <= ?2)
+             ORDER BY spent_on DESC, created_at DESC"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+    let expenses = stmt
+        .query_map(params![range.from, range.to], expense_from_row)
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+    Ok(expenses)
+}
+
+pub(crate) fn create_expense_inner(
+    db: &DbState,
+    payload: CreateExpensePayload,
+) -> Result<Expense, String> {
+    let (category, note, receipt_path) = validate_expense(
+        &payload.spent_on,
+        payload.amount,
+        &payload.category,
+        &payload.note,
+        payload.receipt_path.as_deref(),
+    )?;
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+
+    let id = Uuid::new_v4().to_string();
+    conn.execute(
+        "INSERT INTO expenses (id, spent_on, amount, category, note, receipt_path, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            id,
+            payload.spent_on,
+            payload.amount,
+            category,
+            note,
+            receipt_path,
+            chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        ],
+    )
+    .map_err(|e| format!("Insert error: {e}"))?;
+
+    load_expense(&conn, &id)
+}
+
+pub(crate) fn update_expense_inner(
+    db: &DbState,
+    payload: UpdateExpensePayload,
+) -> Result<Expense, String> {
+    let (category, note, receipt_path) = validate_expense(
+        &payload.spent_on,
+        payload.amount,
+        &payload.category,
+        &payload.note,
+        payload.receipt_path.as_deref(),
+    )?;
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+
+    let rows_affected = conn
+        .execute(
+            "UPDATE expenses
+             SET spent_on = ?1, amount = ?2, category = ?3, note = ?4, receipt_path = ?5
+             WHERE id = ?6",
+            params![
+                payload.spent_on,
+                payload.amount,
+                category,
+                note,
+                receipt_path,
+                payload.id
+            ],
+        )
+        .map_err(|e| format!("Update error: {e}"))?;
+    if rows_affected == 0 {
+        return Err(format!("Expense not found: {}", payload.id));
+    }
+
+    load_expense(&conn, &payload.id)
+}
+
+pub(crate) fn delete_expense_inner(db: &DbState, expense_id: String) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let rows_affected = conn
+        .execute("DELETE FROM expenses WHERE id = ?1", params![expense_id])
+        .map_err(|e| format!("Delete error: {e}"))?;
+    if rows_affected == 0 {
+        return Err(format!("Expense not found: {expense_id}"));
+    }
+    Ok(())
+}
+
+/// Revenue of the sales over `range` minus the expenses spent over it.
+pub(crate) fn get_net_result_inner(db: &DbState, range: DateRange) -> Result<NetResult, String> {
+    validate_range(&range)?;
+    let conn = db.read_conn()?;
+
+    let revenue: i64 = conn
+        .query_row(
+            &format!("SELECT COALESCE(SUM(total), 0) FROM orders WHERE {SALES_FILTER}"),
+            params![range.from, range.to],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    let mut stmt = conn
+        .prepare(
+            "SELECT category, SUM(amount) FROM expenses
+             WHERE (?1 IS NULL OR spent_on >= ?1) AND (?2 IS NULL OR spent_on <= ?2)
+             GROUP BY category
+             ORDER BY SUM(amount) DESC, category",
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
+    let per_category = stmt
+        .query_map(params![range.from, range.to], |row| {
+            Ok(ExpenseCategoryTotal {
+                category: row.get(0)?,
+                amount: row.get(1)?,
+            })
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    let expenses = per_category.iter().map(|total| total.amount).sum::<i64>();
+    Ok(NetResult {
+        revenue,
+        expenses,
+        net: revenue - expenses,
+        per_category,
+    })
+}
+
+/// Largest file `add_attachment_inner` accepts: 20 MiB.
+const MAX_ATTACHMENT_SIZE: u64 = 20 * 1024 * 1024;
+
+/// Content type of the files that can be attached, from their extension.
+fn attachment_content_type(path: &std::path::Path) -> Result<&'static str, String> {
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default()
+        .to_lowercase();
+    match extension.as_str() {
+        "jpg" | "jpeg" => Ok("image/jpeg"),
+        "png" => Ok("image/png"),
+        "webp" => Ok("image/webp"),
+        "pdf" => Ok("application/pdf"),
+        _ => Err(format!(
+            "Unsupported attachment '{}': expected an image or a PDF",
+            path.display()
+        )),
+    }
+}
+
+const ATTACHMENT_COLUMNS: &str =
+    "id, expense_id, file_name, stored_name, content_type, size, created_at";
+
+fn query_attachments(
+    conn: &Connection,
+    data_dir: &std::path::Path,
+    filter: &str,
+    params: impl rusqlite::Params,
+) -> Result<Vec<Attachment>, String> {
+    let dir = data_dir.join("attachments");
+    let mut stmt = conn
+        .prepare(&format!(
+            "SELECT {ATTACHMENT_COLUMNS} FROM attachments WHERE {filter}
+             ORDER BY created_at DESC, id"
+        ))
+        .map_err(|e| format!("Query error: {e}"))?;
+    let attachments = stmt
+        .query_map(params, |row| {
+            let stored_name: String = row.get(3)?;
+            Ok(Attachment {
+                id: row.get(0)?,
+                expense_id: row.get(1)?,
+                file_name: row.get(2)?,
+                content_type: row.get(4)?,
+                size: row.get(5)?,
+                path: dir.join(stored_name).to_string_lossy().into_owned(),
+                created_at: row.get(6)?,
+            })
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+    Ok(attachments)
+}
+
+/// Copies an image or PDF into `data_dir/attachments` and records it,
+/// optionally linked to an expense.
+pub(crate) fn add_attachment_inner(
+    db: &DbState,
+    data_dir: &std::path::Path,
+    payload: AddAttachmentPayload,
+) -> Result<Attachment, String> {
+    let source = std::path::Path::new(&payload.source_path);
+    let content_type = attachment_content_type(source)?;
+    let size = std::fs::metadata(source)
+        .map_err(|e| format!("Failed to read {}: {e}", source.display()))?
+        .len();
+    if size > MAX_ATTACHMENT_SIZE {
+        return Err(format!(
+            "Attachment too large: {size} bytes (at most {MAX_ATTACHMENT_SIZE})"
+        ));
+    }
+    let file_name = source
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    if let Some(expense_id) = &payload.expense_id {
+        load_expense(&conn, expense_id)?;
+    }
+
+    let id = Uuid::new_v4().to_string();
+    let extension = source
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let stored_name = format!("{id}.{extension}");
+    let dir = data_dir.join("attachments");
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    let stored_path = dir.join(&stored_name);
+    std::fs::copy(source, &stored_path)
+        .map_err(|e| format!("Failed to copy {}: {e}", source.display()))?;
+
+    let inserted = conn.execute(
+        "INSERT INTO attachments (id, expense_id, file_name, stored_name, content_type, size, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            id,
+            payload.expense_id,
+            file_name,
+            stored_name,
+            content_type,
+            size as i64,
+            chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        ],
+    );
+    if let Err(e) = inserted {
+        let _ = std::fs::remove_file(&stored_path);
+        return Err(format!("Insert error: {e}"));
+    }
+
+    query_attachments(&conn, data_dir, "id = ?1", params![id])?
+        .pop()
+        .ok_or_else(|| format!("Attachment not found: {id}"))
+}
+
+/// Attachments of an expense, or all of them when `expense_id` is `None`,
+/// most recent first.
+pub(crate) fn list_attachments_inner(
+    db: &DbState,
+    data_dir: &std::path::Path,
+    expense_id: Option<String>,
+) -> Result<Vec<Attachment>, String> {
+    let conn = db.read_conn()?;
+    query_attachments(
+        &conn,
+        data_dir,
+        "?1 IS NULL OR expense_id = ?1",
+        params![expense_id],
+    )
+}
+
+/// An attachment, checking that its stored copy is still there.
+pub(crate) fn get_attachment_inner(
+    db: &DbState,
+    data_dir: &std::path::Path,
+    attachment_id: String,
+) -> Result<Attachment, String> {
+    let conn = db.read_conn()?;
+    let attachment = query_attachments(&conn, data_dir, "id = ?1", params![attachment_id])?
+        .pop()
+        .ok_or_else(|| format!("Attachment not found: {attachment_id}"))?;
+    if !std::path::Path::new(&attachment.path).is_file() {
+        return Err(format!("Attachment file is missing: {}", attachment.path));
+    }
+    Ok(attachment)
+}
+
+/// Deletes an attachment and its stored copy.
+pub(crate) fn delete_attachment_inner(
+    db: &DbState,
+    data_dir: &std::path::Path,
+    attachment_id: String,
+) -> Result<(), String> {
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let attachment = query_attachments(&conn, data_dir, "id = ?1", params![attachment_id])?
+        .pop()
+        .ok_or_else(|| format!("Attachment not found: {attachment_id}"))?;
+    conn.execute(
+        "DELETE FROM attachments WHERE id = ?1",
+        params![attachment_id],
+    )
+    .map_err(|e| format!("Delete error: {e}"))?;
+    match std::fs::remove_file(&attachment.path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            Err(format!("Failed to delete {}: {e}", attachment.path))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Drops an open stocktake and its counts without touching the stock.
+pub(crate) fn cancel_stocktake_inner(db: &DbState, stocktake_id: String) -> Result<(), String> {
+    require_feature(db, Feature::Stock)?;
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    load_open_stocktake(&conn, &stocktake_id)?;
+
+    conn.execute(
+        "DELETE FROM stocktakes WHERE id = ?1",
+        params![stocktake_id],
+    )
+    .map_err(|e| format!("Delete error: {e}"))?;
+    Ok(())
+}
+
+/// Dry run of the migrations on another database file, e.g. an older
+/// `pos.db` about to be restored, which is opened read-only.
+pub(crate) fn check_database_migrations_inner(path: String) -> Result<MigrationReport, String> {
+    let conn = Connection::open_with_flags(&path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open database at {path}: {e}"))?;
+    crate::db::plan_migrations(&conn).map_err(|e| e.to_string())
+}
+
+/// How `repair_database_inner` fixes a `DatabaseIssue`.
+enum Repair {
+    DeleteRow,
+    ClearColumn(String),
+    /// Recreates the category products still reference.
+    CreateCategory(String),
+}
+
+impl Repair {
+    fn describe(&self) -> String {
+        match self {
+            Repair::DeleteRow => "Delete the row".to_string(),
+            Repair::ClearColumn(column) => format!("Clear {column}"),
+            Repair::CreateCategory(id) => format!("Recreate category {id}"),
+        }
+    }
+}
+
+/// Rows referencing missing data, with the repair of each.
+fn find_database_issues(conn: &Connection) -> Result<Vec<(DatabaseIssue, Option<Repair>)>, String> {
+    let mut issues = Vec::new();
+
+    let mut stmt = conn
+        .prepare("PRAGMA foreign_key_check")
+        .map_err(|e| format!("Query error: {e}"))?;
+    let violations = stmt
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+            ))
+        })
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+    for (table, row_id, parent, fk_id) in violations {
+        let column: String = conn
+            .query_row(
+                "SELECT \"from\" FROM pragma_foreign_key_list(?1) WHERE id = ?2",
+                params![table, fk_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Query error: {e}"))?;
+        let value: String = conn
+            .query_row(
+                &format!("SELECT \"{column}\" FROM \"{table}\" WHERE rowid = ?1"),
+                params![row_id],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Query error: {e}"))?;
+
+        let repair = match (table.as_str(), column.as_str()) {
+            ("products", _) => Some(Repair::CreateCategory(value.clone())),
+            ("categories" | "attachments", _) => Some(Repair::ClearColumn(column.clone())),
+            // Lines of a missing order are not part of any sale.
+            ("order_items", "order_id") => Some(Repair::DeleteRow),
+            // Sales records are never rewritten.
+            ("orders" | "order_items" | "invoices", _) => None,
+            _ => Some(Repair::DeleteRow),
+        };
+        issues.push((
+            DatabaseIssue {
+                table,
+                row_id,
+                problem: format!("{column} references missing {parent} {value}"),
+                fix: repair.as_ref().map(Repair::describe),
+            },
+            repair,
+        ));
+    }
+
+    Ok(issues)
+}
+
+/// Lists the rows referencing missing data and, with `fix` (which needs the
+/// admin PIN), repairs them in one transaction.
+pub(crate) fn repair_database_inner(
+    db: &DbState,
+    fix: bool,
+    admin_pin: Option<String>,
+) -> Result<RepairReport, String> {
+    if !fix {
+        let conn = db.read_conn()?;
+        return Ok(RepairReport {
+            issues: find_database_issues(&conn)?
+                .into_iter()
+                .map(|(issue, _)| issue)
+                .collect(),
+            repaired: false,
+        });
+    }
+
+    verify_admin_pin(db, admin_pin.as_deref())?;
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let issues = write_transaction(&mut conn, |tx| {
+        let issues = find_database_issues(tx)?;
+        for (issue, repair) in &issues {
+            let (table, row_id) = (&issue.table, issue.row_id);
+            match repair {
+                Some(Repair::DeleteRow) => tx.execute(
+                    &format!("DELETE FROM \"{table}\" WHERE rowid = ?1"),
+                    params![row_id],
+                ),
+                Some(Repair::ClearColumn(column)) => tx.execute(
+                    &format!("UPDATE \"{table}\" SET \"{column}\" = NULL WHERE rowid = ?1"),
+                    params![row_id],
+                ),
+                Some(Repair::CreateCategory(id)) => tx.execute(
+                    "INSERT OR IGNORE INTO categories (id, label, color) VALUES (?1, ?1, '#6b7280')",
+                    params![id],
+                ),
+                None => continue,
+            }
+            .map_err(|e| format!("Failed to repair {table} row {row_id}: {e}"))?;
+        }
+        Ok(issues)
+    })?;
+
+    Ok(RepairReport {
+        issues: issues.into_iter().map(|(issue, _)| issue).collect(),
+        repaired: true,
+    })
+}
+
+/// Checks the database against its schema: `PRAGMA integrity_check` reports
+/// corruption and rows breaking a NOT NULL or CHECK constraint (e.g. written
+/// before 34-strict-schema added them), and the rows referencing missing
+/// data are listed as by `repair_database_inner`.
+pub(crate) fn verify_database_schema_inner(db: &DbState) -> Result<SchemaVerification, String> {
+    let conn = db.read_conn()?;
+    let mut stmt = conn
+        .prepare("PRAGMA integrity_check")
+        .map_err(|e| format!("Query error: {e}"))?;
+    let problems = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?
+        .into_iter()
+        .filter(|message| message != "ok")
+        .collect::<Vec<_>>();
+    let issues = find_database_issues(&conn)?
+        .into_iter()
+        .map(|(issue, _)| issue)
+        .collect::<Vec<_>>();
+
+    Ok(SchemaVerification {
+        valid: problems.is_empty() && issues.is_empty(),
+        problems,
+        issues,
+    })
+}
+
+/// Writes a consistent snapshot of the database to `path`, while orders can
+/// still be taken.
+pub(crate) fn backup_database_inner(db: &DbState, path: String) -> Result<(), String> {
+    if std::path::Path::new(&path).exists() {
+        return Err(format!("{path} already exists"));
+    }
+    let conn = db.read_conn()?;
+    crate::db::backup_to(&conn, std::path::Path::new(&path))
+}
+
+/// Replaces the whole database with the backup at `path`, then brings its
+/// schema up to date. Backups from a newer version of the app are refused.
+pub(crate) fn restore_database_inner(
+    db: &DbState,
+    path: String,
+    admin_pin: Option<String>,
+) -> Result<MigrationReport, String> {
+    verify_admin_pin(db, admin_pin.as_deref())?;
+    check_database_migrations_inner(path.clone())?;
+
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    crate::db::restore_from(&mut conn, std::path::Path::new(&path))?;
+    crate::db::migrate(&mut conn, None).map_err(|e| e.to_string())
+}
+
+/// Settings key of the admin PIN, stored as `salt:sha256(salt + PIN)`.
+/// Empty or missing when no PIN is set.
+const ADMIN_PIN_KEY: &str = "admin.pin";
+const ADMIN_PIN_FAILURES_KEY: &str = "admin.pin_failures";
+/// Unix timestamp until which every PIN is refused.
+const ADMIN_PIN_LOCKED_UNTIL_KEY: &str = "admin.pin_locked_until";
+
+/// Wrong PINs in a row after which the PIN is refused for
+/// `ADMIN_PIN_LOCKOUT_SECONDS`.
+const MAX_ADMIN_PIN_ATTEMPTS: u32 = 5;
+const ADMIN_PIN_LOCKOUT_SECONDS: i64 = 60;
+
+fn hash_admin_pin(salt: &str, pin: &str) -> String {
+    use sha2::{Digest, Sha256};
+
+    format!("{:x}", Sha256::digest(format!("{salt}{pin}")))
+}
+
+fn admin_pin_hash(store: &impl Store) -> Result<Option<String>, String> {
+    Ok(store
+        .get_setting(ADMIN_PIN_KEY)?
+        .filter(|hash| !hash.is_empty()))
+}
+
+/// Checks `pin` against the admin PIN, which destructive commands require
+/// even when the till is left unlocked. Passes when no PIN is set.
+fn verify_admin_pin(store: &impl Store, pin: Option<&str>) -> Result<(), String> {
+    let Some(stored) = admin_pin_hash(store)? else {
+        return Ok(());
+    };
+
+    let now = chrono::Utc::now().timestamp();
+    let locked_until: i64 = parse_number(store, ADMIN_PIN_LOCKED_UNTIL_KEY, 0)?;
+    if locked_until > now {
+        return Err(format!(
+            "Too many wrong PINs, try again in {} seconds",
+            locked_until - now
+        ));
+    }
+    let pin = pin.ok_or("Admin PIN required")?;
+
+    let (salt, hash) = stored.split_once(':').unwrap_or_default();
+    if hash_admin_pin(salt, pin) == hash {
+        store.set_settings(&[(ADMIN_PIN_FAILURES_KEY, "0".to_string())])?;
+        return Ok(());
+    }
+
+    let failures = parse_number::<u32>(store, ADMIN_PIN_FAILURES_KEY, 0)? + 1;
+    if failures >= MAX_ADMIN_PIN_ATTEMPTS {
+        store.set_settings(&[
+            (ADMIN_PIN_FAILURES_KEY, "0".to_string()),
+            (
+                ADMIN_PIN_LOCKED_UNTIL_KEY,
+                (now + ADMIN_PIN_LOCKOUT_SECONDS).to_string(),
+            ),
+        ])?;
+    } else {
+        store.set_settings(&[(ADMIN_PIN_FAILURES_KEY, failures.to_string())])?;
+    }
+    Err("Wrong admin PIN".to_string())
+}
+
+pub(crate) fn has_admin_pin_inner(store: &impl Store) -> Result<bool, String> {
+    Ok(admin_pin_hash(store)?.is_some())
+}
+
+/// Sets, changes or (with no `new_pin`) removes the admin PIN. Changing or
+/// removing it requires the current one.
+pub(crate) fn set_admin_pin_inner(
+    store: &impl Store,
+    current_pin: Option<String>,
+    new_pin: Option<String>,
+) -> Result<(), String> {
+    verify_admin_pin(store, current_pin.as_deref())?;
+
+    let value = match new_pin {
+        Some(pin) => {
+            if !(4..=8).contains(&pin.len()) || !pin.chars().all(|c| c.is_ascii_digit()) {
+                return Err("The admin PIN must be 4 to 8 digits".to_string());
+            }
+            let salt = Uuid::new_v4().simple().to_string();
+            format!("{salt}:{}", hash_admin_pin(&salt, &pin))
+        }
+        None => String::new(),
+    };
+    store.set_settings(&[(ADMIN_PIN_KEY, value)])
+}
+
+pub(crate) fn get_features_inner(db: &DbState) -> Result<Vec<FeatureFlag>, String> {
+    use rusqlite::OptionalExtension;
+
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    Feature::ALL
+        .iter()
+        .map(|feature| {
+            let enabled: Option<bool> = conn
+                .query_row(
+                    "SELECT enabled FROM feature_flags WHERE feature = ?1",
+                    params![feature.as_db_str()],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|e| format!("Query error: {e}"))?;
+            Ok(FeatureFlag {
+                feature: *feature,
+                enabled: enabled.unwrap_or(true),
+            })
+        })
+        .collect()
+}
+
+fn save_feature(conn: &Connection, feature: Feature, enabled: bool) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO feature_flags (feature, enabled) VALUES (?1, ?2)
+         ON CONFLICT(feature) DO UPDATE SET enabled = excluded.enabled",
+        params![feature.as_db_str(), enabled],
+    )
+    .map_err(|e| format!("Update error: {e}"))?;
+    Ok(())
+}
+
+pub(crate) fn set_feature_inner(
+    db: &DbState,
+    feature: Feature,
+    enabled: bool,
+) -> Result<Vec<FeatureFlag>, String> {
+    {
+        let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+        save_feature(&conn, feature, enabled)?;
+    }
+    get_features_inner(db)
+}
+
+/// Fails when `feature` was switched off, so that its commands are refused
+/// whatever the UI shows.
+fn feature_enabled(db: &DbState, feature: Feature) -> Result<bool, String> {
+    Ok(get_features_inner(db)?
+        .iter()
+        .any(|flag| flag.feature == feature && flag.enabled))
+}
+
+fn require_feature(db: &DbState, feature: Feature) -> Result<(), String> {
+    if feature_enabled(db, feature)? {
+        Ok(())
+    } else {
+        Err(format!("The {} feature is disabled", feature.as_db_str()))
+    }
+}
+
+/// Settings keys holding the `TelemetrySettings`.
+const TELEMETRY_ENABLED_KEY: &str = "telemetry.enabled";
+const TELEMETRY_ENDPOINT_KEY: &str = "telemetry.endpoint";
+
+pub(crate) fn get_telemetry_settings_inner(
+    store: &impl Store,
+) -> Result<TelemetrySettings, String> {
+    Ok(TelemetrySettings {
+        enabled: parse_number(store, TELEMETRY_ENABLED_KEY, false)?,
+        endpoint: store
+            .get_setting(TELEMETRY_ENDPOINT_KEY)?
+            .unwrap_or_default(),
+    })
+}
+
+/// Turning usage metrics off also forgets what was counted so far.
+pub(crate) fn update_telemetry_settings_inner(
+    db: &DbState,
+    settings: TelemetrySettings,
+) -> Result<TelemetrySettings, String> {
+    db.set_settings(&[
+        (TELEMETRY_ENABLED_KEY, settings.enabled.to_string()),
+        (TELEMETRY_ENDPOINT_KEY, settings.endpoint.trim().to_string()),
+    ])?;
+    if !settings.enabled {
+        let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+        conn.execute("DELETE FROM usage_counters", [])
+            .map_err(|e| format!("Delete error: {e}"))?;
+    }
+    get_telemetry_settings_inner(db)
+}
+
+/// Counts a call of `command`, if usage metrics are enabled. Runs before
+/// every command: the flag is read on the read connection, so that commands
+/// only wait for the writer when calls are counted.
+pub(crate) fn record_usage_inner(db: &DbState, command: &str) -> Result<(), String> {
+    let enabled = parse_number(&*db.read_conn()?, TELEMETRY_ENABLED_KEY, false)?;
+    if !enabled {
+        return Ok(());
+    }
+    let conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    conn.execute(
+        "INSERT INTO usage_counters (name, count) VALUES (?1, 1)
+         ON CONFLICT(name) DO UPDATE SET count = count + 1",
+        params![command],
+    )
+    .map_err(|e| format!("Insert error: {e}"))?;
+    Ok(())
+}
+
+/// The report `upload_usage_report` would send, for the user to review.
+pub(crate) fn get_usage_report_inner(db: &DbState) -> Result<UsageReport, String> {
+    let conn = db.read_conn()?;
+    let products: i64 = conn
+        .query_row("SELECT COUNT(*) FROM products", [], |row| row.get(0))
+        .map_err(|e| format!("Query error: {e}"))?;
+    let mut stmt = conn
+        .prepare("SELECT name, count FROM usage_counters ORDER BY name")
+        .map_err(|e| format!("Query error: {e}"))?;
+    let commands = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| format!("Query error: {e}"))?
+        .collect::<Result<_, _>>()
+        .map_err(|e| format!("Row mapping error: {e}"))?;
+
+    Ok(UsageReport {
+        app_version: env!("APP_VERSION").to_string(),
+        os: env!("APP_OS").to_string(),
+        arch: env!("APP_ARCH").to_string(),
+        catalog_size: crate::telemetry::catalog_size_bucket(products).to_string(),
+        commands,
+    })
+}
+
+/// Takes the uploaded counts off the counters, keeping the calls made while
+/// the upload was running.
+pub(crate) fn clear_reported_usage_inner(db: &DbState, report: &UsageReport) -> Result<(), String> {
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+    let tx = conn
+        .transaction()
+        .map_err(|e| format!("Transaction begin error: {e}"))?;
+    for (name, count) in &report.commands {
+        tx.execute(
+            "UPDATE usage_counters SET count = count - ?1 WHERE name = ?2",
+            params![count, name],
+        )
+        .map_err(|e| format!("Update error: {e}"))?;
+    }
+    tx.execute("DELETE FROM usage_counters WHERE count <= 0", [])
+        .map_err(|e| format!("Delete error: {e}"))?;
+    tx.commit()
+        .map_err(|e| format!("Transaction commit error: {e}"))
+}
+
+/// Settings key holding the `RetentionSettings`.
+const RETENTION_DAYS_KEY: &str = "retention.days";
+
+/// Orders deleted per transaction by `purge_old_data`, so that sales can be
+/// recorded between batches.
+const PURGE_BATCH_SIZE: i64 = 500;
+
+pub(crate) fn get_retention_settings_inner(
+    store: &impl Store,
+) -> Result<RetentionSettings, String> {
+    Ok(RetentionSettings {
+        days: parse_number(store, RETENTION_DAYS_KEY, RetentionSettings::default().days)?,
+    })
+}
+
+pub(crate) fn update_retention_settings_inner(
+    store: &impl Store,
+    settings: RetentionSettings,
+) -> Result<RetentionSettings, String> {
+    if settings.days == 0 {
+        return Err("Orders must be kept for at least one day".to_string());
+    }
+    store.set_settings(&[(RETENTION_DAYS_KEY, settings.days.to_string())])?;
+    get_retention_settings_inner(store)
+}
+
+/// Rowid of the first order to keep when purging the orders of the days
+/// before `before_date`. The purge always removes a prefix of the chain, and
+/// stops at invoiced orders (invoice numbers follow the highest one left) and
+/// at orders refunded by an order that is kept.
+fn purge_boundary(conn: &Connection, before_date: &str) -> Result<i64, String> {
+    let mut boundary: i64 = conn
+        .query_row(
+            "SELECT COALESCE(
+                 (SELECT MIN(rowid) FROM orders WHERE date(created_at, 'localtime') >= ?1),
+                 (SELECT COALESCE(MAX(rowid), 0) + 1 FROM orders))",
+            params![before_date],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
+
+    loop {
+        let blocked: Option<i64> = conn
+            .query_row(
+                "SELECT MIN(o.rowid) FROM orders o
+                 WHERE o.rowid < ?1
+                   AND (EXISTS (SELECT 1 FROM invoices i WHERE i.order_id = o.id)
+                        OR EXISTS (SELECT 1 FROM orders r
+                                   WHERE r.refund_of = o.id AND r.rowid >= ?1))",
+                params![boundary],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Query error: {e}"))?;
+        match blocked {
+            Some(rowid) => boundary = rowid,
+            None => return Ok(boundary),
+        }
+    }
+}
+
+/// Deletes the orders up to rowid `last` (included). Their sales are turned
+/// into stock movements so that stock levels do not change, and the hash of
+/// the last chained one becomes the chain anchor. Ingredient movements of
+/// sales outlive their lines, so ingredient stock needs nothing.
+fn purge_orders_through(conn: &Connection, last: i64, reason: &str) -> Result<i64, String> {
+    use rusqlite::OptionalExtension;
+
+    let now = chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    let sold = |sql: &str| -> Result<Vec<(String, f64)>, String> {
+        let mut stmt = conn.prepare(sql).map_err(|e| format!("Query error: {e}"))?;
+        let rows = stmt
+            .query_map(params![last], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(|e| format!("Query error: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Row mapping error: {e}"))?;
+        Ok(rows)
+    };
+
+    // Only products whose stock is tracked, i.e. listed by `get_stock_levels`.
+    let products = sold(
+        "SELECT oi.product_id, SUM(oi.quantity) FROM order_items oi
+         JOIN orders o ON o.id = oi.order_id
+         WHERE o.rowid <= ?1 AND o.voided_at IS NULL
+           AND (oi.product_id IN (SELECT product_id FROM stock_movements)
+                OR oi.product_id IN (SELECT product_id FROM stocktake_counts))
+         GROUP BY oi.product_id",
+    )?;
+    for (product_id, quantity) in products {
+        conn.execute(
+            "INSERT INTO stock_movements (id, product_id, kind, quantity, reason, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                Uuid::new_v4().to_string(),
+                product_id,
+                StockMovementKind::Adjustment.as_db_str(),
+                -quantity,
+                reason,
+                now
+            ],
+        )
+        .map_err(|e| format!("Insert error: {e}"))?;
+    }
+
+    let anchor: Option<String> = conn
+        .query_row(
+            "SELECT hash FROM orders WHERE rowid <= ?1 AND hash IS NOT NULL
+             ORDER BY rowid DESC LIMIT 1",
+            params![last],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("Query error: {e}"))?;
+    if let Some(anchor) = anchor {
+        crate::db::set_setting(conn, CHAIN_ANCHOR_KEY, &anchor)?;
+    }
+
+    conn.execute(
+        "DELETE FROM order_items
+         WHERE order_id IN (SELECT id FROM orders WHERE rowid <= ?1)",
+        params![last],
+    )
+    .map_err(|e| format!("Delete error: {e}"))?;
+    let deleted = conn
+        .execute("DELETE FROM orders WHERE rowid <= ?1", params![last])
+        .map_err(|e| format!("Delete error: {e}"))?;
+    Ok(deleted as i64)
+}
+
+/// Archives all the data to `archive_path` (see `export_all_data`), then
+/// deletes the orders of the days before `before_date` in batches. The date
+/// must respect the retention policy.
+pub(crate) fn purge_old_data_inner(
+    db: &DbState,
+    before_date: String,
+    archive_path: String,
+    admin_pin: Option<String>,
+) -> Result<PurgeReport, String> {
+    use chrono::Days;
+    use rusqlite::OptionalExtension;
+
+    verify_admin_pin(db, admin_pin.as_deref())?;
+    let before = parse_date(&before_date)?;
+    let days = get_retention_settings_inner(db)?.days;
+    let latest = chrono::Local::now().date_naive() - Days::new(u64::from(days));
+    if before > latest {
+        return Err(format!(
+            "Orders are kept for {days} days: the purge date can be {} at the latest",
+            latest.format("%Y-%m-%d")
+        ));
+    }
+
+    export_all_data_inner(db, archive_path.clone())?;
+
+    let reason = format!("Sales before {before_date} purged");
+    let mut deleted_orders = 0;
+    let kept_from_order_id = loop {
+        let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Transaction begin error: {e}"))?;
+
+        let boundary = purge_boundary(&tx, &before_date)?;
+        let batch_end: Option<i64> = tx
+            .query_row(
+                "SELECT MAX(rowid) FROM (SELECT rowid FROM orders WHERE rowid < ?1
+                                         ORDER BY rowid LIMIT ?2)",
+                params![boundary, PURGE_BATCH_SIZE],
+                |row| row.get(0),
+            )
+            .map_err(|e| format!("Query error: {e}"))?;
+        let Some(batch_end) = batch_end else {
+            // Nothing left to purge: report the old order the purge stopped at.
+            let kept: Option<String> = tx
+                .query_row(
+                    "SELECT id FROM orders
+                     WHERE rowid = ?1 AND date(created_at, 'localtime') < ?2",
+                    params![boundary, before_date],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map_err(|e| format!("Query error: {e}"))?;
+            break kept;
+        };
+
+        deleted_orders += purge_orders_through(&tx, batch_end, &reason)?;
+        tx.commit()
+            .map_err(|e| format!("Transaction commit error: {e}"))?;
+    };
+
+    Ok(PurgeReport {
+        archive_path,
+        deleted_orders,
+        kept_from_order_id,
+    })
+}
+
+/// Replaces the database with an empty one, to be set up again with
+/// `bootstrap_workspace_inner`.
+pub(crate) fn reset_database_inner(db: &DbState, admin_pin: Option<String>) -> Result<(), String> {
+    verify_admin_pin(db, admin_pin.as_deref())?;
+
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+
+    let mut reader = match &db.reader {
+        Some(reader) => Some(reader.lock().map_err(|e| format!("DB lock error: {e}"))?),
+        None => None,
+    };
+
+    let db_path = &db.db_path;
+    let is_file_db = db_path != ":memory:";
+
+    // Close the current connections by replacing them with temporary in-memory ones.
+    // This ensures the file handles are released before we delete the file.
+    if is_file_db {
+        *conn = Connection::open_in_memory()
+            .map_err(|e| format!("Failed to create temporary connection: {e}"))?;
+        if let Some(reader) = reader.as_mut() {
+            **reader = Connection::open_in_memory()
+                .map_err(|e| format!("Failed to create temporary connection: {e}"))?;
+        }
+
+        std::fs::remove_file(db_path)
+            .map_err(|e| format!("Failed to delete database file: {e}"))?;
+        // Also remove WAL/SHM sidecar files if they exist.
+        let _ = std::fs::remove_file(format!("{db_path}-wal"));
+        let _ = std::fs::remove_file(format!("{db_path}-shm"));
+
+        *conn = Connection::open(db_path).map_err(|e| format!("Failed to reopen database: {e}"))?;
+        crate::db::set_busy_timeout(&conn)?;
+
+        conn.execute_batch("PRAGMA journal_mode=WAL;")
+            .map_err(|e| format!("Failed to set WAL mode: {e}"))?;
+    } else {
+        *conn = Connection::open_in_memory()
+            .map_err(|e| format!("Failed to open in-memory database: {e}"))?;
+    }
+
+    conn.execute_batch("PRAGMA foreign_keys=ON;")
+        .map_err(|e| format!("Failed to enable foreign keys: {e}"))?;
+
+    crate::db::migrate(&mut conn, None).map_err(|e| e.to_string())?;
+
+    if let Some(reader) = reader.as_mut() {
+        **reader = crate::db::open_reader(db_path)?;
+    }
+
+    Ok(())
+}
+
+/// Settings key set once `bootstrap_workspace_inner` has run.
+const WORKSPACE_BOOTSTRAPPED_KEY: &str = "workspace.bootstrapped";
+
+/// Whether this is a new install waiting for `bootstrap_workspace_inner`:
+/// never set up, with an empty catalog. Databases created before the setup
+/// existed already hold a catalog.
+pub(crate) fn needs_bootstrap_inner(db: &DbState) -> Result<bool, String> {
+    needs_bootstrap(&db.read_conn()?)
+}
+
+fn needs_bootstrap(conn: &Connection) -> Result<bool, String> {
+    if conn.get_setting(WORKSPACE_BOOTSTRAPPED_KEY)?.is_some() {
+        return Ok(false);
+    }
+    let catalog_size: i64 = conn
+        .query_row(
+            "SELECT (SELECT COUNT(*) FROM categories) + (SELECT COUNT(*) FROM products)",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(|e| format!("Query error: {e}"))?;
+    Ok(catalog_size == 0)
+}
+
+/// First-launch setup: saves the business settings, the admin PIN and the
+/// display currency, and creates the starter catalog if asked, all in one
+/// transaction. The check runs within that transaction, so that two setups
+/// racing each other cannot both apply.
+pub(crate) fn bootstrap_workspace_inner(
+    db: &DbState,
+    payload: BootstrapPayload,
+) -> Result<(), String> {
+    let mut conn = db.conn.lock().map_err(|e| format!("DB lock error: {e}"))?;
+
+    write_transaction(&mut conn, |tx| {
+        let store: &Connection = tx;
+        if !needs_bootstrap(store)? {
+            return Err("The workspace is already set up".to_string());
+        }
+        update_business_settings_inner(store, payload.business.clone())?;
+        if let Some(currency) = &payload.display_currency {
+            update_display_currency_inner(store, currency.clone())?;
+        }
+        if let Some(pin) = &payload.admin_pin {
+            set_admin_pin_inner(store, None, Some(pin.clone()))?;
+        }
+        if payload.starter_catalog {
+            crate::db::create_default_data(store)?;
+        }
+        store.set_settings(&[(WORKSPACE_BOOTSTRAPPED_KEY, "1".to_string())])
+    })
+}
+
+// ── Tauri command wrappers ──────────────────────────────────────────────────
+
+#[tauri::command]
+pub fn list_categories(state: State<'_, DbState>) -> Result<Vec<Category>, String> {
+    list_categories_inner(&state)
+}
+
+#[tauri::command]
+pub fn create_category(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    payload: CreateCategoryPayload,
+    admin_pin: Option<String>,
+) -> Result<Category, String> {
+    let category = create_category_inner(&state, payload, admin_pin.as_deref())?;
+    emit_dashboard_update(&app);
+    Ok(category)
+}
+
+#[tauri::command]
+pub fn update_category(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    payload: UpdateCategoryPayload,
+    admin_pin: Option<String>,
+) -> Result<Category, String> {
+    let category = update_category_inner(&state, payload, admin_pin.as_deref())?;
+    emit_dashboard_update(&app);
+    Ok(category)
+}
+
+#[tauri::command]
+pub fn list_palette(state: State<'_, DbState>) -> Result<Vec<PaletteColor>, String> {
+    list_palette_inner(&state)
+}
+
+#[tauri::command]
+pub fn update_palette(
+    state: State<'_, DbState>,
+    colors: Vec<PaletteColor>,
+) -> Result<Vec<PaletteColor>, String> {
+    update_palette_inner(&state, colors)
+}
+
+#[tauri::command]
+pub fn list_products(
+    state: State<'_, DbState>,
+    active_catalog_only: Option<bool>,
+    filter: Option<ProductFilter>,
+) -> Result<Vec<Product>, String> {
+    list_products_inner(
+        state.inner(),
+        active_catalog_only.unwrap_or(false),
+        &filter.unwrap_or_default(),
+    )
+}
+
+#[tauri::command]
+pub fn create_product(
+    state: State<'_, DbState>,
+    payload: CreateProductPayload,
+    admin_pin: Option<String>,
+) -> Result<Product, String> {
+    create_product_inner(state.inner(), payload, admin_pin.as_deref())
+}
+
+#[tauri::command]
+pub fn update_product(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    payload: UpdateProductPayload,
+    admin_pin: Option<String>,
+) -> Result<Product, String> {
+    let product = update_product_inner(state.inner(), payload, admin_pin.as_deref())?;
+    emit_dashboard_update(&app);
+    Ok(product)
+}
+
+#[tauri::command]
+pub fn get_catalog_review(state: State<'_, DbState>) -> Result<bool, String> {
+    get_catalog_review_inner(state.inner())
+}
+
+#[tauri::command]
+pub fn set_catalog_review(
+    state: State<'_, DbState>,
+    enabled: bool,
+    admin_pin: Option<String>,
+) -> Result<bool, String> {
+    set_catalog_review_inner(state.inner(), enabled, admin_pin)
+}
+
+#[tauri::command]
+pub fn propose_product_change(
+    state: State<'_, DbState>,
+    payload: UpdateProductPayload,
+    proposed_by: String,
+) -> Result<PendingProductChange, String> {
+    propose_product_change_inner(&state, payload, proposed_by)
+}
+
+#[tauri::command]
+pub fn list_pending_product_changes(
+    state: State<'_, DbState>,
+) -> Result<Vec<PendingProductChange>, String> {
+    list_pending_product_changes_inner(&state)
+}
+
+#[tauri::command]
+pub fn approve_product_change(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    change_id: String,
+    admin_pin: Option<String>,
+) -> Result<Product, String> {
+    let product = approve_product_change_inner(&state, change_id, admin_pin)?;
+    emit_dashboard_update(&app);
+    Ok(product)
+}
+
+#[tauri::command]
+pub fn reject_product_change(
+    state: State<'_, DbState>,
+    change_id: String,
+    admin_pin: Option<String>,
+) -> Result<(), String> {
+    reject_product_change_inner(&state, change_id, admin_pin)
+}
+
+#[tauri::command]
+pub fn toggle_product_availability(
+    state: State<'_, DbState>,
+    product_id: String,
+    admin_pin: Option<String>,
+) -> Result<bool, String> {
+    toggle_product_availability_inner(state.inner(), product_id, admin_pin.as_deref())
+}
+
+#[tauri::command]
+pub fn set_product_dates(
+    state: State<'_, DbState>,
+    product_id: String,
+    from: Option<String>,
+    until: Option<String>,
+    admin_pin: Option<String>,
+) -> Result<Product, String> {
+    set_product_dates_inner(state.inner(), product_id, from, until, admin_pin.as_deref())
+}
+
+#[tauri::command]
+pub fn delete_product(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    product_id: String,
+    admin_pin: Option<String>,
+) -> Result<(), String> {
+    delete_product_inner(state.inner(), product_id, admin_pin.as_deref())?;
+    emit_dashboard_update(&app);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn delete_category(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    category_id: String,
+    admin_pin: Option<String>,
+) -> Result<(), String> {
+    delete_category_inner(&state, category_id, admin_pin.as_deref())?;
+    emit_dashboard_update(&app);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn create_order(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    payload: CreateOrderPayload,
+) -> Result<OrderWithItems, String> {
+    let order = create_order_inner(&state, payload)?;
+    emit_created_orders(&app, &state, std::slice::from_ref(&order));
+    Ok(order)
+}
+
+#[tauri::command]
+pub fn split_order(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    payload: SplitOrderPayload,
+) -> Result<Vec<OrderWithItems>, String> {
+    let orders = split_order_inner(&state, payload)?;
+    emit_created_orders(&app, &state, &orders);
+    Ok(orders)
+}
+
+#[tauri::command]
+pub fn open_tab(state: State<'_, DbState>, name: String) -> Result<Tab, String> {
+    open_tab_inner(&state, name)
+}
+
+#[tauri::command]
+pub fn list_open_tabs(state: State<'_, DbState>) -> Result<Vec<Tab>, String> {
+    list_open_tabs_inner(&state)
+}
+
+#[tauri::command]
+pub fn add_to_tab(
+    state: State<'_, DbState>,
+    tab_id: String,
+    payload: AddToTabPayload,
+) -> Result<Tab, String> {
+    add_to_tab_inner(&state, tab_id, payload)
+}
+
+#[tauri::command]
+pub fn remove_from_tab(
+    state: State<'_, DbState>,
+    tab_id: String,
+    item_id: String,
+    admin_pin: Option<String>,
+) -> Result<Tab, String> {
+    remove_from_tab_inner(&state, tab_id, item_id, admin_pin)
+}
+
+#[tauri::command]
+pub fn cancel_tab(
+    state: State<'_, DbState>,
+    tab_id: String,
+    admin_pin: Option<String>,
+) -> Result<(), String> {
+    cancel_tab_inner(&state, tab_id, admin_pin)
+}
+
+#[tauri::command]
+pub fn settle_tab(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    tab_id: String,
+    payments: Vec<SplitOrderPart>,
+    limits_confirmed: Option<bool>,
+) -> Result<Vec<OrderWithItems>, String> {
+    let orders = settle_tab_inner(&state, tab_id, payments, limits_confirmed.unwrap_or(false))?;
+    emit_created_orders(&app, &state, &orders);
+    Ok(orders)
+}
+
+/// Without a `page`, every order is returned.
+#[tauri::command]
+pub fn list_orders(
+    state: State<'_, DbState>,
+    tag: Option<String>,
+    page: Option<OrderPageQuery>,
+) -> Result<Vec<OrderWithItems>, String> {
+    list_orders_page_inner(&state, tag, page.unwrap_or_default())
+}
+
+#[tauri::command]
+pub fn tag_order(
+    state: State<'_, DbState>,
+    order_id: String,
+    tag: String,
+) -> Result<OrderWithItems, String> {
+    tag_order_inner(&state, order_id, tag)
+}
+
+#[tauri::command]
+pub fn untag_order(
+    state: State<'_, DbState>,
+    order_id: String,
+    tag: String,
+) -> Result<OrderWithItems, String> {
+    untag_order_inner(&state, order_id, tag)
+}
+
+#[tauri::command]
+pub fn set_order_test_flag(
+    state: State<'_, DbState>,
+    order_id: String,
+    is_test: bool,
+) -> Result<OrderWithItems, String> {
+    set_order_test_flag_inner(&state, order_id, is_test)
+}
+
+#[tauri::command]
+pub fn purge_test_orders(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    admin_pin: Option<String>,
+) -> Result<Vec<OrderWithItems>, String> {
+    let orders = purge_test_orders_inner(&state, admin_pin)?;
+    emit_orders(&app, ORDER_VOIDED_EVENT, &orders);
+    Ok(orders)
+}
+
+#[tauri::command]
+pub fn list_order_tags(state: State<'_, DbState>) -> Result<Vec<String>, String> {
+    list_order_tags_inner(&state)
+}
+
+#[tauri::command]
+pub fn get_sales_by_tag(
+    state: State<'_, DbState>,
+    range: DateRange,
+) -> Result<Vec<TagSales>, String> {
+    get_sales_by_tag_inner(&state, range)
+}
+
+#[tauri::command]
+pub fn get_dashboard_summary(
+    state: State<'_, DbState>,
+    cache: State<'_, DashboardCache>,
+    products: Option<ProductSummaryQuery>,
+    source: Option<OrderSource>,
+) -> Result<DashboardSummary, String> {
+    cached_dashboard_summary_inner(&state, &cache, products.unwrap_or_default(), source)
+}
+
+#[tauri::command]
+pub fn get_today_stats(state: State<'_, DbState>) -> Result<TodayStats, String> {
+    get_today_stats_inner(&state)
+}
+
+#[tauri::command]
+pub fn get_revenue_goal(state: State<'_, DbState>) -> Result<RevenueGoal, String> {
+    get_revenue_goal_inner(state.inner())
+}
+
+#[tauri::command]
+pub fn update_revenue_goal(
+    state: State<'_, DbState>,
+    goal: RevenueGoal,
+) -> Result<RevenueGoal, String> {
+    update_revenue_goal_inner(state.inner(), goal)
+}
+
+#[tauri::command]
+pub fn get_goal_progress(state: State<'_, DbState>) -> Result<GoalProgress, String> {
+    get_goal_progress_inner(&state, chrono::Local::now().naive_local())
+}
+
+#[tauri::command]
+pub fn get_hourly_breakdown(
+    state: State<'_, DbState>,
+    date: String,
+) -> Result<Vec<HourlySales>, String> {
+    get_hourly_breakdown_inner(&state, date)
+}
+
+#[tauri::command]
+pub fn get_weekday_breakdown(
+    state: State<'_, DbState>,
+    range: DateRange,
+) -> Result<Vec<WeekdaySales>, String> {
+    get_weekday_breakdown_inner(&state, range)
+}
+
+#[tauri::command]
+pub fn compare_periods(
+    state: State<'_, DbState>,
+    range_a: DateRange,
+    range_b: DateRange,
+) -> Result<PeriodComparison, String> {
+    compare_periods_inner(&state, range_a, range_b)
+}
+
+#[tauri::command]
+pub fn get_sales_timeseries(
+    state: State<'_, DbState>,
+    from: String,
+    to: String,
+    bucket: TimeBucket,
+) -> Result<Vec<SalesPoint>, String> {
+    get_sales_timeseries_inner(&state, from, to, bucket)
+}
+
+#[tauri::command]
+pub fn get_sales_heatmap(
+    state: State<'_, DbState>,
+    range: DateRange,
+) -> Result<SalesHeatmap, String> {
+    get_sales_heatmap_inner(&state, range)
+}
+
+#[tauri::command]
+pub fn get_product_pairs(
+    state: State<'_, DbState>,
+    range: DateRange,
+    limit: Option<u32>,
+) -> Result<Vec<ProductPair>, String> {
+    get_product_pairs_inner(&state, range, limit)
+}
+
+#[tauri::command]
+pub fn export_dashboard(
+    state: State<'_, DbState>,
+    range: DateRange,
+    products: Option<ProductSummaryQuery>,
+    source: Option<OrderSource>,
+    format: ExportFormat,
+    split_by_day: Option<bool>,
+    path: String,
+) -> Result<(), String> {
+    export_dashboard_inner(
+        &state,
+        range,
+        products.unwrap_or_default(),
+        source,
+        format,
+        split_by_day.unwrap_or(false),
+        path,
+    )
+}
+
+#[tauri::command]
+pub fn export_orders_xlsx(
+    state: State<'_, DbState>,
+    range: DateRange,
+    split_by_day: Option<bool>,
+    path: String,
+) -> Result<(), String> {
+    export_orders_xlsx_inner(&state, range, split_by_day.unwrap_or(false), path)
+}
+
+#[tauri::command]
+pub fn export_fec(state: State<'_, DbState>, range: DateRange, path: String) -> Result<(), String> {
+    export_fec_inner(&state, range, path)
+}
+
+#[tauri::command]
+pub fn get_ledger_accounts(state: State<'_, DbState>) -> Result<LedgerAccounts, String> {
+    get_ledger_accounts_inner(state.inner())
+}
+
+#[tauri::command]
+pub fn update_ledger_accounts(
+    state: State<'_, DbState>,
+    accounts: LedgerAccounts,
+) -> Result<LedgerAccounts, String> {
+    update_ledger_accounts_inner(state.inner(), accounts)
+}
+
+#[tauri::command]
+pub fn export_ledger_csv(
+    state: State<'_, DbState>,
+    range: DateRange,
+    path: String,
+) -> Result<(), String> {
+    export_ledger_csv_inner(&state, range, path)
+}
+
+#[tauri::command]
+pub fn list_custom_reports() -> Vec<CustomReport> {
+    list_custom_reports_inner()
+}
+
+#[tauri::command]
+pub fn run_custom_report(
+    state: State<'_, DbState>,
+    template_id: String,
+    params: std::collections::HashMap<String, String>,
+    path: String,
+) -> Result<(), String> {
+    run_custom_report_inner(&state, template_id, params, path)
+}
+
+#[tauri::command]
+pub fn list_report_presets(state: State<'_, DbState>) -> Result<Vec<ReportPreset>, String> {
+    list_report_presets_inner(&state)
+}
+
+#[tauri::command]
+pub fn create_report_preset(
+    state: State<'_, DbState>,
+    payload: CreateReportPresetPayload,
+) -> Result<ReportPreset, String> {
+    create_report_preset_inner(&state, payload)
+}
+
+#[tauri::command]
+pub fn update_report_preset(
+    state: State<'_, DbState>,
+    payload: UpdateReportPresetPayload,
+) -> Result<ReportPreset, String> {
+    update_report_preset_inner(&state, payload)
+}
+
+#[tauri::command]
+pub fn delete_report_preset(state: State<'_, DbState>, preset_id: String) -> Result<(), String> {
+    delete_report_preset_inner(&state, preset_id)
+}
+
+#[tauri::command]
+pub fn run_report_preset(
+    state: State<'_, DbState>,
+    preset_id: String,
+    path: String,
+) -> Result<DateRange, String> {
+    run_report_preset_inner(&state, preset_id, path)
+}
+
+#[tauri::command]
+pub fn list_scheduled_jobs(state: State<'_, DbState>) -> Result<Vec<ScheduledJob>, String> {
+    list_scheduled_jobs_inner(&state)
+}
+
+#[tauri::command]
+pub fn update_schedule(
+    state: State<'_, DbState>,
+    payload: UpdateSchedulePayload,
+) -> Result<ScheduledJob, String> {
+    update_schedule_inner(&state, payload)
+}
+
+#[tauri::command]
+pub fn get_smtp_settings(state: State<'_, DbState>) -> Result<SmtpSettings, String> {
+    get_smtp_settings_inner(state.inner())
+}
+
+#[tauri::command]
+pub fn update_smtp_settings(
+    state: State<'_, DbState>,
+    settings: SmtpSettings,
+) -> Result<SmtpSettings, String> {
+    update_smtp_settings_inner(state.inner(), settings)
+}
+
+/// Sending talks to a remote server, so it runs off the main thread.
+#[tauri::command]
+pub async fn send_report_email(
+    state: State<'_, DbState>,
+    range: DateRange,
+    recipients: Vec<String>,
+) -> Result<(), String> {
+    let (settings, message) = build_report_email_inner(&state, range, recipients)?;
+    tauri::async_runtime::spawn_blocking(move || crate::mail::send(&settings, &message))
+        .await
+        .map_err(|e| format!("Email task failed: {e}"))?
+}
+
+#[tauri::command]
+pub fn get_notification_settings(
+    state: State<'_, DbState>,
+) -> Result<NotificationSettings, String> {
+    get_notification_settings_inner(state.inner())
+}
+
+#[tauri::command]
+pub fn update_notification_settings(
+    state: State<'_, DbState>,
+    settings: NotificationSettings,
+) -> Result<NotificationSettings, String> {
+    update_notification_settings_inner(state.inner(), settings)
+}
+
+#[tauri::command]
+pub async fn send_summary_notification(
+    state: State<'_, DbState>,
+    date: Option<String>,
+) -> Result<(), String> {
+    let (settings, text) = build_summary_notification_inner(&state, date)?;
+    tauri::async_runtime::spawn_blocking(move || crate::notify::send(&settings, &text))
+        .await
+        .map_err(|e| format!("Notification task failed: {e}"))?
+}
+
+#[tauri::command]
+pub fn verify_order_chain(state: State<'_, DbState>) -> Result<ChainVerification, String> {
+    verify_order_chain_inner(&state)
+}
+
+#[tauri::command]
+pub fn export_journal(
+    state: State<'_, DbState>,
+    range: DateRange,
+    path: String,
+) -> Result<Journal, String> {
+    export_journal_inner(&state, range, path)
+}
+
+#[tauri::command]
+pub fn get_scale_settings(state: State<'_, DbState>) -> Result<ScaleSettings, String> {
+    get_scale_settings_inner(state.inner())
+}
+
+#[tauri::command]
+pub fn update_scale_settings(
+    state: State<'_, DbState>,
+    settings: ScaleSettings,
+) -> Result<ScaleSettings, String> {
+    update_scale_settings_inner(state.inner(), settings)
+}
+
+/// Reads the weight on the scale, in kilograms.
+#[tauri::command]
+pub async fn read_scale_weight(state: State<'_, DbState>) -> Result<f64, String> {
+    let settings = get_scale_settings_inner(state.inner())?;
+    tauri::async_runtime::spawn_blocking(move || crate::scale::read_weight(&settings))
+        .await
+        .map_err(|e| format!("Scale task failed: {e}"))?
+}
+
+#[tauri::command]
+pub fn get_locale_settings(state: State<'_, DbState>) -> Result<LocaleSettings, String> {
+    get_locale_settings_inner(state.inner())
+}
+
+#[tauri::command]
+pub fn update_locale_settings(
+    state: State<'_, DbState>,
+    settings: LocaleSettings,
+) -> Result<LocaleSettings, String> {
+    update_locale_settings_inner(state.inner(), settings)
+}
+
+#[tauri::command]
+pub fn get_business_settings(state: State<'_, DbState>) -> Result<BusinessSettings, String> {
+    get_business_settings_inner(state.inner())
+}
+
+#[tauri::command]
+pub fn update_business_settings(
+    state: State<'_, DbState>,
+    settings: BusinessSettings,
+) -> Result<BusinessSettings, String> {
+    update_business_settings_inner(state.inner(), settings)
+}
+
+#[tauri::command]
+pub fn list_surcharge_rules(state: State<'_, DbState>) -> Result<Vec<SurchargeRule>, String> {
+    list_surcharge_rules_inner(&state)
+}
+
+#[tauri::command]
+pub fn set_surcharge_rule(
+    state: State<'_, DbState>,
+    rule: SurchargeRule,
+) -> Result<SurchargeRule, String> {
+    set_surcharge_rule_inner(&state, rule)
+}
+
+#[tauri::command]
+pub fn delete_surcharge_rule(
+    state: State<'_, DbState>,
+    payment_method: PaymentMethod,
+) -> Result<(), String> {
+    delete_surcharge_rule_inner(&state, payment_method)
+}
+
+#[tauri::command]
+pub fn get_rounding_settings(state: State<'_, DbState>) -> Result<RoundingSettings, String> {
+    get_rounding_settings_inner(state.inner())
+}
+
+#[tauri::command]
+pub fn update_rounding_settings(
+    state: State<'_, DbState>,
+    settings: RoundingSettings,
+) -> Result<RoundingSettings, String> {
+    update_rounding_settings_inner(state.inner(), settings)
+}
+
+#[tauri::command]
+pub fn get_order_limits(state: State<'_, DbState>) -> Result<OrderLimits, String> {
+    get_order_limits_inner(state.inner())
+}
+
+#[tauri::command]
+pub fn update_order_limits(
+    state: State<'_, DbState>,
+    limits: OrderLimits,
+) -> Result<OrderLimits, String> {
+    update_order_limits_inner(state.inner(), limits)
+}
+
+#[tauri::command]
+pub fn get_display_currency(state: State<'_, DbState>) -> Result<DisplayCurrencySettings, String> {
+    get_display_currency_inner(state.inner())
+}
+
+#[tauri::command]
+pub fn update_display_currency(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    settings: DisplayCurrencySettings,
+) -> Result<DisplayCurrencySettings, String> {
+    let settings = update_display_currency_inner(state.inner(), settings)?;
+    emit_dashboard_update(&app);
+    Ok(settings)
+}
+
+#[tauri::command]
+pub fn generate_invoice(
+    state: State<'_, DbState>,
+    order_id: String,
+    customer_details: CustomerDetails,
+    path: String,
+) -> Result<Invoice, String> {
+    generate_invoice_inner(&state, order_id, customer_details, path)
+}
+
+#[tauri::command]
+pub fn generate_receipt(
+    state: State<'_, DbState>,
+    order_id: String,
+    path: String,
+) -> Result<(), String> {
+    generate_receipt_inner(&state, order_id, path)
+}
+
+#[tauri::command]
+pub fn export_menu_pdf(state: State<'_, DbState>, path: String) -> Result<(), String> {
+    export_menu_pdf_inner(&state, path)
+}
+
+#[tauri::command]
+pub fn generate_kitchen_ticket(
+    state: State<'_, DbState>,
+    order_id: String,
+    path: String,
+) -> Result<(), String> {
+    generate_kitchen_ticket_inner(&state, order_id, path)
+}
+
+#[tauri::command]
+pub fn generate_station_tickets(
+    state: State<'_, DbState>,
+    order_id: String,
+    dir: String,
+) -> Result<Vec<StationTicket>, String> {
+    generate_station_tickets_inner(&state, order_id, dir)
+}
+
+#[tauri::command]
+pub fn list_station_routes(state: State<'_, DbState>) -> Result<Vec<StationRoute>, String> {
+    list_station_routes_inner(&state)
+}
+
+#[tauri::command]
+pub fn set_station_route(
+    state: State<'_, DbState>,
+    route: StationRoute,
+) -> Result<StationRoute, String> {
+    set_station_route_inner(&state, route)
+}
+
+#[tauri::command]
+pub fn delete_station_route(state: State<'_, DbState>, category_id: String) -> Result<(), String> {
+    delete_station_route_inner(&state, category_id)
+}
+
+/// Sending talks to a remote server, so it runs off the main thread.
+#[tauri::command]
+pub async fn email_receipt(
+    state: State<'_, DbState>,
+    order_id: String,
+    address: String,
+) -> Result<(), String> {
+    let (settings, message) = build_receipt_email_inner(&state, order_id, address)?;
+    tauri::async_runtime::spawn_blocking(move || crate::mail::send(&settings, &message))
+        .await
+        .map_err(|e| format!("Email task failed: {e}"))?
+}
+
+#[tauri::command]
+pub fn verify_receipt_code(
+    state: State<'_, DbState>,
+    code: String,
+) -> Result<ReceiptVerification, String> {
+    verify_receipt_code_inner(&state, code)
+}
+
+#[tauri::command]
+pub fn print_product_labels(
+    state: State<'_, DbState>,
+    product_ids: Vec<String>,
+    path: String,
+) -> Result<Vec<Product>, String> {
+    print_product_labels_inner(&state, product_ids, path)
+}
+
+#[tauri::command]
+pub fn undo_last_order(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    admin_pin: Option<String>,
+) -> Result<OrderWithItems, String> {
+    let order = undo_last_order_inner(&state, admin_pin)?;
+    emit_orders(&app, ORDER_VOIDED_EVENT, std::slice::from_ref(&order));
+    Ok(order)
+}
+
+#[tauri::command]
+pub fn refund_items(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    order_id: String,
+    item_refunds: Vec<ItemRefund>,
+) -> Result<OrderWithItems, String> {
+    let refund = refund_items_inner(&state, order_id, item_refunds)?;
+    emit_created_orders(&app, &state, std::slice::from_ref(&refund));
+    Ok(refund)
+}
+
+#[tauri::command]
+pub fn exchange_order(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    order_id: String,
+    item_refunds: Vec<ItemRefund>,
+    replacement: CreateOrderPayload,
+) -> Result<Exchange, String> {
+    let exchange = exchange_order_inner(&state, order_id, item_refunds, replacement)?;
+    emit_created_orders(
+        &app,
+        &state,
+        &[exchange.refund.clone(), exchange.replacement.clone()],
+    );
+    Ok(exchange)
+}
+
+#[tauri::command]
+pub fn list_catalogs(state: State<'_, DbState>) -> Result<Vec<Catalog>, String> {
+    list_catalogs_inner(&state)
+}
+
+#[tauri::command]
+pub fn create_catalog(
+    state: State<'_, DbState>,
+    payload: CreateCatalogPayload,
+) -> Result<Catalog, String> {
+    create_catalog_inner(&state, payload)
+}
+
+#[tauri::command]
+pub fn update_catalog(
+    state: State<'_, DbState>,
+    payload: UpdateCatalogPayload,
+) -> Result<Catalog, String> {
+    update_catalog_inner(&state, payload)
+}
+
+#[tauri::command]
+pub fn delete_catalog(state: State<'_, DbState>, catalog_id: String) -> Result<(), String> {
+    delete_catalog_inner(&state, catalog_id)
+}
+
+#[tauri::command]
+pub fn set_active_catalog(
+    state: State<'_, DbState>,
+    catalog_id: Option<String>,
+) -> Result<Option<Catalog>, String> {
+    set_active_catalog_inner(&state, catalog_id)
+}
+
+#[tauri::command]
+pub fn list_registers(state: State<'_, DbState>) -> Result<Vec<Register>, String> {
+    list_registers_inner(&state)
+}
+
+#[tauri::command]
+pub fn create_register(
+    state: State<'_, DbState>,
+    payload: CreateRegisterPayload,
+) -> Result<Register, String> {
+    create_register_inner(&state, payload)
+}
+
+#[tauri::command]
+pub fn update_register(
+    state: State<'_, DbState>,
+    payload: UpdateRegisterPayload,
+) -> Result<Register, String> {
+    update_register_inner(&state, payload)
+}
+
+#[tauri::command]
+pub fn delete_register(state: State<'_, DbState>, register_id: String) -> Result<(), String> {
+    delete_register_inner(&state, register_id)
+}
+
+#[tauri::command]
+pub fn set_current_register(
+    state: State<'_, DbState>,
+    register_id: Option<String>,
+) -> Result<Option<Register>, String> {
+    set_current_register_inner(&state, register_id)
+}
+
+#[tauri::command]
+pub fn get_stock_levels(state: State<'_, DbState>) -> Result<Vec<StockLevel>, String> {
+    get_stock_levels_inner(&state)
+}
+
+#[tauri::command]
+pub fn start_stocktake(state: State<'_, DbState>) -> Result<Stocktake, String> {
+    start_stocktake_inner(&state)
+}
+
+#[tauri::command]
+pub fn count_stocktake_product(
+    state: State<'_, DbState>,
+    stocktake_id: String,
+    product_id: String,
+    counted: f64,
+) -> Result<(), String> {
+    count_stocktake_product_inner(&state, stocktake_id, product_id, counted)
+}
+
+#[tauri::command]
+pub fn get_stocktake_report(
+    state: State<'_, DbState>,
+    stocktake_id: String,
+) -> Result<StocktakeReport, String> {
+    get_stocktake_report_inner(&state, stocktake_id)
+}
+
+#[tauri::command]
+pub fn commit_stocktake(
+    state: State<'_, DbState>,
+    stocktake_id: String,
+) -> Result<StocktakeReport, String> {
+    commit_stocktake_inner(&state, stocktake_id)
+}
+
+#[tauri::command]
+pub fn cancel_stocktake(state: State<'_, DbState>, stocktake_id: String) -> Result<(), String> {
+    cancel_stocktake_inner(&state, stocktake_id)
+}
+
+#[tauri::command]
+pub fn record_waste(
+    state: State<'_, DbState>,
+    product_id: String,
+    quantity: f64,
+    reason: String,
+) -> Result<StockMovement, String> {
+    record_waste_inner(&state, product_id, quantity, reason)
+}
+
+#[tauri::command]
+pub fn get_waste_report(
+    state: State<'_, DbState>,
+    range: DateRange,
+) -> Result<Vec<WasteSummary>, String> {
+    get_waste_report_inner(&state, range)
+}
+
+#[tauri::command]
+pub fn add_stock_batch(
+    state: State<'_, DbState>,
+    payload: AddStockBatchPayload,
+) -> Result<StockBatch, String> {
+    add_stock_batch_inner(&state, payload)
+}
+
+#[tauri::command]
+pub fn get_expiring_batches(
+    state: State<'_, DbState>,
+    days: u32,
+) -> Result<Vec<ExpiringBatch>, String> {
+    get_expiring_batches_inner(&state, chrono::Local::now().date_naive(), days)
+}
+
+#[tauri::command]
+pub fn list_ingredients(state: State<'_, DbState>) -> Result<Vec<Ingredient>, String> {
+    list_ingredients_inner(&state)
+}
+
+#[tauri::command]
+pub fn get_low_stock_ingredients(state: State<'_, DbState>) -> Result<Vec<Ingredient>, String> {
+    get_low_stock_ingredients_inner(&state)
+}
+
+#[tauri::command]
+pub fn create_ingredient(
+    state: State<'_, DbState>,
+    payload: CreateIngredientPayload,
+) -> Result<Ingredient, String> {
+    create_ingredient_inner(&state, payload)
+}
+
+#[tauri::command]
+pub fn update_ingredient(
+    state: State<'_, DbState>,
+    payload: UpdateIngredientPayload,
+) -> Result<Ingredient, String> {
+    update_ingredient_inner(&state, payload)
+}
+
+#[tauri::command]
+pub fn delete_ingredient(state: State<'_, DbState>, ingredient_id: String) -> Result<(), String> {
+    delete_ingredient_inner(&state, ingredient_id)
+}
+
+#[tauri::command]
+pub fn adjust_ingredient_stock(
+    state: State<'_, DbState>,
+    ingredient_id: String,
+    quantity: f64,
+    reason: Option<String>,
+) -> Result<Ingredient, String> {
+    adjust_ingredient_stock_inner(&state, ingredient_id, quantity, reason)
+}
+
+#[tauri::command]
+pub fn get_recipe(
+    state: State<'_, DbState>,
+    product_id: String,
+) -> Result<Vec<RecipeItem>, String> {
+    get_recipe_inner(&state, product_id)
+}
+
+#[tauri::command]
+pub fn set_recipe(
+    state: State<'_, DbState>,
+    product_id: String,
+    items: Vec<RecipeItem>,
+) -> Result<(), String> {
+    set_recipe_inner(&state, product_id, items)
+}
+
+#[tauri::command]
+pub fn get_reorder_settings(state: State<'_, DbState>) -> Result<ReorderSettings, String> {
+    get_reorder_settings_inner(state.inner())
+}
+
+#[tauri::command]
+pub fn update_reorder_settings(
+    state: State<'_, DbState>,
+    settings: ReorderSettings,
+) -> Result<ReorderSettings, String> {
+    update_reorder_settings_inner(state.inner(), settings)
+}
+
+#[tauri::command]
+pub fn get_reorder_suggestions(
+    state: State<'_, DbState>,
+) -> Result<Vec<ReorderSuggestion>, String> {
+    get_reorder_suggestions_inner(&state)
+}
+
+#[tauri::command]
+pub fn list_expenses(state: State<'_, DbState>, range: DateRange) -> Result<Vec<Expense>, String> {
+    list_expenses_inner(&state, range)
+}
+
+#[tauri::command]
+pub fn create_expense(
+    state: State<'_, DbState>,
+    payload: CreateExpensePayload,
+) -> Result<Expense, String> {
+    create_expense_inner(&state, payload)
+}
+
+#[tauri::command]
+pub fn update_expense(
+    state: State<'_, DbState>,
+    payload: UpdateExpensePayload,
+) -> Result<Expense, String> {
+    update_expense_inner(&state, payload)
+}
+
+#[tauri::command]
+pub fn delete_expense(state: State<'_, DbState>, expense_id: String) -> Result<(), String> {
+    delete_expense_inner(&state, expense_id)
+}
+
+#[tauri::command]
+pub fn get_net_result(state: State<'_, DbState>, range: DateRange) -> Result<NetResult, String> {
+    get_net_result_inner(&state, range)
+}
+
+fn app_data_dir(app: &AppHandle) -> Result<std::path::PathBuf, String> {
+    app.path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to resolve app data dir: {e}"))
+}
+
+#[tauri::command]
+pub fn add_attachment(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    payload: AddAttachmentPayload,
+) -> Result<Attachment, String> {
+    add_attachment_inner(&state, &app_data_dir(&app)?, payload)
+}
+
+#[tauri::command]
+pub fn list_attachments(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    expense_id: Option<String>,
+) -> Result<Vec<Attachment>, String> {
+    list_attachments_inner(&state, &app_data_dir(&app)?, expense_id)
+}
+
+#[tauri::command]
+pub fn get_attachment(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    attachment_id: String,
+) -> Result<Attachment, String> {
+    get_attachment_inner(&state, &app_data_dir(&app)?, attachment_id)
+}
+
+#[tauri::command]
+pub fn delete_attachment(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    attachment_id: String,
+) -> Result<(), String> {
+    delete_attachment_inner(&state, &app_data_dir(&app)?, attachment_id)
+}
+
+#[tauri::command]
+pub fn export_all_data(state: State<'_, DbState>, path: String) -> Result<(), String> {
+    export_all_data_inner(&state, path)
+}
+
+#[tauri::command]
+pub fn import_all_data(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    path: String,
+    strategy: ImportStrategy,
+    admin_pin: Option<String>,
+) -> Result<ImportReport, String> {
+    let report = import_all_data_inner(&state, path, strategy, admin_pin)?;
+    emit_dashboard_update(&app);
+    Ok(report)
+}
+
+#[tauri::command]
+pub fn export_settings(state: State<'_, DbState>, path: String) -> Result<(), String> {
+    export_settings_inner(&state, path)
+}
+
+#[tauri::command]
+pub fn import_settings(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    path: String,
+    admin_pin: Option<String>,
+) -> Result<ImportCounts, String> {
+    let counts = import_settings_inner(&state, path, admin_pin)?;
+    emit_dashboard_update(&app);
+    Ok(counts)
+}
+
+#[tauri::command]
+pub fn has_admin_pin(state: State<'_, DbState>) -> Result<bool, String> {
+    has_admin_pin_inner(state.inner())
+}
+
+#[tauri::command]
+pub fn set_admin_pin(
+    state: State<'_, DbState>,
+    current_pin: Option<String>,
+    new_pin: Option<String>,
+) -> Result<(), String> {
+    set_admin_pin_inner(state.inner(), current_pin, new_pin)
+}
+
+#[tauri::command]
+pub fn get_features(state: State<'_, DbState>) -> Result<Vec<FeatureFlag>, String> {
+    get_features_inner(&state)
+}
+
+#[tauri::command]
+pub fn set_feature(
+    state: State<'_, DbState>,
+    feature: Feature,
+    enabled: bool,
+) -> Result<Vec<FeatureFlag>, String> {
+    set_feature_inner(&state, feature, enabled)
+}
+
+#[tauri::command]
+pub fn get_telemetry_settings(state: State<'_, DbState>) -> Result<TelemetrySettings, String> {
+    get_telemetry_settings_inner(state.inner())
+}
+
+#[tauri::command]
+pub fn update_telemetry_settings(
+    state: State<'_, DbState>,
+    settings: TelemetrySettings,
+) -> Result<TelemetrySettings, String> {
+    update_telemetry_settings_inner(&state, settings)
+}
+
+#[tauri::command]
+pub fn get_usage_report(state: State<'_, DbState>) -> Result<UsageReport, String> {
+    get_usage_report_inner(&state)
+}
+
+/// Uploading talks to a remote server, so it runs off the main thread.
+#[tauri::command]
+pub async fn upload_usage_report(state: State<'_, DbState>) -> Result<UsageReport, String> {
+    let settings = get_telemetry_settings_inner(state.inner())?;
+    if !settings.enabled {
+        return Err("Usage metrics are disabled".to_string());
+    }
+    let report = get_usage_report_inner(&state)?;
+    let uploaded = report.clone();
+    tauri::async_runtime::spawn_blocking(move || {
+        crate::telemetry::upload(&settings.endpoint, &uploaded)
+    })
+    .await
+    .map_err(|e| format!("Upload task failed: {e}"))??;
+    clear_reported_usage_inner(&state, &report)?;
+    Ok(report)
+}
+
+#[tauri::command]
+pub fn get_retention_settings(state: State<'_, DbState>) -> Result<RetentionSettings, String> {
+    get_retention_settings_inner(state.inner())
+}
+
+#[tauri::command]
+pub fn update_retention_settings(
+    state: State<'_, DbState>,
+    settings: RetentionSettings,
+) -> Result<RetentionSettings, String> {
+    update_retention_settings_inner(state.inner(), settings)
+}
+
+#[tauri::command]
+pub fn purge_old_data(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    before_date: String,
+    archive_path: String,
+    admin_pin: Option<String>,
+) -> Result<PurgeReport, String> {
+    let report = purge_old_data_inner(&state, before_date, archive_path, admin_pin)?;
+    emit_dashboard_update(&app);
+    Ok(report)
+}
+
+#[tauri::command]
+pub fn needs_bootstrap(state: State<'_, DbState>) -> Result<bool, String> {
+    needs_bootstrap_inner(&state)
+}
+
+#[tauri::command]
+pub fn bootstrap_workspace(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    payload: BootstrapPayload,
+) -> Result<(), String> {
+    bootstrap_workspace_inner(&state, payload)?;
+    emit_dashboard_update(&app);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn reset_database(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    admin_pin: Option<String>,
+) -> Result<(), String> {
+    reset_database_inner(&state, admin_pin)?;
+    emit_dashboard_update(&app);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_migration_report(state: State<'_, DbState>) -> MigrationReport {
+    state.migration.clone()
+}
+
+#[tauri::command]
+pub fn check_database_migrations(path: String) -> Result<MigrationReport, String> {
+    check_database_migrations_inner(path)
+}
+
+#[tauri::command]
+pub fn repair_database(
+    state: State<'_, DbState>,
+    fix: bool,
+    admin_pin: Option<String>,
+) -> Result<RepairReport, String> {
+    repair_database_inner(&state, fix, admin_pin)
+}
+
+#[tauri::command]
+pub fn verify_database_schema(state: State<'_, DbState>) -> Result<SchemaVerification, String> {
+    verify_database_schema_inner(&state)
+}
+
+#[tauri::command]
+pub fn backup_database(state: State<'_, DbState>, path: String) -> Result<(), String> {
+    backup_database_inner(&state, path)
+}
+
+#[tauri::command]
+pub fn restore_database(
+    app: AppHandle,
+    state: State<'_, DbState>,
+    path: String,
+    admin_pin: Option<String>,
+) -> Result<MigrationReport, String> {
+    let report = restore_database_inner(&state, path, admin_pin)?;
+    emit_dashboard_update(&app);
+    Ok(report)
+}
+
+#[tauri::command]
+pub fn get_health(state: State<'_, DbState>) -> Result<HealthReport, String> {
+    Ok(HealthReport {
+        schema_version: state.migration.to_version,
+        storage: crate::storage::status(&state.db_path),
+        scheduler_error: get_scheduler_error_inner(state.inner())?,
+    })
+}
+
+#[tauri::command]
+pub fn get_db_path(state: State<'_, DbState>) -> String {
+    state.db_path.clone()
+}
+
+#[tauri::command]
+pub fn get_app_version() -> AppVersion {
+    AppVersion {
+        version: env!("APP_VERSION").to_string(),
+        os: env!("APP_OS").to_string(),
+        arch: env!("APP_ARCH").to_string(),
+    }
+}
+
+// ── Tests ───────────────────────────────────────────────────────────────────
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::init_db_in_memory;
+
+    fn make_product(db: &DbState, name: &str, price: i64, category_id: &str) -> Product {
+        create_product_inner(
+            db,
+            CreateProductPayload {
+                name: name.to_string(),
+                price,
+                category_id: category_id.to_string(),
+                age_restricted: false,
+                unit: QuantityUnit::Piece,
+                translations: Translations::new(),
+            },
+            None,
+        )
+        .expect("create_product_inner failed")
+    }
+
+    fn make_order(
+        db: &DbState,
+        items: &[(&Product, i64)],
+        payment_method: PaymentMethod,
+    ) -> OrderWithItems {
+        create_order_inner(
+            db,
+            CreateOrderPayload {
+                items: items
+                    .iter()
+                    .map(|(p, quantity)| CreateOrderItemPayload {
+                        product_id: p.id.clone(),
+                        product_name: p.name.clone(),
+                        unit_price: p.price,
+                        quantity: *quantity as f64,
+                    })
+                    .collect(),
+                payment_method,
+                age_confirmed: false,
+                limits_confirmed: false,
+                adjustments: Vec::new(),
+                source: OrderSource::Counter,
+            },
+        )
+        .expect("create_order_inner failed")
+    }
+
+    /// Backdates an order to the given UTC timestamp (`YYYY-MM-DDTHH:MM:SSZ`).
+    fn set_order_created_at(db: &DbState, order_id: &str, created_at: &str) {
+        let conn = db.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE orders SET created_at = ?1 WHERE id = ?2",
+            params![created_at, order_id],
+        )
+        .unwrap();
+    }
+
+    /// Returns a unique path in the system temp directory.
+    fn temp_path(extension: &str) -> String {
+        std::env::temp_dir()
+            .join(format!("pos-test-{}.{extension}", Uuid::new_v4()))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    /// Converts a stored UTC timestamp to the machine's local time, the same
+    /// way SQLite's `localtime` modifier does.
+    fn to_local(created_at: &str) -> chrono::DateTime<chrono::Local> {
+        chrono::DateTime::parse_from_rfc3339(created_at)
+            .unwrap()
+            .with_timezone(&chrono::Local)
+    }
+
+    #[test]
+    fn list_products_returns_defaults() {
+        let db = init_db_in_memory();
+        let products = list_products_inner(&db, false, &ProductFilter::default()).unwrap();
+        assert_eq!(products.len(), 20);
+    }
+
+    #[test]
+    fn create_and_list_products() {
+        let db = init_db_in_memory();
+        let created = make_product(&db, "Cola", 150, "boisson-sans-alcool");
+        assert_eq!(created.name, "Cola");
+        assert_eq!(created.price, 150);
+        assert_eq!(created.category_id, "boisson-sans-alcool");
+        assert!(created.available);
+
+        let products = list_products_inner(&db, false, &ProductFilter::default()).unwrap();
+        assert_eq!(products.len(), 21);
+        let cola = products
+            .iter()
+            .find(|p| p.id == created.id)
+            .expect("Cola should be in the list");
+        assert_eq!(cola.name, "Cola");
+    }
+
+    #[test]
+    fn update_product_changes_fields() {
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Chips", 200, "snack");
+
+        let updated = update_product_inner(
+            &db,
+            UpdateProductPayload {
+                id: p.id.clone(),
+                name: "Crisps".to_string(),
+                price: 250,
+                category_id: "snack".to_string(),
+                available: false,
+                age_restricted: None,
+                unit: None,
+                translations: None,
+            },
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(updated.id, p.id);
+        assert_eq!(updated.name, "Crisps");
+        assert_eq!(updated.price, 250);
+        assert!(!updated.available);
+
+        // Verify via list
+        let products = list_products_inner(&db, false, &ProductFilter::default()).unwrap();
+        let crisps = products
+            .iter()
+            .find(|p| p.id == updated.id)
+            .expect("Crisps should be in the list");
+        assert_eq!(crisps.name, "Crisps");
+        assert!(!crisps.available);
+    }
+
+    #[test]
+    fn update_product_not_found() {
+        let db = init_db_in_memory();
+        let result = update_product_inner(
+            &db,
+            UpdateProductPayload {
+                id: "nonexistent".to_string(),
+                name: "X".to_string(),
+                price: 100,
+                category_id: "snack".to_string(),
+                available: true,
+                age_restricted: None,
+                unit: None,
+                translations: None,
+            },
+            None,
+        );
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Product not found"));
+    }
+
+    #[test]
+    fn toggle_availability() {
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Water", 100, "boisson-sans-alcool");
+        assert!(p.available);
+
+        // Toggle off
+        let new_state = toggle_product_availability_inner(&db, p.id.clone(), None).unwrap();
+        assert!(!new_state);
+
+        // Toggle back on
+        let new_state = toggle_product_availability_inner(&db, p.id.clone(), None).unwrap();
+        assert!(new_state);
+
+        // Toggle off again
+        let new_state = toggle_product_availability_inner(&db, p.id, None).unwrap();
+        assert!(!new_state);
+    }
+
+    #[test]
+    fn create_order_success() {
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Candy", 50, "sucreries");
+
+        let order = create_order_inner(
+            &db,
+            CreateOrderPayload {
+                items: vec![CreateOrderItemPayload {
+                    product_id: p.id.clone(),
+                    product_name: "Candy".to_string(),
+                    unit_price: 50,
+                    quantity: 3.0,
+                }],
+                payment_method: PaymentMethod::Cash,
+                age_confirmed: false,
+                limits_confirmed: false,
+                adjustments: Vec::new(),
+                source: OrderSource::Counter,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(order.order.total, 150);
+        assert_eq!(order.order.payment_method, PaymentMethod::Cash);
+        assert_eq!(order.items.len(), 1);
+        assert_eq!(order.items[0].product_id, p.id);
+        assert_eq!(order.items[0].quantity, 3.0);
+        assert_eq!(order.items[0].total, 150);
+
+        // Verify via list_orders
+        let orders = list_orders_inner(&db, None).unwrap();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].order.total, 150);
+        assert_eq!(orders[0].items.len(), 1);
+    }
+
+    #[test]
+    fn create_order_empty_items_fails() {
+        let db = init_db_in_memory();
+        let result = create_order_inner(
+            &db,
+            CreateOrderPayload {
+                items: vec![],
+                payment_method: PaymentMethod::Card,
+                age_confirmed: false,
+                limits_confirmed: false,
+                adjustments: Vec::new(),
+                source: OrderSource::Counter,
+            },
+        );
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("no items"));
+    }
+
+    #[test]
+    fn dashboard_summary_reflects_orders() {
+        let db = init_db_in_memory();
+        let p1 = make_product(&db, "Soda", 200, "boisson-sans-alcool");
+        let p2 = make_product(&db, "Bar", 100, "snack");
+
+        // Order 1: 2x Soda, paid by cash => 400
+        create_order_inner(
+            &db,
+            CreateOrderPayload {
+                items: vec![CreateOrderItemPayload {
+                    product_id: p1.id.clone(),
+                    product_name: "Soda".to_string(),
+                    unit_price: 200,
+                    quantity: 2.0,
+                }],
+                payment_method: PaymentMethod::Cash,
+                age_confirmed: false,
+                limits_confirmed: false,
+                adjustments: Vec::new(),
+                source: OrderSource::Counter,
+            },
+        )
+        .unwrap();
+
+        // Order 2: 1x Soda + 3x Bar, paid by card => 200 + 300 = 500
+        create_order_inner(
+            &db,
+            CreateOrderPayload {
+                items: vec![
+                    CreateOrderItemPayload {
+                        product_id: p1.id.clone(),
+                        product_name: "Soda".to_string(),
+                        unit_price: 200,
+                        quantity: 1.0,
+                    },
+                    CreateOrderItemPayload {
+                        product_id: p2.id.clone(),
+                        product_name: "Bar".to_string(),
+                        unit_price: 100,
+                        quantity: 3.0,
+                    },
+                ],
+                payment_method: PaymentMethod::Card,
+                age_confirmed: false,
+                limits_confirmed: false,
+                adjustments: Vec::new(),
+                source: OrderSource::Counter,
+            },
+        )
+        .unwrap();
+
+        let summary =
+            get_dashboard_summary_inner(&db, ProductSummaryQuery::default(), None).unwrap();
+        assert_eq!(summary.total_revenue, 900);
+        assert_eq!(summary.total_transactions, 2);
+
+        // Per-product: Soda = 600 (3 units), Bar = 300 (3 units). Ordered by revenue DESC.
+        assert_eq!(summary.per_product.len(), 2);
+        assert_eq!(summary.per_product[0].product_name, "Soda");
+        assert_eq!(summary.per_product[0].total_quantity, 3.0);
+        assert_eq!(summary.per_product[0].total_revenue, 600);
+        assert_eq!(summary.per_product[1].product_name, "Bar");
+        assert_eq!(summary.per_product[1].total_quantity, 3.0);
+        assert_eq!(summary.per_product[1].total_revenue, 300);
+
+        // Per-payment-method: ordered by payment_method ASC => card, cash
+        assert_eq!(summary.per_payment_method.len(), 2);
+        assert_eq!(
+            summary.per_payment_method[0].payment_method,
+            PaymentMethod::Card
+        );
+        assert_eq!(summary.per_payment_method[0].total_revenue, 500);
+        assert_eq!(summary.per_payment_method[0].transaction_count, 1);
+        assert_eq!(
+            summary.per_payment_method[1].payment_method,
+            PaymentMethod::Cash
+        );
+        assert_eq!(summary.per_payment_method[1].total_revenue, 400);
+        assert_eq!(summary.per_payment_method[1].transaction_count, 1);
+    }
+
+    #[test]
+    fn delete_product_success() {
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Temp Item", 100, "snack");
+
+        let before = list_products_inner(&db, false, &ProductFilter::default())
+            .unwrap()
+            .len();
+        delete_product_inner(&db, p.id.clone(), None).unwrap();
+        let after = list_products_inner(&db, false, &ProductFilter::default())
+            .unwrap()
+            .len();
+
+        assert_eq!(after, before - 1);
+        assert!(list_products_inner(&db, false, &ProductFilter::default())
+            .unwrap()
+            .iter()
+            .all(|prod| prod.id != p.id));
+    }
+
+    #[test]
+    fn delete_product_not_found() {
+        let db = init_db_in_memory();
+        let result = delete_product_inner(&db, "nonexistent".to_string(), None);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Product not found"));
+    }
+
+    #[test]
+    fn delete_product_with_order_items_succeeds() {
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Ordered Item", 200, "snack");
+
+        // Create an order referencing this product.
+        create_order_inner(
+            &db,
+            CreateOrderPayload {
+                items: vec![CreateOrderItemPayload {
+                    product_id: p.id.clone(),
+                    product_name: "Ordered Item".to_string(),
+                    unit_price: 200,
+                    quantity: 1.0,
+                }],
+                payment_method: PaymentMethod::Cash,
+                age_confirmed: false,
+                limits_confirmed: false,
+                adjustments: Vec::new(),
+                source: OrderSource::Counter,
+            },
+        )
+        .unwrap();
+
+        let result = delete_product_inner(&db, p.id, None);
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn delete_category_success() {
+        let db = init_db_in_memory();
+
+        // Create a fresh category with no products.
+        create_category_inner(
+            &db,
+            CreateCategoryPayload {
+                id: "test-cat".to_string(),
+                label: "Test Cat".to_string(),
+                color: "#ff0000".to_string(),
+                translations: Translations::new(),
+                icon: None,
+                parent_id: None,
+                allow_custom_color: false,
+            },
+            None,
+        )
+        .unwrap();
+
+        let before = list_categories_inner(&db).unwrap().len();
+        delete_category_inner(&db, "test-cat".to_string(), None).unwrap();
+        let after = list_categories_inner(&db).unwrap().len();
+
+        assert_eq!(after, before - 1);
+    }
+
+    #[test]
+    fn delete_category_not_found() {
+        let db = init_db_in_memory();
+        let result = delete_category_inner(&db, "nonexistent".to_string(), None);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Category not found"));
+    }
+
+    #[test]
+    fn delete_category_with_products_fails() {
+        let db = init_db_in_memory();
+
+        // The "snack" category has default products referencing it.
+        let result = delete_category_inner(&db, "snack".to_string(), None);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("referenced by"));
+    }
+
+    #[test]
+    fn hourly_breakdown_buckets_by_local_hour() {
+        use chrono::Timelike;
+
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Coffee", 100, "boisson-sans-alcool");
+
+        let first = make_order(&db, &[(&p, 1)], PaymentMethod::Cash);
+        let second = make_order(&db, &[(&p, 2)], PaymentMethod::Card);
+        set_order_created_at(&db, &first.order.id, "2026-06-21T12:05:00Z");
+        set_order_created_at(&db, &second.order.id, "2026-06-21T12:45:00Z");
+
+        let local = to_local("2026-06-21T12:05:00Z");
+        let date = local.format("%Y-%m-%d").to_string();
+        let buckets = get_hourly_breakdown_inner(&db, date).unwrap();
+
+        assert_eq!(buckets.len(), 24);
+        let busy = &buckets[local.hour() as usize];
+        assert_eq!(busy.order_count, 2);
+        assert_eq!(busy.total_revenue, 300);
+        assert_eq!(buckets.iter().map(|b| b.order_count).sum::<i64>(), 2);
+    }
+
+    #[test]
+    fn hourly_breakdown_rejects_invalid_date() {
+        let db = init_db_in_memory();
+        let result = get_hourly_breakdown_inner(&db, "21/06/2026".to_string());
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Invalid date"));
+    }
+
+    #[test]
+    fn weekday_breakdown_groups_by_iso_weekday() {
+        use chrono::Datelike;
+
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Crepe", 250, "sucreries");
+
+        let saturday = make_order(&db, &[(&p, 2)], PaymentMethod::Cash);
+        let sunday = make_order(&db, &[(&p, 1)], PaymentMethod::Card);
+        let outside = make_order(&db, &[(&p, 4)], PaymentMethod::Cash);
+        set_order_created_at(&db, &saturday.order.id, "2026-06-20T12:00:00Z");
+        set_order_created_at(&db, &sunday.order.id, "2026-06-21T12:00:00Z");
+        set_order_created_at(&db, &outside.order.id, "2026-07-04T12:00:00Z");
+
+        let buckets = get_weekday_breakdown_inner(
+            &db,
+            DateRange {
+                from: Some("2026-06-15".to_string()),
+                to: Some("2026-06-28".to_string()),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(buckets.len(), 7);
+        let sat = to_local("2026-06-20T12:00:00Z")
+            .weekday()
+            .number_from_monday() as usize;
+        let sun = to_local("2026-06-21T12:00:00Z")
+            .weekday()
+            .number_from_monday() as usize;
+        assert_eq!(buckets[sat - 1].total_revenue, 500);
+        assert_eq!(buckets[sun - 1].total_revenue, 250);
+        assert_eq!(buckets.iter().map(|b| b.order_count).sum::<i64>(), 2);
+    }
+
+    #[test]
+    fn weekday_breakdown_rejects_inverted_range() {
+        let db = init_db_in_memory();
+        let result = get_weekday_breakdown_inner(
+            &db,
+            DateRange {
+                from: Some("2026-06-28".to_string()),
+                to: Some("2026-06-15".to_string()),
+            },
+        );
+        assert!(result.unwrap_err().contains("Invalid date range"));
+    }
+
+    #[test]
+    fn dashboard_summary_groups_by_category() {
+        let db = init_db_in_memory();
+        let soda = make_product(&db, "Soda", 200, "boisson-sans-alcool");
+        let water = make_product(&db, "Water", 100, "boisson-sans-alcool");
+        let chips = make_product(&db, "Chips", 150, "snack");
+        let gone = make_product(&db, "Gone", 50, "snack");
+
+        make_order(
+            &db,
+            &[(&soda, 2), (&water, 1), (&chips, 1), (&gone, 2)],
+            PaymentMethod::Cash,
+        );
+        delete_product_inner(&db, gone.id, None).unwrap();
+
+        let summary =
+            get_dashboard_summary_inner(&db, ProductSummaryQuery::default(), None).unwrap();
+        assert_eq!(summary.per_category.len(), 3);
+
+        let drinks = &summary.per_category[0];
+        assert_eq!(drinks.category_id.as_deref(), Some("boisson-sans-alcool"));
+        assert_eq!(drinks.total_quantity, 3.0);
+        assert_eq!(drinks.total_revenue, 500);
+
+        let snacks = &summary.per_category[1];
+        assert_eq!(snacks.category_id.as_deref(), Some("snack"));
+        assert_eq!(snacks.total_revenue, 150);
+
+        let unknown = &summary.per_category[2];
+        assert_eq!(unknown.category_id, None);
+        assert_eq!(unknown.total_revenue, 100);
+    }
+
+    #[test]
+    fn compare_periods_returns_both_summaries_and_deltas() {
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Cider", 300, "alcool");
+
+        let last_year = make_order(&db, &[(&p, 2)], PaymentMethod::Cash);
+        let this_year_1 = make_order(&db, &[(&p, 3)], PaymentMethod::Card);
+        let this_year_2 = make_order(&db, &[(&p, 1)], PaymentMethod::Cash);
+        set_order_created_at(&db, &last_year.order.id, "2025-06-21T12:00:00Z");
+        set_order_created_at(&db, &this_year_1.order.id, "2026-06-20T12:00:00Z");
+        set_order_created_at(&db, &this_year_2.order.id, "2026-06-21T12:00:00Z");
+
+        let comparison = compare_periods_inner(
+            &db,
+            DateRange {
+                from: Some("2026-06-19".to_string()),
+                to: Some("2026-06-22".to_string()),
+            },
+            DateRange {
+                from: Some("2025-06-19".to_string()),
+                to: Some("2025-06-22".to_string()),
+            },
+        )
+        .unwrap();
+
+        assert_eq!(comparison.period_a.total_revenue, 1200);
+        assert_eq!(comparison.period_a.total_transactions, 2);
+        assert_eq!(comparison.period_a.per_product[0].total_quantity, 4.0);
+        assert_eq!(comparison.period_b.total_revenue, 600);
+        assert_eq!(comparison.period_b.total_transactions, 1);
+        assert_eq!(comparison.revenue_delta, 600);
+        assert_eq!(comparison.transactions_delta, 1);
+        assert_eq!(comparison.revenue_change_percent, Some(100.0));
+    }
+
+    #[test]
+    fn dashboard_summary_basket_stats() {
+        let db = init_db_in_memory();
+        let coffee = make_product(&db, "Coffee", 100, "boisson-sans-alcool");
+        let sandwich = make_product(&db, "Sandwich", 400, "snack");
+
+        make_order(&db, &[(&coffee, 1)], PaymentMethod::Cash);
+        make_order(&db, &[(&coffee, 1)], PaymentMethod::Cash);
+        make_order(&db, &[(&coffee, 2), (&sandwich, 1)], PaymentMethod::Card);
+
+        let basket = get_dashboard_summary_inner(&db, ProductSummaryQuery::default(), None)
+            .unwrap()
+            .basket;
+        // Totals: 100, 100, 600.
+        assert_eq!(basket.average_total, 267);
+        assert_eq!(basket.median_total, 100);
+        assert!((basket.average_items_per_order - 5.0 / 3.0).abs() < 1e-9);
+
+        assert_eq!(basket.histogram.len(), 6);
+        assert_eq!(basket.histogram[1].min, 100);
+        assert_eq!(basket.histogram[1].order_count, 2);
+        assert_eq!(basket.histogram[3].min, 500);
+        assert_eq!(basket.histogram[3].order_count, 1);
+        assert_eq!(basket.histogram[5].max, None);
+    }
+
+    #[test]
+    fn dashboard_summary_basket_stats_without_orders() {
+        let db = init_db_in_memory();
+        let basket = get_dashboard_summary_inner(&db, ProductSummaryQuery::default(), None)
+            .unwrap()
+            .basket;
+        assert_eq!(basket.average_total, 0);
+        assert_eq!(basket.median_total, 0);
+        assert!(basket.histogram.iter().all(|b| b.order_count == 0));
+    }
+
+    #[test]
+    fn dashboard_summary_top_products_by_quantity() {
+        let db = init_db_in_memory();
+        let beer = make_product(&db, "Beer", 300, "alcool");
+        let coffee = make_product(&db, "Coffee", 100, "boisson-sans-alcool");
+        let cake = make_product(&db, "Cake", 150, "sucreries");
+
+        // Revenue: Beer 600, Coffee 500, Cake 150. Volume: Coffee 5, Beer 2, Cake 1.
+        make_order(
+            &db,
+            &[(&beer, 2), (&coffee, 5), (&cake, 1)],
+            PaymentMethod::Cash,
+        );
+
+        let by_revenue = get_dashboard_summary_inner(
+            &db,
+            ProductSummaryQuery {
+                sort_by: ProductSortKey::Revenue,
+                limit: Some(2),
+            },
+            None,
+        )
+        .unwrap();
+        assert_eq!(by_revenue.per_product.len(), 2);
+        assert_eq!(by_revenue.per_product[0].product_name, "Beer");
+        assert_eq!(by_revenue.per_product[1].product_name, "Coffee");
+
+        let by_quantity = get_dashboard_summary_inner(
+            &db,
+            ProductSummaryQuery {
+                sort_by: ProductSortKey::Quantity,
+                limit: Some(1),
+            },
+            None,
+        )
+        .unwrap();
+        assert_eq!(by_quantity.per_product.len(), 1);
+        assert_eq!(by_quantity.per_product[0].product_name, "Coffee");
+        // Totals are not affected by the product limit.
+        assert_eq!(by_quantity.total_revenue, 1250);
+    }
+
+    #[test]
+    fn sales_timeseries_fills_empty_buckets() {
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Soda", 200, "boisson-sans-alcool");
+
+        let order = make_order(&db, &[(&p, 2)], PaymentMethod::Cash);
+        set_order_created_at(&db, &order.order.id, "2026-06-21T12:30:00Z");
+        let local = to_local("2026-06-21T12:30:00Z");
+        let day = local.format("%Y-%m-%d").to_string();
+
+        let daily = get_sales_timeseries_inner(
+            &db,
+            "2026-06-19".to_string(),
+            "2026-06-23".to_string(),
+            TimeBucket::Day,
+        )
+        .unwrap();
+        assert_eq!(daily.len(), 5);
+        let point = daily.iter().find(|p| p.period == day).unwrap();
+        assert_eq!(point.total_revenue, 400);
+        assert_eq!(daily.iter().map(|p| p.order_count).sum::<i64>(), 1);
+
+        let hourly = get_sales_timeseries_inner(&db, day.clone(), day, TimeBucket::Hour).unwrap();
+        assert_eq!(hourly.len(), 24);
+        let hour = local.format("%Y-%m-%d %H:00").to_string();
+        let point = hourly.iter().find(|p| p.period == hour).unwrap();
+        assert_eq!(point.order_count, 1);
+
+        let series = |from: &str, to: &str, bucket| {
+            get_sales_timeseries_inner(&db, from.to_string(), to.to_string(), bucket)
+        };
+        assert_eq!(
+            series("2026-01-01", "2026-12-31", TimeBucket::Day)
+                .unwrap()
+                .len(),
+            365
+        );
+        assert!(series("2000-01-01", "2026-12-31", TimeBucket::Day).is_err());
+        assert!(series("2026-06-01", "2026-06-30", TimeBucket::Hour).is_ok());
+        assert!(series("2026-01-01", "2026-12-31", TimeBucket::Hour).is_err());
+    }
+
+    #[test]
+    fn sales_heatmap_places_orders_by_weekday_and_hour() {
+        use chrono::{Datelike, Timelike};
+
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Beer", 300, "alcool");
+
+        for _ in 0..3 {
+            let order = make_order(&db, &[(&p, 1)], PaymentMethod::Cash);
+            set_order_created_at(&db, &order.order.id, "2026-06-20T17:10:00Z");
+        }
+
+        let heatmap = get_sales_heatmap_inner(&db, DateRange::default()).unwrap();
+        assert_eq!(heatmap.order_counts.len(), 7);
+        assert!(heatmap.order_counts.iter().all(|row| row.len() == 24));
+
+        let local = to_local("2026-06-20T17:10:00Z");
+        let w = local.weekday().num_days_from_monday() as usize;
+        let h = local.hour() as usize;
+        assert_eq!(heatmap.order_counts[w][h], 3);
+        assert_eq!(heatmap.revenue[w][h], 900);
+        assert_eq!(heatmap.order_counts.iter().flatten().sum::<i64>(), 3);
+    }
+
+    #[test]
+    fn product_pairs_counts_co_occurrences() {
+        let db = init_db_in_memory();
+        let crepe = make_product(&db, "Crepe", 250, "sucreries");
+        let cider = make_product(&db, "Cider", 300, "alcool");
+        let coffee = make_product(&db, "Coffee", 100, "boisson-sans-alcool");
+
+        make_order(&db, &[(&crepe, 1), (&cider, 1)], PaymentMethod::Cash);
+        make_order(&db, &[(&crepe, 2), (&cider, 2)], PaymentMethod::Card);
+        make_order(&db, &[(&crepe, 1), (&coffee, 1)], PaymentMethod::Cash);
+        make_order(&db, &[(&coffee, 1)], PaymentMethod::Cash);
+
+        let pairs = get_product_pairs_inner(&db, DateRange::default(), None).unwrap();
+        assert_eq!(pairs.len(), 2);
+
+        let top = &pairs[0];
+        let mut names = [top.product_a_name.as_str(), top.product_b_name.as_str()];
+        names.sort_unstable();
+        assert_eq!(names, ["Cider", "Crepe"]);
+        assert_eq!(top.order_count, 2);
+        assert_eq!(pairs[1].order_count, 1);
+
+        let limited = get_product_pairs_inner(&db, DateRange::default(), Some(1)).unwrap();
+        assert_eq!(limited.len(), 1);
+    }
+
+    #[test]
+    fn export_dashboard_writes_csv() {
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Crêpe \"maison\"", 250, "sucreries");
+        make_order(&db, &[(&p, 2)], PaymentMethod::Card);
+
+        let path = temp_path("csv");
+        export_dashboard_inner(
+            &db,
+            DateRange::default(),
+            ProductSummaryQuery::default(),
+            None,
+            ExportFormat::Csv,
+            false,
+            path.clone(),
+        )
+        .unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(csv.contains("\"Total revenue\";\"5.00\""));
+        assert!(csv.contains("\"Crêpe \"\"maison\"\"\";\"2\";\"5.00\""));
+        assert!(csv.contains("\"card\";\"1\";\"5.00\""));
+        assert!(csv.contains("\"Sucreries\";\"2\";\"5.00\""));
+    }
+
+    #[test]
+    fn export_dashboard_writes_xlsx() {
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Soda", 200, "boisson-sans-alcool");
+        make_order(&db, &[(&p, 1)], PaymentMethod::Cash);
+
+        let path = temp_path("xlsx");
+        export_dashboard_inner(
+            &db,
+            DateRange::default(),
+            ProductSummaryQuery::default(),
+            None,
+            ExportFormat::Xlsx,
+            false,
+            path.clone(),
+        )
+        .unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        // XLSX files are zip archives.
+        assert!(bytes.starts_with(b"PK"));
+    }
+
+    #[test]
+    fn export_fec_writes_balanced_entries() {
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Beer", 350, "alcool");
+        let cash = make_order(&db, &[(&p, 2)], PaymentMethod::Cash);
+        let card = make_order(&db, &[(&p, 1)], PaymentMethod::Card);
+        set_order_created_at(&db, &cash.order.id, "2026-06-20T12:00:00Z");
+        set_order_created_at(&db, &card.order.id, "2026-06-21T12:00:00Z");
+
+        let path = temp_path("txt");
+        export_fec_inner(&db, DateRange::default(), path.clone()).unwrap();
+        let fec = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<Vec<&str>> = fec.lines().map(|l| l.split('\t').collect()).collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[0][0], "JournalCode");
+        assert!(lines.iter().all(|l| l.len() == 18));
+
+        // Entry 1: cash order, debit 530000 / credit 707000.
+        assert_eq!(lines[1][2], "1");
+        assert_eq!(lines[1][3], "20260620");
+        assert_eq!(lines[1][4], "530000");
+        assert_eq!(lines[1][11], "7,00");
+        assert_eq!(lines[2][4], "707000");
+        assert_eq!(lines[2][12], "7,00");
+
+        // Entry 2: card order.
+        assert_eq!(lines[3][2], "2");
+        assert_eq!(lines[3][4], "511200");
+        assert_eq!(lines[3][11], "3,50");
+        assert_eq!(lines[4][8], card.order.id);
+    }
+
+    #[test]
+    fn accounting_exports_reverse_refunds() {
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Panini", 400, "snack");
+        let order = make_order(&db, &[(&p, 2)], PaymentMethod::Cash);
+        let refund = refund_items_inner(
+            &db,
+            order.order.id.clone(),
+            vec![ItemRefund {
+                item_id: order.items[0].id.clone(),
+                quantity: 1.0,
+            }],
+        )
+        .unwrap();
+        set_order_created_at(&db, &order.order.id, "2026-06-20T12:00:00Z");
+        set_order_created_at(&db, &refund.order.id, "2026-06-20T13:00:00Z");
+
+        let path = temp_path("txt");
+        export_fec_inner(&db, DateRange::default(), path.clone()).unwrap();
+        let fec = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<Vec<&str>> = fec.lines().map(|l| l.split('\t').collect()).collect();
+        assert_eq!(lines.len(), 5);
+        // The cash account is credited and the sales account debited.
+        assert_eq!(lines[3][4], "530000");
+        assert_eq!(lines[3][10], format!("Remboursement {}", refund.order.id));
+        assert_eq!(lines[3][11], "0,00");
+        assert_eq!(lines[3][12], "4,00");
+        assert_eq!(lines[4][4], "707000");
+        assert_eq!(lines[4][11], "4,00");
+        assert_eq!(lines[4][12], "0,00");
+
+        let path = temp_path("csv");
+        export_ledger_csv_inner(&db, DateRange::default(), path.clone()).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert!(lines[3].contains("\"Refund (cash)\""));
+        assert!(lines[3].ends_with("\"0.00\";\"4.00\""));
+        assert!(lines[4].ends_with("\"4.00\";\"0.00\""));
+    }
+
+    #[test]
+    fn ledger_accounts_default_and_update() {
+        let db = init_db_in_memory();
+        assert_eq!(
+            get_ledger_accounts_inner(&db).unwrap(),
+            LedgerAccounts::default()
+        );
+
+        let updated = update_ledger_accounts_inner(
+            &db,
+            LedgerAccounts {
+                journal_code: "BV".to_string(),
+                sales_account: " 706100 ".to_string(),
+                ..LedgerAccounts::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(updated.journal_code, "BV");
+        assert_eq!(updated.sales_account, "706100");
+        assert_eq!(get_ledger_accounts_inner(&db).unwrap(), updated);
+
+        let result = update_ledger_accounts_inner(
+            &db,
+            LedgerAccounts {
+                cash_account: "".to_string(),
+                ..LedgerAccounts::default()
+            },
+        );
+        assert!(result.unwrap_err().contains("cannot be empty"));
+    }
+
+    #[test]
+    fn export_ledger_csv_uses_configured_accounts() {
+        let db = init_db_in_memory();
+        update_ledger_accounts_inner(
+            &db,
+            LedgerAccounts {
+                card_account: "512000".to_string(),
+                ..LedgerAccounts::default()
+            },
+        )
+        .unwrap();
+        let p = make_product(&db, "Panini", 400, "snack");
+        let order = make_order(&db, &[(&p, 1)], PaymentMethod::Card);
+
+        let path = temp_path("csv");
+        export_ledger_csv_inner(&db, DateRange::default(), path.clone()).unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[1].contains("\"512000\""));
+        assert!(lines[1].ends_with("\"4.00\";\"0.00\""));
+        assert!(lines[2].contains("\"707000\""));
+        assert!(lines[2].ends_with("\"0.00\";\"4.00\""));
+        assert!(lines[2].contains(&order.order.id));
+    }
+
+    #[test]
+    fn smtp_settings_round_trip() {
+        let db = init_db_in_memory();
+        assert_eq!(
+            get_smtp_settings_inner(&db).unwrap(),
+            SmtpSettings::default()
+        );
+
+        let settings = SmtpSettings {
+            host: "smtp.example.org".to_string(),
+            port: 465,
+            security: SmtpSecurity::Tls,
+            username: "buvette".to_string(),
+            password: "secret".to_string(),
+            from: "Buvette <buvette@example.org>".to_string(),
+        };
+        let saved = update_smtp_settings_inner(&db, settings.clone()).unwrap();
+        assert_eq!(saved, settings);
+        assert_eq!(get_smtp_settings_inner(&db).unwrap(), settings);
+    }
+
+    #[test]
+    fn build_report_email_contains_summary_and_attachment() {
+        let db = init_db_in_memory();
+        update_smtp_settings_inner(
+            &db,
+            SmtpSettings {
+                host: "smtp.example.org".to_string(),
+                from: "buvette@example.org".to_string(),
+                ..SmtpSettings::default()
+            },
+        )
+        .unwrap();
+        let p = make_product(&db, "Soda", 200, "boisson-sans-alcool");
+        make_order(&db, &[(&p, 3)], PaymentMethod::Cash);
+
+        let (_, message) = build_report_email_inner(
+            &db,
+            DateRange::default(),
+            vec!["treasurer@example.org".to_string()],
+        )
+        .unwrap();
+        let raw = String::from_utf8_lossy(&message.formatted()).into_owned();
+
+        assert!(raw.contains("Subject: Sales report (all time)"));
+        assert!(raw.contains("treasurer@example.org"));
+        assert!(raw.contains("sales-report.csv"));
+    }
+
+    #[test]
+    fn build_report_email_requires_recipients() {
+        let db = init_db_in_memory();
+        let result = build_report_email_inner(&db, DateRange::default(), vec![]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn notification_settings_validate_slack_url() {
+        let db = init_db_in_memory();
+        let result = update_notification_settings_inner(
+            &db,
+            NotificationSettings {
+                channel: NotificationChannel::Slack,
+                slack_webhook_url: "hooks.slack.com/services/x".to_string(),
+                ..NotificationSettings::default()
+            },
+        );
+        assert!(result.unwrap_err().contains("https://"));
+
+        let saved = update_notification_settings_inner(
+            &db,
+            NotificationSettings {
+                channel: NotificationChannel::Telegram,
+                telegram_bot_token: "123:abc".to_string(),
+                telegram_chat_id: "-1001".to_string(),
+                ..NotificationSettings::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(saved.channel, NotificationChannel::Telegram);
+        assert_eq!(get_notification_settings_inner(&db).unwrap(), saved);
+    }
+
+    #[test]
+    fn summary_notification_lists_top_products() {
+        let db = init_db_in_memory();
+        let crepe = make_product(&db, "Crepe", 250, "sucreries");
+        let cider = make_product(&db, "Cider", 300, "alcool");
+        let coffee = make_product(&db, "Coffee", 100, "boisson-sans-alcool");
+        let water = make_product(&db, "Water", 50, "boisson-sans-alcool");
+        make_order(
+            &db,
+            &[(&crepe, 4), (&cider, 3), (&coffee, 2), (&water, 1)],
+            PaymentMethod::Cash,
+        );
+
+        let (_, text) = build_summary_notification_inner(&db, None).unwrap();
+        assert!(text.contains("21.50 EUR, 1 orders"));
+        assert!(text.contains("Top: Crepe (4), Cider (3), Coffee (2)"));
+        assert!(!text.contains("Water"));
+    }
+
+    #[test]
+    fn order_chain_links_consecutive_orders() {
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Beer", 300, "alcool");
+
+        let first = make_order(&db, &[(&p, 1)], PaymentMethod::Cash);
+        let second = make_order(&db, &[(&p, 2)], PaymentMethod::Card);
+        assert!(first.order.hash.is_some());
+        assert_ne!(first.order.hash, second.order.hash);
+
+        let previous: Option<String> = db
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT previous_hash FROM orders WHERE id = ?1",
+                params![second.order.id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(previous, first.order.hash);
+
+        let verification = verify_order_chain_inner(&db).unwrap();
+        assert!(verification.valid);
+        assert_eq!(verification.checked_orders, 2);
+    }
+
+    #[test]
+    fn order_chain_detects_tampering() {
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Beer", 300, "alcool");
+
+        make_order(&db, &[(&p, 1)], PaymentMethod::Cash);
+        let tampered = make_order(&db, &[(&p, 2)], PaymentMethod::Card);
+        make_order(&db, &[(&p, 3)], PaymentMethod::Cash);
+
+        db.conn
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE order_items SET unit_price = 1 WHERE order_id = ?1",
+                params![tampered.order.id],
+            )
+            .unwrap();
+
+        let verification = verify_order_chain_inner(&db).unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.first_invalid_order_id, Some(tampered.order.id));
+    }
+
+    #[test]
+    fn export_journal_writes_chained_entries() {
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Cider", 300, "alcool");
+        let first = make_order(&db, &[(&p, 1)], PaymentMethod::Cash);
+        let second = make_order(&db, &[(&p, 2)], PaymentMethod::Card);
+
+        let path = temp_path("json");
+        let journal = export_journal_inner(&db, DateRange::default(), path.clone()).unwrap();
+        let written: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(journal.entries.len(), 2);
+        assert_eq!(written["format"], "pos-journal");
+        assert_eq!(written["version"], 2);
+        assert_eq!(written["journal_hash"], journal.journal_hash.as_str());
+
+        let entries = written["entries"].as_array().unwrap();
+        assert_eq!(entries[0]["type"], "order");
+        assert_eq!(entries[0]["sequence"], 1);
+        assert_eq!(entries[0]["order"]["id"], first.order.id.as_str());
+        assert_eq!(
+            entries[1]["previous_hash"],
+            first.order.hash.unwrap().as_str()
+        );
+        assert_eq!(
+            entries[1]["order"]["hash"],
+            second.order.hash.unwrap().as_str()
+        );
+        assert_eq!(entries[1]["order"]["items"][0]["quantity"], 2.0);
+    }
+
+    #[test]
+    fn default_alcohol_products_are_age_restricted() {
+        let db = init_db_in_memory();
+        let products = list_products_inner(&db, false, &ProductFilter::default()).unwrap();
+        let beer = products.iter().find(|p| p.id == "biere-25cl").unwrap();
+        let coffee = products.iter().find(|p| p.id == "cafe").unwrap();
+        assert!(beer.age_restricted);
+        assert!(!coffee.age_restricted);
+    }
+
+    #[test]
+    fn create_order_requires_age_confirmation() {
+        let db = init_db_in_memory();
+        let wine = create_product_inner(
+            &db,
+            CreateProductPayload {
+                name: "Vin chaud".to_string(),
+                price: 300,
+                category_id: "alcool".to_string(),
+                age_restricted: true,
+                unit: QuantityUnit::Piece,
+                translations: Translations::new(),
+            },
+            None,
+        )
+        .unwrap();
+        let item = || CreateOrderItemPayload {
+            product_id: wine.id.clone(),
+            product_name: wine.name.clone(),
+            unit_price: wine.price,
+            quantity: 2.0,
+        };
+
+        let refused = create_order_inner(
+            &db,
+            CreateOrderPayload {
+                items: vec![item()],
+                payment_method: PaymentMethod::Cash,
+                age_confirmed: false,
+                limits_confirmed: false,
+                adjustments: Vec::new(),
+                source: OrderSource::Counter,
+            },
+        );
+        assert!(refused
+            .unwrap_err()
+            .contains("Age confirmation required for: Vin chaud"));
+        assert!(list_orders_inner(&db, None).unwrap().is_empty());
+
+        let order = create_order_inner(
+            &db,
+            CreateOrderPayload {
+                items: vec![item()],
+                payment_method: PaymentMethod::Cash,
+                age_confirmed: true,
+                limits_confirmed: false,
+                adjustments: Vec::new(),
+                source: OrderSource::Counter,
+            },
+        )
+        .unwrap();
+
+        let logged: String = db
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT restricted_products FROM age_confirmations WHERE order_id = ?1",
+                params![order.order.id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(logged, "Vin chaud");
+    }
+
+    #[test]
+    fn create_order_with_weighed_product_rounds_line_total() {
+        let db = init_db_in_memory();
+        let cake = create_product_inner(
+            &db,
+            CreateProductPayload {
+                name: "Cake".to_string(),
+                price: 1890,
+                category_id: "sucreries".to_string(),
+                age_restricted: false,
+                unit: QuantityUnit::Kg,
+                translations: Translations::new(),
+            },
+            None,
+        )
+        .unwrap();
+
+        let order = create_order_inner(
+            &db,
+            CreateOrderPayload {
+                items: vec![CreateOrderItemPayload {
+                    product_id: cake.id.clone(),
+                    product_name: cake.name.clone(),
+                    unit_price: cake.price,
+                    quantity: 0.35,
+                }],
+                payment_method: PaymentMethod::Cash,
+                age_confirmed: false,
+                limits_confirmed: false,
+                adjustments: Vec::new(),
+                source: OrderSource::Counter,
+            },
+        )
+        .unwrap();
+
+        // 18.90 EUR/kg * 0.35 kg = 6.615 EUR
+        assert_eq!(order.items[0].unit, QuantityUnit::Kg);
+        assert_eq!(order.items[0].total, 662);
+        assert_eq!(order.order.total, 662);
+
+        let stored = list_orders_inner(&db, None).unwrap();
+        assert_eq!(stored[0].items[0].quantity, 0.35);
+        assert_eq!(stored[0].items[0].unit, QuantityUnit::Kg);
+
+        let summary =
+            get_dashboard_summary_inner(&db, ProductSummaryQuery::default(), None).unwrap();
+        assert_eq!(summary.per_product[0].total_quantity, 0.35);
+        assert_eq!(summary.basket.average_items_per_order, 1.0);
+        assert!(verify_order_chain_inner(&db).unwrap().valid);
+    }
+
+    #[test]
+    fn create_order_rejects_fractional_pieces() {
+        let db = init_db_in_memory();
+        let coffee = make_product(&db, "Coffee", 150, "boisson-sans-alcool");
+
+        let result = create_order_inner(
+            &db,
+            CreateOrderPayload {
+                items: vec![CreateOrderItemPayload {
+                    product_id: coffee.id.clone(),
+                    product_name: coffee.name.clone(),
+                    unit_price: coffee.price,
+                    quantity: 1.5,
+                }],
+                payment_method: PaymentMethod::Card,
+                age_confirmed: false,
+                limits_confirmed: false,
+                adjustments: Vec::new(),
+                source: OrderSource::Counter,
+            },
+        );
+
+        assert!(result.unwrap_err().contains("must be a whole number"));
+    }
+
+    #[test]
+    fn scale_settings_round_trip() {
+        let db = init_db_in_memory();
+        let result = update_scale_settings_inner(
+            &db,
+            ScaleSettings {
+                connection: ScaleConnection::Serial,
+                ..ScaleSettings::default()
+            },
+        );
+        assert!(result.unwrap_err().contains("serial port"));
+
+        let saved = update_scale_settings_inner(
+            &db,
+            ScaleSettings {
+                connection: ScaleConnection::Hid,
+                hid_vendor_id: 0x0922,
+                hid_product_id: 0x8003,
+                ..ScaleSettings::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(saved.baud_rate, 9600);
+        assert_eq!(get_scale_settings_inner(&db).unwrap(), saved);
+    }
+
+    #[test]
+    fn scale_parses_serial_and_hid_weights() {
+        use crate::scale::{parse_hid_report, parse_serial_weight};
+
+        assert_eq!(parse_serial_weight("\n  0.350KG\r\n").unwrap(), 0.35);
+        assert_eq!(parse_serial_weight("ST,GS, 1250 g\r\n").unwrap(), 1.25);
+        assert_eq!(parse_serial_weight("0.500\r").unwrap(), 0.5);
+        assert!(parse_serial_weight("-0.020kg\r").is_err());
+
+        // Stable, grams, exponent 0, 350 g.
+        assert_eq!(parse_hid_report(&[3, 4, 2, 0, 0x5e, 0x01]).unwrap(), 0.35);
+        // Stable, ounces, exponent -1, 12.3 oz.
+        assert_eq!(parse_hid_report(&[3, 4, 11, 0xff, 123, 0]).unwrap(), 0.349);
+        assert!(parse_hid_report(&[3, 3, 2, 0, 10, 0]).is_err());
+    }
+
+    #[test]
+    fn product_translations_are_normalized_and_kept_on_update() {
+        let db = init_db_in_memory();
+        let invalid = create_product_inner(
+            &db,
+            CreateProductPayload {
+                name: "Bière".to_string(),
+                price: 300,
+                category_id: "alcool".to_string(),
+                age_restricted: false,
+                unit: QuantityUnit::Piece,
+                translations: Translations::from([("english".to_string(), "Beer".to_string())]),
+            },
+            None,
+        );
+        assert!(invalid.unwrap_err().contains("Invalid locale"));
+
+        let beer = create_product_inner(
+            &db,
+            CreateProductPayload {
+                name: "Bière".to_string(),
+                price: 300,
+                category_id: "alcool".to_string(),
+                age_restricted: false,
+                unit: QuantityUnit::Piece,
+                translations: Translations::from([
+                    ("en".to_string(), " Beer ".to_string()),
+                    ("de".to_string(), "".to_string()),
+                ]),
+            },
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            beer.translations,
+            Translations::from([("en".to_string(), "Beer".to_string())])
+        );
+
+        let updated = update_product_inner(
+            &db,
+            UpdateProductPayload {
+                id: beer.id.clone(),
+                name: "Bière blonde".to_string(),
+                price: 350,
+                category_id: "alcool".to_string(),
+                available: true,
+                age_restricted: None,
+                unit: None,
+                translations: None,
+            },
+            None,
+        )
+        .unwrap();
+        assert_eq!(updated.translations, beer.translations);
+
+        let listed = list_products_inner(&db, false, &ProductFilter::default()).unwrap();
+        let listed = listed.iter().find(|p| p.id == beer.id).unwrap();
+        assert_eq!(listed.translations.get("en").unwrap(), "Beer");
+    }
+
+    #[test]
+    fn locale_settings_round_trip() {
+        let db = init_db_in_memory();
+        assert_eq!(
+            get_locale_settings_inner(&db).unwrap(),
+            LocaleSettings::default()
+        );
+
+        let invalid = update_locale_settings_inner(
+            &db,
+            LocaleSettings {
+                sales_locale: "fr".to_string(),
+                customer_display_locale: "English".to_string(),
+            },
+        );
+        assert!(invalid.is_err());
+
+        let saved = update_locale_settings_inner(
+            &db,
+            LocaleSettings {
+                sales_locale: "fr".to_string(),
+                customer_display_locale: "en".to_string(),
+            },
+        )
+        .unwrap();
+        assert_eq!(get_locale_settings_inner(&db).unwrap(), saved);
+    }
+
+    fn configure_business(db: &DbState, vat_enabled: bool) {
+        update_business_settings_inner(
+            db,
+            BusinessSettings {
+                name: "Association des Fêtes".to_string(),
+                address: "1 place de la Mairie\n75000 Paris".to_string(),
+                vat_enabled,
+                ..BusinessSettings::default()
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn generate_invoice_numbers_sequentially_and_reprints() {
+        let db = init_db_in_memory();
+        let coffee = make_product(&db, "Café", 150, "boisson-sans-alcool");
+        let first = make_order(&db, &[(&coffee, 2)], PaymentMethod::Card);
+        let second = make_order(&db, &[(&coffee, 1)], PaymentMethod::Cash);
+        let customer = CustomerDetails {
+            name: "ACME SARL".to_string(),
+            address: "2 rue du Port".to_string(),
+            vat_number: String::new(),
+        };
+
+        let unconfigured = generate_invoice_inner(
+            &db,
+            first.order.id.clone(),
+            customer.clone(),
+            temp_path("pdf"),
+        );
+        assert!(unconfigured.is_err());
+        configure_business(&db, true);
+
+        let path = temp_path("pdf");
+        let invoice =
+            generate_invoice_inner(&db, first.order.id.clone(), customer.clone(), path.clone())
+                .unwrap();
+        assert_eq!(invoice.number, "INV-000001");
+        let pdf = std::fs::read(&path).unwrap();
+        assert!(pdf.starts_with(b"%PDF-1.4"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+
+        let next =
+            generate_invoice_inner(&db, second.order.id.clone(), customer.clone(), path.clone())
+                .unwrap();
+        assert_eq!(next.number, "INV-000002");
+
+        // Reprinting keeps the original number and buyer.
+        let reprint = generate_invoice_inner(
+            &db,
+            first.order.id.clone(),
+            CustomerDetails {
+                name: "Someone else".to_string(),
+                ..customer
+            },
+            path.clone(),
+        )
+        .unwrap();
+        assert_eq!(reprint, invoice);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn invoices_keep_the_seller_and_amounts_they_were_issued_with() {
+        let db = init_db_in_memory();
+        let coffee = make_product(&db, "Café", 150, "boisson-sans-alcool");
+        let order = make_order(&db, &[(&coffee, 8)], PaymentMethod::Card);
+        let customer = CustomerDetails {
+            name: "ACME SARL".to_string(),
+            address: "2 rue du Port".to_string(),
+            vat_number: String::new(),
+        };
+        configure_business(&db, true);
+        let path = temp_path("pdf");
+
+        let invoice =
+            generate_invoice_inner(&db, order.order.id.clone(), customer.clone(), path.clone())
+                .unwrap();
+        assert_eq!(invoice.seller.name, "Association des Fêtes");
+        assert!(invoice.seller.vat_enabled);
+        assert_eq!(invoice.total, 1200);
+        assert_eq!(invoice.total_excl_vat, 1000);
+
+        update_business_settings_inner(
+            &db,
+            BusinessSettings {
+                name: "Comité des Fêtes".to_string(),
+                vat_enabled: false,
+                ..BusinessSettings::default()
+            },
+        )
+        .unwrap();
+        let reprint =
+            generate_invoice_inner(&db, order.order.id.clone(), customer.clone(), path.clone())
+                .unwrap();
+        assert_eq!(reprint, invoice);
+
+        let refund = refund_items_inner(
+            &db,
+            order.order.id.clone(),
+            vec![ItemRefund {
+                item_id: order.items[0].id.clone(),
+                quantity: 1.0,
+            }],
+        )
+        .unwrap();
+        let refused =
+            generate_invoice_inner(&db, refund.order.id.clone(), customer.clone(), path.clone());
+        assert_eq!(refused.unwrap_err(), "Refunds cannot be invoiced");
+
+        let voided = make_order(&db, &[(&coffee, 1)], PaymentMethod::Cash);
+        undo_last_order_inner(&db, None).unwrap();
+        let refused = generate_invoice_inner(&db, voided.order.id, customer, path.clone());
+        assert_eq!(refused.unwrap_err(), "Voided orders cannot be invoiced");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn invoice_vat_breakdown_rounds_to_the_cent() {
+        assert_eq!(crate::invoice::vat_breakdown(1200, 20.0), (1000, 200));
+        assert_eq!(crate::invoice::vat_breakdown(150, 5.5), (142, 8));
+    }
+
+    #[test]
+    fn generate_receipt_writes_a_pdf() {
+        let db = init_db_in_memory();
+        let coffee = make_product(&db, "Café", 150, "boisson-sans-alcool");
+        let order = make_order(&db, &[(&coffee, 2)], PaymentMethod::Card);
+
+        let path = temp_path("pdf");
+        generate_receipt_inner(&db, order.order.id.clone(), path.clone()).unwrap();
+        let pdf = std::fs::read(&path).unwrap();
+        assert!(pdf.starts_with(b"%PDF-1.4"));
+        assert!(pdf.ends_with(b"%%EOF\n"));
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn verify_receipt_code_detects_tampering() {
+        let db = init_db_in_memory();
+        let coffee = make_product(&db, "Café", 150, "boisson-sans-alcool");
+        let order = make_order(&db, &[(&coffee, 2)], PaymentMethod::Card);
+        let code = crate::receipt::qr_payload(&order.order);
+
+        let verification = verify_receipt_code_inner(&db, code.clone()).unwrap();
+        assert!(verification.authentic);
+        assert_eq!(verification.order.unwrap().order.id, order.order.id);
+
+        let forged = format!("POS1:{}:{}", order.order.id, "0".repeat(64));
+        assert!(!verify_receipt_code_inner(&db, forged).unwrap().authentic);
+        let unknown = format!("POS1:unknown:{}", order.order.hash.clone().unwrap());
+        let unknown = verify_receipt_code_inner(&db, unknown).unwrap();
+        assert!(!unknown.authentic);
+        assert!(unknown.order.is_none());
+        assert!(verify_receipt_code_inner(&db, "https://example.com".to_string()).is_err());
+
+        {
+            let conn = db.conn.lock().unwrap();
+            conn.execute(
+                "UPDATE order_items SET total = 100 WHERE order_id = ?1",
+                params![order.order.id],
+            )
+            .unwrap();
+        }
+        assert!(!verify_receipt_code_inner(&db, code).unwrap().authentic);
+    }
+
+    #[test]
+    fn print_product_labels_assigns_stable_barcodes() {
+        let db = init_db_in_memory();
+        let jam = make_product(&db, "Confiture de fraises", 450, "snack");
+        let honey = make_product(&db, "Miel", 800, "snack");
+
+        let path = temp_path("pdf");
+        let labelled = print_product_labels_inner(
+            &db,
+            vec![jam.id.clone(), jam.id.clone(), honey.id.clone()],
+            path.clone(),
+        )
+        .unwrap();
+        let pdf = std::fs::read(&path).unwrap();
+        assert!(pdf.starts_with(b"%PDF-1.4"));
+        assert_eq!(labelled.len(), 3);
+        assert_eq!(labelled[0].barcode.as_deref(), Some("2000000000015"));
+        assert_eq!(labelled[1].barcode, labelled[0].barcode);
+        assert_eq!(labelled[2].barcode.as_deref(), Some("2000000000022"));
+
+        // Reprinting keeps the barcode.
+        let again = print_product_labels_inner(&db, vec![honey.id.clone()], path.clone()).unwrap();
+        assert_eq!(again[0].barcode, labelled[2].barcode);
+        let listed = list_products_inner(&db, false, &ProductFilter::default()).unwrap();
+        let listed = listed.iter().find(|p| p.id == jam.id).unwrap();
+        assert_eq!(listed.barcode, labelled[0].barcode);
+
+        assert!(print_product_labels_inner(&db, vec![], path.clone()).is_err());
+        assert!(
+            print_product_labels_inner(&db, vec!["unknown".to_string()], path.clone()).is_err()
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn ean13_encoding() {
+        assert_eq!(crate::labels::ean13("400638133393"), "4006381333931");
+        let modules = crate::labels::ean13_modules("4006381333931").unwrap();
+        assert_eq!(modules.len(), 95);
+        let bits: String = modules.iter().map(|&m| if m { '1' } else { '0' }).collect();
+        // First digit 4 encodes the left half as LGLLGG: "0" then "0" in G.
+        assert!(bits.starts_with("10100011010100111"));
+        assert!(bits.ends_with("101"));
+        assert!(crate::labels::ean13_modules("4006381333932").is_err());
+    }
+
+    #[test]
+    fn build_receipt_email_attaches_the_receipt() {
+        let db = init_db_in_memory();
+        update_smtp_settings_inner(
+            &db,
+            SmtpSettings {
+                host: "smtp.example.org".to_string(),
+                from: "buvette@example.org".to_string(),
+                ..SmtpSettings::default()
+            },
+        )
+        .unwrap();
+        configure_business(&db, false);
+        let coffee = make_product(&db, "Café", 150, "boisson-sans-alcool");
+        let order = make_order(&db, &[(&coffee, 2)], PaymentMethod::Card);
+
+        let (_, message) = build_receipt_email_inner(
+            &db,
+            order.order.id.clone(),
+            " customer@example.org ".to_string(),
+        )
+        .unwrap();
+        let raw = String::from_utf8_lossy(&message.formatted()).into_owned();
+        assert!(raw.contains("customer@example.org"));
+        assert!(raw.contains("receipt.pdf"));
+        assert!(raw.contains("application/pdf"));
+
+        let invalid =
+            build_receipt_email_inner(&db, order.order.id.clone(), "not an address".to_string());
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn undo_last_order_voids_recent_order_only() {
+        let db = init_db_in_memory();
+        assert!(undo_last_order_inner(&db, None).is_err());
+
+        let p = make_product(&db, "Soda", 200, "boisson-sans-alcool");
+        let kept = make_order(&db, &[(&p, 1)], PaymentMethod::Cash);
+        let mistake = make_order(&db, &[(&p, 3)], PaymentMethod::Card);
+
+        let voided = undo_last_order_inner(&db, None).unwrap();
+        assert_eq!(voided.order.id, mistake.order.id);
+        assert!(voided.order.voided_at.is_some());
+        assert!(undo_last_order_inner(&db, None).is_err());
+
+        // The voided order is left out of the sales but not of the chain.
+        let summary =
+            get_dashboard_summary_inner(&db, ProductSummaryQuery::default(), None).unwrap();
+        assert_eq!(summary.total_revenue, kept.order.total);
+        assert_eq!(summary.total_transactions, 1);
+        assert!(verify_order_chain_inner(&db).unwrap().valid);
+
+        set_admin_pin_inner(&db, None, Some("1234".to_string())).unwrap();
+        make_order(&db, &[(&p, 2)], PaymentMethod::Cash);
+        assert!(undo_last_order_inner(&db, None).is_err());
+        assert!(undo_last_order_inner(&db, Some("1234".to_string())).is_ok());
+
+        let late = make_order(&db, &[(&p, 1)], PaymentMethod::Cash);
+        set_order_created_at(&db, &late.order.id, "2020-01-01T10:00:00Z");
+        assert!(undo_last_order_inner(&db, Some("1234".to_string())).is_err());
+    }
+
+    #[test]
+    fn refund_items_creates_linked_negative_lines() {
+        let db = init_db_in_memory();
+        let crepe = make_product(&db, "Crêpe", 300, "snack");
+        let soda = make_product(&db, "Soda", 200, "boisson-sans-alcool");
+        let order = make_order(&db, &[(&crepe, 3), (&soda, 1)], PaymentMethod::Card);
+        let crepe_line = order.items[0].id.clone();
+
+        let refund = refund_items_inner(
+            &db,
+            order.order.id.clone(),
+            vec![ItemRefund {
+                item_id: crepe_line.clone(),
+                quantity: 2.0,
+            }],
+        )
+        .unwrap();
+        assert_eq!(
+            refund.order.refund_of.as_deref(),
+            Some(order.order.id.as_str())
+        );
+        assert_eq!(refund.order.payment_method, PaymentMethod::Card);
+        assert_eq!(refund.order.total, -600);
+        assert_eq!(refund.items.len(), 1);
+        assert_eq!(refund.items[0].quantity, -2.0);
+        assert_eq!(
+            refund.items[0].refunded_item_id.as_deref(),
+            Some(crepe_line.as_str())
+        );
+
+        // Only one crêpe is left to refund.
+        let too_many = refund_items_inner(
+            &db,
+            order.order.id.clone(),
+            vec![ItemRefund {
+                item_id: crepe_line.clone(),
+                quantity: 2.0,
+            }],
+        );
+        assert!(too_many.unwrap_err().contains("only 1 left"));
+        assert!(refund_items_inner(&db, refund.order.id.clone(), vec![]).is_err());
+
+        let summary =
+            get_dashboard_summary_inner(&db, ProductSummaryQuery::default(), None).unwrap();
+        assert_eq!(summary.total_revenue, 300 + 200);
+        assert!(verify_order_chain_inner(&db).unwrap().valid);
+    }
+
+    #[test]
+    fn refund_items_gives_back_remaining_total_of_weighed_line() {
+        let db = init_db_in_memory();
+        let cheese = create_product_inner(
+            &db,
+            CreateProductPayload {
+                name: "Comté".to_string(),
+                price: 1890,
+                category_id: "snack".to_string(),
+                age_restricted: false,
+                unit: QuantityUnit::Kg,
+                translations: Translations::new(),
+            },
+            None,
+        )
+        .unwrap();
+        let order = create_order_inner(
+            &db,
+            CreateOrderPayload {
+                items: vec![CreateOrderItemPayload {
+                    product_id: cheese.id.clone(),
+                    product_name: cheese.name.clone(),
+                    unit_price: cheese.price,
+                    quantity: 0.35,
+                }],
+                payment_method: PaymentMethod::Cash,
+                age_confirmed: false,
+                limits_confirmed: false,
+                adjustments: Vec::new(),
+                source: OrderSource::Counter,
+            },
+        )
+        .unwrap();
+        let line = order.items[0].id.clone();
+        let refund = |quantity: f64| {
+            refund_items_inner(
+                &db,
+                order.order.id.clone(),
+                vec![ItemRefund {
+                    item_id: line.clone(),
+                    quantity,
+                }],
+            )
+        };
+
+        let first = refund(0.1).unwrap();
+        let second = refund(0.25).unwrap();
+        assert_eq!(first.order.total + second.order.total, -order.order.total);
+        assert!(refund(0.001).is_err());
+    }
+
+    #[test]
+    fn exchange_order_settles_the_difference() {
+        let db = init_db_in_memory();
+        let sugar = make_product(&db, "Crêpe sucre", 250, "snack");
+        let nutella = make_product(&db, "Crêpe Nutella", 350, "snack");
+        let order = make_order(&db, &[(&sugar, 2)], PaymentMethod::Cash);
+
+        let replacement = || CreateOrderPayload {
+            items: vec![CreateOrderItemPayload {
+                product_id: nutella.id.clone(),
+                product_name: nutella.name.clone(),
+                unit_price: nutella.price,
+                quantity: 1.0,
+            }],
+            payment_method: PaymentMethod::Cash,
+            age_confirmed: false,
+            limits_confirmed: false,
+            adjustments: Vec::new(),
+            source: OrderSource::Counter,
+        };
+        let refund = vec![ItemRefund {
+            item_id: order.items[0].id.clone(),
+            quantity: 1.0,
+        }];
+
+        let exchange =
+            exchange_order_inner(&db, order.order.id.clone(), refund.clone(), replacement())
+                .unwrap();
+        assert_eq!(exchange.refund.order.total, -250);
+        assert_eq!(exchange.replacement.order.total, 350);
+        assert_eq!(exchange.balance, 100);
+        assert!(verify_order_chain_inner(&db).unwrap().valid);
+
+        // Nothing is stored when one half fails.
+        let empty = CreateOrderPayload {
+            items: vec![],
+            ..replacement()
+        };
+        assert!(exchange_order_inner(&db, order.order.id.clone(), refund, empty).is_err());
+        assert_eq!(list_orders_inner(&db, None).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn category_icon_is_validated_and_updatable() {
+        let db = init_db_in_memory();
+        let payload = |icon: &str| CreateCategoryPayload {
+            id: "crepes".to_string(),
+            label: "Crêpes".to_string(),
+            color: "#f59e0b".to_string(),
+            translations: Translations::new(),
+            icon: Some(icon.to_string()),
+            parent_id: None,
+            allow_custom_color: false,
+        };
+
+        assert!(create_category_inner(&db, payload("Crêpes!"), None).is_err());
+        assert!(create_category_inner(&db, payload("🥞🥞🥞🥞🥞🥞🥞🥞🥞"), None).is_err());
+        let created = create_category_inner(&db, payload(" 🥞 "), None).unwrap();
+        assert_eq!(created.icon.as_deref(), Some("🥞"));
+
+        let update = |icon: Option<&str>| UpdateCategoryPayload {
+            id: "crepes".to_string(),
+            label: "Crêpes".to_string(),
+            color: "#f59e0b".to_string(),
+            translations: None,
+            icon: icon.map(str::to_string),
+            parent_id: None,
+            allow_custom_color: false,
+        };
+        let kept = update_category_inner(&db, update(None), None).unwrap();
+        assert_eq!(kept.icon.as_deref(), Some("🥞"));
+        let renamed = update_category_inner(&db, update(Some("pancakes")), None).unwrap();
+        assert_eq!(renamed.icon.as_deref(), Some("pancakes"));
+        let cleared = update_category_inner(&db, update(Some("")), None).unwrap();
+        assert_eq!(cleared.icon, None);
+
+        let listed = list_categories_inner(&db).unwrap();
+        assert!(listed
+            .iter()
+            .find(|c| c.id == "crepes")
+            .unwrap()
+            .icon
+            .is_none());
+    }
+
+    #[test]
+    fn categories_nest_without_cycles() {
+        let db = init_db_in_memory();
+        let create = |id: &str, label: &str, parent_id: Option<&str>| {
+            create_category_inner(
+                &db,
+                CreateCategoryPayload {
+                    id: id.to_string(),
+                    label: label.to_string(),
+                    color: "#0ea5e9".to_string(),
+                    translations: Translations::new(),
+                    icon: None,
+                    parent_id: parent_id.map(str::to_string),
+                    allow_custom_color: false,
+                },
+                None,
+            )
+        };
+        create("boissons", "Boissons", None).unwrap();
+        let hot = create("chaudes", "Chaudes", Some("boissons")).unwrap();
+        assert_eq!(hot.path, "Boissons > Chaudes");
+        let tea = create("the", "Thé", Some("chaudes")).unwrap();
+        assert_eq!(tea.path, "Boissons > Chaudes > Thé");
+        assert!(create("orphan", "Orphan", Some("missing")).is_err());
+
+        // Moving "boissons" under its own grandchild would create a cycle.
+        let move_under = |id: &str, label: &str, parent_id: &str| {
+            update_category_inner(
+                &db,
+                UpdateCategoryPayload {
+                    id: id.to_string(),
+                    label: label.to_string(),
+                    color: "#0ea5e9".to_string(),
+                    translations: None,
+                    icon: None,
+                    parent_id: Some(parent_id.to_string()),
+                    allow_custom_color: false,
+                },
+                None,
+            )
+        };
+        assert!(move_under("boissons", "Boissons", "the").is_err());
+        assert!(move_under("boissons", "Boissons", "boissons").is_err());
+        let top_level = move_under("chaudes", "Chaudes", "").unwrap();
+        assert_eq!(top_level.parent_id, None);
+        assert_eq!(top_level.path, "Chaudes");
+
+        let listed = list_categories_inner(&db).unwrap();
+        let tea = listed.iter().find(|c| c.id == "the").unwrap();
+        assert_eq!(tea.path, "Chaudes > Thé");
+        assert!(delete_category_inner(&db, "chaudes".to_string(), None)
+            .unwrap_err()
+            .contains("subcategor"));
+    }
+
+    #[test]
+    fn active_catalog_restricts_sales_screen_products() {
+        let db = init_db_in_memory();
+        let all = list_products_inner(&db, false, &ProductFilter::default()).unwrap();
+        let crepe = make_product(&db, "Crêpe", 300, "snack");
+        let galette = make_product(&db, "Galette", 800, "snack");
+
+        let evening = create_catalog_inner(
+            &db,
+            CreateCatalogPayload {
+                name: " Repas du soir ".to_string(),
+                product_ids: vec![galette.id.clone(), crepe.id.clone()],
+            },
+        )
+        .unwrap();
+        assert_eq!(evening.name, "Repas du soir");
+        assert!(!evening.active);
+        // Without an active catalog the sales screen sees everything.
+        assert_eq!(
+            list_products_inner(&db, true, &ProductFilter::default())
+                .unwrap()
+                .len(),
+            all.len() + 2
+        );
+
+        let active = set_active_catalog_inner(&db, Some(evening.id.clone()))
+            .unwrap()
+            .unwrap();
+        assert!(active.active);
+        let sales: Vec<String> = list_products_inner(&db, true, &ProductFilter::default())
+            .unwrap()
+            .into_iter()
+            .map(|p| p.id)
+            .collect();
+        assert_eq!(sales.len(), 2);
+        assert!(sales.contains(&crepe.id) && sales.contains(&galette.id));
+        assert_eq!(
+            list_products_inner(&db, false, &ProductFilter::default())
+                .unwrap()
+                .len(),
+            all.len() + 2
+        );
+
+        assert!(set_active_catalog_inner(&db, Some("unknown".to_string())).is_err());
+        delete_catalog_inner(&db, evening.id).unwrap();
+        assert_eq!(
+            list_products_inner(&db, true, &ProductFilter::default())
+                .unwrap()
+                .len(),
+            all.len() + 2
+        );
+        assert!(list_catalogs_inner(&db).unwrap().is_empty());
+    }
+
+    #[test]
+    fn stocktake_adjusts_stock_to_the_count() {
+        let db = init_db_in_memory();
+        let crepe = make_product(&db, "Crêpe", 300, "snack");
+
+        let first = start_stocktake_inner(&db).unwrap();
+        assert!(start_stocktake_inner(&db).is_err());
+        assert!(
+            count_stocktake_product_inner(&db, first.id.clone(), crepe.id.clone(), 1.5).is_err()
+        );
+        count_stocktake_product_inner(&db, first.id.clone(), crepe.id.clone(), 20.0).unwrap();
+        let report = commit_stocktake_inner(&db, first.id.clone()).unwrap();
+        assert_eq!(report.lines[0].expected, 0.0);
+        assert_eq!(report.lines[0].variance, 20.0);
+        assert!(report.stocktake.committed_at.is_some());
+        assert!(count_stocktake_product_inner(&db, first.id, crepe.id.clone(), 3.0).is_err());
+
+        make_order(&db, &[(&crepe, 5)], PaymentMethod::Cash);
+        let levels = get_stock_levels_inner(&db).unwrap();
+        assert_eq!(levels.len(), 1);
+        assert_eq!(levels[0].quantity, 15.0);
+
+        // Two crêpes went missing.
+        let second = start_stocktake_inner(&db).unwrap();
+        count_stocktake_product_inner(&db, second.id.clone(), crepe.id.clone(), 13.0).unwrap();
+        let preview = get_stocktake_report_inner(&db, second.id.clone()).unwrap();
+        assert_eq!(preview.lines[0].expected, 15.0);
+        let report = commit_stocktake_inner(&db, second.id).unwrap();
+        assert_eq!(report.lines[0].variance, -2.0);
+        assert_eq!(report.total_variance_value, -600);
+        assert_eq!(get_stock_levels_inner(&db).unwrap()[0].quantity, 13.0);
+    }
+
+    #[test]
+    fn record_waste_feeds_stock_and_report() {
+        let db = init_db_in_memory();
+        let batter = create_product_inner(
+            &db,
+            CreateProductPayload {
+                name: "Pâte à crêpes".to_string(),
+                price: 400,
+                category_id: "snack".to_string(),
+                age_restricted: false,
+                unit: QuantityUnit::Litre,
+                translations: Translations::new(),
+            },
+            None,
+        )
+        .unwrap();
+
+        assert!(record_waste_inner(&db, batter.id.clone(), 1.0, " ".to_string()).is_err());
+        assert!(record_waste_inner(&db, batter.id.clone(), 0.0, "Soured".to_string()).is_err());
+        let movement =
+            record_waste_inner(&db, batter.id.clone(), 1.5, " Soured ".to_string()).unwrap();
+        assert_eq!(movement.quantity, -1.5);
+        assert_eq!(movement.reason.as_deref(), Some("Soured"));
+        record_waste_inner(&db, batter.id.clone(), 0.25, "Soured".to_string()).unwrap();
+        record_waste_inner(&db, batter.id.clone(), 0.5, "Dropped".to_string()).unwrap();
+
+        assert_eq!(get_stock_levels_inner(&db).unwrap()[0].quantity, -2.25);
+        let report = get_waste_report_inner(&db, DateRange::default()).unwrap();
+        assert_eq!(report.len(), 2);
+        assert_eq!(report[0].reason, "Soured");
+        assert_eq!(report[0].quantity, 1.75);
+        assert_eq!(report[0].value, 700);
+        assert_eq!(report[1].reason, "Dropped");
+    }
+
+    #[test]
+    fn sales_consume_ingredients_following_recipes() {
+        let db = init_db_in_memory();
+        let crepe = make_product(&db, "Crêpe", 300, "snack");
+        let ingredient = |name: &str, unit, threshold| {
+            create_ingredient_inner(
+                &db,
+                CreateIngredientPayload {
+                    name: name.to_string(),
+                    unit,
+                    low_stock_threshold: threshold,
+                },
+            )
+            .unwrap()
+        };
+        let flour = ingredient("Flour", QuantityUnit::Kg, Some(0.5));
+        let eggs = ingredient("Eggs", QuantityUnit::Piece, None);
+
+        set_recipe_inner(
+            &db,
+            crepe.id.clone(),
+            vec![
+                RecipeItem {
+                    ingredient_id: flour.id.clone(),
+                    quantity: 0.05,
+                },
+                RecipeItem {
+                    ingredient_id: eggs.id.clone(),
+                    quantity: 0.5,
+                },
+            ],
+        )
+        .unwrap();
+        assert_eq!(get_recipe_inner(&db, crepe.id.clone()).unwrap().len(), 2);
+        assert!(delete_ingredient_inner(&db, eggs.id.clone()).is_err());
+
+        adjust_ingredient_stock_inner(&db, flour.id.clone(), 1.0, Some("Delivery".to_string()))
+            .unwrap();
+        adjust_ingredient_stock_inner(&db, eggs.id.clone(), 30.0, None).unwrap();
+        assert!(get_low_stock_ingredients_inner(&db).unwrap().is_empty());
+
+        make_order(&db, &[(&crepe, 12)], PaymentMethod::Cash);
+        let ingredients = list_ingredients_inner(&db).unwrap();
+        assert_eq!(ingredients[0].name, "Eggs");
+        assert_eq!(ingredients[0].stock, 24.0);
+        assert!(!ingredients[0].low_stock);
+        assert_eq!(ingredients[1].stock, 0.4);
+
+        let low = get_low_stock_ingredients_inner(&db).unwrap();
+        assert_eq!(low.len(), 1);
+        assert_eq!(low[0].id, flour.id);
+        assert!(low[0].low_stock);
+    }
+
+    #[test]
+    fn changing_a_recipe_does_not_move_past_consumption() {
+        let db = init_db_in_memory();
+        let crepe = make_product(&db, "Crêpe", 300, "snack");
+        let eggs = create_ingredient_inner(
+            &db,
+            CreateIngredientPayload {
+                name: "Eggs".to_string(),
+                unit: QuantityUnit::Piece,
+                low_stock_threshold: None,
+            },
+        )
+        .unwrap();
+        let recipe = |quantity| {
+            set_recipe_inner(
+                &db,
+                crepe.id.clone(),
+                vec![RecipeItem {
+                    ingredient_id: eggs.id.clone(),
+                    quantity,
+                }],
+            )
+            .unwrap()
+        };
+        let stock = || list_ingredients_inner(&db).unwrap()[0].stock;
+
+        recipe(0.5);
+        adjust_ingredient_stock_inner(&db, eggs.id.clone(), 30.0, None).unwrap();
+        make_order(&db, &[(&crepe, 4)], PaymentMethod::Cash);
+        assert_eq!(stock(), 28.0);
+
+        recipe(2.0);
+        assert_eq!(stock(), 28.0);
+        set_recipe_inner(&db, crepe.id.clone(), Vec::new()).unwrap();
+        assert_eq!(stock(), 28.0);
+
+        // A void gives back what the sale took, not what the recipe says now.
+        recipe(2.0);
+        make_order(&db, &[(&crepe, 1)], PaymentMethod::Cash);
+        assert_eq!(stock(), 26.0);
+        recipe(1.0);
+        undo_last_order_inner(&db, None).unwrap();
+        assert_eq!(stock(), 28.0);
+    }
+
+    #[test]
+    fn reorder_suggestions_cover_the_next_sales_days() {
+        let db = init_db_in_memory();
+        let cola = make_product(&db, "Cola", 150, "boisson-sans-alcool");
+        let crepe = make_product(&db, "Crêpe", 300, "snack");
+        let eggs = create_ingredient_inner(
+            &db,
+            CreateIngredientPayload {
+                name: "Eggs".to_string(),
+                unit: QuantityUnit::Piece,
+                low_stock_threshold: None,
+            },
+        )
+        .unwrap();
+        set_recipe_inner(
+            &db,
+            crepe.id.clone(),
+            vec![RecipeItem {
+                ingredient_id: eggs.id.clone(),
+                quantity: 0.5,
+            }],
+        )
+        .unwrap();
+
+        let stocktake = start_stocktake_inner(&db).unwrap();
+        count_stocktake_product_inner(&db, stocktake.id.clone(), cola.id.clone(), 30.0).unwrap();
+        count_stocktake_product_inner(&db, stocktake.id.clone(), crepe.id.clone(), 0.0).unwrap();
+        commit_stocktake_inner(&db, stocktake.id).unwrap();
+        adjust_ingredient_stock_inner(&db, eggs.id.clone(), 6.0, None).unwrap();
+        make_order(&db, &[(&cola, 20), (&crepe, 9)], PaymentMethod::Cash);
+
+        update_reorder_settings_inner(
+            &db,
+            ReorderSettings {
+                lookback_days: 7,
+                cover_days: 2,
+            },
+        )
+        .unwrap();
+        let suggestions = get_reorder_suggestions_inner(&db).unwrap();
+        // Crêpes are made from eggs, not bought.
+        assert_eq!(suggestions.len(), 2);
+        assert_eq!(suggestions[0].name, "Cola");
+        assert_eq!(suggestions[0].stock, 10.0);
+        assert_eq!(suggestions[0].suggested_quantity, 30.0);
+        assert!(suggestions[1].ingredient);
+        assert_eq!(suggestions[1].daily_usage, 4.5);
+        // 9 eggs for two days, 1.5 left: whole eggs only.
+        assert_eq!(suggestions[1].suggested_quantity, 8.0);
+    }
+
+    /// A `Store` keeping everything in memory, to test the command logic
+    /// without SQLite.
+    #[derive(Default)]
+    struct MemoryStore {
+        settings: std::cell::RefCell<std::collections::BTreeMap<String, String>>,
+        products: std::cell::RefCell<Vec<Product>>,
+    }
+
+    impl Store for MemoryStore {
+        fn get_setting(&self, key: &str) -> Result<Option<String>, String> {
+            Ok(self.settings.borrow().get(key).cloned())
+        }
+
+        fn set_settings(&self, values: &[(&str, String)]) -> Result<(), String> {
+            let mut settings = self.settings.borrow_mut();
+            for (key, value) in values {
+                settings.insert(key.to_string(), value.clone());
+            }
+            Ok(())
+        }
+
+        fn list_products(
+            &self,
+            catalog_id: Option<&str>,
+            register_id: Option<&str>,
+            filter: &ProductFilter,
+        ) -> Result<Vec<Product>, String> {
+            assert!(catalog_id.is_none(), "catalogs are not mocked");
+            assert!(register_id.is_none(), "registers are not mocked");
+            assert_eq!(filter, &ProductFilter::default(), "filters are not mocked");
+            Ok(self.products.borrow().clone())
+        }
+
+        fn get_product(&self, product_id: &str) -> Result<Option<Product>, String> {
+            Ok(self
+                .products
+                .borrow()
+                .iter()
+                .find(|p| p.id == product_id)
+                .cloned())
+        }
+
+        fn insert_product(&self, product: &Product) -> Result<(), String> {
+            self.products.borrow_mut().push(product.clone());
+            Ok(())
+        }
+
+        fn update_product(&self, product: &Product) -> Result<(), String> {
+            let mut products = self.products.borrow_mut();
+            let existing = products
+                .iter_mut()
+                .find(|p| p.id == product.id)
+                .ok_or_else(|| format!("Product not found: {}", product.id))?;
+            *existing = product.clone();
+            Ok(())
+        }
+
+        fn delete_product(&self, product_id: &str) -> Result<(), String> {
+            let mut products = self.products.borrow_mut();
+            let count = products.len();
+            products.retain(|p| p.id != product_id);
+            if products.len() == count {
+                return Err(format!("Product not found: {product_id}"));
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn product_commands_run_against_a_mock_store() {
+        let store = MemoryStore::default();
+        let product = create_product_inner(
+            &store,
+            CreateProductPayload {
+                name: "Cidre".to_string(),
+                price: 350,
+                category_id: "boisson-alcoolisee".to_string(),
+                age_restricted: true,
+                unit: QuantityUnit::Piece,
+                translations: Translations::from([("en".to_string(), " Cider ".to_string())]),
+            },
+            None,
+        )
+        .unwrap();
+        assert_eq!(product.translations["en"], "Cider");
+
+        let updated = update_product_inner(
+            &store,
+            UpdateProductPayload {
+                id: product.id.clone(),
+                name: "Cidre brut".to_string(),
+                price: 400,
+                category_id: product.category_id.clone(),
+                available: true,
+                age_restricted: None,
+                unit: None,
+                translations: None,
+            },
+            None,
+        )
+        .unwrap();
+        // Fields absent from the payload are left unchanged.
+        assert!(updated.age_restricted);
+        assert_eq!(updated.translations["en"], "Cider");
+
+        assert!(!toggle_product_availability_inner(&store, product.id.clone(), None).unwrap());
+        assert!(
+            !list_products_inner(&store, false, &ProductFilter::default()).unwrap()[0].available
+        );
+        delete_product_inner(&store, product.id.clone(), None).unwrap();
+        assert!(toggle_product_availability_inner(&store, product.id, None).is_err());
+    }
+
+    #[test]
+    fn settings_commands_run_against_a_mock_store() {
+        let store = MemoryStore::default();
+        assert_eq!(
+            get_reorder_settings_inner(&store).unwrap(),
+            ReorderSettings::default()
+        );
+
+        let invalid = update_reorder_settings_inner(
+            &store,
+            ReorderSettings {
+                lookback_days: 0,
+                cover_days: 1,
+            },
+        );
+        assert!(invalid.is_err());
+        assert!(store.settings.borrow().is_empty());
+
+        update_business_settings_inner(
+            &store,
+            BusinessSettings {
+                name: " Buvette du club ".to_string(),
+                ..BusinessSettings::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(
+            store
+                .settings
+                .borrow()
+                .get("business.name")
+                .map(String::as_str),
+            Some("Buvette du club")
+        );
+        assert_eq!(
+            get_business_settings_inner(&store).unwrap().name,
+            "Buvette du club"
+        );
+    }
+
+    #[test]
+    fn migrate_refuses_databases_from_a_newer_build() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        crate::db::migrate(&mut conn, None).unwrap();
+        // Migrating an up-to-date database is a no-op.
+        assert!(crate::db::migrate(&mut conn, None)
+            .unwrap()
+            .migrations
+            .is_empty());
+
+        let supported = crate::db::migrations().len();
+        conn.pragma_update(None, "user_version", supported as i64 + 1)
+            .unwrap();
+        match crate::db::migrate(&mut conn, None) {
+            Err(crate::db::DbError::NewerSchema {
+                version,
+                supported: max,
+            }) => {
+                assert_eq!(version, supported + 1);
+                assert_eq!(max, supported);
+            }
+            other => panic!("expected a NewerSchema error, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn migrate_backs_up_outdated_databases() {
+        let path = temp_path("db");
+        let backup = temp_path("db");
+        let mut conn = Connection::open(&path).unwrap();
+        rusqlite_migration::Migrations::new(crate::db::migrations().into_iter().take(3).collect())
+            .to_latest(&mut conn)
+            .unwrap();
+
+        let plan = check_database_migrations_inner(path.clone()).unwrap();
+        assert_eq!(plan.from_version, 3);
+        assert_eq!(plan.migrations[0], "3-age-restriction");
+        assert_eq!(plan.to_version, 3 + plan.migrations.len());
+
+        let report = crate::db::migrate(&mut conn, Some(std::path::Path::new(&backup))).unwrap();
+        assert_eq!(report.backup_path.as_deref(), Some(backup.as_str()));
+        let backed_up: i64 = Connection::open(&backup)
+            .unwrap()
+            .pragma_query_value(None, "user_version", |row| row.get(0))
+            .unwrap();
+        assert_eq!(backed_up, 3);
+        assert!(check_database_migrations_inner(path.clone())
+            .unwrap()
+            .migrations
+            .is_empty());
+
+        drop(conn);
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(&backup).unwrap();
+    }
+
+    #[test]
+    fn reports_read_through_a_separate_connection() {
+        let path = temp_path("db");
+        let mut conn = Connection::open(&path).unwrap();
+        conn.execute_batch("PRAGMA journal_mode=WAL;").unwrap();
+        let migration = crate::db::migrate(&mut conn, None).unwrap();
+        crate::db::create_default_data(&conn).unwrap();
+        let db = DbState {
+            conn: std::sync::Mutex::new(conn),
+            reader: Some(std::sync::Mutex::new(
+                crate::db::open_reader(&path).unwrap(),
+            )),
+            db_path: path.clone(),
+            migration,
+        };
+
+        let bar = make_product(&db, "Bar", 300, "snack");
+        make_order(&db, &[(&bar, 2)], PaymentMethod::Cash);
+        // Reports still work while the write connection is held.
+        let writer = db.conn.lock().unwrap();
+        let summary =
+            get_dashboard_summary_inner(&db, ProductSummaryQuery::default(), None).unwrap();
+        assert_eq!(summary.total_revenue, 600);
+        drop(writer);
+
+        assert!(db
+            .read_conn()
+            .unwrap()
+            .execute("DELETE FROM orders", [])
+            .is_err());
+
+        drop(db);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{path}{suffix}"));
+        }
+    }
+
+    #[test]
+    fn create_order_inserts_items_in_chunks() {
+        let db = init_db_in_memory();
+        let products: Vec<Product> = (0..250)
+            .map(|i| make_product(&db, &format!("Item {i}"), 100 + i, "snack"))
+            .collect();
+        let lines: Vec<(&Product, i64)> = products.iter().map(|p| (p, 2)).collect();
+
+        let order = make_order(&db, &lines, PaymentMethod::Card);
+
+        let stored = load_order(&db.conn.lock().unwrap(), &order.order.id).unwrap();
+        assert_eq!(stored.items.len(), 250);
+        assert_eq!(stored.order.total, order.order.total);
+        let mut names: Vec<&str> = stored
+            .items
+            .iter()
+            .map(|i| i.product_name.as_str())
+            .collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), 250);
+    }
+
+    #[test]
+    fn list_products_filters_and_paginates() {
+        let db = init_db_in_memory();
+        let cola = make_product(&db, "Cola Zero", 250, "boisson-sans-alcool");
+        make_product(&db, "Cola", 250, "boisson-sans-alcool");
+        let crisps = make_product(&db, "Crisps", 150, "snack");
+        toggle_product_availability_inner(&db, cola.id.clone(), None).unwrap();
+        let all = list_products_inner(&db, false, &ProductFilter::default()).unwrap();
+
+        let names = |filter: ProductFilter| -> Vec<String> {
+            list_products_inner(&db, false, &filter)
+                .unwrap()
+                .into_iter()
+                .map(|p| p.name)
+                .collect()
+        };
+        let search = |search: &str| ProductFilter {
+            search: Some(search.to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(names(search("  cOLA ")), ["Cola", "Cola Zero"]);
+        assert_eq!(
+            names(ProductFilter {
+                available: Some(false),
+                ..search("cola")
+            }),
+            ["Cola Zero"]
+        );
+        let snacks = names(ProductFilter {
+            category_id: Some("snack".to_string()),
+            ..Default::default()
+        });
+        assert!(snacks.contains(&crisps.name));
+        assert!(all
+            .iter()
+            .filter(|p| p.category_id == "snack")
+            .all(|p| snacks.contains(&p.name)));
+
+        let page = |offset| {
+            names(ProductFilter {
+                limit: Some(2),
+                offset: Some(offset),
+                ..Default::default()
+            })
+        };
+        assert_eq!(page(0), [all[0].name.clone(), all[1].name.clone()]);
+        assert_eq!(page(2), [all[2].name.clone(), all[3].name.clone()]);
+        assert!(page(all.len() as u32).is_empty());
+    }
+
+    #[test]
+    fn export_all_data_writes_a_complete_dump() {
+        let db = init_db_in_memory();
+        let product = make_product(&db, "Bar", 300, "snack");
+        let order = make_order(&db, &[(&product, 2)], PaymentMethod::Cash);
+        {
+            let conn = db.conn.lock().unwrap();
+            crate::db::set_setting(&conn, SMTP_HOST_KEY, "smtp.example.com").unwrap();
+            crate::db::set_setting(&conn, SMTP_PASSWORD_KEY, "secret").unwrap();
+        }
+        let path = temp_path("json");
+
+        export_all_data_inner(&db, path.clone()).unwrap();
+
+        let dump: DataDump =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(dump.format, "pos-dump");
+        assert_eq!(dump.schema_version, crate::db::migrations().len() as i64);
+        assert_eq!(
+            dump.categories.len(),
+            list_categories_inner(&db).unwrap().len()
+        );
+        assert!(dump.products.contains(&product));
+        assert_eq!(dump.orders.len(), 1);
+        assert_eq!(dump.orders[0].order, order);
+        assert_eq!(dump.orders[0].previous_hash.as_deref(), Some(GENESIS_HASH));
+        assert_eq!(dump.settings[SMTP_HOST_KEY], "smtp.example.com");
+        assert!(!dump.settings.contains_key(SMTP_PASSWORD_KEY));
+    }
+
+    #[test]
+    fn import_all_data_restores_a_dump_into_an_empty_database() {
+        let source = init_db_in_memory();
+        let product = make_product(&source, "Bar", 300, "snack");
+        let order = make_order(&source, &[(&product, 2)], PaymentMethod::Cash);
+        let path = temp_path("json");
+        export_all_data_inner(&source, path.clone()).unwrap();
+
+        let target = init_db_in_memory();
+        let report =
+            import_all_data_inner(&target, path.clone(), ImportStrategy::Skip, None).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(report.products.inserted, 1);
+        assert_eq!(report.orders.inserted, 1);
+        assert!(report.categories.skipped > 0);
+        assert!(report.warnings.is_empty());
+        assert_eq!(
+            list_orders_inner(&target, None).unwrap(),
+            list_orders_inner(&source, None).unwrap()
+        );
+        assert!(verify_order_chain_inner(&target).unwrap().valid);
+        assert_eq!(list_orders_inner(&target, None).unwrap()[0], order);
+    }
+
+    #[test]
+    fn import_all_data_keeps_the_order_chain_of_a_non_empty_database() {
+        let source = init_db_in_memory();
+        let product = make_product(&source, "Bar", 300, "snack");
+        make_order(&source, &[(&product, 2)], PaymentMethod::Cash);
+        crate::db::set_setting(
+            &source.conn.lock().unwrap(),
+            CHAIN_ANCHOR_KEY,
+            "source anchor",
+        )
+        .unwrap();
+        let path = temp_path("json");
+        export_all_data_inner(&source, path.clone()).unwrap();
+        let dump: DataDump =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert!(!dump.settings.contains_key(CHAIN_ANCHOR_KEY));
+
+        let target = init_db_in_memory();
+        let local_product = make_product(&target, "Cola", 150, "boisson-sans-alcool");
+        let local = make_order(&target, &[(&local_product, 1)], PaymentMethod::Card);
+        let report =
+            import_all_data_inner(&target, path.clone(), ImportStrategy::Merge, None).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(report.orders.inserted, 0);
+        assert_eq!(report.orders.skipped, 1);
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(list_orders_inner(&target, None).unwrap(), vec![local]);
+        assert!(verify_order_chain_inner(&target).unwrap().valid);
+        let anchor =
+            crate::db::get_setting(&target.conn.lock().unwrap(), CHAIN_ANCHOR_KEY).unwrap();
+        assert_eq!(anchor, None);
+    }
+
+    #[test]
+    fn import_all_data_strategies() {
+        let db = init_db_in_memory();
+        let product = make_product(&db, "Bar", 300, "snack");
+        let path = temp_path("json");
+        export_all_data_inner(&db, path.clone()).unwrap();
+        let dumped = list_products_inner(&db, false, &ProductFilter::default())
+            .unwrap()
+            .len() as u32;
+
+        let mut renamed = product.clone();
+        renamed.name = "Renamed".to_string();
+        db.update_product(&renamed).unwrap();
+        let extra = make_product(&db, "Extra", 100, "snack");
+
+        let report = import_all_data_inner(&db, path.clone(), ImportStrategy::Skip, None).unwrap();
+        assert_eq!(report.products.inserted, 0);
+        assert_eq!(
+            db.get_product(&product.id).unwrap().unwrap().name,
+            "Renamed"
+        );
+
+        let report = import_all_data_inner(&db, path.clone(), ImportStrategy::Merge, None).unwrap();
+        assert_eq!(report.products.updated, dumped);
+        assert_eq!(db.get_product(&product.id).unwrap().unwrap().name, "Bar");
+        assert!(db.get_product(&extra.id).unwrap().is_some());
+
+        let report =
+            import_all_data_inner(&db, path.clone(), ImportStrategy::Overwrite, None).unwrap();
+        assert_eq!(report.products.deleted, 1);
+        assert!(db.get_product(&extra.id).unwrap().is_none());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn today_stats_only_count_todays_sales() {
+        let db = init_db_in_memory();
+        let product = make_product(&db, "Bar", 300, "snack");
+        let old = make_order(&db, &[(&product, 5)], PaymentMethod::Cash);
+        set_order_created_at(&db, &old.order.id, "2020-01-01T12:00:00Z");
+        make_order(&db, &[(&product, 1)], PaymentMethod::Cash);
+        make_order(&db, &[(&product, 2)], PaymentMethod::Card);
+        undo_last_order_inner(&db, None).unwrap();
+        let last_kept = make_order(&db, &[(&product, 3)], PaymentMethod::Card);
+
+        let stats = get_today_stats_inner(&db).unwrap();
+        assert_eq!(stats.order_count, 2);
+        assert_eq!(stats.total_revenue, 1200);
+        assert_eq!(stats.cash_revenue, 300);
+        assert_eq!(stats.card_revenue, 900);
+        assert_eq!(stats.last_order_at, Some(last_kept.order.created_at));
+    }
+
+    #[test]
+    fn admin_pin_guards_destructive_commands() {
+        let db = init_db_in_memory();
+        reset_database_inner(&db, None).unwrap();
+
+        set_admin_pin_inner(&db, None, Some("12a4".to_string())).unwrap_err();
+        set_admin_pin_inner(&db, None, Some("1234".to_string())).unwrap();
+        assert!(has_admin_pin_inner(&db).unwrap());
+        assert_eq!(
+            reset_database_inner(&db, None).unwrap_err(),
+            "Admin PIN required"
+        );
+        set_admin_pin_inner(&db, None, Some("5678".to_string())).unwrap_err();
+        reset_database_inner(&db, Some("1234".to_string())).unwrap();
+    }
+
+    #[test]
+    fn admin_pin_locks_after_repeated_failures() {
+        let db = init_db_in_memory();
+        set_admin_pin_inner(&db, None, Some("1234".to_string())).unwrap();
+
+        for _ in 0..MAX_ADMIN_PIN_ATTEMPTS {
+            assert_eq!(
+                verify_admin_pin(&db, Some("0000")).unwrap_err(),
+                "Wrong admin PIN"
+            );
+        }
+        let err = verify_admin_pin(&db, Some("1234")).unwrap_err();
+        assert!(err.starts_with("Too many wrong PINs"), "{err}");
+
+        db.set_settings(&[(ADMIN_PIN_LOCKED_UNTIL_KEY, "0".to_string())])
+            .unwrap();
+        verify_admin_pin(&db, Some("1234")).unwrap();
+        set_admin_pin_inner(&db, Some("1234".to_string()), None).unwrap();
+        assert!(!has_admin_pin_inner(&db).unwrap());
+    }
+
+    #[test]
+    fn tag_and_filter_orders() {
+        let db = init_db_in_memory();
+        let product = make_product(&db, "Bar", 300, "snack");
+        let vip = make_order(&db, &[(&product, 2)], PaymentMethod::Cash);
+        make_order(&db, &[(&product, 1)], PaymentMethod::Card);
+
+        let id = vip.order.id.clone();
+        tag_order_inner(&db, id.clone(), " VIP ".to_string()).unwrap();
+        tag_order_inner(&db, id.clone(), "tournoi".to_string()).unwrap();
+        let tagged = tag_order_inner(&db, id.clone(), "VIP".to_string()).unwrap();
+        assert_eq!(tagged.order.tags, ["VIP", "tournoi"]);
+        assert!(tag_order_inner(&db, id.clone(), "  ".to_string()).is_err());
+        assert!(tag_order_inner(&db, "missing".to_string(), "VIP".to_string()).is_err());
+        assert!(verify_order_chain_inner(&db).unwrap().valid);
+
+        let listed = list_orders_inner(&db, Some("VIP".to_string())).unwrap();
+        assert_eq!(listed, [tagged.clone()]);
+        assert_eq!(list_orders_inner(&db, None).unwrap().len(), 2);
+        assert_eq!(list_order_tags_inner(&db).unwrap(), ["VIP", "tournoi"]);
+
+        let sales = get_sales_by_tag_inner(&db, DateRange::default()).unwrap();
+        assert_eq!(sales.len(), 2);
+        assert_eq!(sales[0].total_revenue, 600);
+        assert_eq!(sales[0].order_count, 1);
+
+        let untagged = untag_order_inner(&db, id, "VIP".to_string()).unwrap();
+        assert_eq!(untagged.order.tags, ["tournoi"]);
+        assert!(list_orders_inner(&db, Some("VIP".to_string()))
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn resolve_date_range_modes() {
+        // A Wednesday.
+        let today = chrono::NaiveDate::from_ymd_opt(2025, 3, 5).unwrap();
+        let range = |mode: DateRangeMode| {
+            let range = resolve_date_range(&mode, today);
+            (range.from.unwrap_or_default(), range.to.unwrap_or_default())
+        };
+        let dates = |from: &str, to: &str| (from.to_string(), to.to_string());
+
+        assert_eq!(
+            range(DateRangeMode::Today),
+            dates("2025-03-05", "2025-03-05")
+        );
+        assert_eq!(
+            range(DateRangeMode::Yesterday),
+            dates("2025-03-04", "2025-03-04")
+        );
+        assert_eq!(
+            range(DateRangeMode::LastDays { days: 7 }),
+            dates("2025-02-27", "2025-03-05")
+        );
+        assert_eq!(
+            range(DateRangeMode::ThisWeek),
+            dates("2025-03-03", "2025-03-05")
+        );
+        assert_eq!(
+            range(DateRangeMode::LastWeek),
+            dates("2025-02-24", "2025-03-02")
+        );
+        assert_eq!(
+            range(DateRangeMode::ThisMonth),
+            dates("2025-03-01", "2025-03-05")
+        );
+        assert_eq!(
+            range(DateRangeMode::LastMonth),
+            dates("2025-02-01", "2025-02-28")
+        );
+    }
+
+    #[test]
+    fn report_preset_crud_and_run() {
+        let db = init_db_in_memory();
+        let product = make_product(&db, "Bar", 300, "snack");
+        make_order(&db, &[(&product, 2)], PaymentMethod::Cash);
+
+        let config = ReportPresetConfig {
+            report: ReportKind::Dashboard,
+            range: DateRangeMode::LastDays { days: 0 },
+            format: None,
+            products: ProductSummaryQuery::default(),
+        };
+        assert!(create_report_preset_inner(
+            &db,
+            CreateReportPresetPayload {
+                name: "Weekly".to_string(),
+                config: config.clone(),
+            },
+        )
+        .is_err());
+
+        let preset = create_report_preset_inner(
+            &db,
+            CreateReportPresetPayload {
+                name: " Weekly ".to_string(),
+                config: ReportPresetConfig {
+                    range: DateRangeMode::ThisWeek,
+                    ..config
+                },
+            },
+        )
+        .unwrap();
+        assert_eq!(preset.name, "Weekly");
+        assert_eq!(list_report_presets_inner(&db).unwrap(), [preset.clone()]);
+
+        let path = temp_path("csv");
+        let range = run_report_preset_inner(&db, preset.id.clone(), path.clone()).unwrap();
+        assert_eq!(
+            range.to,
+            Some(chrono::Local::now().format("%Y-%m-%d").to_string())
+        );
+        assert!(std::fs::read_to_string(&path).unwrap().contains("Bar"));
+        std::fs::remove_file(&path).unwrap();
+
+        let updated = update_report_preset_inner(
+            &db,
+            UpdateReportPresetPayload {
+                id: preset.id.clone(),
+                name: "Treasurer".to_string(),
+                config: ReportPresetConfig {
+                    report: ReportKind::Fec,
+                    ..preset.config.clone()
+                },
+            },
+        )
+        .unwrap();
+        assert_eq!(updated.config.report, ReportKind::Fec);
+
+        delete_report_preset_inner(&db, preset.id.clone()).unwrap();
+        assert!(run_report_preset_inner(&db, preset.id, temp_path("csv")).is_err());
+    }
+
+    #[test]
+    fn custom_reports_run_with_checked_parameters() {
+        let db = init_db_in_memory();
+        let product = make_product(&db, "Bar", 300, "snack");
+        for _ in 0..3 {
+            make_order(&db, &[(&product, 1)], PaymentMethod::Card);
+        }
+        let path = temp_path("csv");
+        let params = |pairs: &[(&str, &str)]| -> std::collections::HashMap<String, String> {
+            pairs
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect()
+        };
+
+        for report in list_custom_reports_inner() {
+            let required: Vec<(&str, &str)> = report
+                .params
+                .iter()
+                .filter(|p| p.required)
+                .map(|p| match p.kind {
+                    CustomReportParamKind::Integer => (p.name.as_str(), "5"),
+                    _ => (p.name.as_str(), "x"),
+                })
+                .collect();
+            run_custom_report_inner(&db, report.id.clone(), params(&required), path.clone())
+                .unwrap_or_else(|e| panic!("{}: {e}", report.id));
+        }
+
+        run_custom_report_inner(
+            &db,
+            "top_products".to_string(),
+            params(&[("limit", "1")]),
+            path.clone(),
+        )
+        .unwrap();
+        let csv = std::fs::read_to_string(&path).unwrap();
+        assert!(csv.contains("\"Bar\";\"3\";\"9.00\";\"3\""), "{csv}");
+        std::fs::remove_file(&path).unwrap();
+
+        let run = |id: &str, pairs: &[(&str, &str)]| {
+            run_custom_report_inner(&db, id.to_string(), params(pairs), path.clone()).unwrap_err()
+        };
+        assert_eq!(run("top_products", &[]), "Missing parameter: limit");
+        assert!(
+            run("top_products", &[("limit", "1; DROP TABLE orders")]).starts_with("Invalid number")
+        );
+        assert!(
+            run("top_products", &[("limit", "1"), ("from", "yesterday")])
+                .starts_with("Invalid date")
+        );
+        assert!(
+            run("top_products", &[("limit", "1"), ("sql", "1")]).starts_with("Unknown parameter")
+        );
+        assert!(run("missing", &[]).starts_with("Unknown report"));
+    }
+
+    #[test]
+    fn scheduler_runs_due_jobs_once_a_day() {
+        use chrono::TimeZone;
+
+        let db = init_db_in_memory();
+        let at = |day: u32, hour: u32| {
+            chrono::Local
+                .with_ymd_and_hms(2026, 3, day, hour, 0, 0)
+                .single()
+                .unwrap()
+        };
+        let dir = std::env::temp_dir().join(format!("pos-test-{}", Uuid::new_v4()));
+
+        for kind in [ScheduledJobKind::Backup, ScheduledJobKind::StockSnapshot] {
+            update_schedule_inner(
+                &db,
+                UpdateSchedulePayload {
+                    kind,
+                    enabled: true,
+                    time: "22:00".to_string(),
+                    recipients: Vec::new(),
+                },
+            )
+            .unwrap();
+        }
+        let err = update_schedule_inner(
+            &db,
+            UpdateSchedulePayload {
+                kind: ScheduledJobKind::ReportEmail,
+                enabled: true,
+                time: "22:00".to_string(),
+                recipients: vec![" ".to_string()],
+            },
+        )
+        .unwrap_err();
+        assert!(err.contains("recipient"), "{err}");
+
+        let run = |now| crate::scheduler::run_due_jobs(&db, now, &dir).unwrap();
+        assert!(run(at(2, 21)).is_empty());
+        assert_eq!(
+            run(at(2, 22)),
+            vec![ScheduledJobKind::Backup, ScheduledJobKind::StockSnapshot]
+        );
+        assert!(run(at(2, 23)).is_empty());
+        assert_eq!(run(at(3, 22)).len(), 2);
+
+        assert_eq!(std::fs::read_dir(dir.join("backups")).unwrap().count(), 2);
+        assert_eq!(std::fs::read_dir(dir.join("stock")).unwrap().count(), 2);
+
+        // Only the last backups and snapshots are kept.
+        let kept = crate::scheduler::KEPT_SCHEDULED_FILES;
+        for day in 4..=(kept as u32 + 4) {
+            run(at(day, 22));
+        }
+        assert_eq!(
+            std::fs::read_dir(dir.join("backups")).unwrap().count(),
+            kept
+        );
+        assert_eq!(std::fs::read_dir(dir.join("stock")).unwrap().count(), kept);
+        assert!(dir.join("backups").join("pos-20260305-220000.db").exists());
+        assert!(!dir.join("backups").join("pos-20260304-220000.db").exists());
+
+        let jobs = list_scheduled_jobs_inner(&db).unwrap();
+        assert_eq!(jobs.len(), 5);
+        let backup = &jobs[0];
+        assert!(backup.last_run_at.is_some());
+        assert_eq!(backup.last_error, None);
+        assert!(!jobs[1].enabled);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn scheduler_errors_are_kept_until_a_tick_succeeds() {
+        let db = init_db_in_memory();
+        assert_eq!(get_scheduler_error_inner(&db).unwrap(), None);
+        record_scheduler_error(&db, Some("DB lock error")).unwrap();
+        assert_eq!(
+            get_scheduler_error_inner(&db).unwrap().as_deref(),
+            Some("DB lock error")
+        );
+        record_scheduler_error(&db, None).unwrap();
+        assert_eq!(get_scheduler_error_inner(&db).unwrap(), None);
+    }
+
+    #[test]
+    fn purge_old_data_archives_and_keeps_chain_and_stock() {
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Foo", 100, "snack");
+        record_waste_inner(&db, p.id.clone(), 1.0, "dropped".to_string()).unwrap();
+
+        let old = [
+            make_order(&db, &[(&p, 2)], PaymentMethod::Cash),
+            make_order(&db, &[(&p, 2)], PaymentMethod::Card),
+        ];
+        set_order_created_at(&db, &old[0].order.id, "2020-01-01T12:00:00Z");
+        set_order_created_at(&db, &old[1].order.id, "2020-01-02T12:00:00Z");
+        make_order(&db, &[(&p, 2)], PaymentMethod::Cash);
+        {
+            // Re-hash the chain over the backdated orders.
+            let conn = db.conn.lock().unwrap();
+            let mut stmt = conn
+                .prepare("SELECT id FROM orders ORDER BY rowid")
+                .unwrap();
+            let ids: Vec<String> = stmt
+                .query_map([], |row| row.get(0))
+                .unwrap()
+                .collect::<Result<_, _>>()
+                .unwrap();
+            let mut previous = GENESIS_HASH.to_string();
+            for id in ids {
+                let OrderWithItems { order, items } = load_order(&conn, &id).unwrap();
+                let hash = order_hash(&previous, &order, &items);
+                conn.execute(
+                    "UPDATE orders SET previous_hash = ?1, hash = ?2 WHERE id = ?3",
+                    params![previous, hash, id],
+                )
+                .unwrap();
+                previous = hash;
+            }
+        }
+        assert!(verify_order_chain_inner(&db).unwrap().valid);
+        let stock = || get_stock_levels_inner(&db).unwrap()[0].quantity;
+        assert_eq!(stock(), -7.0);
+
+        let archive = temp_path("json");
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        let err = purge_old_data_inner(&db, today, archive.clone(), None).unwrap_err();
+        assert!(err.contains("kept for 365 days"), "{err}");
+
+        let report =
+            purge_old_data_inner(&db, "2021-01-01".to_string(), archive.clone(), None).unwrap();
+        assert_eq!(report.deleted_orders, 2);
+        assert_eq!(report.kept_from_order_id, None);
+        let dump: DataDump =
+            serde_json::from_str(&std::fs::read_to_string(&archive).unwrap()).unwrap();
+        assert_eq!(dump.orders.len(), 3);
+        std::fs::remove_file(&archive).unwrap();
+
+        assert_eq!(list_orders_inner(&db, None).unwrap().len(), 1);
+        assert_eq!(stock(), -7.0);
+        make_order(&db, &[(&p, 1)], PaymentMethod::Cash);
+        let verification = verify_order_chain_inner(&db).unwrap();
+        assert!(verification.valid);
+        assert_eq!(verification.checked_orders, 2);
+    }
+
+    #[test]
+    fn storage_status_reports_database_and_wal_sizes() {
+        let memory = crate::storage::status(":memory:");
+        assert_eq!(memory.database_bytes, 0);
+        assert_eq!(memory.free_disk_bytes, None);
+
+        let path = temp_path("db");
+        std::fs::write(&path, vec![0u8; 4096]).unwrap();
+        std::fs::write(format!("{path}-wal"), vec![0u8; 100]).unwrap();
+        let status = crate::storage::status(&path);
+        assert_eq!(status.database_bytes, 4096);
+        assert_eq!(status.wal_bytes, 100);
+        assert!(status.free_disk_bytes.is_some());
+        std::fs::remove_file(&path).unwrap();
+        std::fs::remove_file(format!("{path}-wal")).unwrap();
+    }
+
+    #[test]
+    fn settings_export_configures_another_till() {
+        let source = init_db_in_memory();
+        update_smtp_settings_inner(
+            &source,
+            SmtpSettings {
+                host: "smtp.example.org".to_string(),
+                password: "secret".to_string(),
+                ..SmtpSettings::default()
+            },
+        )
+        .unwrap();
+        create_report_preset_inner(
+            &source,
+            CreateReportPresetPayload {
+                name: "Weekly".to_string(),
+                config: ReportPresetConfig {
+                    report: ReportKind::Fec,
+                    range: DateRangeMode::LastWeek,
+                    format: None,
+                    products: ProductSummaryQuery::default(),
+                },
+            },
+        )
+        .unwrap();
+        update_schedule_inner(
+            &source,
+            UpdateSchedulePayload {
+                kind: ScheduledJobKind::Backup,
+                enabled: true,
+                time: "04:30".to_string(),
+                recipients: Vec::new(),
+            },
+        )
+        .unwrap();
+        set_surcharge_rule_inner(
+            &source,
+            SurchargeRule {
+                payment_method: PaymentMethod::Card,
+                amount: 50,
+                below: Some(1000),
+            },
+        )
+        .unwrap();
+        set_station_route_inner(
+            &source,
+            StationRoute {
+                category_id: "alcool".to_string(),
+                station: "Bar".to_string(),
+            },
+        )
+        .unwrap();
+        update_palette_inner(
+            &source,
+            vec![
+                PaletteColor {
+                    color: "#3b82f6".to_string(),
+                    name: "Blue".to_string(),
+                },
+                PaletteColor {
+                    color: "#8b5cf6".to_string(),
+                    name: "Violet".to_string(),
+                },
+            ],
+        )
+        .unwrap();
+        {
+            let conn = source.conn.lock().unwrap();
+            crate::db::set_setting(&conn, ACTIVE_CATALOG_KEY, "summer").unwrap();
+            crate::db::set_setting(&conn, CURRENT_REGISTER_KEY, "bar").unwrap();
+        }
+
+        let path = temp_path("json");
+        export_settings_inner(&source, path.clone()).unwrap();
+        let target = init_db_in_memory();
+        import_settings_inner(&target, path.clone(), None).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let smtp = get_smtp_settings_inner(&target).unwrap();
+        assert_eq!(smtp.host, "smtp.example.org");
+        assert_eq!(smtp.password, "");
+        let presets = list_report_presets_inner(&target).unwrap();
+        assert_eq!(presets.len(), 1);
+        assert_eq!(presets[0].config.range, DateRangeMode::LastWeek);
+        let backup = &list_scheduled_jobs_inner(&target).unwrap()[0];
+        assert!(backup.enabled);
+        assert_eq!(backup.time, "04:30");
+        assert_eq!(
+            list_surcharge_rules_inner(&target).unwrap(),
+            list_surcharge_rules_inner(&source).unwrap()
+        );
+        assert_eq!(
+            list_station_routes_inner(&target).unwrap(),
+            list_station_routes_inner(&source).unwrap()
+        );
+        assert_eq!(
+            list_palette_inner(&target).unwrap(),
+            list_palette_inner(&source).unwrap()
+        );
+        // Catalogs and registers are not exported, nor is the choice of one.
+        assert_eq!(target.get_setting(ACTIVE_CATALOG_KEY).unwrap(), None);
+        assert_eq!(target.get_setting(CURRENT_REGISTER_KEY).unwrap(), None);
+    }
+
+    #[test]
+    fn disabled_features_reject_their_commands() {
+        let db = init_db_in_memory();
+        assert!(get_features_inner(&db).unwrap().iter().all(|f| f.enabled));
+        assert!(get_stock_levels_inner(&db).is_ok());
+
+        let flags = set_feature_inner(&db, Feature::Stock, false).unwrap();
+        assert_eq!(
+            flags,
+            [
+                FeatureFlag {
+                    feature: Feature::Stock,
+                    enabled: false
+                },
+                FeatureFlag {
+                    feature: Feature::Printing,
+                    enabled: true
+                },
+            ]
+        );
+        assert_eq!(
+            get_stock_levels_inner(&db).unwrap_err(),
+            "The stock feature is disabled"
+        );
+        assert!(start_stocktake_inner(&db).is_err());
+
+        set_feature_inner(&db, Feature::Stock, true).unwrap();
+        assert!(start_stocktake_inner(&db).is_ok());
+    }
+
+    #[test]
+    fn usage_metrics_are_only_counted_once_opted_in() {
+        let db = init_db_in_memory();
+        record_usage_inner(&db, "create_order").unwrap();
+        assert!(get_usage_report_inner(&db).unwrap().commands.is_empty());
+
+        update_telemetry_settings_inner(
+            &db,
+            TelemetrySettings {
+                enabled: true,
+                endpoint: "https://metrics.example.org".to_string(),
+            },
+        )
+        .unwrap();
+        record_usage_inner(&db, "create_order").unwrap();
+        record_usage_inner(&db, "create_order").unwrap();
+        let report = get_usage_report_inner(&db).unwrap();
+        assert_eq!(report.commands["create_order"], 2);
+        assert_eq!(report.catalog_size, "0-25");
+
+        record_usage_inner(&db, "create_order").unwrap();
+        clear_reported_usage_inner(&db, &report).unwrap();
+        assert_eq!(
+            get_usage_report_inner(&db).unwrap().commands["create_order"],
+            1
+        );
+
+        update_telemetry_settings_inner(&db, TelemetrySettings::default()).unwrap();
+        assert!(get_usage_report_inner(&db).unwrap().commands.is_empty());
+    }
+
+    #[test]
+    fn dashboard_converts_total_to_display_currency() {
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Foo", 350, "snack");
+        make_order(&db, &[(&p, 1)], PaymentMethod::Cash);
+        assert_eq!(
+            get_dashboard_summary_inner(&db, ProductSummaryQuery::default(), None)
+                .unwrap()
+                .converted_total,
+            None
+        );
+
+        assert!(update_display_currency_inner(
+            &db,
+            DisplayCurrencySettings {
+                code: "swiss".to_string(),
+                rate: 0.95,
+            },
+        )
+        .is_err());
+        let settings = update_display_currency_inner(
+            &db,
+            DisplayCurrencySettings {
+                code: " chf ".to_string(),
+                rate: 0.95,
+            },
+        )
+        .unwrap();
+        assert_eq!(settings.code, "CHF");
+
+        let converted = get_dashboard_summary_inner(&db, ProductSummaryQuery::default(), None)
+            .unwrap()
+            .converted_total
+            .unwrap();
+        assert_eq!(converted.currency, "CHF");
+        assert_eq!(converted.amount, 333);
+    }
+
+    #[test]
+    fn create_order_applies_rounding_policy() {
+        let db = init_db_in_memory();
+        let a = make_product(&db, "A", 333, "snack");
+        let b = make_product(&db, "B", 111, "snack");
+
+        let order = make_order(&db, &[(&a, 1), (&b, 1)], PaymentMethod::Cash);
+        assert_eq!(order.order.total, 444);
+
+        let per_line = RoundingSettings {
+            mode: RoundingMode::PerLine,
+            increment: 5,
+        };
+        update_rounding_settings_inner(&db, per_line).unwrap();
+        let order = make_order(&db, &[(&a, 1), (&b, 1)], PaymentMethod::Cash);
+        assert_eq!(
+            order.items.iter().map(|i| i.total).collect::<Vec<_>>(),
+            [335, 110]
+        );
+        assert_eq!(order.order.total, 445);
+
+        let order_total = RoundingSettings {
+            mode: RoundingMode::OrderTotal,
+            increment: 10,
+        };
+        update_rounding_settings_inner(&db, order_total).unwrap();
+        let order = make_order(&db, &[(&a, 1), (&b, 1)], PaymentMethod::Cash);
+        assert_eq!(order.order.total, 440);
+        let rounding = order.items.last().unwrap();
+        assert_eq!(rounding.product_id, ROUNDING_PRODUCT_ID);
+        assert_eq!(rounding.total, -4);
+        assert_eq!(order.items.iter().map(|i| i.total).sum::<i64>(), 440);
+        assert!(verify_order_chain_inner(&db).unwrap().valid);
+
+        assert!(update_rounding_settings_inner(
+            &db,
+            RoundingSettings {
+                mode: RoundingMode::PerLine,
+                increment: 0,
+            },
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn card_surcharge_is_itemized_and_reported() {
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Foo", 200, "snack");
+        set_surcharge_rule_inner(
+            &db,
+            SurchargeRule {
+                payment_method: PaymentMethod::Card,
+                amount: 30,
+                below: Some(500),
+            },
+        )
+        .unwrap();
+
+        let small = make_order(&db, &[(&p, 1)], PaymentMethod::Card);
+        assert_eq!(small.order.total, 230);
+        assert_eq!(small.items[1].product_id, SURCHARGE_PRODUCT_ID);
+        assert_eq!(
+            make_order(&db, &[(&p, 3)], PaymentMethod::Card).order.total,
+            600
+        );
+        assert_eq!(
+            make_order(&db, &[(&p, 1)], PaymentMethod::Cash).order.total,
+            200
+        );
+
+        let summary =
+            get_dashboard_summary_inner(&db, ProductSummaryQuery::default(), None).unwrap();
+        let card = summary
+            .per_payment_method
+            .iter()
+            .find(|pm| pm.payment_method == PaymentMethod::Card)
+            .unwrap();
+        assert_eq!(card.total_revenue, 830);
+        assert_eq!(card.surcharge_revenue, 30);
+
+        delete_surcharge_rule_inner(&db, PaymentMethod::Card).unwrap();
+        assert!(list_surcharge_rules_inner(&db).unwrap().is_empty());
+    }
+
+    #[test]
+    fn split_order_creates_one_order_per_part() {
+        let db = init_db_in_memory();
+        let beer = make_product(&db, "Pitcher", 1200, "snack");
+        let crepe = make_product(&db, "Crepe", 300, "snack");
+        let line = |p: &Product, quantity: f64| CreateOrderItemPayload {
+            product_id: p.id.clone(),
+            product_name: p.name.clone(),
+            unit_price: p.price,
+            quantity,
+        };
+        let split = |quantities: [f64; 2]| SplitOrderPayload {
+            items: vec![line(&beer, 1.0), line(&crepe, 2.0)],
+            parts: vec![
+                SplitOrderPart {
+                    payment_method: PaymentMethod::Card,
+                    items: vec![
+                        SplitItemAssignment {
+                            item_index: 0,
+                            quantity: 1.0,
+                        },
+                        SplitItemAssignment {
+                            item_index: 1,
+                            quantity: quantities[0],
+                        },
+                    ],
+                },
+                SplitOrderPart {
+                    payment_method: PaymentMethod::Cash,
+                    items: vec![SplitItemAssignment {
+                        item_index: 1,
+                        quantity: quantities[1],
+                    }],
+                },
+            ],
+            age_confirmed: false,
+            limits_confirmed: false,
+        };
+
+        let err = split_order_inner(&db, split([1.0, 2.0])).unwrap_err();
+        assert!(err.contains("assigned 3 times"), "{err}");
+        assert!(list_orders_inner(&db, None).unwrap().is_empty());
+
+        let orders = split_order_inner(&db, split([1.0, 1.0])).unwrap();
+        assert_eq!(
+            orders.iter().map(|o| o.order.total).collect::<Vec<_>>(),
+            [1500, 300]
+        );
+        assert_eq!(orders[1].order.payment_method, PaymentMethod::Cash);
+        assert!(verify_order_chain_inner(&db).unwrap().valid);
+    }
+
+    #[test]
+    fn settling_a_tab_records_its_items_as_paid_orders() {
+        let db = init_db_in_memory();
+        let beer = make_product(&db, "Beer", 250, "snack");
+        let line = |quantity: f64| CreateOrderItemPayload {
+            product_id: beer.id.clone(),
+            product_name: beer.name.clone(),
+            unit_price: beer.price,
+            quantity,
+        };
+
+        let tab = open_tab_inner(&db, "Jo".to_string()).unwrap();
+        for quantity in [2.0, 1.0] {
+            add_to_tab_inner(
+                &db,
+                tab.id.clone(),
+                AddToTabPayload {
+                    items: vec![line(quantity)],
+                    age_confirmed: false,
+                },
+            )
+            .unwrap();
+        }
+        let tabs = list_open_tabs_inner(&db).unwrap();
+        assert_eq!(tabs.len(), 1);
+        assert_eq!(tabs[0].total, 750);
+        assert!(list_orders_inner(&db, None).unwrap().is_empty());
+
+        let pay = |item_index: usize| SplitOrderPart {
+            payment_method: PaymentMethod::Card,
+            items: vec![SplitItemAssignment {
+                item_index,
+                quantity: tabs[0].items[item_index].quantity,
+            }],
+        };
+        assert!(settle_tab_inner(&db, tab.id.clone(), vec![pay(0)], false).is_err());
+
+        let orders = settle_tab_inner(&db, tab.id.clone(), vec![pay(0), pay(1)], false).unwrap();
+        assert_eq!(
+            orders.iter().map(|o| o.order.total).collect::<Vec<_>>(),
+            [500, 250]
+        );
+        assert!(list_open_tabs_inner(&db).unwrap().is_empty());
+        assert!(settle_tab_inner(&db, tab.id, vec![pay(0), pay(1)], false).is_err());
+    }
+
+    #[test]
+    fn tab_lines_follow_the_order_rules_and_can_be_removed() {
+        let db = init_db_in_memory();
+        let beer = make_product(&db, "Beer", 250, "snack");
+        let line = |quantity: f64| AddToTabPayload {
+            items: vec![CreateOrderItemPayload {
+                product_id: beer.id.clone(),
+                product_name: beer.name.clone(),
+                unit_price: beer.price,
+                quantity,
+            }],
+            age_confirmed: false,
+        };
+
+        let tab = open_tab_inner(&db, "Jo".to_string()).unwrap();
+        let err = add_to_tab_inner(&db, tab.id.clone(), line(1.5)).unwrap_err();
+        assert!(err.contains("whole number"));
+        let tab = add_to_tab_inner(&db, tab.id.clone(), line(2.0000001)).unwrap();
+        assert_eq!(tab.items[0].quantity, 2.0);
+        let tab = add_to_tab_inner(&db, tab.id.clone(), line(1.0)).unwrap();
+
+        let tab =
+            remove_from_tab_inner(&db, tab.id.clone(), tab.items[0].id.clone(), None).unwrap();
+        assert_eq!(tab.items.len(), 1);
+        assert_eq!(tab.total, 250);
+        assert!(remove_from_tab_inner(&db, tab.id.clone(), "missing".to_string(), None).is_err());
+
+        cancel_tab_inner(&db, tab.id.clone(), None).unwrap();
+        assert!(list_open_tabs_inner(&db).unwrap().is_empty());
+        assert!(list_orders_inner(&db, None).unwrap().is_empty());
+        assert!(cancel_tab_inner(&db, tab.id, None).is_err());
+    }
+
+    #[test]
+    fn kitchen_ticket_leaves_out_prices_and_surcharges() {
+        let db = init_db_in_memory();
+        set_surcharge_rule_inner(
+            &db,
+            SurchargeRule {
+                payment_method: PaymentMethod::Card,
+                amount: 30,
+                below: None,
+            },
+        )
+        .unwrap();
+        let crepe = make_product(&db, "Crepe", 350, "snack");
+        let order = make_order(&db, &[(&crepe, 2)], PaymentMethod::Card);
+
+        let path = temp_path("pdf");
+        generate_kitchen_ticket_inner(&db, order.order.id.clone(), path.clone()).unwrap();
+        let pdf = String::from_utf8_lossy(&std::fs::read(&path).unwrap()).into_owned();
+        std::fs::remove_file(&path).unwrap();
+        assert!(pdf.contains("Crepe"));
+        assert!(pdf.contains(&crate::receipt::kitchen_reference(&order.order)));
+        assert!(!pdf.contains("3.50") && !pdf.contains("Surcharge"));
+    }
+
+    #[test]
+    fn new_orders_get_a_ticket_in_the_kitchen_folder() {
+        let db = init_db_in_memory();
+        let crepe = make_product(&db, "Crepe", 350, "snack");
+        let order = make_order(&db, &[(&crepe, 2)], PaymentMethod::Card);
+        let dir = std::env::temp_dir().join(format!("pos-test-{}", Uuid::new_v4()));
+
+        let tickets = write_order_tickets_inner(&db, &order.order.id, &dir).unwrap();
+        assert_eq!(tickets.len(), 1);
+        assert_eq!(tickets[0].station, "Kitchen");
+        let reference = crate::receipt::kitchen_reference(&order.order);
+        assert_eq!(
+            std::path::Path::new(&tickets[0].path),
+            dir.join("Kitchen").join(format!("{reference}.pdf"))
+        );
+        let pdf = String::from_utf8_lossy(&std::fs::read(&tickets[0].path).unwrap()).into_owned();
+        assert!(pdf.contains("Crepe"));
+
+        let refund = refund_items_inner(
+            &db,
+            order.order.id.clone(),
+            vec![ItemRefund {
+                item_id: order.items[0].id.clone(),
+                quantity: 1.0,
+            }],
+        )
+        .unwrap();
+        assert!(write_order_tickets_inner(&db, &refund.order.id, &dir)
+            .unwrap()
+            .is_empty());
+        set_feature_inner(&db, Feature::Printing, false).unwrap();
+        assert!(write_order_tickets_inner(&db, &order.order.id, &dir)
+            .unwrap()
+            .is_empty());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn station_tickets_follow_category_routes() {
+        let db = init_db_in_memory();
+        for (category_id, station) in [("boisson-sans-alcool", "Bar"), ("snack", "Kitchen")] {
+            set_station_route_inner(
+                &db,
+                StationRoute {
+                    category_id: category_id.to_string(),
+                    station: station.to_string(),
+                },
+            )
+            .unwrap();
+        }
+        let cola = make_product(&db, "Cola", 200, "boisson-sans-alcool");
+        let water = make_product(&db, "Water", 100, "boisson-sans-alcool");
+        let crepe = make_product(&db, "Crepe", 350, "snack");
+        let order = make_order(
+            &db,
+            &[(&cola, 1), (&crepe, 1), (&water, 2)],
+            PaymentMethod::Cash,
+        );
+
+        let dir = std::env::temp_dir().join(format!("pos-test-{}", Uuid::new_v4()));
+        let tickets = generate_station_tickets_inner(
+            &db,
+            order.order.id.clone(),
+            dir.to_string_lossy().into_owned(),
+        )
+        .unwrap();
+        let contents: Vec<(String, String)> = tickets
+            .iter()
+            .map(|t| {
+                let pdf = String::from_utf8_lossy(&std::fs::read(&t.path).unwrap()).into_owned();
+                (t.station.clone(), pdf)
+            })
+            .collect();
+        assert_eq!(contents.len(), 2);
+        assert_eq!(contents[0].0, "Bar");
+        assert!(contents[0].1.contains("Cola") && contents[0].1.contains("Water"));
+        assert!(!contents[0].1.contains("Crepe"));
+        assert_eq!(contents[1].0, "Kitchen");
+        assert!(contents[1].1.contains("Crepe"));
+        let reference = crate::receipt::kitchen_reference(&order.order);
+        assert_eq!(
+            std::path::Path::new(&tickets[0].path),
+            dir.join("Bar").join(format!("{reference}.pdf"))
+        );
+
+        // New orders fan out the same way.
+        assert_eq!(
+            write_order_tickets_inner(&db, &order.order.id, &dir).unwrap(),
+            tickets
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn purge_test_orders_voids_flagged_orders() {
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Chips", 200, "snack");
+        let test = make_order(&db, &[(&p, 1)], PaymentMethod::Cash);
+        let sale = make_order(&db, &[(&p, 2)], PaymentMethod::Cash);
+
+        let flagged = set_order_test_flag_inner(&db, test.order.id.clone(), true).unwrap();
+        assert!(flagged.order.is_test);
+        assert_eq!(purge_test_orders_inner(&db, None).unwrap().len(), 1);
+        assert!(purge_test_orders_inner(&db, None).unwrap().is_empty());
+
+        let orders = list_orders_inner(&db, None).unwrap();
+        let voided = |id: &str| {
+            orders
+                .iter()
+                .find(|o| o.order.id == id)
+                .unwrap()
+                .order
+                .voided_at
+                .is_some()
+        };
+        assert!(voided(&test.order.id));
+        assert!(!voided(&sale.order.id));
+        assert!(verify_order_chain_inner(&db).unwrap().valid);
+    }
+
+    #[test]
+    fn category_colors_are_checked_against_the_palette() {
+        let db = init_db_in_memory();
+        let create = |id: &str, color: &str, allow_custom_color: bool| {
+            create_category_inner(
+                &db,
+                CreateCategoryPayload {
+                    id: id.to_string(),
+                    label: id.to_string(),
+                    color: color.to_string(),
+                    translations: Translations::new(),
+                    icon: None,
+                    parent_id: None,
+                    allow_custom_color,
+                },
+                None,
+            )
+        };
+        let color = |color: &str| PaletteColor {
+            color: color.to_string(),
+            name: String::new(),
+        };
+
+        assert!(update_palette_inner(&db, vec![color("blue")]).is_err());
+        assert!(update_palette_inner(&db, vec![color("#3B82F6"), color("#3b82f6")]).is_err());
+        let palette =
+            update_palette_inner(&db, vec![color(" #3B82F6 "), color("#e8a735")]).unwrap();
+        assert_eq!(palette[0].color, "#3b82f6");
+        assert_eq!(list_palette_inner(&db).unwrap(), palette);
+
+        create("glaces", "#3b82f6", false).unwrap();
+        assert!(create("gaufres", "#3b82f7", false).is_err());
+        create("gaufres", "#3b82f7", true).unwrap();
+
+        update_palette_inner(&db, Vec::new()).unwrap();
+        create("frites", "#123456", false).unwrap();
+    }
+
+    #[test]
+    fn write_transaction_waits_for_a_busy_database() {
+        let path = temp_path("db");
+        let mut conn = Connection::open(&path).unwrap();
+        conn.execute_batch("PRAGMA journal_mode=WAL; CREATE TABLE t (n INTEGER);")
+            .unwrap();
+        crate::db::set_busy_timeout(&conn).unwrap();
+
+        // Another connection holds the write lock for a moment.
+        let other = Connection::open(&path).unwrap();
+        other.execute_batch("BEGIN IMMEDIATE").unwrap();
+        let holder = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(300));
+            other
+                .execute_batch("INSERT INTO t VALUES (1); COMMIT")
+                .unwrap();
+        });
+
+        let count = write_transaction(&mut conn, |tx| {
+            tx.execute("INSERT INTO t VALUES (2)", [])
+                .map_err(|e| e.to_string())?;
+            tx.query_row("SELECT COUNT(*) FROM t", [], |row| row.get::<_, i64>(0))
+                .map_err(|e| e.to_string())
+        })
+        .unwrap();
+        holder.join().unwrap();
+        assert_eq!(count, 2);
+
+        drop(conn);
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{path}{suffix}"));
+        }
+    }
+
+    #[test]
+    fn backup_and_restore_use_consistent_snapshots() {
+        let path = temp_path("db");
+        let mut conn = Connection::open(&path).unwrap();
+        conn.execute_batch("PRAGMA journal_mode=WAL;").unwrap();
+        let migration = crate::db::migrate(&mut conn, None).unwrap();
+        crate::db::create_default_data(&conn).unwrap();
+        let db = DbState {
+            conn: std::sync::Mutex::new(conn),
+            reader: Some(std::sync::Mutex::new(
+                crate::db::open_reader(&path).unwrap(),
+            )),
+            db_path: path.clone(),
+            migration,
+        };
+        let p = make_product(&db, "Chips", 200, "snack");
+        let kept = make_order(&db, &[(&p, 1)], PaymentMethod::Cash);
+
+        // The order is still in the WAL, which a file copy would miss.
+        let backup = temp_path("db");
+        backup_database_inner(&db, backup.clone()).unwrap();
+        assert!(backup_database_inner(&db, backup.clone()).is_err());
+        make_order(&db, &[(&p, 2)], PaymentMethod::Cash);
+
+        restore_database_inner(&db, backup.clone(), None).unwrap();
+        let orders = list_orders_inner(&db, None).unwrap();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].order.id, kept.order.id);
+        assert!(verify_order_chain_inner(&db).unwrap().valid);
+
+        drop(db);
+        for file in [&path, &backup] {
+            for suffix in ["", "-wal", "-shm"] {
+                let _ = std::fs::remove_file(format!("{file}{suffix}"));
+            }
+        }
+    }
+
+    #[test]
+    fn list_orders_pages_follow_each_other() {
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Chips", 200, "snack");
+        let ids: Vec<String> = (1..=5)
+            .map(|quantity| {
+                make_order(&db, &[(&p, quantity)], PaymentMethod::Cash)
+                    .order
+                    .id
+            })
+            .collect();
+
+        let mut listed = Vec::new();
+        let mut before = None;
+        loop {
+            let page = list_orders_page_inner(
+                &db,
+                None,
+                OrderPageQuery {
+                    before: before.clone(),
+                    limit: Some(2),
+                },
+            )
+            .unwrap();
+            let Some(last) = page.last() else { break };
+            before = Some(last.order.id.clone());
+            assert!(page.iter().all(|o| o.items.len() == 1));
+            listed.extend(page.into_iter().map(|o| o.order.id));
+        }
+        // Orders made within the same second keep their insertion order.
+        assert_eq!(listed, ids.into_iter().rev().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn export_orders_xlsx_writes_a_workbook() {
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Crêpe", 250, "sucreries");
+        let order = make_order(&db, &[(&p, 2)], PaymentMethod::Card);
+        set_order_created_at(&db, &order.order.id, "2026-06-20T12:00:00Z");
+
+        let path = temp_path("xlsx");
+        let range = DateRange {
+            from: Some("2026-06-01".to_string()),
+            to: Some("2026-06-30".to_string()),
+        };
+        export_orders_xlsx_inner(&db, range, false, path.clone()).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(bytes.starts_with(b"PK"));
+
+        let mut sheet = export::Sheet::new("Dates", &["Date"]);
+        sheet
+            .rows
+            .push(vec![export::Cell::Date("2026-06-20T14:00:00".to_string())]);
+        assert!(export::to_csv(&[sheet]).contains("\"2026-06-20 14:00:00\""));
+    }
+
+    #[test]
+    fn export_menu_pdf_lists_available_products_by_category() {
+        let db = init_db_in_memory();
+        make_product(&db, "Galette", 650, "snack");
+        let hidden = make_product(&db, "Old cake", 150, "snack");
+        update_product_inner(
+            &db,
+            UpdateProductPayload {
+                id: hidden.id.clone(),
+                name: hidden.name.clone(),
+                price: hidden.price,
+                category_id: hidden.category_id.clone(),
+                available: false,
+                age_restricted: None,
+                unit: None,
+                translations: None,
+            },
+            None,
+        )
+        .unwrap();
+
+        let path = temp_path("pdf");
+        export_menu_pdf_inner(&db, path.clone()).unwrap();
+        let pdf = String::from_utf8_lossy(&std::fs::read(&path).unwrap()).into_owned();
+        std::fs::remove_file(&path).unwrap();
+        assert!(pdf.contains("(Snack)") && pdf.contains("(Galette)"));
+        assert!(pdf.contains("(6.50 EUR)"));
+        assert!(!pdf.contains("Old cake"));
+    }
+
+    #[test]
+    fn order_limits_require_a_confirmation() {
+        let db = init_db_in_memory();
+        let beer = make_product(&db, "Beer", 350, "snack");
+        update_order_limits_inner(
+            &db,
+            OrderLimits {
+                max_total: 10_000,
+                max_quantity: 50.0,
+            },
+        )
+        .unwrap();
+        let order = |quantity: f64, limits_confirmed: bool| CreateOrderPayload {
+            items: vec![CreateOrderItemPayload {
+                product_id: beer.id.clone(),
+                product_name: beer.name.clone(),
+                unit_price: beer.price,
+                quantity,
+            }],
+            payment_method: PaymentMethod::Cash,
+            age_confirmed: false,
+            limits_confirmed,
+            adjustments: Vec::new(),
+            source: OrderSource::Counter,
+        };
+
+        let err = create_order_inner(&db, order(999.0, false)).unwrap_err();
+        assert!(err.contains("quantity 999 of Beer"), "{err}");
+        let err = create_order_inner(&db, order(30.0, false)).unwrap_err();
+        assert!(err.contains("total 105.00 EUR"), "{err}");
+        assert!(list_orders_inner(&db, None).unwrap().is_empty());
+
+        create_order_inner(&db, order(20.0, false)).unwrap();
+        create_order_inner(&db, order(999.0, true)).unwrap();
+        assert_eq!(list_orders_inner(&db, None).unwrap().len(), 2);
+
+        assert!(update_order_limits_inner(
+            &db,
+            OrderLimits {
+                max_total: -1,
+                max_quantity: 0.0,
+            },
+        )
+        .is_err());
     }
 
     #[test]
-    fn update_product_not_found() {
+    fn split_order_parts_respect_the_order_limits() {
         let db = init_db_in_memory();
-        let result = update_product_inner(
+        let beer = make_product(&db, "Beer", 350, "snack");
+        update_order_limits_inner(
             &db,
-            UpdateProductPayload {
-                id: "nonexistent".to_string(),
-                name: "X".to_string(),
-                price: 100,
-                category_id: "snack".to_string(),
-                available: true,
+            OrderLimits {
+                max_total: 10_000,
+                max_quantity: 0.0,
             },
+        )
+        .unwrap();
+        let split = |quantities: [f64; 2], limits_confirmed: bool| SplitOrderPayload {
+            items: vec![CreateOrderItemPayload {
+                product_id: beer.id.clone(),
+                product_name: beer.name.clone(),
+                unit_price: beer.price,
+                quantity: quantities[0] + quantities[1],
+            }],
+            parts: quantities
+                .iter()
+                .map(|&quantity| SplitOrderPart {
+                    payment_method: PaymentMethod::Card,
+                    items: vec![SplitItemAssignment {
+                        item_index: 0,
+                        quantity,
+                    }],
+                })
+                .collect(),
+            age_confirmed: false,
+            limits_confirmed,
+        };
+
+        // Each part is an order of its own, checked on its own.
+        let err = split_order_inner(&db, split([30.0, 2.0], false)).unwrap_err();
+        assert!(err.contains("total 105.00 EUR"), "{err}");
+        assert!(list_orders_inner(&db, None).unwrap().is_empty());
+        assert_eq!(
+            split_order_inner(&db, split([20.0, 20.0], false))
+                .unwrap()
+                .len(),
+            2
         );
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Product not found"));
+        split_order_inner(&db, split([30.0, 2.0], true)).unwrap();
+        assert_eq!(list_orders_inner(&db, None).unwrap().len(), 4);
     }
 
     #[test]
-    fn toggle_availability() {
+    fn adjustments_are_deducted_down_to_zero() {
         let db = init_db_in_memory();
-        let p = make_product(&db, "Water", 100, "boisson-sans-alcool");
-        assert!(p.available);
-
-        // Toggle off
-        let new_state = toggle_product_availability_inner(&db, p.id.clone()).unwrap();
-        assert!(!new_state);
-
-        // Toggle back on
-        let new_state = toggle_product_availability_inner(&db, p.id.clone()).unwrap();
-        assert!(new_state);
+        let beer = make_product(&db, "Beer", 350, "snack");
+        let order = |amount: i64| CreateOrderPayload {
+            items: vec![CreateOrderItemPayload {
+                product_id: beer.id.clone(),
+                product_name: beer.name.clone(),
+                unit_price: beer.price,
+                quantity: 2.0,
+            }],
+            payment_method: PaymentMethod::Cash,
+            age_confirmed: false,
+            limits_confirmed: false,
+            adjustments: vec![OrderAdjustment {
+                label: "Returned cups".to_string(),
+                amount,
+            }],
+            source: OrderSource::Counter,
+        };
+
+        let created = create_order_inner(&db, order(-200)).unwrap();
+        assert_eq!(created.order.total, 500);
+        let adjustment = &created.items[1];
+        assert_eq!(adjustment.product_id, ADJUSTMENT_PRODUCT_ID);
+        assert_eq!(adjustment.product_name, "Returned cups");
+        assert_eq!(adjustment.total, -200);
+
+        assert_eq!(create_order_inner(&db, order(-700)).unwrap().order.total, 0);
+        assert!(create_order_inner(&db, order(-701)).is_err());
+        assert!(create_order_inner(&db, order(100)).is_err());
+        assert_eq!(list_orders_inner(&db, None).unwrap().len(), 2);
+    }
 
-        // Toggle off again
-        let new_state = toggle_product_availability_inner(&db, p.id).unwrap();
-        assert!(!new_state);
+    #[test]
+    fn expiring_batches_hold_the_newest_stock() {
+        let db = init_db_in_memory();
+        let sandwich = make_product(&db, "Sandwich", 450, "snack");
+        let today = chrono::NaiveDate::from_ymd_opt(2026, 10, 16).unwrap();
+        let batch = |quantity: f64, expires_on: Option<&str>| {
+            add_stock_batch_inner(
+                &db,
+                AddStockBatchPayload {
+                    product_id: sandwich.id.clone(),
+                    quantity,
+                    expires_on: expires_on.map(str::to_string),
+                },
+            )
+        };
+        assert!(batch(2.5, None).is_err());
+        assert!(batch(2.0, Some("16/10/2026")).is_err());
+
+        let friday = batch(6.0, Some("2026-10-15")).unwrap();
+        let today_batch = batch(4.0, Some("2026-10-16")).unwrap();
+        batch(10.0, Some("2026-10-20")).unwrap();
+        assert_eq!(get_stock_levels_inner(&db).unwrap()[0].quantity, 20.0);
+
+        // Sales use up the oldest batch first.
+        make_order(&db, &[(&sandwich, 8)], PaymentMethod::Cash);
+        let expiring = get_expiring_batches_inner(&db, today, 1).unwrap();
+        assert_eq!(expiring.len(), 1);
+        assert_eq!(expiring[0].batch_id, today_batch.id);
+        assert_eq!(expiring[0].remaining, 2.0);
+        assert_eq!(expiring[0].days_left, 0);
+        assert!(!expiring.iter().any(|b| b.batch_id == friday.id));
+        assert_eq!(get_expiring_batches_inner(&db, today, 4).unwrap().len(), 2);
+
+        let (_, text) = build_expiry_alert_inner(&db, today).unwrap().unwrap();
+        assert!(text.contains("Sandwich x2: today"), "{text}");
+        make_order(&db, &[(&sandwich, 2)], PaymentMethod::Cash);
+        assert!(build_expiry_alert_inner(&db, today).unwrap().is_none());
     }
 
     #[test]
-    fn create_order_success() {
+    fn export_dashboard_splits_by_day() {
         let db = init_db_in_memory();
-        let p = make_product(&db, "Candy", 50, "sucreries");
+        let p = make_product(&db, "Crêpe", 250, "sucreries");
+        let saturday = make_order(&db, &[(&p, 2)], PaymentMethod::Cash);
+        set_order_created_at(&db, &saturday.order.id, "2026-06-20T12:00:00Z");
+        let sunday = make_order(&db, &[(&p, 3)], PaymentMethod::Cash);
+        set_order_created_at(&db, &sunday.order.id, "2026-06-21T12:00:00Z");
+
+        let path = temp_path("csv");
+        let run = |from: Option<&str>| {
+            export_dashboard_inner(
+                &db,
+                DateRange {
+                    from: from.map(str::to_string),
+                    to: Some("2026-06-21".to_string()),
+                },
+                ProductSummaryQuery::default(),
+                None,
+                ExportFormat::Csv,
+                true,
+                path.clone(),
+            )
+        };
+        assert!(run(None).is_err());
+        run(Some("2026-06-20")).unwrap();
+
+        let day_path = |day: &str| path.replace(".csv", &format!("-{day}.csv"));
+        let saturday_csv = std::fs::read_to_string(day_path("2026-06-20")).unwrap();
+        let sunday_csv = std::fs::read_to_string(day_path("2026-06-21")).unwrap();
+        std::fs::remove_file(day_path("2026-06-20")).unwrap();
+        std::fs::remove_file(day_path("2026-06-21")).unwrap();
+        assert!(!std::path::Path::new(&path).exists());
+        assert!(saturday_csv.contains("\"Total revenue\";\"5.00\""));
+        assert!(sunday_csv.contains("\"Total revenue\";\"7.50\""));
+    }
 
-        let order = create_order_inner(
+    #[test]
+    fn orders_keep_their_source_for_reports() {
+        let db = init_db_in_memory();
+        let crepe = make_product(&db, "Crêpe", 250, "sucreries");
+        make_order(&db, &[(&crepe, 1)], PaymentMethod::Cash);
+        let online = create_order_inner(
             &db,
             CreateOrderPayload {
                 items: vec![CreateOrderItemPayload {
-                    product_id: p.id.clone(),
-                    product_name: "Candy".to_string(),
-                    unit_price: 50,
-                    quantity: 3,
+                    product_id: crepe.id.clone(),
+                    product_name: crepe.name.clone(),
+                    unit_price: crepe.price,
+                    quantity: 4.0,
                 }],
-                payment_method: PaymentMethod::Cash,
+                payment_method: PaymentMethod::Card,
+                age_confirmed: false,
+                limits_confirmed: false,
+                adjustments: Vec::new(),
+                source: OrderSource::Online,
             },
         )
         .unwrap();
+        assert_eq!(online.order.source, OrderSource::Online);
+        let refund = refund_items_inner(
+            &db,
+            online.order.id.clone(),
+            vec![ItemRefund {
+                item_id: online.items[0].id.clone(),
+                quantity: 1.0,
+            }],
+        )
+        .unwrap();
+        assert_eq!(refund.order.source, OrderSource::Online);
+        let orders = list_orders_inner(&db, None).unwrap();
+        assert_eq!(orders[2].order.source, OrderSource::Counter);
 
-        assert_eq!(order.order.total, 150);
-        assert_eq!(order.order.payment_method, PaymentMethod::Cash);
-        assert_eq!(order.items.len(), 1);
-        assert_eq!(order.items[0].product_id, p.id);
-        assert_eq!(order.items[0].quantity, 3);
-        assert_eq!(order.items[0].total, 150);
+        let summary =
+            get_dashboard_summary_inner(&db, ProductSummaryQuery::default(), None).unwrap();
+        assert_eq!(
+            summary.per_source,
+            vec![
+                SourceBreakdown {
+                    source: OrderSource::Counter,
+                    total_revenue: 250,
+                    transaction_count: 1,
+                },
+                SourceBreakdown {
+                    source: OrderSource::Online,
+                    total_revenue: 750,
+                    transaction_count: 2,
+                },
+            ]
+        );
+        let online_only = get_dashboard_summary_inner(
+            &db,
+            ProductSummaryQuery::default(),
+            Some(OrderSource::Online),
+        )
+        .unwrap();
+        assert_eq!(online_only.total_revenue, 750);
+        assert_eq!(online_only.per_product[0].total_quantity, 3.0);
+        assert_eq!(online_only.per_payment_method.len(), 1);
+    }
 
-        // Verify via list_orders
-        let orders = list_orders_inner(&db).unwrap();
-        assert_eq!(orders.len(), 1);
-        assert_eq!(orders[0].order.total, 150);
-        assert_eq!(orders[0].items.len(), 1);
+    #[test]
+    fn sales_tick_follows_the_day() {
+        let db = init_db_in_memory();
+        let crepe = make_product(&db, "Crêpe", 250, "sucreries");
+        make_order(&db, &[(&crepe, 2)], PaymentMethod::Cash);
+        let last = make_order(&db, &[(&crepe, 1)], PaymentMethod::Card);
+
+        let tick = sales_tick_inner(&db, std::slice::from_ref(&last)).unwrap();
+        assert_eq!(
+            tick,
+            SalesTick {
+                daily_total: 750,
+                order_count: 2,
+                last_order_total: 250,
+            }
+        );
     }
 
     #[test]
-    fn create_order_empty_items_fails() {
+    fn goal_progress_projects_the_current_pace() {
         let db = init_db_in_memory();
-        let result = create_order_inner(
+        let at = |date: &str, time: &str| {
+            chrono::NaiveDateTime::parse_from_str(&format!("{date} {time}"), "%Y-%m-%d %H:%M")
+                .unwrap()
+        };
+        assert!(get_goal_progress_inner(&db, at("2026-06-20", "12:00")).is_err());
+        assert!(update_revenue_goal_inner(
             &db,
-            CreateOrderPayload {
-                items: vec![],
-                payment_method: PaymentMethod::Card,
+            RevenueGoal {
+                target: 100_000,
+                from: "2026-06-21".to_string(),
+                to: "2026-06-20".to_string(),
             },
+        )
+        .is_err());
+        update_revenue_goal_inner(
+            &db,
+            RevenueGoal {
+                target: 100_000,
+                from: "2026-06-20".to_string(),
+                to: "2026-06-21".to_string(),
+            },
+        )
+        .unwrap();
+
+        let crepe = make_product(&db, "Crêpe", 250, "sucreries");
+        let order = make_order(&db, &[(&crepe, 5)], PaymentMethod::Cash);
+        set_order_created_at(&db, &order.order.id, "2026-06-20T10:00:00Z");
+        let before = make_order(&db, &[(&crepe, 1)], PaymentMethod::Cash);
+        set_order_created_at(&db, &before.order.id, "2026-06-10T10:00:00Z");
+
+        // A quarter of the two days has passed.
+        let progress = get_goal_progress_inner(&db, at("2026-06-20", "12:00")).unwrap();
+        assert_eq!(
+            progress,
+            GoalProgress {
+                target: 100_000,
+                achieved: 1250,
+                projected: 5000,
+            }
         );
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("no items"));
+        let after = get_goal_progress_inner(&db, at("2026-06-23", "12:00")).unwrap();
+        assert_eq!(after.projected, 1250);
     }
 
     #[test]
-    fn dashboard_summary_reflects_orders() {
+    fn net_result_subtracts_expenses_from_sales() {
         let db = init_db_in_memory();
-        let p1 = make_product(&db, "Soda", 200, "boisson-sans-alcool");
-        let p2 = make_product(&db, "Bar", 100, "snack");
-
-        // Order 1: 2x Soda, paid by cash => 400
-        create_order_inner(
+        let expense = |spent_on: &str, amount: i64, category: &str| CreateExpensePayload {
+            spent_on: spent_on.to_string(),
+            amount,
+            category: category.to_string(),
+            note: String::new(),
+            receipt_path: None,
+        };
+        assert!(create_expense_inner(&db, expense("2026-06-20", 0, "supplies")).is_err());
+        assert!(create_expense_inner(&db, expense("2026-06-20", 100, " ")).is_err());
+
+        let flour = create_expense_inner(&db, expense("2026-06-20", 800, " supplies ")).unwrap();
+        assert_eq!(flour.category, "supplies");
+        create_expense_inner(&db, expense("2026-06-20", 3000, "rental")).unwrap();
+        let old = create_expense_inner(&db, expense("2026-06-01", 500, "supplies")).unwrap();
+        let updated = update_expense_inner(
             &db,
-            CreateOrderPayload {
-                items: vec![CreateOrderItemPayload {
-                    product_id: p1.id.clone(),
-                    product_name: "Soda".to_string(),
-                    unit_price: 200,
-                    quantity: 2,
-                }],
-                payment_method: PaymentMethod::Cash,
+            UpdateExpensePayload {
+                id: flour.id.clone(),
+                spent_on: "2026-06-20".to_string(),
+                amount: 1000,
+                category: "supplies".to_string(),
+                note: "Flour".to_string(),
+                receipt_path: Some("receipts/flour.jpg".to_string()),
             },
         )
         .unwrap();
-
-        // Order 2: 1x Soda + 3x Bar, paid by card => 200 + 300 = 500
-        create_order_inner(
-            &db,
-            CreateOrderPayload {
-                items: vec![
-                    CreateOrderItemPayload {
-                        product_id: p1.id.clone(),
-                        product_name: "Soda".to_string(),
-                        unit_price: 200,
-                        quantity: 1,
+        assert_eq!(updated.receipt_path.as_deref(), Some("receipts/flour.jpg"));
+        delete_expense_inner(&db, old.id.clone()).unwrap();
+        assert!(delete_expense_inner(&db, old.id).is_err());
+
+        let crepe = make_product(&db, "Crêpe", 250, "sucreries");
+        let order = make_order(&db, &[(&crepe, 20)], PaymentMethod::Cash);
+        set_order_created_at(&db, &order.order.id, "2026-06-20T10:00:00Z");
+
+        let range = DateRange {
+            from: Some("2026-06-20".to_string()),
+            to: Some("2026-06-20".to_string()),
+        };
+        assert_eq!(list_expenses_inner(&db, range.clone()).unwrap().len(), 2);
+        assert_eq!(
+            get_net_result_inner(&db, range).unwrap(),
+            NetResult {
+                revenue: 5000,
+                expenses: 4000,
+                net: 1000,
+                per_category: vec![
+                    ExpenseCategoryTotal {
+                        category: "rental".to_string(),
+                        amount: 3000,
                     },
-                    CreateOrderItemPayload {
-                        product_id: p2.id.clone(),
-                        product_name: "Bar".to_string(),
-                        unit_price: 100,
-                        quantity: 3,
+                    ExpenseCategoryTotal {
+                        category: "supplies".to_string(),
+                        amount: 1000,
                     },
                 ],
-                payment_method: PaymentMethod::Card,
+            }
+        );
+    }
+
+    #[test]
+    fn attachments_are_copied_to_the_data_dir() {
+        let db = init_db_in_memory();
+        let data_dir = std::path::PathBuf::from(temp_path("d"));
+        let source = temp_path("JPG");
+        std::fs::write(&source, b"receipt").unwrap();
+        let expense = create_expense_inner(
+            &db,
+            CreateExpensePayload {
+                spent_on: "2026-06-20".to_string(),
+                amount: 800,
+                category: "supplies".to_string(),
+                note: String::new(),
+                receipt_path: None,
             },
         )
         .unwrap();
 
-        let summary = get_dashboard_summary_inner(&db).unwrap();
-        assert_eq!(summary.total_revenue, 900);
-        assert_eq!(summary.total_transactions, 2);
-
-        // Per-product: Soda = 600 (3 units), Bar = 300 (3 units). Ordered by revenue DESC.
-        assert_eq!(summary.per_product.len(), 2);
-        assert_eq!(summary.per_product[0].product_name, "Soda");
-        assert_eq!(summary.per_product[0].total_quantity, 3);
-        assert_eq!(summary.per_product[0].total_revenue, 600);
-        assert_eq!(summary.per_product[1].product_name, "Bar");
-        assert_eq!(summary.per_product[1].total_quantity, 3);
-        assert_eq!(summary.per_product[1].total_revenue, 300);
-
-        // Per-payment-method: ordered by payment_method ASC => card, cash
-        assert_eq!(summary.per_payment_method.len(), 2);
-        assert_eq!(
-            summary.per_payment_method[0].payment_method,
-            PaymentMethod::Card
-        );
-        assert_eq!(summary.per_payment_method[0].total_revenue, 500);
-        assert_eq!(summary.per_payment_method[0].transaction_count, 1);
+        let text = temp_path("txt");
+        std::fs::write(&text, b"notes").unwrap();
+        let payload = |source_path: &str, expense_id: &str| AddAttachmentPayload {
+            source_path: source_path.to_string(),
+            expense_id: Some(expense_id.to_string()),
+        };
+        assert!(add_attachment_inner(&db, &data_dir, payload(&text, &expense.id)).is_err());
+        assert!(add_attachment_inner(&db, &data_dir, payload(&source, "missing")).is_err());
+
+        let attachment =
+            add_attachment_inner(&db, &data_dir, payload(&source, &expense.id)).unwrap();
+        assert_eq!(attachment.content_type, "image/jpeg");
+        assert_eq!(attachment.size, 7);
+        assert_eq!(std::fs::read(&attachment.path).unwrap(), b"receipt");
         assert_eq!(
-            summary.per_payment_method[1].payment_method,
-            PaymentMethod::Cash
+            list_attachments_inner(&db, &data_dir, Some(expense.id.clone())).unwrap(),
+            vec![attachment.clone()]
         );
-        assert_eq!(summary.per_payment_method[1].total_revenue, 400);
-        assert_eq!(summary.per_payment_method[1].transaction_count, 1);
+
+        // The attachment outlives its expense.
+        delete_expense_inner(&db, expense.id).unwrap();
+        let fetched = get_attachment_inner(&db, &data_dir, attachment.id.clone()).unwrap();
+        assert_eq!(fetched.expense_id, None);
+
+        delete_attachment_inner(&db, &data_dir, attachment.id.clone()).unwrap();
+        assert!(!std::path::Path::new(&attachment.path).exists());
+        assert!(get_attachment_inner(&db, &data_dir, attachment.id).is_err());
+
+        let _ = std::fs::remove_file(source);
+        let _ = std::fs::remove_file(text);
+        let _ = std::fs::remove_dir_all(data_dir);
     }
 
     #[test]
-    fn delete_product_success() {
+    fn current_register_restricts_sales_screen_products() {
         let db = init_db_in_memory();
-        let p = make_product(&db, "Temp Item", 100, "snack");
+        let all = list_products_inner(&db, false, &ProductFilter::default()).unwrap();
+        create_category_inner(
+            &db,
+            CreateCategoryPayload {
+                id: "glaces".to_string(),
+                label: "Glaces".to_string(),
+                color: "#0ea5e9".to_string(),
+                translations: Translations::new(),
+                icon: None,
+                parent_id: Some("sucreries".to_string()),
+                allow_custom_color: false,
+            },
+            None,
+        )
+        .unwrap();
+        let crepe = make_product(&db, "Crêpe", 300, "sucreries");
+        let sorbet = make_product(&db, "Sorbet", 250, "glaces");
+        let coffee = make_product(&db, "Café", 150, "boisson-sans-alcool");
+        make_product(&db, "Bière", 350, "alcool");
 
-        let before = list_products_inner(&db).unwrap().len();
-        delete_product_inner(&db, p.id.clone()).unwrap();
-        let after = list_products_inner(&db).unwrap().len();
+        let desserts = create_register_inner(
+            &db,
+            CreateRegisterPayload {
+                name: " Desserts ".to_string(),
+                product_ids: vec![coffee.id.clone()],
+                category_ids: vec!["sucreries".to_string()],
+            },
+        )
+        .unwrap();
+        assert_eq!(desserts.name, "Desserts");
+        assert!(!desserts.current);
 
-        assert_eq!(after, before - 1);
-        assert!(list_products_inner(&db)
+        assert!(set_current_register_inner(&db, Some("unknown".to_string())).is_err());
+        let current = set_current_register_inner(&db, Some(desserts.id.clone()))
             .unwrap()
+            .unwrap();
+        assert!(current.current);
+        // Subcategories of a shown category are shown too.
+        let shown = list_products_inner(&db, true, &ProductFilter::default()).unwrap();
+        assert!(shown.iter().all(
+            |p| p.id == coffee.id || ["sucreries", "glaces"].contains(&p.category_id.as_str())
+        ));
+        assert!([&crepe, &sorbet, &coffee]
             .iter()
-            .all(|prod| prod.id != p.id));
+            .all(|product| shown.iter().any(|p| p.id == product.id)));
+        // The product manager still lists everything.
+        assert_eq!(
+            list_products_inner(&db, false, &ProductFilter::default())
+                .unwrap()
+                .len(),
+            all.len() + 4
+        );
+
+        delete_register_inner(&db, desserts.id).unwrap();
+        assert_eq!(
+            list_products_inner(&db, true, &ProductFilter::default())
+                .unwrap()
+                .len(),
+            all.len() + 4
+        );
+        assert!(list_registers_inner(&db).unwrap().is_empty());
     }
 
     #[test]
-    fn delete_product_not_found() {
+    fn catalog_review_queues_product_edits_for_an_admin() {
         let db = init_db_in_memory();
-        let result = delete_product_inner(&db, "nonexistent".to_string());
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Product not found"));
+        let beer = make_product(&db, "Bière", 350, "alcool");
+        let edit = |price: i64| UpdateProductPayload {
+            id: beer.id.clone(),
+            name: beer.name.clone(),
+            price,
+            category_id: beer.category_id.clone(),
+            available: true,
+            age_restricted: None,
+            unit: None,
+            translations: None,
+        };
+        assert!(set_catalog_review_inner(&db, true, None).is_err());
+        set_admin_pin_inner(&db, None, Some("1234".to_string())).unwrap();
+        assert!(set_catalog_review_inner(&db, true, None).is_err());
+        set_catalog_review_inner(&db, true, Some("1234".to_string())).unwrap();
+        assert!(get_catalog_review_inner(&db).unwrap());
+
+        assert!(update_product_inner(&db, edit(3), None).is_err());
+        let change = propose_product_change_inner(&db, edit(3), " Léa ".to_string()).unwrap();
+        assert_eq!(change.current.price, 350);
+        assert_eq!(change.proposed.price, 3);
+        assert_eq!(change.proposed_by, "Léa");
+        assert!(reject_product_change_inner(&db, change.id.clone(), None).is_err());
+        reject_product_change_inner(&db, change.id, Some("1234".to_string())).unwrap();
+
+        let change = propose_product_change_inner(&db, edit(400), "Léa".to_string()).unwrap();
+        assert_eq!(list_pending_product_changes_inner(&db).unwrap().len(), 1);
+        let approved =
+            approve_product_change_inner(&db, change.id, Some("1234".to_string())).unwrap();
+        assert_eq!(approved.price, 400);
+        assert!(list_pending_product_changes_inner(&db).unwrap().is_empty());
+
+        let updated = update_product_inner(&db, edit(450), Some("1234")).unwrap();
+        assert_eq!(updated.price, 450);
+
+        // Every other catalog change needs the PIN too.
+        let drinks = || CreateCategoryPayload {
+            id: "boissons".to_string(),
+            label: "Boissons".to_string(),
+            color: "#4a90d9".to_string(),
+            translations: Translations::new(),
+            icon: None,
+            parent_id: None,
+            allow_custom_color: true,
+        };
+        assert!(create_category_inner(&db, drinks(), None).is_err());
+        create_category_inner(&db, drinks(), Some("1234")).unwrap();
+        assert!(delete_category_inner(&db, "boissons".to_string(), None).is_err());
+        assert!(toggle_product_availability_inner(&db, beer.id.clone(), None).is_err());
+        assert!(set_product_dates_inner(&db, beer.id.clone(), None, None, None).is_err());
+        assert!(delete_product_inner(&db, beer.id.clone(), None).is_err());
+        delete_product_inner(&db, beer.id.clone(), Some("1234")).unwrap();
     }
 
     #[test]
-    fn delete_product_with_order_items_succeeds() {
+    fn seasonal_products_are_only_listed_between_their_dates() {
         let db = init_db_in_memory();
-        let p = make_product(&db, "Ordered Item", 200, "snack");
-
-        // Create an order referencing this product.
-        create_order_inner(
+        let mulled_wine = make_product(&db, "Vin chaud", 300, "alcool");
+        assert!(set_product_dates_inner(
             &db,
-            CreateOrderPayload {
-                items: vec![CreateOrderItemPayload {
-                    product_id: p.id.clone(),
-                    product_name: "Ordered Item".to_string(),
-                    unit_price: 200,
-                    quantity: 1,
-                }],
-                payment_method: PaymentMethod::Cash,
-            },
+            mulled_wine.id.clone(),
+            Some("2026-12-24".to_string()),
+            Some("2026-11-27".to_string()),
+            None
+        )
+        .is_err());
+        let seasonal = set_product_dates_inner(
+            &db,
+            mulled_wine.id.clone(),
+            Some("2026-11-27".to_string()),
+            Some("2026-12-24".to_string()),
+            None,
         )
         .unwrap();
+        assert_eq!(seasonal.available_until.as_deref(), Some("2026-12-24"));
+
+        let offered_on = |date: &str| {
+            let filter = ProductFilter {
+                available_on: Some(date.to_string()),
+                ..Default::default()
+            };
+            list_products_inner(&db, true, &filter)
+                .unwrap()
+                .iter()
+                .any(|p| p.id == mulled_wine.id)
+        };
+        assert!(!offered_on("2026-11-26"));
+        assert!(offered_on("2026-11-27"));
+        assert!(offered_on("2026-12-24"));
+        assert!(!offered_on("2026-12-25"));
+        // The product manager lists it whatever the date.
+        assert!(list_products_inner(&db, false, &ProductFilter::default())
+            .unwrap()
+            .iter()
+            .any(|p| p.id == mulled_wine.id));
 
-        let result = delete_product_inner(&db, p.id);
-        assert!(result.is_ok());
+        set_product_dates_inner(&db, mulled_wine.id.clone(), None, None, None).unwrap();
+        assert!(offered_on("2027-06-01"));
     }
 
     #[test]
-    fn delete_category_success() {
+    fn bootstrap_workspace_sets_up_a_new_install() {
         let db = init_db_in_memory();
-
-        // Create a fresh category with no products.
-        create_category_inner(
-            &db,
-            CreateCategoryPayload {
-                id: "test-cat".to_string(),
-                label: "Test Cat".to_string(),
-                color: "#ff0000".to_string(),
+        assert!(!needs_bootstrap_inner(&db).unwrap());
+        reset_database_inner(&db, None).unwrap();
+        assert!(needs_bootstrap_inner(&db).unwrap());
+        assert!(list_categories_inner(&db).unwrap().is_empty());
+
+        let payload = |admin_pin: &str| BootstrapPayload {
+            business: BusinessSettings {
+                name: "Amicale laïque".to_string(),
+                ..BusinessSettings::default()
             },
-        )
-        .unwrap();
+            admin_pin: Some(admin_pin.to_string()),
+            display_currency: Some(DisplayCurrencySettings {
+                code: "chf".to_string(),
+                rate: 0.95,
+            }),
+            starter_catalog: true,
+        };
+        // Nothing is saved when a step fails.
+        assert!(bootstrap_workspace_inner(&db, payload("12")).is_err());
+        assert!(needs_bootstrap_inner(&db).unwrap());
+        assert_eq!(load_business_settings(&db).unwrap().name, "");
+
+        bootstrap_workspace_inner(&db, payload("1234")).unwrap();
+        assert!(!needs_bootstrap_inner(&db).unwrap());
+        assert_eq!(load_business_settings(&db).unwrap().name, "Amicale laïque");
+        assert_eq!(get_display_currency_inner(&db).unwrap().code, "CHF");
+        assert!(has_admin_pin_inner(&db).unwrap());
+        assert!(!list_categories_inner(&db).unwrap().is_empty());
+        assert!(bootstrap_workspace_inner(&db, payload("1234")).is_err());
+    }
 
-        let before = list_categories_inner(&db).unwrap().len();
-        delete_category_inner(&db, "test-cat".to_string()).unwrap();
-        let after = list_categories_inner(&db).unwrap().len();
+    #[test]
+    fn repair_database_fixes_rows_referencing_missing_data() {
+        let db = init_db_in_memory();
+        let crepe = make_product(&db, "Crêpe", 300, "sucreries");
+        {
+            let conn = db.conn.lock().unwrap();
+            conn.execute_batch(&format!(
+                "PRAGMA foreign_keys=OFF;
+                 INSERT INTO order_items (id, order_id, product_id, product_name, unit_price, quantity, total)
+                 VALUES ('orphan', 'gone', '{id}', 'Crêpe', 300, 1, 300);
+                 INSERT INTO products (id, name, price, category_id) VALUES ('lost', 'Lost', 100, 'gone');
+                 INSERT INTO catalog_products (catalog_id, product_id) VALUES ('gone', '{id}');
+                 PRAGMA foreign_keys=ON;",
+                id = crepe.id
+            ))
+            .unwrap();
+        }
 
-        assert_eq!(after, before - 1);
+        let report = repair_database_inner(&db, false, None).unwrap();
+        assert!(!report.repaired);
+        let mut tables: Vec<&str> = report.issues.iter().map(|i| i.table.as_str()).collect();
+        tables.sort();
+        assert_eq!(tables, ["catalog_products", "order_items", "products"]);
+        let lost = report
+            .issues
+            .iter()
+            .find(|i| i.table == "products")
+            .unwrap();
+        assert_eq!(lost.fix.as_deref(), Some("Recreate category gone"));
+
+        set_admin_pin_inner(&db, None, Some("1234".to_string())).unwrap();
+        assert!(repair_database_inner(&db, true, None).is_err());
+        let repaired = repair_database_inner(&db, true, Some("1234".to_string())).unwrap();
+        assert!(repaired.repaired);
+        assert_eq!(repaired.issues.len(), 3);
+        assert!(repair_database_inner(&db, false, None)
+            .unwrap()
+            .issues
+            .is_empty());
+        assert!(list_categories_inner(&db)
+            .unwrap()
+            .iter()
+            .any(|c| c.id == "gone"));
     }
 
     #[test]
-    fn delete_category_not_found() {
+    fn strict_schema_rejects_impossible_rows() {
         let db = init_db_in_memory();
-        let result = delete_category_inner(&db, "nonexistent".to_string());
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Category not found"));
+        let crepe = make_product(&db, "Crêpe", 300, "sucreries");
+        let order = make_order(&db, &[(&crepe, 1)], PaymentMethod::Cash);
+        assert!(verify_database_schema_inner(&db).unwrap().valid);
+
+        let mut free = crepe.clone();
+        free.price = -100;
+        assert!(db.update_product(&free).is_err());
+        {
+            let conn = db.conn.lock().unwrap();
+            assert!(conn
+                .execute(
+                    "INSERT INTO order_items (id, order_id, product_id, product_name, unit_price, quantity, total)
+                     VALUES ('zero', ?1, ?2, 'Crêpe', 300, 0, 0)",
+                    params![order.order.id, crepe.id],
+                )
+                .is_err());
+            assert!(conn
+                .execute("DELETE FROM orders WHERE id = ?1", params![order.order.id])
+                .is_err());
+            conn.execute_batch(
+                "PRAGMA ignore_check_constraints=ON;
+                 UPDATE products SET price = -100;
+                 PRAGMA ignore_check_constraints=OFF;",
+            )
+            .unwrap();
+        }
+
+        let verification = verify_database_schema_inner(&db).unwrap();
+        assert!(!verification.valid);
+        assert!(verification.issues.is_empty());
+        assert!(verification
+            .problems
+            .iter()
+            .any(|problem| problem.contains("products")));
     }
 
     #[test]
-    fn delete_category_with_products_fails() {
+    fn dashboard_cache_serves_summary_until_refreshed() {
         let db = init_db_in_memory();
+        let cache = DashboardCache::default();
+        let crepe = make_product(&db, "Crêpe", 300, "sucreries");
+        make_order(&db, &[(&crepe, 1)], PaymentMethod::Cash);
+
+        let first =
+            cached_dashboard_summary_inner(&db, &cache, ProductSummaryQuery::default(), None)
+                .unwrap();
+        assert_eq!(first.total_transactions, 1);
+
+        make_order(&db, &[(&crepe, 2)], PaymentMethod::Card);
+        let cached =
+            cached_dashboard_summary_inner(&db, &cache, ProductSummaryQuery::default(), None)
+                .unwrap();
+        assert_eq!(cached, first);
+
+        let refreshed = refresh_dashboard_inner(&db, &cache).unwrap();
+        assert_eq!(refreshed.total_transactions, 2);
+        assert_eq!(refreshed.total_revenue, 900);
+        assert_eq!(
+            cached_dashboard_summary_inner(&db, &cache, ProductSummaryQuery::default(), None)
+                .unwrap(),
+            refreshed
+        );
+    }
 
-        // The "snack" category has default products referencing it.
-        let result = delete_category_inner(&db, "snack".to_string());
-        assert!(result.is_err());
-        assert!(result.unwrap_err().contains("referenced by"));
+    #[test]
+    fn voids_are_chained_and_journaled() {
+        let db = init_db_in_memory();
+        let p = make_product(&db, "Soda", 200, "boisson-sans-alcool");
+        let kept = make_order(&db, &[(&p, 1)], PaymentMethod::Cash);
+        let mistake = make_order(&db, &[(&p, 3)], PaymentMethod::Card);
+        undo_last_order_inner(&db, None).unwrap();
+
+        let verification = verify_order_chain_inner(&db).unwrap();
+        assert!(verification.valid);
+        assert_eq!(verification.checked_voids, 1);
+
+        let path = temp_path("json");
+        let journal = export_journal_inner(&db, DateRange::default(), path.clone()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(journal.entries.len(), 3);
+        assert!(matches!(
+            &journal.entries[2],
+            JournalEntry::Void { sequence: 3, void } if void.order_id == mistake.order.id
+        ));
+
+        // Un-voiding, or voiding outside the app, breaks the chain.
+        let conn = db.conn.lock().unwrap();
+        conn.execute(
+            "UPDATE orders SET voided_at = NULL WHERE id = ?1",
+            params![mistake.order.id],
+        )
+        .unwrap();
+        conn.execute(
+            "UPDATE orders SET voided_at = '2030-01-01T00:00:00Z' WHERE id = ?1",
+            params![kept.order.id],
+        )
+        .unwrap();
+        drop(conn);
+        let verification = verify_order_chain_inner(&db).unwrap();
+        assert!(!verification.valid);
+        assert_eq!(verification.first_invalid_order_id, Some(kept.order.id));
+    }
+
+    #[test]
+    fn purge_archive_keeps_age_confirmations() {
+        let db = init_db_in_memory();
+        let wine = create_product_inner(
+            &db,
+            CreateProductPayload {
+                name: "Vin chaud".to_string(),
+                price: 300,
+                category_id: "alcool".to_string(),
+                age_restricted: true,
+                unit: QuantityUnit::Piece,
+                translations: Translations::new(),
+            },
+            None,
+        )
+        .unwrap();
+        let order = create_order_inner(
+            &db,
+            CreateOrderPayload {
+                items: vec![CreateOrderItemPayload {
+                    product_id: wine.id.clone(),
+                    product_name: wine.name.clone(),
+                    unit_price: wine.price,
+                    quantity: 1.0,
+                }],
+                payment_method: PaymentMethod::Cash,
+                age_confirmed: true,
+                limits_confirmed: false,
+                adjustments: Vec::new(),
+                source: OrderSource::Counter,
+            },
+        )
+        .unwrap();
+        set_order_created_at(&db, &order.order.id, "2020-01-01T12:00:00Z");
+
+        let archive = temp_path("json");
+        let report =
+            purge_old_data_inner(&db, "2021-01-01".to_string(), archive.clone(), None).unwrap();
+        assert_eq!(report.deleted_orders, 1);
+        let json = std::fs::read_to_string(&archive).unwrap();
+        std::fs::remove_file(&archive).unwrap();
+        let dump: DataDump = serde_json::from_str(&json).unwrap();
+        let confirmation = dump.orders[0].age_confirmation.as_ref().unwrap();
+        assert_eq!(confirmation.restricted_products, "Vin chaud");
+
+        let restored = temp_path("json");
+        std::fs::write(&restored, json).unwrap();
+        import_all_data_inner(&db, restored.clone(), ImportStrategy::Skip, None).unwrap();
+        std::fs::remove_file(&restored).unwrap();
+        let logged: String = db
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT restricted_products FROM age_confirmations WHERE order_id = ?1",
+                params![order.order.id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(logged, "Vin chaud");
+    }
+
+    #[test]
+    fn dashboard_refresh_applies_new_currency_rate() {
+        let db = init_db_in_memory();
+        let cache = DashboardCache::default();
+        let p = make_product(&db, "Foo", 350, "snack");
+        make_order(&db, &[(&p, 1)], PaymentMethod::Cash);
+        let settings = |rate| DisplayCurrencySettings {
+            code: "CHF".to_string(),
+            rate,
+        };
+        update_display_currency_inner(&db, settings(0.95)).unwrap();
+        let cached = refresh_dashboard_inner(&db, &cache).unwrap();
+        assert_eq!(cached.converted_total.unwrap().amount, 333);
+
+        update_display_currency_inner(&db, settings(1.1)).unwrap();
+        let refreshed = refresh_dashboard_inner(&db, &cache).unwrap();
+        assert_eq!(refreshed.converted_total.unwrap().amount, 385);
+        assert_eq!(
+            cached_dashboard_summary_inner(&db, &cache, ProductSummaryQuery::default(), None)
+                .unwrap()
+                .converted_total
+                .unwrap()
+                .amount,
+            385
+        );
     }
 }
@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::Connection;
+
+use crate::export::{Cell, Sheet};
+use crate::models::{CustomReport, CustomReportParam, CustomReportParamKind};
+
+/// A curated report query. Parameters are bound by name (`:from`), so the
+/// values given by the user never end up in the SQL text.
+struct Template {
+    id: &'static str,
+    name: &'static str,
+    description: &'static str,
+    /// (name, kind, required)
+    params: &'static [(&'static str, CustomReportParamKind, bool)],
+    sql: &'static str,
+    /// Integer columns holding cents, written as euro amounts.
+    money_columns: &'static [&'static str],
+}
+
+/// Restricts `o.created_at` to the optional `:from` / `:to` local dates.
+macro_rules! date_filter {
+    () => {
+        "(:from IS NULL OR date(o.created_at, 'localtime') >= :from)
+         AND (:to IS NULL OR date(o.created_at, 'localtime') <= :to)"
+    };
+}
+
+const RANGE_PARAMS: &[(&str, CustomReportParamKind, bool)] = &[
+    ("from", CustomReportParamKind::Date, false),
+    ("to", CustomReportParamKind::Date, false),
+];
+
+const TEMPLATES: &[Template] = &[
+    Template {
+        id: "sales_by_product_and_payment",
+        name: "Sales by product and payment method",
+        description: "Quantity and revenue of each product, split by payment method.",
+        params: RANGE_PARAMS,
+        sql: concat!(
+            "SELECT oi.product_name AS product, o.payment_method,
+                    ROUND(SUM(oi.quantity), 3) AS quantity, SUM(oi.total) AS revenue
+             FROM order_items oi
+             JOIN orders o ON o.id = oi.order_id
+             WHERE o.voided_at IS NULL AND ",
+            date_filter!(),
+            " GROUP BY oi.product_name, o.payment_method
+             ORDER BY revenue DESC, product"
+        ),
+        money_columns: &["revenue"],
+    },
+    Template {
+        id: "daily_sales_by_category",
+        name: "Daily sales by category",
+        description: "Revenue of each category per day.",
+        params: RANGE_PARAMS,
+        sql: concat!(
+            "SELECT date(o.created_at, 'localtime') AS day,
+                    COALESCE(c.label, '?') AS category, SUM(oi.total) AS revenue
+             FROM order_items oi
+             JOIN orders o ON o.id = oi.order_id
+             LEFT JOIN products p ON p.id = oi.product_id
+             LEFT JOIN categories c ON c.id = p.category_id
+             WHERE o.voided_at IS NULL AND ",
+            date_filter!(),
+            " GROUP BY day, category
+             ORDER BY day, revenue DESC"
+        ),
+        money_columns: &["revenue"],
+    },
+    Template {
+        id: "refunds_and_voids",
+        name: "Refunds and voided orders",
+        description: "Every refund and voided order, most recent first.",
+        params: RANGE_PARAMS,
+        sql: concat!(
+            "SELECT o.created_at, o.id AS order_id,
+                    CASE WHEN o.voided_at IS NOT NULL THEN 'void' ELSE 'refund' END AS kind,
+                    o.refund_of, o.payment_method, o.total
+             FROM orders o
+             WHERE (o.voided_at IS NOT NULL OR o.refund_of IS NOT NULL) AND ",
+            date_filter!(),
+            " ORDER BY o.created_at DESC"
+        ),
+        money_columns: &["total"],
+    },
+    Template {
+        id: "orders_with_tag",
+        name: "Orders with a tag",
+        description: "The orders carrying a tag, with their totals.",
+        params: &[
+            ("tag", CustomReportParamKind::Text, true),
+            ("from", CustomReportParamKind::Date, false),
+            ("to", CustomReportParamKind::Date, false),
+        ],
+        sql: concat!(
+            "SELECT o.created_at, o.id AS order_id, o.payment_method, o.total
+             FROM orders o
+             JOIN order_tags t ON t.order_id = o.id AND t.tag = :tag
+             WHERE o.voided_at IS NULL AND ",
+            date_filter!(),
+            " ORDER BY o.created_at"
+        ),
+        money_columns: &["total"],
+    },
+    Template {
+        id: "top_products",
+        name: "Top products",
+        description: "The best-selling products by revenue.",
+        params: &[
+            ("limit", CustomReportParamKind::Integer, true),
+            ("from", CustomReportParamKind::Date, false),
+            ("to", CustomReportParamKind::Date, false),
+        ],
+        sql: concat!(
+            "SELECT oi.product_name AS product, ROUND(SUM(oi.quantity), 3) AS quantity,
+                    SUM(oi.total) AS revenue, COUNT(DISTINCT o.id) AS orders
+             FROM order_items oi
+             JOIN orders o ON o.id = oi.order_id
+             WHERE o.voided_at IS NULL AND ",
+            date_filter!(),
+            " GROUP BY oi.product_name
+             ORDER BY revenue DESC
+             LIMIT :limit"
+        ),
+        money_columns: &["revenue"],
+    },
+];
+
+/// The available reports, for the UI to list and build parameter forms.
+pub fn list() -> Vec<CustomReport> {
+    TEMPLATES
+        .iter()
+        .map(|t| CustomReport {
+            id: t.id.to_string(),
+            name: t.name.to_string(),
+            description: t.description.to_string(),
+            params: t
+                .params
+                .iter()
+                .map(|&(name, kind, required)| CustomReportParam {
+                    name: name.to_string(),
+                    kind,
+                    required,
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+/// Checks and converts the value of one parameter.
+fn param_value(name: &str, kind: CustomReportParamKind, value: &str) -> Result<Value, String> {
+    match kind {
+        CustomReportParamKind::Date => chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map(|_| Value::Text(value.to_string()))
+            .map_err(|e| format!("Invalid date for {name} '{value}' (expected YYYY-MM-DD): {e}")),
+        CustomReportParamKind::Text => Ok(Value::Text(value.to_string())),
+        CustomReportParamKind::Integer => value
+            .parse()
+            .map(Value::Integer)
+            .map_err(|e| format!("Invalid number for {name} '{value}': {e}")),
+    }
+}
+
+/// Runs the report `template_id` with `params` (missing optional parameters
+/// are NULL) and returns its rows. Fails on any statement that could write,
+/// on top of callers using the read-only connection.
+pub fn run(
+    conn: &Connection,
+    template_id: &str,
+    params: &HashMap<String, String>,
+) -> Result<Sheet, String> {
+    let template = TEMPLATES
+        .iter()
+        .find(|t| t.id == template_id)
+        .ok_or_else(|| format!("Unknown report: {template_id}"))?;
+
+    if let Some(unknown) = params.keys().find(|key| {
+        !template
+            .params
+            .iter()
+            .any(|(name, _, _)| *name == key.as_str())
+    }) {
+        return Err(format!("Unknown parameter for {template_id}: {unknown}"));
+    }
+    let mut values = Vec::with_capacity(template.params.len());
+    for &(name, kind, required) in template.params {
+        let value = match params.get(name).map(|v| v.trim()).filter(|v| !v.is_empty()) {
+            Some(value) => param_value(name, kind, value)?,
+            None if required => return Err(format!("Missing parameter: {name}")),
+            None => Value::Null,
+        };
+        values.push((format!(":{name}"), value));
+    }
+
+    let mut stmt = conn
+        .prepare(template.sql)
+        .map_err(|e| format!("Query error: {e}"))?;
+    if !stmt.readonly() {
+        return Err(format!("Report {template_id} is not read-only"));
+    }
+
+    let header: Vec<String> = stmt.column_names().into_iter().map(String::from).collect();
+    let money: Vec<bool> = header
+        .iter()
+        .map(|column| template.money_columns.contains(&column.as_str()))
+        .collect();
+    let mut sheet = Sheet {
+        name: template.name.to_string(),
+        header,
+        rows: Vec::new(),
+    };
+
+    let named: Vec<(&str, &dyn rusqlite::ToSql)> = values
+        .iter()
+        .map(|(name, value)| (name.as_str(), value as &dyn rusqlite::ToSql))
+        .collect();
+    let mut rows = stmt
+        .query(named.as_slice())
+        .map_err(|e| format!("Query error: {e}"))?;
+    while let Some(row) = rows.next().map_err(|e| format!("Query error: {e}"))? {
+        let mut cells = Vec::with_capacity(money.len());
+        for (i, &is_money) in money.iter().enumerate() {
+            let value = row
+                .get_ref(i)
+                .map_err(|e| format!("Row mapping error: {e}"))?;
+            cells.push(match value {
+                ValueRef::Integer(cents) if is_money => Cell::Money(cents),
+                ValueRef::Integer(n) => Cell::Integer(n),
+                ValueRef::Real(x) => Cell::Number(x),
+                ValueRef::Text(text) => Cell::Text(String::from_utf8_lossy(text).into_owned()),
+                ValueRef::Null | ValueRef::Blob(_) => Cell::Text(String::new()),
+            });
+        }
+        sheet.rows.push(cells);
+    }
+
+    Ok(sheet)
+}
@@ -1,20 +1,290 @@
-use rusqlite::Connection;
-use std::sync::Mutex;
+use rusqlite::OptionalExtension;
+use rusqlite::{Connection, Transaction, TransactionBehavior};
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
 use tauri::AppHandle;
 use tauri::Manager;
 
-/// Wrapper around a SQLite connection so it can be managed as Tauri state.
-/// The Mutex ensures that concurrent command invocations do not race on the
-/// single connection.
+use crate::models::MigrationReport;
+
+/// Wrapper around the SQLite connections so they can be managed as Tauri
+/// state. The Mutexes ensure that concurrent command invocations do not race
+/// on a connection.
 pub struct DbState {
     pub conn: Mutex<Connection>,
+    /// Read-only connection to the database file, see `read_conn`. `None`
+    /// for in-memory databases, which cannot be shared between connections.
+    pub reader: Option<Mutex<Connection>>,
     pub db_path: String,
+    /// What `migrate` did when the database was opened.
+    pub migration: MigrationReport,
+}
+
+impl DbState {
+    /// Connection for dashboards, reports and exports. WAL mode lets its
+    /// reads run alongside writes on `conn`, so a heavy export does not hold
+    /// up `create_order`. Falls back to `conn` when there is no reader.
+    pub fn read_conn(&self) -> Result<MutexGuard<'_, Connection>, String> {
+        self.reader
+            .as_ref()
+            .unwrap_or(&self.conn)
+            .lock()
+            .map_err(|e| format!("DB lock error: {e}"))
+    }
+}
+
+/// Opens the read-only connection of `DbState::reader`.
+pub fn open_reader(db_path: &str) -> Result<Connection, String> {
+    let conn = Connection::open_with_flags(db_path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open read connection to {db_path}: {e}"))?;
+    set_busy_timeout(&conn)?;
+    Ok(conn)
+}
+
+/// How long a statement waits for a lock held by another connection, e.g.
+/// a checkpoint or a backup, before failing with SQLITE_BUSY.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Attempts of `write_transaction` on a database that stays busy.
+const WRITE_ATTEMPTS: u32 = 3;
+
+/// Pause before the next attempt, multiplied by the attempt number.
+const WRITE_RETRY_DELAY: Duration = Duration::from_millis(200);
+
+/// Makes `conn` wait for locks instead of failing at once.
+pub fn set_busy_timeout(conn: &Connection) -> Result<(), String> {
+    conn.busy_timeout(BUSY_TIMEOUT)
+        .map_err(|e| format!("Failed to set busy timeout: {e}"))
+}
+
+fn is_busy(error: &rusqlite::Error) -> bool {
+    matches!(
+        error.sqlite_error_code(),
+        Some(rusqlite::ErrorCode::DatabaseBusy | rusqlite::ErrorCode::DatabaseLocked)
+    )
+}
+
+/// Runs `write` in a transaction and commits it. The transaction takes the
+/// write lock upfront, so contention can only fail its start or its commit:
+/// those are retried, up to `WRITE_ATTEMPTS` times, and `write` is run
+/// again on a fresh transaction. Errors of `write` itself are not retried.
+pub fn write_transaction<T>(
+    conn: &mut Connection,
+    mut write: impl FnMut(&Transaction<'_>) -> Result<T, String>,
+) -> Result<T, String> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let retry = attempt < WRITE_ATTEMPTS;
+        let tx = match conn.transaction_with_behavior(TransactionBehavior::Immediate) {
+            Ok(tx) => tx,
+            Err(e) if retry && is_busy(&e) => {
+                std::thread::sleep(WRITE_RETRY_DELAY * attempt);
+                continue;
+            }
+            Err(e) => return Err(format!("Transaction begin error: {e}")),
+        };
+        let value = write(&tx)?;
+        match tx.commit() {
+            Ok(()) => return Ok(value),
+            Err(e) if retry && is_busy(&e) => std::thread::sleep(WRITE_RETRY_DELAY * attempt),
+            Err(e) => return Err(format!("Transaction commit error: {e}")),
+        }
+    }
+}
+
+/// Why the database could not be opened.
+#[derive(Debug)]
+pub enum DbError {
+    /// The file was migrated by a newer build of the app, whose schema this
+    /// build does not know; writing to it could corrupt it.
+    NewerSchema {
+        version: usize,
+        supported: usize,
+    },
+    Other(String),
+}
+
+impl std::fmt::Display for DbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DbError::NewerSchema { version, supported } => write!(
+                f,
+                "This database was created by a newer version of the app (schema version \
+                 {version}, this version supports up to {supported}). Please update the app."
+            ),
+            DbError::Other(message) => f.write_str(message),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl From<String> for DbError {
+    fn from(message: String) -> Self {
+        DbError::Other(message)
+    }
 }
 
+/// `(name, SQL)` of the migration file `migrations/<name>.sql`.
+macro_rules! migration {
+    ($name:literal) => {
+        ($name, include_str!(concat!("./migrations/", $name, ".sql")))
+    };
+}
+
+/// Schema migrations, in order. Never edit or reorder applied ones: the
+/// schema version is the number of migrations applied.
+const MIGRATIONS: &[(&str, &str)] = &[
+    migration!("0-init"),
+    migration!("1-settings"),
+    migration!("2-order-hash-chain"),
+    migration!("3-age-restriction"),
+    migration!("4-quantity-units"),
+    migration!("5-translations"),
+    migration!("6-invoices"),
+    migration!("7-product-barcodes"),
+    migration!("8-void-orders"),
+    migration!("9-refunds"),
+    migration!("10-category-icons"),
+    migration!("11-category-tree"),
+    migration!("12-catalogs"),
+    migration!("13-stock"),
+    migration!("14-stock-reasons"),
+    migration!("15-ingredients"),
+    migration!("16-orders-created-at-index"),
+    migration!("17-order-tags"),
+    migration!("18-report-presets"),
+    migration!("19-scheduled-jobs"),
+    migration!("20-feature-flags"),
+    migration!("21-usage-counters"),
+    migration!("22-surcharge-rules"),
+    migration!("23-tabs"),
+    migration!("24-station-routes"),
+    migration!("25-test-orders"),
+    migration!("26-color-palette"),
+    migration!("27-stock-batches"),
+    migration!("28-order-sources"),
+    migration!("29-expenses"),
+    migration!("30-attachments"),
+    migration!("31-registers"),
+    migration!("32-pending-product-changes"),
+    migration!("33-product-dates"),
+    migration!("34-strict-schema"),
+    migration!("35-void-chain"),
+    migration!("36-ingredient-sales"),
+    migration!("37-invoice-snapshot"),
+];
+
 pub fn migrations() -> Vec<rusqlite_migration::M<'static>> {
-    vec![rusqlite_migration::M::up(include_str!(
-        "./migrations/0-init.sql"
-    ))]
+    MIGRATIONS
+        .iter()
+        .map(|&(_, sql)| rusqlite_migration::M::up(sql))
+        .collect()
+}
+
+/// Dry run of `migrate`: reports the migrations the database is missing
+/// without touching it. The schema version is the number of applied
+/// migrations, recorded in `PRAGMA user_version`; a database from a newer
+/// build is refused.
+pub fn plan_migrations(conn: &Connection) -> Result<MigrationReport, DbError> {
+    let version: i64 = conn
+        .pragma_query_value(None, "user_version", |row| row.get(0))
+        .map_err(|e| format!("Failed to read schema version: {e}"))?;
+    let supported = MIGRATIONS.len();
+    let version = usize::try_from(version).unwrap_or(usize::MAX);
+    if version > supported {
+        return Err(DbError::NewerSchema { version, supported });
+    }
+
+    Ok(MigrationReport {
+        from_version: version,
+        to_version: supported,
+        migrations: MIGRATIONS[version..]
+            .iter()
+            .map(|(name, _)| name.to_string())
+            .collect(),
+        backup_path: None,
+    })
+}
+
+/// Copies the database of `conn` to a new file at `path` with SQLite's
+/// online backup, which, unlike a copy of `pos.db`, includes the pages
+/// still in the WAL. The copy runs in a single step, a consistent snapshot
+/// that in WAL mode holds no more than a read transaction: orders can still
+/// be written meanwhile through another connection.
+pub fn backup_to(conn: &Connection, path: &Path) -> Result<(), String> {
+    let mut dst = Connection::open(path)
+        .map_err(|e| format!("Failed to create backup {}: {e}", path.display()))?;
+    rusqlite::backup::Backup::new(conn, &mut dst)
+        .and_then(|backup| backup.run_to_completion(-1, Duration::ZERO, None))
+        .map_err(|e| format!("Failed to back up the database to {}: {e}", path.display()))
+}
+
+/// Replaces the content of the database of `conn` with the database file at
+/// `path`, e.g. a backup written by `backup_to`. Other connections to the
+/// database see the restored content on their next read.
+pub fn restore_from(conn: &mut Connection, path: &Path) -> Result<(), String> {
+    let src = Connection::open_with_flags(path, rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .map_err(|e| format!("Failed to open backup {}: {e}", path.display()))?;
+    rusqlite::backup::Backup::new(&src, conn)
+        .and_then(|backup| backup.run_to_completion(-1, Duration::ZERO, None))
+        .map_err(|e| format!("Failed to restore {}: {e}", path.display()))
+}
+
+/// Brings the schema up to date. When migrations are due on a database that
+/// already has a schema, it is first backed up to `backup_path`.
+pub fn migrate(
+    conn: &mut Connection,
+    backup_path: Option<&Path>,
+) -> Result<MigrationReport, DbError> {
+    let mut report = plan_migrations(conn)?;
+    if report.migrations.is_empty() {
+        return Ok(report);
+    }
+
+    if let (Some(path), true) = (backup_path, report.from_version > 0) {
+        backup_to(conn, path)?;
+        report.backup_path = Some(path.to_string_lossy().into_owned());
+    }
+
+    // Rebuilding a table (see 34-strict-schema) drops the old one, which
+    // must not cascade to the rows referencing it. Foreign keys cannot be
+    // turned off inside the transaction of a migration.
+    let foreign_keys: bool = conn
+        .pragma_query_value(None, "foreign_keys", |row| row.get(0))
+        .map_err(|e| DbError::Other(format!("Failed to read foreign_keys: {e}")))?;
+    conn.pragma_update(None, "foreign_keys", false)
+        .map_err(|e| DbError::Other(format!("Failed to disable foreign keys: {e}")))?;
+    let migrated = rusqlite_migration::Migrations::new(migrations()).to_latest(conn);
+    conn.pragma_update(None, "foreign_keys", foreign_keys)
+        .map_err(|e| DbError::Other(format!("Failed to restore foreign keys: {e}")))?;
+    migrated.map_err(|e| DbError::Other(format!("Migration error: {e}")))?;
+
+    Ok(report)
+}
+
+/// Reads a value from the key/value `settings` table.
+pub fn get_setting(conn: &Connection, key: &str) -> Result<Option<String>, String> {
+    conn.query_row(
+        "SELECT value FROM settings WHERE key = ?1",
+        rusqlite::params![key],
+        |row| row.get(0),
+    )
+    .optional()
+    .map_err(|e| format!("Failed to read setting {key}: {e}"))
+}
+
+/// Inserts or replaces a value in the key/value `settings` table.
+pub fn set_setting(conn: &Connection, key: &str, value: &str) -> Result<(), String> {
+    conn.execute(
+        "INSERT INTO settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        rusqlite::params![key, value],
+    )
+    .map(|_| ())
+    .map_err(|e| format!("Failed to write setting {key}: {e}"))
 }
 
 #[cfg(test)]
@@ -23,14 +293,15 @@ pub fn init_db_in_memory() -> DbState {
     conn.execute_batch("PRAGMA foreign_keys=ON;")
         .expect("Failed to enable foreign keys");
 
-    let migrations = rusqlite_migration::Migrations::new(migrations());
-    migrations.to_latest(&mut conn).unwrap();
+    let migration = migrate(&mut conn, None).unwrap();
 
-    create_default_data(&conn);
+    create_default_data(&conn).expect("Failed to insert default data");
 
     DbState {
         conn: Mutex::new(conn),
+        reader: None,
         db_path: ":memory:".to_string(),
+        migration,
     }
 }
 
@@ -39,7 +310,7 @@ pub fn init_db_in_memory() -> DbState {
 ///
 /// The database file is named `pos.db` and lives in the directory returned by
 /// `app_handle.path().app_data_dir()`.
-pub fn init_db(app_handle: &AppHandle) -> Result<DbState, String> {
+pub fn init_db(app_handle: &AppHandle) -> Result<DbState, DbError> {
     let data_dir = app_handle
         .path()
         .app_data_dir()
@@ -53,6 +324,7 @@ pub fn init_db(app_handle: &AppHandle) -> Result<DbState, String> {
 
     let mut conn = Connection::open(&db_path)
         .map_err(|e| format!("Failed to open database at {}: {e}", db_path.display()))?;
+    set_busy_timeout(&conn)?;
 
     // Enable WAL mode for better concurrent read performance.
     conn.execute_batch("PRAGMA journal_mode=WAL;")
@@ -62,19 +334,27 @@ pub fn init_db(app_handle: &AppHandle) -> Result<DbState, String> {
     conn.execute_batch("PRAGMA foreign_keys=ON;")
         .map_err(|e| format!("Failed to enable foreign keys: {e}"))?;
 
-    let migrations = rusqlite_migration::Migrations::new(migrations());
-    migrations.to_latest(&mut conn).unwrap();
-
-    create_default_data(&conn);
+    // Kept next to the database, e.g. `pos-v12-20250601-093000.db`.
+    let backup_path = data_dir.join(format!(
+        "pos-v{}-{}.db",
+        plan_migrations(&conn)?.from_version,
+        chrono::Local::now().format("%Y%m%d-%H%M%S")
+    ));
+    let migration = migrate(&mut conn, Some(&backup_path))?;
 
+    let db_path = db_path.to_string_lossy().into_owned();
     Ok(DbState {
         conn: Mutex::new(conn),
-        db_path: db_path.to_string_lossy().into_owned(),
+        reader: Some(Mutex::new(open_reader(&db_path)?)),
+        db_path,
+        migration,
     })
 }
 
-/// Inserts the default categories if they do not already exist.
-pub fn create_default_data(conn: &Connection) {
+/// Inserts the starter catalog of a French club bar (categories and
+/// products) where they do not already exist. New installs get it through
+/// `bootstrap_workspace`, when asked for.
+pub fn create_default_data(conn: &Connection) -> Result<(), String> {
     let defaults = [
         ("snack", "Snack", "#e8a735"),
         ("boisson-sans-alcool", "Boisson sans alcool", "#3b82f6"),
@@ -87,7 +367,7 @@ pub fn create_default_data(conn: &Connection) {
             "INSERT OR IGNORE INTO categories (id, label, color) VALUES (?1, ?2, ?3)",
             rusqlite::params![id, label, color],
         )
-        .expect("Failed to insert default category");
+        .map_err(|e| format!("Failed to insert default category {id}: {e}"))?;
     }
 
     let default_products: [(&str, &str, i64, &str); 20] = [
@@ -113,10 +393,13 @@ pub fn create_default_data(conn: &Connection) {
         ("panini", "Panini", 400, "snack"),
     ];
     for (id, name, price, category_id) in &default_products {
+        let age_restricted = *category_id == "alcool";
         conn.execute(
-            "INSERT OR IGNORE INTO products (id, name, price, category_id) VALUES (?1, ?2, ?3, ?4)",
-            rusqlite::params![id, name, price, category_id],
+            "INSERT OR IGNORE INTO products (id, name, price, category_id, age_restricted)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            rusqlite::params![id, name, price, category_id, age_restricted],
         )
-        .expect("Failed to insert default product");
+        .map_err(|e| format!("Failed to insert default product {id}: {e}"))?;
     }
+    Ok(())
 }
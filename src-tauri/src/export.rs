@@ -0,0 +1,277 @@
+use std::path::{Path, PathBuf};
+
+use rust_xlsxwriter::{ExcelDateTime, Format, Workbook};
+
+use crate::models::{ExportFormat, QuantityUnit};
+
+/// A single value in an exported table.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cell {
+    Text(String),
+    Integer(i64),
+    /// Decimal value, such as a quantity sold by weight.
+    Number(f64),
+    /// Amount in cents, written as a decimal euro value.
+    Money(i64),
+    /// Local date (`YYYY-MM-DD`) or date and time (`YYYY-MM-DDTHH:MM:SS`),
+    /// written as a real date in XLSX.
+    Date(String),
+}
+
+/// A named table: one worksheet in XLSX, one block in CSV.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sheet {
+    pub name: String,
+    pub header: Vec<String>,
+    pub rows: Vec<Vec<Cell>>,
+}
+
+impl Sheet {
+    pub fn new(name: &str, header: &[&str]) -> Self {
+        Sheet {
+            name: name.to_string(),
+            header: header.iter().map(|h| h.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+}
+
+/// Formats cents as a decimal amount ("12.50"), the way the frontend CSV
+/// exports do.
+pub fn format_cents(cents: i64) -> String {
+    let sign = if cents < 0 { "-" } else { "" };
+    let abs = cents.unsigned_abs();
+    format!("{sign}{}.{:02}", abs / 100, abs % 100)
+}
+
+/// Formats a quantity with its unit: "2" for pieces, "0.35 kg", "1.5 L".
+pub fn format_quantity(quantity: f64, unit: QuantityUnit) -> String {
+    match unit {
+        QuantityUnit::Piece => quantity.to_string(),
+        QuantityUnit::Kg => format!("{quantity} kg"),
+        QuantityUnit::Litre => format!("{quantity} L"),
+    }
+}
+
+fn csv_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('"', "\"\""))
+}
+
+/// Renders the sheets as semicolon-separated CSV, each table separated by a
+/// blank line (same layout as the dashboard CSV export of the frontend).
+pub fn to_csv(sheets: &[Sheet]) -> String {
+    let mut lines: Vec<String> = Vec::new();
+    for (i, sheet) in sheets.iter().enumerate() {
+        if i > 0 {
+            lines.push(String::new());
+        }
+        lines.push(
+            sheet
+                .header
+                .iter()
+                .map(|h| csv_quote(h))
+                .collect::<Vec<_>>()
+                .join(";"),
+        );
+        for row in &sheet.rows {
+            lines.push(
+                row.iter()
+                    .map(|cell| match cell {
+                        Cell::Text(s) => csv_quote(s),
+                        Cell::Integer(n) => csv_quote(&n.to_string()),
+                        Cell::Number(n) => csv_quote(&n.to_string()),
+                        Cell::Money(cents) => csv_quote(&format_cents(*cents)),
+                        Cell::Date(date) => csv_quote(&date.replace('T', " ")),
+                    })
+                    .collect::<Vec<_>>()
+                    .join(";"),
+            );
+        }
+    }
+    lines.join("\n")
+}
+
+/// Writes the sheets to an XLSX workbook, one worksheet per sheet.
+pub fn write_xlsx(sheets: &[Sheet], path: &Path) -> Result<(), String> {
+    let mut workbook = Workbook::new();
+    let bold = Format::new().set_bold();
+    let money = Format::new().set_num_format("0.00");
+    let date = Format::new().set_num_format("yyyy-mm-dd");
+    let datetime = Format::new().set_num_format("yyyy-mm-dd hh:mm:ss");
+
+    for sheet in sheets {
+        let worksheet = workbook.add_worksheet();
+        worksheet
+            .set_name(&sheet.name)
+            .map_err(|e| format!("XLSX error: {e}"))?;
+
+        for (col, title) in sheet.header.iter().enumerate() {
+            worksheet
+                .write_string_with_format(0, col as u16, title, &bold)
+                .map_err(|e| format!("XLSX error: {e}"))?;
+        }
+
+        for (r, row) in sheet.rows.iter().enumerate() {
+            let r = (r + 1) as u32;
+            for (col, cell) in row.iter().enumerate() {
+                let col = col as u16;
+                match cell {
+                    Cell::Text(s) => worksheet.write_string(r, col, s),
+                    Cell::Integer(n) => worksheet.write_number(r, col, *n as f64),
+                    Cell::Number(n) => worksheet.write_number(r, col, *n),
+                    Cell::Money(cents) => {
+                        worksheet.write_number_with_format(r, col, *cents as f64 / 100.0, &money)
+                    }
+                    Cell::Date(value) => {
+                        let format = if value.contains('T') {
+                            &datetime
+                        } else {
+                            &date
+                        };
+                        match ExcelDateTime::parse_from_str(value) {
+                            Ok(parsed) => {
+                                worksheet.write_datetime_with_format(r, col, &parsed, format)
+                            }
+                            Err(_) => worksheet.write_string(r, col, value),
+                        }
+                    }
+                }
+                .map_err(|e| format!("XLSX error: {e}"))?;
+            }
+        }
+        worksheet.autofit();
+    }
+
+    workbook
+        .save(path)
+        .map_err(|e| format!("Failed to write {}: {e}", path.display()))
+}
+
+/// Writes the sheets to `path` in the requested format.
+pub fn write_sheets(sheets: &[Sheet], format: ExportFormat, path: &Path) -> Result<(), String> {
+    match format {
+        ExportFormat::Csv => std::fs::write(path, to_csv(sheets))
+            .map_err(|e| format!("Failed to write {}: {e}", path.display())),
+        ExportFormat::Xlsx => write_xlsx(sheets, path),
+    }
+}
+
+/// Writes the sheets of each day, given as `(YYYY-MM-DD, sheets)`. In XLSX
+/// every day goes to the same workbook, its sheet names prefixed with the
+/// day; in CSV each day gets its own file, named after `path` with the day
+/// appended (`sales.csv` becomes `sales-2026-10-16.csv`).
+pub fn write_sheets_by_day(
+    days: Vec<(String, Vec<Sheet>)>,
+    format: ExportFormat,
+    path: &Path,
+) -> Result<(), String> {
+    match format {
+        ExportFormat::Csv => days
+            .into_iter()
+            .try_for_each(|(day, sheets)| write_sheets(&sheets, format, &day_path(path, &day))),
+        ExportFormat::Xlsx => {
+            let sheets: Vec<Sheet> = days
+                .into_iter()
+                .flat_map(|(day, sheets)| {
+                    sheets.into_iter().map(move |sheet| Sheet {
+                        name: format!("{day} {}", sheet.name),
+                        ..sheet
+                    })
+                })
+                .collect();
+            write_xlsx(&sheets, path)
+        }
+    }
+}
+
+/// `path` with `-day` appended to its file stem.
+fn day_path(path: &Path, day: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}-{day}.{}", ext.to_string_lossy()),
+        None => format!("{stem}-{day}"),
+    };
+    path.with_file_name(name)
+}
+
+// ── FEC (Fichier des Écritures Comptables) ──────────────────────────────────
+
+/// Column headers mandated by article A47 A-1 of the French Livre des
+/// procédures fiscales.
+const FEC_HEADER: [&str; 18] = [
+    "JournalCode",
+    "JournalLib",
+    "EcritureNum",
+    "EcritureDate",
+    "CompteNum",
+    "CompteLib",
+    "CompAuxNum",
+    "CompAuxLib",
+    "PieceRef",
+    "PieceDate",
+    "EcritureLib",
+    "Debit",
+    "Credit",
+    "EcritureLet",
+    "DateLet",
+    "ValidDate",
+    "Montantdevise",
+    "Idevise",
+];
+
+/// One line (debit or credit) of a FEC accounting entry.
+#[derive(Debug, Clone, PartialEq)]
+pub struct FecLine {
+    pub journal_code: String,
+    pub journal_label: String,
+    pub entry_number: i64,
+    /// Entry date as `YYYYMMDD`.
+    pub entry_date: String,
+    pub account_number: String,
+    pub account_label: String,
+    pub piece_ref: String,
+    pub label: String,
+    pub debit: i64,
+    pub credit: i64,
+}
+
+/// Formats cents with a decimal comma ("12,50"), as expected in a FEC.
+fn format_fec_amount(cents: i64) -> String {
+    format_cents(cents).replace('.', ",")
+}
+
+/// Renders FEC lines as a tab-separated file, header included.
+pub fn to_fec(lines: &[FecLine]) -> String {
+    let mut out = FEC_HEADER.join("\t");
+    out.push_str("\r\n");
+    for line in lines {
+        let number = line.entry_number.to_string();
+        let debit = format_fec_amount(line.debit);
+        let credit = format_fec_amount(line.credit);
+        let fields = [
+            line.journal_code.as_str(),
+            line.journal_label.as_str(),
+            number.as_str(),
+            line.entry_date.as_str(),
+            line.account_number.as_str(),
+            line.account_label.as_str(),
+            "",
+            "",
+            line.piece_ref.as_str(),
+            line.entry_date.as_str(),
+            line.label.as_str(),
+            debit.as_str(),
+            credit.as_str(),
+            "",
+            "",
+            line.entry_date.as_str(),
+            "",
+            "",
+        ];
+        // Tabs are the field separator and cannot appear inside a value.
+        let fields: Vec<String> = fields.iter().map(|f| f.replace('\t', " ")).collect();
+        out.push_str(&fields.join("\t"));
+        out.push_str("\r\n");
+    }
+    out
+}
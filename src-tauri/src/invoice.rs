@@ -0,0 +1,204 @@
+use crate::export::{format_cents, format_quantity};
+use crate::models::{Invoice, OrderWithItems};
+use crate::pdf::{self, Font, Page};
+
+const MARGIN: f32 = 50.0;
+const ROW_HEIGHT: f32 = 18.0;
+/// Lowest baseline used for item rows before continuing on a new page.
+const LAST_ROW_Y: f32 = pdf::A4_HEIGHT - 150.0;
+
+/// Splits a price including VAT into (excluding VAT, VAT), in cents.
+pub fn vat_breakdown(total_incl_vat: i64, rate_percent: f64) -> (i64, i64) {
+    let excl = (total_incl_vat as f64 / (1.0 + rate_percent / 100.0)).round() as i64;
+    (excl, total_incl_vat - excl)
+}
+
+/// Local calendar date of a stored UTC timestamp.
+fn local_date(created_at: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(created_at)
+        .map(|d| {
+            d.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d")
+                .to_string()
+        })
+        .unwrap_or_else(|_| created_at.to_string())
+}
+
+fn euros(cents: i64) -> String {
+    format!("{} EUR", format_cents(cents))
+}
+
+/// Renders the invoice as an A4 PDF, with the seller details and amounts
+/// recorded when it was issued.
+pub fn render(invoice: &Invoice, order: &OrderWithItems) -> Vec<u8> {
+    let business = &invoice.seller;
+    let right = pdf::A4_WIDTH - MARGIN;
+    // Pages are pushed to `pages` once full; `page` is the one being filled.
+    let mut pages = Vec::new();
+    let mut page = Page::new(pdf::A4_WIDTH, pdf::A4_HEIGHT);
+
+    // Seller.
+    let mut y = 70.0;
+    page.text(MARGIN, y, 16.0, Font::Bold, &business.name);
+    for line in business.address.lines() {
+        y += 14.0;
+        page.text(MARGIN, y, 10.0, Font::Regular, line);
+    }
+    if !business.siret.is_empty() {
+        y += 14.0;
+        page.text(
+            MARGIN,
+            y,
+            10.0,
+            Font::Regular,
+            &format!("SIRET: {}", business.siret),
+        );
+    }
+    if !business.vat_number.is_empty() {
+        y += 14.0;
+        page.text(
+            MARGIN,
+            y,
+            10.0,
+            Font::Regular,
+            &format!("VAT number: {}", business.vat_number),
+        );
+    }
+
+    // Invoice reference.
+    page.text_right(right, 70.0, 20.0, Font::Bold, "INVOICE");
+    page.text_right(
+        right,
+        90.0,
+        10.0,
+        Font::Regular,
+        &format!("No. {}", invoice.number),
+    );
+    page.text_right(
+        right,
+        104.0,
+        10.0,
+        Font::Regular,
+        &format!("Date: {}", invoice.issued_at),
+    );
+    page.text_right(
+        right,
+        118.0,
+        8.0,
+        Font::Regular,
+        &format!("Order {}", order.order.id),
+    );
+
+    // Buyer.
+    let customer_x = pdf::A4_WIDTH / 2.0 + 20.0;
+    let mut y = y.max(118.0) + 40.0;
+    page.text(customer_x, y, 9.0, Font::Regular, "Bill to:");
+    y += 14.0;
+    page.text(customer_x, y, 11.0, Font::Bold, &invoice.customer.name);
+    for line in invoice.customer.address.lines() {
+        y += 14.0;
+        page.text(customer_x, y, 10.0, Font::Regular, line);
+    }
+    if !invoice.customer.vat_number.is_empty() {
+        y += 14.0;
+        page.text(
+            customer_x,
+            y,
+            10.0,
+            Font::Regular,
+            &format!("VAT number: {}", invoice.customer.vat_number),
+        );
+    }
+
+    // Items.
+    let mut y = y + 40.0;
+    let columns = [right - 210.0, right - 100.0, right];
+    let table_header = |page: &mut Page, y: f32| {
+        page.text(MARGIN, y, 10.0, Font::Bold, "Description");
+        page.text_right(columns[0], y, 10.0, Font::Bold, "Quantity");
+        page.text_right(columns[1], y, 10.0, Font::Bold, "Unit price");
+        page.text_right(columns[2], y, 10.0, Font::Bold, "Amount");
+        page.line(MARGIN, y + 6.0, right, y + 6.0);
+    };
+    table_header(&mut page, y);
+
+    for item in &order.items {
+        if y > LAST_ROW_Y {
+            pages.push(std::mem::replace(
+                &mut page,
+                Page::new(pdf::A4_WIDTH, pdf::A4_HEIGHT),
+            ));
+            y = 70.0;
+            table_header(&mut page, y);
+        }
+        y += ROW_HEIGHT;
+        page.text(MARGIN, y, 10.0, Font::Regular, &item.product_name);
+        page.text_right(
+            columns[0],
+            y,
+            10.0,
+            Font::Regular,
+            &format_quantity(item.quantity, item.unit),
+        );
+        page.text_right(columns[1], y, 10.0, Font::Regular, &euros(item.unit_price));
+        page.text_right(columns[2], y, 10.0, Font::Regular, &euros(item.total));
+    }
+
+    // Totals.
+    y += 10.0;
+    page.line(columns[0] - 60.0, y, right, y);
+    let label_x = columns[1];
+    if business.vat_enabled {
+        let vat = invoice.total - invoice.total_excl_vat;
+        y += ROW_HEIGHT;
+        page.text_right(label_x, y, 10.0, Font::Regular, "Total excl. VAT");
+        page.text_right(
+            right,
+            y,
+            10.0,
+            Font::Regular,
+            &euros(invoice.total_excl_vat),
+        );
+        y += ROW_HEIGHT;
+        page.text_right(
+            label_x,
+            y,
+            10.0,
+            Font::Regular,
+            &format!("VAT {}%", business.vat_rate),
+        );
+        page.text_right(right, y, 10.0, Font::Regular, &euros(vat));
+        y += ROW_HEIGHT;
+        page.text_right(label_x, y, 11.0, Font::Bold, "Total incl. VAT");
+    } else {
+        y += ROW_HEIGHT;
+        page.text_right(label_x, y, 11.0, Font::Bold, "Total");
+    }
+    page.text_right(right, y, 11.0, Font::Bold, &euros(invoice.total));
+
+    y += 40.0;
+    page.text(
+        MARGIN,
+        y,
+        10.0,
+        Font::Regular,
+        &format!(
+            "Paid by {} on {}.",
+            order.order.payment_method,
+            local_date(&order.order.created_at)
+        ),
+    );
+    if !business.vat_enabled {
+        y += 14.0;
+        page.text(
+            MARGIN,
+            y,
+            9.0,
+            Font::Regular,
+            "VAT not applicable, article 293 B of the French tax code (CGI).",
+        );
+    }
+
+    pages.push(page);
+    pdf::render(&pages)
+}
@@ -0,0 +1,178 @@
+use crate::export::format_cents;
+use crate::models::{Product, QuantityUnit};
+use crate::pdf::{self, Font, Page};
+
+/// Label sheet layout: 3 x 8 labels of 70 x 37mm filling an A4 page.
+const COLUMNS: usize = 3;
+const ROWS: usize = 8;
+const LABEL_WIDTH: f32 = pdf::A4_WIDTH / COLUMNS as f32;
+const LABEL_HEIGHT: f32 = pdf::A4_HEIGHT / ROWS as f32;
+const PADDING: f32 = 4.0 * pdf::MM;
+
+/// Width of one barcode module: 0.33mm is the nominal EAN-13 size.
+const MODULE: f32 = 0.33 * pdf::MM;
+const BAR_HEIGHT: f32 = 14.0 * pdf::MM;
+/// Guard bars run this much lower than the data bars.
+const GUARD_EXTRA: f32 = 1.5 * pdf::MM;
+
+/// EAN-13 "L" patterns of the digits 0-9, most significant bit first. "R"
+/// patterns are their complement and "G" patterns the reversed "R" ones.
+const L_PATTERNS: [u8; 10] = [
+    0b0001101, 0b0011001, 0b0010011, 0b0111101, 0b0100011, 0b0110001, 0b0101111, 0b0111011,
+    0b0110111, 0b0001011,
+];
+
+/// Which digits of the left half use "G" patterns (bit 5 = second digit),
+/// depending on the first digit, which is not drawn but encoded this way.
+const G_PARITY: [u8; 10] = [
+    0b000000, 0b001011, 0b001101, 0b001110, 0b010011, 0b011001, 0b011100, 0b010101, 0b010110,
+    0b011010,
+];
+
+/// Appends the check digit to the 12 digits of `digits`.
+pub fn ean13(digits: &str) -> String {
+    let sum: u32 = digits
+        .chars()
+        .filter_map(|c| c.to_digit(10))
+        .enumerate()
+        .map(|(i, d)| if i % 2 == 0 { d } else { 3 * d })
+        .sum();
+    format!("{digits}{}", (10 - sum % 10) % 10)
+}
+
+/// The 95 modules (`true` for a bar) of a 13-digit EAN code.
+pub fn ean13_modules(code: &str) -> Result<Vec<bool>, String> {
+    let digits: Vec<u8> = code
+        .chars()
+        .map(|c| c.to_digit(10).map(|d| d as u8))
+        .collect::<Option<_>>()
+        .filter(|d: &Vec<u8>| d.len() == 13)
+        .ok_or_else(|| format!("Invalid EAN-13 code: {code}"))?;
+    if ean13(&code[..12]) != code {
+        return Err(format!("Invalid EAN-13 check digit: {code}"));
+    }
+
+    let mut modules = Vec::with_capacity(95);
+    let mut push = |pattern: u8, width: u32| {
+        for bit in (0..width).rev() {
+            modules.push((pattern >> bit) & 1 == 1);
+        }
+    };
+
+    push(0b101, 3);
+    let parity = G_PARITY[usize::from(digits[0])];
+    for (i, &d) in digits[1..7].iter().enumerate() {
+        let l = L_PATTERNS[usize::from(d)];
+        if (parity >> (5 - i)) & 1 == 1 {
+            push((!l & 0x7f).reverse_bits() >> 1, 7);
+        } else {
+            push(l, 7);
+        }
+    }
+    push(0b01010, 5);
+    for &d in &digits[7..] {
+        push(!L_PATTERNS[usize::from(d)] & 0x7f, 7);
+    }
+    push(0b101, 3);
+
+    Ok(modules)
+}
+
+/// Whether module `i` belongs to the start, middle or end guard.
+fn is_guard(i: usize) -> bool {
+    i < 3 || (45..50).contains(&i) || i >= 92
+}
+
+/// Draws the barcode `modules` with the top-left corner at (`x`, `y`).
+fn draw_barcode(page: &mut Page, modules: &[bool], x: f32, y: f32) {
+    let mut i = 0;
+    while i < modules.len() {
+        if !modules[i] {
+            i += 1;
+            continue;
+        }
+        let start = i;
+        while i < modules.len() && modules[i] && is_guard(i) == is_guard(start) {
+            i += 1;
+        }
+        let height = if is_guard(start) {
+            BAR_HEIGHT + GUARD_EXTRA
+        } else {
+            BAR_HEIGHT
+        };
+        page.rect(
+            x + start as f32 * MODULE,
+            y,
+            (i - start) as f32 * MODULE,
+            height,
+        );
+    }
+}
+
+/// Shortens `text` with an ellipsis so that it fits in `width`.
+fn fit_text(text: &str, width: f32, size: f32, font: Font) -> String {
+    if pdf::text_width(text, size, font) <= width {
+        return text.to_string();
+    }
+    let mut fitted: String = text.to_string();
+    while !fitted.is_empty() && pdf::text_width(&format!("{fitted}…"), size, font) > width {
+        fitted.pop();
+    }
+    format!("{}…", fitted.trim_end())
+}
+
+fn label_price(product: &Product) -> String {
+    let price = format!("{} EUR", format_cents(product.price));
+    match product.unit {
+        QuantityUnit::Piece => price,
+        QuantityUnit::Kg => format!("{price} / kg"),
+        QuantityUnit::Litre => format!("{price} / L"),
+    }
+}
+
+/// Renders one label per product (repeat a product to get several labels)
+/// on A4 label sheets. Every product must have a barcode.
+pub fn render(products: &[Product]) -> Result<Vec<u8>, String> {
+    let per_page = COLUMNS * ROWS;
+    let mut pages = Vec::new();
+
+    for chunk in products.chunks(per_page) {
+        let mut page = Page::new(pdf::A4_WIDTH, pdf::A4_HEIGHT);
+        for (i, product) in chunk.iter().enumerate() {
+            let code = product
+                .barcode
+                .as_deref()
+                .ok_or_else(|| format!("Product has no barcode: {}", product.name))?;
+            let modules = ean13_modules(code)?;
+
+            let left = (i % COLUMNS) as f32 * LABEL_WIDTH;
+            let top = (i / COLUMNS) as f32 * LABEL_HEIGHT;
+            let center = left + LABEL_WIDTH / 2.0;
+            let inner_width = LABEL_WIDTH - 2.0 * PADDING;
+
+            let name = fit_text(&product.name, inner_width, 10.0, Font::Bold);
+            page.text(left + PADDING, top + PADDING + 8.0, 10.0, Font::Bold, &name);
+            page.text_right(
+                left + LABEL_WIDTH - PADDING,
+                top + PADDING + 24.0,
+                14.0,
+                Font::Bold,
+                &label_price(product),
+            );
+
+            let bars_top = top + PADDING + 30.0;
+            let bars_left = center - modules.len() as f32 * MODULE / 2.0;
+            draw_barcode(&mut page, &modules, bars_left, bars_top);
+            page.text_centered(
+                center,
+                bars_top + BAR_HEIGHT + GUARD_EXTRA + 8.0,
+                8.0,
+                Font::Regular,
+                code,
+            );
+        }
+        pages.push(page);
+    }
+
+    Ok(pdf::render(&pages))
+}
@@ -1,6 +1,20 @@
 mod commands;
+mod custom_reports;
 mod db;
+mod export;
+mod invoice;
+mod labels;
+mod mail;
+mod menu;
 mod models;
+mod notify;
+mod pdf;
+mod receipt;
+mod scale;
+mod scheduler;
+mod storage;
+mod store;
+mod telemetry;
 
 use commands::*;
 use tauri::Manager;
@@ -15,6 +29,9 @@ pub fn run() {
             let db_state =
                 db::init_db(app.handle()).map_err(|e| Box::<dyn std::error::Error>::from(e))?;
             app.manage(db_state);
+            app.manage(DashboardCache::default());
+            scheduler::spawn(app.handle().clone());
+            storage::spawn(app.handle().clone());
 
             #[cfg(debug_assertions)]
             if let Some(window) = app.get_webview_window("main") {
@@ -23,23 +40,170 @@ pub fn run() {
 
             Ok(())
         })
-        .invoke_handler(tauri::generate_handler![
+        .invoke_handler(telemetry::count_commands(tauri::generate_handler![
             list_categories,
             create_category,
             update_category,
+            list_palette,
+            update_palette,
             list_products,
             create_product,
             update_product,
+            get_catalog_review,
+            set_catalog_review,
+            propose_product_change,
+            list_pending_product_changes,
+            approve_product_change,
+            reject_product_change,
             toggle_product_availability,
+            set_product_dates,
             delete_product,
             delete_category,
             create_order,
+            split_order,
+            open_tab,
+            list_open_tabs,
+            add_to_tab,
+            remove_from_tab,
+            cancel_tab,
+            settle_tab,
             list_orders,
+            tag_order,
+            untag_order,
+            set_order_test_flag,
+            purge_test_orders,
+            list_order_tags,
+            get_sales_by_tag,
             get_dashboard_summary,
+            get_today_stats,
+            get_revenue_goal,
+            update_revenue_goal,
+            get_goal_progress,
+            get_hourly_breakdown,
+            get_weekday_breakdown,
+            compare_periods,
+            get_sales_timeseries,
+            get_sales_heatmap,
+            get_product_pairs,
+            export_dashboard,
+            export_orders_xlsx,
+            export_fec,
+            get_ledger_accounts,
+            update_ledger_accounts,
+            export_ledger_csv,
+            list_report_presets,
+            create_report_preset,
+            update_report_preset,
+            delete_report_preset,
+            run_report_preset,
+            list_scheduled_jobs,
+            update_schedule,
+            get_smtp_settings,
+            update_smtp_settings,
+            send_report_email,
+            get_notification_settings,
+            update_notification_settings,
+            send_summary_notification,
+            verify_order_chain,
+            export_journal,
+            get_scale_settings,
+            update_scale_settings,
+            read_scale_weight,
+            get_locale_settings,
+            update_locale_settings,
+            get_business_settings,
+            update_business_settings,
+            list_surcharge_rules,
+            set_surcharge_rule,
+            delete_surcharge_rule,
+            get_rounding_settings,
+            update_rounding_settings,
+            get_order_limits,
+            update_order_limits,
+            get_display_currency,
+            update_display_currency,
+            generate_invoice,
+            generate_receipt,
+            export_menu_pdf,
+            generate_kitchen_ticket,
+            generate_station_tickets,
+            list_station_routes,
+            set_station_route,
+            delete_station_route,
+            email_receipt,
+            verify_receipt_code,
+            print_product_labels,
+            undo_last_order,
+            refund_items,
+            exchange_order,
+            list_catalogs,
+            create_catalog,
+            update_catalog,
+            delete_catalog,
+            set_active_catalog,
+            list_registers,
+            create_register,
+            update_register,
+            delete_register,
+            set_current_register,
+            get_stock_levels,
+            start_stocktake,
+            count_stocktake_product,
+            get_stocktake_report,
+            commit_stocktake,
+            cancel_stocktake,
+            record_waste,
+            get_waste_report,
+            add_stock_batch,
+            get_expiring_batches,
+            list_ingredients,
+            get_low_stock_ingredients,
+            create_ingredient,
+            update_ingredient,
+            delete_ingredient,
+            adjust_ingredient_stock,
+            get_recipe,
+            set_recipe,
+            get_reorder_settings,
+            update_reorder_settings,
+            get_reorder_suggestions,
+            list_expenses,
+            create_expense,
+            update_expense,
+            delete_expense,
+            get_net_result,
+            add_attachment,
+            list_attachments,
+            get_attachment,
+            delete_attachment,
+            get_migration_report,
+            check_database_migrations,
+            repair_database,
+            verify_database_schema,
+            backup_database,
+            restore_database,
+            export_all_data,
+            import_all_data,
+            export_settings,
+            import_settings,
+            has_admin_pin,
+            set_admin_pin,
+            get_features,
+            set_feature,
+            get_telemetry_settings,
+            update_telemetry_settings,
+            get_usage_report,
+            upload_usage_report,
+            get_retention_settings,
+            update_retention_settings,
+            purge_old_data,
+            needs_bootstrap,
+            bootstrap_workspace,
             reset_database,
+            get_health,
             get_db_path,
             get_app_version,
-        ])
+        ]))
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
 }
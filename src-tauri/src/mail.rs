@@ -0,0 +1,82 @@
+use lettre::message::header::ContentType;
+use lettre::message::{Attachment, Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{Message, SmtpTransport, Transport};
+
+use crate::models::{SmtpSecurity, SmtpSettings};
+
+/// A file attached to an outgoing email.
+pub struct MailAttachment {
+    pub filename: String,
+    pub content_type: String,
+    pub content: Vec<u8>,
+}
+
+/// Builds a plain-text email with optional attachments, sent from the
+/// configured sender address.
+pub fn build_message(
+    settings: &SmtpSettings,
+    recipients: &[String],
+    subject: &str,
+    body: String,
+    attachments: Vec<MailAttachment>,
+) -> Result<Message, String> {
+    if recipients.is_empty() {
+        return Err("At least one recipient is required".to_string());
+    }
+
+    let from: Mailbox = settings
+        .from
+        .parse()
+        .map_err(|e| format!("Invalid sender address '{}': {e}", settings.from))?;
+
+    let mut builder = Message::builder().from(from).subject(subject);
+    for recipient in recipients {
+        let to: Mailbox = recipient
+            .parse()
+            .map_err(|e| format!("Invalid recipient address '{recipient}': {e}"))?;
+        builder = builder.to(to);
+    }
+
+    let mut parts = MultiPart::mixed().singlepart(SinglePart::plain(body));
+    for attachment in attachments {
+        let content_type = ContentType::parse(&attachment.content_type)
+            .map_err(|e| format!("Invalid content type {}: {e}", attachment.content_type))?;
+        parts = parts.singlepart(
+            Attachment::new(attachment.filename).body(attachment.content, content_type),
+        );
+    }
+
+    builder
+        .multipart(parts)
+        .map_err(|e| format!("Failed to build email: {e}"))
+}
+
+/// Sends a message through the configured SMTP server. Blocks until the
+/// server has accepted (or refused) the message.
+pub fn send(settings: &SmtpSettings, message: &Message) -> Result<(), String> {
+    if settings.host.trim().is_empty() {
+        return Err("SMTP server is not configured".to_string());
+    }
+
+    let builder = match settings.security {
+        SmtpSecurity::Tls => SmtpTransport::relay(&settings.host),
+        SmtpSecurity::StartTls => SmtpTransport::starttls_relay(&settings.host),
+        SmtpSecurity::None => Ok(SmtpTransport::builder_dangerous(&settings.host)),
+    }
+    .map_err(|e| format!("Invalid SMTP server {}: {e}", settings.host))?;
+
+    let mut builder = builder.port(settings.port);
+    if !settings.username.is_empty() {
+        builder = builder.credentials(Credentials::new(
+            settings.username.clone(),
+            settings.password.clone(),
+        ));
+    }
+
+    builder
+        .build()
+        .send(message)
+        .map(|_| ())
+        .map_err(|e| format!("Failed to send email: {e}"))
+}
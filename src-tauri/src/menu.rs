@@ -0,0 +1,83 @@
+use crate::export::format_cents;
+use crate::models::{Category, Product, QuantityUnit};
+use crate::pdf::{self, Font, Page};
+
+const MARGIN: f32 = 20.0 * pdf::MM;
+const LINE_HEIGHT: f32 = 18.0;
+const SWATCH: f32 = 10.0;
+
+/// Parses a `#rrggbb` category color.
+fn parse_color(color: &str) -> Option<[u8; 3]> {
+    let hex = color.trim().strip_prefix('#')?;
+    if hex.len() != 6 {
+        return None;
+    }
+    let channel = |i: usize| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
+
+fn price_label(product: &Product) -> String {
+    let unit = match product.unit {
+        QuantityUnit::Piece => "",
+        QuantityUnit::Kg => " / kg",
+        QuantityUnit::Litre => " / L",
+    };
+    format!("{} EUR{unit}", format_cents(product.price))
+}
+
+/// Renders the price list as A4 pages: `title` at the top, then one section
+/// per category with its color, listing the products and their prices.
+/// Categories without products are left out; a section is never split
+/// right after its heading.
+pub fn render(title: &str, categories: &[Category], products: &[Product]) -> Vec<u8> {
+    let right = pdf::A4_WIDTH - MARGIN;
+    let bottom = pdf::A4_HEIGHT - MARGIN;
+    // Pages are pushed to `pages` once full; `page` is the one being filled.
+    let mut pages = Vec::new();
+    let mut page = Page::new(pdf::A4_WIDTH, pdf::A4_HEIGHT);
+    let mut y = MARGIN + LINE_HEIGHT;
+    page.text_centered(pdf::A4_WIDTH / 2.0, y, 20.0, Font::Bold, title);
+    y += LINE_HEIGHT;
+
+    for category in categories {
+        let items: Vec<&Product> = products
+            .iter()
+            .filter(|p| p.category_id == category.id)
+            .collect();
+        if items.is_empty() {
+            continue;
+        }
+
+        y += LINE_HEIGHT;
+        if y + 2.0 * LINE_HEIGHT > bottom {
+            pages.push(std::mem::replace(
+                &mut page,
+                Page::new(pdf::A4_WIDTH, pdf::A4_HEIGHT),
+            ));
+            y = MARGIN + LINE_HEIGHT;
+        }
+        if let Some(rgb) = parse_color(&category.color) {
+            page.color_rect(MARGIN, y - SWATCH, SWATCH, SWATCH, rgb);
+        }
+        page.text(MARGIN + SWATCH + 6.0, y, 13.0, Font::Bold, &category.path);
+        y += LINE_HEIGHT / 2.0;
+        page.line(MARGIN, y, right, y);
+        y += LINE_HEIGHT;
+
+        for product in items {
+            if y > bottom {
+                pages.push(std::mem::replace(
+                    &mut page,
+                    Page::new(pdf::A4_WIDTH, pdf::A4_HEIGHT),
+                ));
+                y = MARGIN + LINE_HEIGHT;
+            }
+            page.text(MARGIN + SWATCH + 6.0, y, 11.0, Font::Regular, &product.name);
+            page.text_right(right, y, 11.0, Font::Bold, &price_label(product));
+            y += LINE_HEIGHT;
+        }
+    }
+
+    pages.push(page);
+    pdf::render(&pages)
+}
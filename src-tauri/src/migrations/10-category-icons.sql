@@ -0,0 +1,2 @@
+-- Optional emoji or icon name shown next to the category label.
+ALTER TABLE categories ADD COLUMN icon TEXT;
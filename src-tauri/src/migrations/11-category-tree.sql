@@ -0,0 +1,2 @@
+-- Categories can be nested, e.g. "Boissons > Chaudes".
+ALTER TABLE categories ADD COLUMN parent_id TEXT REFERENCES categories(id);
@@ -0,0 +1,15 @@
+-- Named selections of products (e.g. "Buvette", "Repas du soir"). The
+-- active one, stored in the `catalog.active` setting, restricts the products
+-- offered on the sales screen.
+CREATE TABLE IF NOT EXISTS catalogs (
+    id   TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS catalog_products (
+    catalog_id TEXT NOT NULL,
+    product_id TEXT NOT NULL,
+    PRIMARY KEY (catalog_id, product_id),
+    FOREIGN KEY (catalog_id) REFERENCES catalogs(id) ON DELETE CASCADE,
+    FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE
+);
@@ -0,0 +1,33 @@
+-- Stock is not stored but derived: the sum of the stock movements of a
+-- product minus its sold quantities (refund lines, being negative, put items
+-- back in stock; voided orders are ignored).
+CREATE TABLE IF NOT EXISTS stocktakes (
+    id           TEXT PRIMARY KEY NOT NULL,
+    started_at   TEXT NOT NULL,
+    committed_at TEXT
+);
+
+CREATE TABLE IF NOT EXISTS stocktake_counts (
+    stocktake_id TEXT NOT NULL,
+    product_id   TEXT NOT NULL,
+    counted      REAL NOT NULL,
+    -- Stock level when the stocktake was committed.
+    expected     REAL,
+    PRIMARY KEY (stocktake_id, product_id),
+    FOREIGN KEY (stocktake_id) REFERENCES stocktakes(id) ON DELETE CASCADE,
+    FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS stock_movements (
+    id           TEXT PRIMARY KEY NOT NULL,
+    product_id   TEXT NOT NULL,
+    kind         TEXT NOT NULL,
+    quantity     REAL NOT NULL,
+    stocktake_id TEXT,
+    created_at   TEXT NOT NULL,
+    FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE,
+    FOREIGN KEY (stocktake_id) REFERENCES stocktakes(id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_stock_movements_product_id ON stock_movements (product_id);
+CREATE INDEX IF NOT EXISTS idx_order_items_product_id ON order_items (product_id);
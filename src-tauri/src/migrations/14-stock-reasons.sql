@@ -0,0 +1,2 @@
+-- Free-text reason of a stock movement, e.g. why goods were thrown away.
+ALTER TABLE stock_movements ADD COLUMN reason TEXT;
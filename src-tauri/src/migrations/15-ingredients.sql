@@ -0,0 +1,32 @@
+-- Ingredients consumed by the products sold, following their recipes. Like
+-- products, their stock is derived: movements (deliveries, corrections) minus
+-- the consumption of the non-voided sales, computed from the current recipes.
+CREATE TABLE IF NOT EXISTS ingredients (
+    id                  TEXT PRIMARY KEY NOT NULL,
+    name                TEXT NOT NULL UNIQUE,
+    unit                TEXT NOT NULL DEFAULT 'piece',
+    -- Stock at or below which the ingredient is reported as running low.
+    low_stock_threshold REAL
+);
+
+-- Quantity of each ingredient used per unit (piece, kg, litre) of a product.
+CREATE TABLE IF NOT EXISTS recipe_items (
+    product_id    TEXT NOT NULL,
+    ingredient_id TEXT NOT NULL,
+    quantity      REAL NOT NULL,
+    PRIMARY KEY (product_id, ingredient_id),
+    FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE,
+    FOREIGN KEY (ingredient_id) REFERENCES ingredients(id)
+);
+
+CREATE TABLE IF NOT EXISTS ingredient_movements (
+    id            TEXT PRIMARY KEY NOT NULL,
+    ingredient_id TEXT NOT NULL,
+    quantity      REAL NOT NULL,
+    reason        TEXT,
+    created_at    TEXT NOT NULL,
+    FOREIGN KEY (ingredient_id) REFERENCES ingredients(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_recipe_items_ingredient_id ON recipe_items (ingredient_id);
+CREATE INDEX IF NOT EXISTS idx_ingredient_movements_ingredient_id ON ingredient_movements (ingredient_id);
@@ -0,0 +1,2 @@
+-- Lets queries on recent orders (e.g. today's stats) skip older ones.
+CREATE INDEX IF NOT EXISTS idx_orders_created_at ON orders (created_at);
@@ -0,0 +1,9 @@
+-- Free-form labels on orders, e.g. "VIP" or "erreur-caisse".
+CREATE TABLE IF NOT EXISTS order_tags (
+    order_id TEXT NOT NULL,
+    tag      TEXT NOT NULL,
+    PRIMARY KEY (order_id, tag),
+    FOREIGN KEY (order_id) REFERENCES orders(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_order_tags_tag ON order_tags (tag);
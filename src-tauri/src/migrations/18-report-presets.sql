@@ -0,0 +1,6 @@
+-- Named report configurations, see `ReportPresetConfig` for `config`.
+CREATE TABLE IF NOT EXISTS report_presets (
+    id     TEXT PRIMARY KEY NOT NULL,
+    name   TEXT NOT NULL UNIQUE,
+    config TEXT NOT NULL
+);
@@ -0,0 +1,12 @@
+-- Recurring jobs run by the scheduler, one row per configured job kind.
+CREATE TABLE IF NOT EXISTS scheduled_jobs (
+    kind        TEXT PRIMARY KEY NOT NULL,
+    enabled     INTEGER NOT NULL DEFAULT 0,
+    -- Local time of day, `HH:MM`.
+    time        TEXT NOT NULL,
+    -- JSON array of email addresses, for report emails.
+    recipients  TEXT NOT NULL DEFAULT '[]',
+    last_run_at TEXT,
+    -- NULL when the last run succeeded.
+    last_error  TEXT
+);
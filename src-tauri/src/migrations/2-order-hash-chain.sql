@@ -0,0 +1,5 @@
+-- Tamper-evidence chain: each order stores the hash of the previous order
+-- and a hash of its own content. Orders recorded before this migration keep
+-- NULL hashes and are not part of the chain.
+ALTER TABLE orders ADD COLUMN previous_hash TEXT;
+ALTER TABLE orders ADD COLUMN hash TEXT;
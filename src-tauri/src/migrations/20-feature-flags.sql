@@ -0,0 +1,6 @@
+-- Optional subsystems switched off by the association; a feature without a
+-- row is enabled.
+CREATE TABLE IF NOT EXISTS feature_flags (
+    feature TEXT PRIMARY KEY NOT NULL,
+    enabled INTEGER NOT NULL
+);
@@ -0,0 +1,6 @@
+-- Anonymous usage metrics, only recorded once the user opted in: how many
+-- times each command was called since the last upload.
+CREATE TABLE IF NOT EXISTS usage_counters (
+    name  TEXT PRIMARY KEY NOT NULL,
+    count INTEGER NOT NULL
+);
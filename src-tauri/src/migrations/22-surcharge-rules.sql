@@ -0,0 +1,7 @@
+-- Amount added to orders paid by `payment_method`, when their total is
+-- below `below` (always when NULL). Amounts in cents.
+CREATE TABLE IF NOT EXISTS surcharge_rules (
+    payment_method TEXT PRIMARY KEY NOT NULL,
+    amount         INTEGER NOT NULL,
+    below          INTEGER
+);
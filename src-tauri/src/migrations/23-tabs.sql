@@ -0,0 +1,20 @@
+-- Open tabs: items served now and paid later, when the tab is settled.
+CREATE TABLE IF NOT EXISTS tabs (
+    id         TEXT PRIMARY KEY NOT NULL,
+    name       TEXT NOT NULL,
+    opened_at  TEXT NOT NULL,
+    settled_at TEXT
+);
+
+CREATE TABLE IF NOT EXISTS tab_items (
+    id           TEXT PRIMARY KEY NOT NULL,
+    tab_id       TEXT NOT NULL,
+    product_id   TEXT NOT NULL,
+    product_name TEXT NOT NULL,
+    unit_price   INTEGER NOT NULL,
+    quantity     REAL NOT NULL,
+    added_at     TEXT NOT NULL,
+    FOREIGN KEY (tab_id) REFERENCES tabs(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_tab_items_tab_id ON tab_items (tab_id);
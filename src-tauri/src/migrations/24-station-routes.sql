@@ -0,0 +1,7 @@
+-- Station (e.g. "Bar", "Kitchen") receiving the preparation tickets of the
+-- products of a category and of its subcategories.
+CREATE TABLE IF NOT EXISTS station_routes (
+    category_id TEXT PRIMARY KEY NOT NULL,
+    station     TEXT NOT NULL,
+    FOREIGN KEY (category_id) REFERENCES categories(id) ON DELETE CASCADE
+);
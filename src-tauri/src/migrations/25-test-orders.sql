@@ -0,0 +1,2 @@
+-- Sales made to try the till out, voided in bulk by `purge_test_orders`.
+ALTER TABLE orders ADD COLUMN is_test INTEGER NOT NULL DEFAULT 0;
@@ -0,0 +1,6 @@
+-- Approved category colors, in display order. Empty: any color is accepted.
+CREATE TABLE IF NOT EXISTS palette_colors (
+    color    TEXT PRIMARY KEY NOT NULL,
+    name     TEXT NOT NULL,
+    position INTEGER NOT NULL
+);
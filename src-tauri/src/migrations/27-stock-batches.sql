@@ -0,0 +1,10 @@
+-- Goods put in stock, each batch being the `receipt` stock movement that
+-- brought it in, with the date it must be sold by.
+CREATE TABLE IF NOT EXISTS stock_batches (
+    movement_id TEXT PRIMARY KEY NOT NULL,
+    -- `YYYY-MM-DD`; NULL for goods that do not expire.
+    expires_on  TEXT,
+    FOREIGN KEY (movement_id) REFERENCES stock_movements(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_stock_batches_expires_on ON stock_batches (expires_on);
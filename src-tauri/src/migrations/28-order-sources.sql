@@ -0,0 +1,2 @@
+-- Channel an order came through: counter, preorder, phone or online.
+ALTER TABLE orders ADD COLUMN source TEXT NOT NULL DEFAULT 'counter';
@@ -0,0 +1,13 @@
+-- Money spent for the stand (supplies, rental...), set against the sales in
+-- the net result report. Amounts are in cents.
+CREATE TABLE IF NOT EXISTS expenses (
+    id TEXT PRIMARY KEY,
+    spent_on TEXT NOT NULL,
+    amount INTEGER NOT NULL,
+    category TEXT NOT NULL,
+    note TEXT NOT NULL DEFAULT '',
+    receipt_path TEXT,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_expenses_spent_on ON expenses(spent_on);
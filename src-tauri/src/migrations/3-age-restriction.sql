@@ -0,0 +1,12 @@
+ALTER TABLE products ADD COLUMN age_restricted INTEGER NOT NULL DEFAULT 0;
+
+UPDATE products SET age_restricted = 1 WHERE category_id = 'alcool';
+
+-- One row per order containing age-restricted products, recording that the
+-- cashier explicitly confirmed the customer's age.
+CREATE TABLE IF NOT EXISTS age_confirmations (
+    order_id             TEXT PRIMARY KEY NOT NULL,
+    confirmed_at         TEXT NOT NULL,
+    restricted_products  TEXT NOT NULL,
+    FOREIGN KEY (order_id) REFERENCES orders(id) ON DELETE CASCADE
+);
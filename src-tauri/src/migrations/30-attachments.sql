@@ -0,0 +1,14 @@
+-- Files (receipt photos, supplier invoices...) copied to the attachments
+-- folder of the app data dir under `stored_name`. An attachment outlives the
+-- expense it belongs to, so that its file is never left untracked.
+CREATE TABLE IF NOT EXISTS attachments (
+    id TEXT PRIMARY KEY,
+    expense_id TEXT REFERENCES expenses(id) ON DELETE SET NULL,
+    file_name TEXT NOT NULL,
+    stored_name TEXT NOT NULL,
+    content_type TEXT NOT NULL,
+    size INTEGER NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_attachments_expense ON attachments(expense_id);
@@ -0,0 +1,23 @@
+-- Tills of an event (e.g. "Bar", "Desserts"). The one this till is, stored
+-- in the `register.current` setting, restricts the sales screen to its
+-- products and to the products of its categories and their subcategories.
+CREATE TABLE IF NOT EXISTS registers (
+    id   TEXT PRIMARY KEY NOT NULL,
+    name TEXT NOT NULL UNIQUE
+);
+
+CREATE TABLE IF NOT EXISTS register_products (
+    register_id TEXT NOT NULL,
+    product_id TEXT NOT NULL,
+    PRIMARY KEY (register_id, product_id),
+    FOREIGN KEY (register_id) REFERENCES registers(id) ON DELETE CASCADE,
+    FOREIGN KEY (product_id) REFERENCES products(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS register_categories (
+    register_id TEXT NOT NULL,
+    category_id TEXT NOT NULL,
+    PRIMARY KEY (register_id, category_id),
+    FOREIGN KEY (register_id) REFERENCES registers(id) ON DELETE CASCADE,
+    FOREIGN KEY (category_id) REFERENCES categories(id) ON DELETE CASCADE
+);
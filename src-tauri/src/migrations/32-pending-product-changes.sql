@@ -0,0 +1,10 @@
+-- Product edits proposed while catalog review is on (`catalog.review`
+-- setting), waiting for an admin to approve or reject them. `payload` holds
+-- the proposed `UpdateProductPayload` as JSON.
+CREATE TABLE IF NOT EXISTS pending_product_changes (
+    id TEXT PRIMARY KEY NOT NULL,
+    product_id TEXT NOT NULL REFERENCES products(id) ON DELETE CASCADE,
+    payload TEXT NOT NULL,
+    proposed_by TEXT NOT NULL DEFAULT '',
+    created_at TEXT NOT NULL
+);
@@ -0,0 +1,4 @@
+-- First and last local date (YYYY-MM-DD) a seasonal product is offered on
+-- the sales screen; NULL leaves that side open.
+ALTER TABLE products ADD COLUMN available_from TEXT;
+ALTER TABLE products ADD COLUMN available_until TEXT;
@@ -0,0 +1,71 @@
+-- Constraints the first tables were created without, so that a buggy client
+-- cannot store impossible rows: prices are never negative, only refund lines
+-- have a negative quantity, and a category, order or sold line still
+-- referenced cannot be deleted. SQLite cannot add them to existing tables,
+-- which are rebuilt; `migrate` runs with foreign keys off for that.
+-- Existing rows breaking them are copied as they are, for
+-- `verify_database_schema` to report.
+--
+-- The other tables keep their ON DELETE CASCADE, on purpose:
+-- - age_confirmations and order_tags describe their order and nothing
+--   else. Orders are only deleted by `purge_old_data`, after archiving
+--   them with their tags and age confirmations, and their lines must be
+--   deleted first anyway.
+-- - stock_movements and stocktake_counts only tell the stock of their
+--   product, which means nothing once the product is gone. Sold lines keep
+--   the product name and price, so the sales history does not need them.
+PRAGMA ignore_check_constraints = ON;
+
+CREATE TABLE products_new (
+    id              TEXT PRIMARY KEY NOT NULL,
+    name            TEXT NOT NULL,
+    price           INTEGER NOT NULL CHECK (price >= 0),
+    category_id     TEXT NOT NULL,
+    available       INTEGER NOT NULL DEFAULT 1,
+    age_restricted  INTEGER NOT NULL DEFAULT 0,
+    unit            TEXT NOT NULL DEFAULT 'piece' CHECK (unit IN ('piece', 'kg', 'litre')),
+    translations    TEXT NOT NULL DEFAULT '{}',
+    barcode         TEXT,
+    available_from  TEXT,
+    available_until TEXT,
+    FOREIGN KEY (category_id) REFERENCES categories(id) ON DELETE RESTRICT
+);
+
+INSERT INTO products_new
+    (id, name, price, category_id, available, age_restricted, unit, translations, barcode,
+     available_from, available_until)
+SELECT id, name, price, category_id, available, age_restricted, unit, translations, barcode,
+       available_from, available_until
+FROM products;
+
+DROP TABLE products;
+ALTER TABLE products_new RENAME TO products;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_products_barcode ON products(barcode);
+
+CREATE TABLE order_items_new (
+    id               TEXT PRIMARY KEY NOT NULL,
+    order_id         TEXT NOT NULL,
+    product_id       TEXT NOT NULL,
+    product_name     TEXT NOT NULL,
+    unit_price       INTEGER NOT NULL,
+    quantity         INTEGER NOT NULL CHECK (quantity > 0 OR refunded_item_id IS NOT NULL),
+    total            INTEGER NOT NULL,
+    unit             TEXT NOT NULL DEFAULT 'piece' CHECK (unit IN ('piece', 'kg', 'litre')),
+    refunded_item_id TEXT,
+    FOREIGN KEY (order_id) REFERENCES orders(id) ON DELETE RESTRICT,
+    FOREIGN KEY (refunded_item_id) REFERENCES order_items(id) ON DELETE RESTRICT
+);
+
+INSERT INTO order_items_new
+    (id, order_id, product_id, product_name, unit_price, quantity, total, unit, refunded_item_id)
+SELECT id, order_id, product_id, product_name, unit_price, quantity, total, unit, refunded_item_id
+FROM order_items;
+
+DROP TABLE order_items;
+ALTER TABLE order_items_new RENAME TO order_items;
+
+CREATE INDEX IF NOT EXISTS idx_order_items_order_id ON order_items (order_id);
+CREATE INDEX IF NOT EXISTS idx_order_items_product_id ON order_items (product_id);
+
+PRAGMA ignore_check_constraints = OFF;
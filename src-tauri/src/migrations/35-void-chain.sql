@@ -0,0 +1,14 @@
+-- Each void is an entry of its own hash chain, so that voiding or un-voiding
+-- an order outside the app breaks `verify_order_chain`. Voids recorded
+-- before this migration keep NULL hashes and are not part of the chain.
+-- No foreign key: voids stay in the chain when `purge_old_data` deletes
+-- their orders.
+CREATE TABLE IF NOT EXISTS order_voids (
+    order_id      TEXT PRIMARY KEY NOT NULL,
+    voided_at     TEXT NOT NULL,
+    previous_hash TEXT,
+    hash          TEXT
+);
+
+INSERT INTO order_voids (order_id, voided_at)
+SELECT id, voided_at FROM orders WHERE voided_at IS NOT NULL ORDER BY rowid;
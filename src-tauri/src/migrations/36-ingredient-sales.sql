@@ -0,0 +1,17 @@
+-- Sales consume ingredients through movements written when the order is
+-- saved, following the recipes of that moment, so that editing a recipe
+-- leaves past consumption alone. The sales recorded before this migration
+-- are backfilled from the current recipes, which keeps stock levels as
+-- they were computed until now.
+ALTER TABLE ingredient_movements ADD COLUMN order_item_id TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_ingredient_movements_order_item
+    ON ingredient_movements (order_item_id);
+
+INSERT INTO ingredient_movements (id, ingredient_id, quantity, reason, created_at, order_item_id)
+SELECT lower(hex(randomblob(16))), r.ingredient_id, -(oi.quantity * r.quantity), 'sale',
+       o.created_at, oi.id
+FROM order_items oi
+JOIN orders o ON o.id = oi.order_id
+JOIN recipe_items r ON r.product_id = oi.product_id
+WHERE o.voided_at IS NULL;
@@ -0,0 +1,26 @@
+-- An invoice keeps the seller details, VAT settings and amounts it was
+-- issued with, so that reprints match the original whatever the business
+-- settings become. Invoices issued before this migration take the current
+-- settings, which is what their reprints showed until now.
+ALTER TABLE invoices ADD COLUMN seller_name TEXT NOT NULL DEFAULT '';
+ALTER TABLE invoices ADD COLUMN seller_address TEXT NOT NULL DEFAULT '';
+ALTER TABLE invoices ADD COLUMN seller_siret TEXT NOT NULL DEFAULT '';
+ALTER TABLE invoices ADD COLUMN seller_vat_number TEXT NOT NULL DEFAULT '';
+ALTER TABLE invoices ADD COLUMN vat_enabled INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE invoices ADD COLUMN vat_rate REAL NOT NULL DEFAULT 20.0;
+ALTER TABLE invoices ADD COLUMN total INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE invoices ADD COLUMN total_excl_vat INTEGER NOT NULL DEFAULT 0;
+
+UPDATE invoices SET
+    seller_name = COALESCE((SELECT value FROM settings WHERE key = 'business.name'), ''),
+    seller_address = COALESCE((SELECT value FROM settings WHERE key = 'business.address'), ''),
+    seller_siret = COALESCE((SELECT value FROM settings WHERE key = 'business.siret'), ''),
+    seller_vat_number = COALESCE((SELECT value FROM settings WHERE key = 'business.vat_number'), ''),
+    vat_enabled = COALESCE((SELECT value = '1' FROM settings WHERE key = 'business.vat_enabled'), 0),
+    vat_rate = COALESCE((SELECT CAST(value AS REAL) FROM settings WHERE key = 'business.vat_rate'), 20.0),
+    total = COALESCE((SELECT o.total FROM orders o WHERE o.id = invoices.order_id), 0);
+
+UPDATE invoices SET total_excl_vat = CASE
+    WHEN vat_enabled THEN CAST(round(total / (1.0 + vat_rate / 100.0)) AS INTEGER)
+    ELSE total
+END;
@@ -0,0 +1,8 @@
+-- Products can be sold by weight or volume. Their quantities are decimal;
+-- SQLite keeps non-integral values in the INTEGER `quantity` column as REAL.
+ALTER TABLE products ADD COLUMN unit TEXT NOT NULL DEFAULT 'piece'
+    CHECK (unit IN ('piece', 'kg', 'litre'));
+
+-- Unit snapshot at sale time, like the product name and price.
+ALTER TABLE order_items ADD COLUMN unit TEXT NOT NULL DEFAULT 'piece'
+    CHECK (unit IN ('piece', 'kg', 'litre'));
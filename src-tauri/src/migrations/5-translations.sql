@@ -0,0 +1,4 @@
+-- Optional labels per locale, stored as a JSON object such as {"en": "Beer"}.
+-- The `label` / `name` column stays the default when a locale is missing.
+ALTER TABLE categories ADD COLUMN translations TEXT NOT NULL DEFAULT '{}';
+ALTER TABLE products ADD COLUMN translations TEXT NOT NULL DEFAULT '{}';
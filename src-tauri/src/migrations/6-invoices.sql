@@ -0,0 +1,11 @@
+-- Invoices are numbered sequentially without gaps. An order gets at most one
+-- invoice; generating it again reprints the same number and buyer details.
+CREATE TABLE IF NOT EXISTS invoices (
+    number              INTEGER PRIMARY KEY NOT NULL,
+    order_id            TEXT NOT NULL UNIQUE,
+    issued_at           TEXT NOT NULL,
+    customer_name       TEXT NOT NULL,
+    customer_address    TEXT NOT NULL,
+    customer_vat_number TEXT NOT NULL,
+    FOREIGN KEY (order_id) REFERENCES orders(id)
+);
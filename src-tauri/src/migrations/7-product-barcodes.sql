@@ -0,0 +1,5 @@
+-- In-store EAN-13 code printed on product labels, assigned when the first
+-- label of the product is printed.
+ALTER TABLE products ADD COLUMN barcode TEXT;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_products_barcode ON products(barcode);
@@ -0,0 +1,3 @@
+-- Voided orders stay in the hash chain and the journal, but are left out of
+-- sales figures and accounting exports.
+ALTER TABLE orders ADD COLUMN voided_at TEXT;
@@ -0,0 +1,4 @@
+-- A refund is an order of its own, chained like any other, whose lines
+-- negate (part of) the lines of the refunded order.
+ALTER TABLE orders ADD COLUMN refund_of TEXT REFERENCES orders(id);
+ALTER TABLE order_items ADD COLUMN refunded_item_id TEXT REFERENCES order_items(id);
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::fmt;
 
 use serde::{Deserialize, Serialize};
@@ -11,6 +12,19 @@ pub struct AppVersion {
     pub arch: String,
 }
 
+// ── Database ─────────────────────────────────────────────────────────────────
+
+/// Schema migrations applied, or in a dry run to be applied, to a database.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MigrationReport {
+    pub from_version: usize,
+    pub to_version: usize,
+    /// Names of the migrations, in order.
+    pub migrations: Vec<String>,
+    /// Copy of the database taken before migrating it, if any.
+    pub backup_path: Option<String>,
+}
+
 // ── PaymentMethod ───────────────────────────────────────────────────────────
 
 /// The accepted payment methods.
@@ -51,14 +65,100 @@ impl PaymentMethod {
     }
 }
 
+// ── OrderSource ─────────────────────────────────────────────────────────────
+
+/// Channel an order came through, to compare e.g. online pre-sales with
+/// walk-up sales. Serializes to/from lowercase strings ("counter", ...).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OrderSource {
+    /// Sold at the till.
+    #[default]
+    Counter,
+    /// Ordered ahead and collected at the stand.
+    Preorder,
+    Phone,
+    Online,
+}
+
+impl OrderSource {
+    /// Parse a string from the database into an `OrderSource`.
+    pub fn from_db_str(s: &str) -> Result<Self, String> {
+        match s {
+            "counter" => Ok(OrderSource::Counter),
+            "preorder" => Ok(OrderSource::Preorder),
+            "phone" => Ok(OrderSource::Phone),
+            "online" => Ok(OrderSource::Online),
+            other => Err(format!("Unknown order source: {other}")),
+        }
+    }
+
+    /// Return the lowercase string representation stored in SQLite.
+    pub fn as_db_str(&self) -> &'static str {
+        match self {
+            OrderSource::Counter => "counter",
+            OrderSource::Preorder => "preorder",
+            OrderSource::Phone => "phone",
+            OrderSource::Online => "online",
+        }
+    }
+}
+
+// ── QuantityUnit ────────────────────────────────────────────────────────────
+
+/// The unit a product is sold by. Products sold by weight or volume are
+/// priced per kilogram or litre and accept decimal quantities.
+/// Serializes to/from lowercase strings ("piece", "kg", "litre").
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuantityUnit {
+    #[default]
+    Piece,
+    Kg,
+    Litre,
+}
+
+impl QuantityUnit {
+    /// Parse a string from the database into a `QuantityUnit`.
+    pub fn from_db_str(s: &str) -> Result<Self, String> {
+        match s {
+            "piece" => Ok(QuantityUnit::Piece),
+            "kg" => Ok(QuantityUnit::Kg),
+            "litre" => Ok(QuantityUnit::Litre),
+            other => Err(format!("Unknown quantity unit: {other}")),
+        }
+    }
+
+    /// Return the lowercase string representation stored in SQLite.
+    pub fn as_db_str(&self) -> &'static str {
+        match self {
+            QuantityUnit::Piece => "piece",
+            QuantityUnit::Kg => "kg",
+            QuantityUnit::Litre => "litre",
+        }
+    }
+}
+
 // ── Category ─────────────────────────────────────────────────────────────────
 
+/// Labels keyed by locale code (e.g. `"en"`). A screen whose locale is
+/// missing falls back to the default label.
+pub type Translations = BTreeMap<String, String>;
+
 /// A product category with display label and color.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Category {
     pub id: String,
     pub label: String,
     pub color: String,
+    pub translations: Translations,
+    /// Emoji (e.g. "🍺") or icon name (e.g. "beer-mug").
+    pub icon: Option<String>,
+    /// Parent category, `None` for top-level categories.
+    pub parent_id: Option<String>,
+    /// Labels from the top-level category down to this one, joined with
+    /// " > " (e.g. "Boissons > Chaudes").
+    pub path: String,
 }
 
 /// Payload sent from the frontend when creating a new category.
@@ -67,6 +167,15 @@ pub struct CreateCategoryPayload {
     pub id: String,
     pub label: String,
     pub color: String,
+    #[serde(default)]
+    pub translations: Translations,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    /// Accept a color outside of the palette (see `update_palette`).
+    #[serde(default)]
+    pub allow_custom_color: bool,
 }
 
 /// Payload sent from the frontend when updating an existing category.
@@ -75,6 +184,27 @@ pub struct UpdateCategoryPayload {
     pub id: String,
     pub label: String,
     pub color: String,
+    /// Left unchanged when absent.
+    #[serde(default)]
+    pub translations: Option<Translations>,
+    /// Left unchanged when absent; an empty string removes the icon.
+    #[serde(default)]
+    pub icon: Option<String>,
+    /// Left unchanged when absent; an empty string makes it top-level.
+    #[serde(default)]
+    pub parent_id: Option<String>,
+    /// Accept a color outside of the palette (see `update_palette`).
+    #[serde(default)]
+    pub allow_custom_color: bool,
+}
+
+/// An approved category color.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaletteColor {
+    /// `#rrggbb`, lowercase.
+    pub color: String,
+    /// E.g. "Sky blue".
+    pub name: String,
 }
 
 // ── Product ──────────────────────────────────────────────────────────────────
@@ -91,6 +221,85 @@ pub struct Product {
     pub category_id: String,
     /// Whether the product appears on the sales screen.
     pub available: bool,
+    /// Whether selling the product requires confirming the customer's age.
+    pub age_restricted: bool,
+    /// Unit the price applies to (per piece, per kg or per litre).
+    pub unit: QuantityUnit,
+    /// Product name per locale.
+    pub translations: Translations,
+    /// In-store EAN-13 code, assigned when its first label is printed.
+    pub barcode: Option<String>,
+    /// First local date (`YYYY-MM-DD`) the product is offered on, for
+    /// seasonal items; always offered before when `None`.
+    #[serde(default)]
+    pub available_from: Option<String>,
+    /// Last local date the product is offered on; always offered after
+    /// when `None`.
+    #[serde(default)]
+    pub available_until: Option<String>,
+}
+
+/// A named selection of products offered on the sales screen.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Catalog {
+    pub id: String,
+    pub name: String,
+    pub product_ids: Vec<String>,
+    /// Whether this catalog restricts the sales screen.
+    pub active: bool,
+}
+
+/// Payload sent from the frontend when creating a catalog.
+#[derive(Debug, Deserialize)]
+pub struct CreateCatalogPayload {
+    pub name: String,
+    #[serde(default)]
+    pub product_ids: Vec<String>,
+}
+
+/// Payload sent from the frontend when updating a catalog.
+#[derive(Debug, Deserialize)]
+pub struct UpdateCatalogPayload {
+    pub id: String,
+    pub name: String,
+    /// Left unchanged when absent.
+    #[serde(default)]
+    pub product_ids: Option<Vec<String>>,
+}
+
+/// A till of the event, showing only some products on its sales screen.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Register {
+    pub id: String,
+    pub name: String,
+    pub product_ids: Vec<String>,
+    /// Categories whose products, subcategories included, are shown.
+    pub category_ids: Vec<String>,
+    /// Whether this till is this register.
+    pub current: bool,
+}
+
+/// Payload sent from the frontend when creating a register.
+#[derive(Debug, Deserialize)]
+pub struct CreateRegisterPayload {
+    pub name: String,
+    #[serde(default)]
+    pub product_ids: Vec<String>,
+    #[serde(default)]
+    pub category_ids: Vec<String>,
+}
+
+/// Payload sent from the frontend when updating a register.
+#[derive(Debug, Deserialize)]
+pub struct UpdateRegisterPayload {
+    pub id: String,
+    pub name: String,
+    /// Left unchanged when absent.
+    #[serde(default)]
+    pub product_ids: Option<Vec<String>>,
+    /// Left unchanged when absent.
+    #[serde(default)]
+    pub category_ids: Option<Vec<String>>,
 }
 
 /// Payload sent from the frontend when creating a new product.
@@ -99,16 +308,85 @@ pub struct CreateProductPayload {
     pub name: String,
     pub price: i64,
     pub category_id: String,
+    #[serde(default)]
+    pub age_restricted: bool,
+    #[serde(default)]
+    pub unit: QuantityUnit,
+    #[serde(default)]
+    pub translations: Translations,
 }
 
 /// Payload sent from the frontend when updating an existing product.
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct UpdateProductPayload {
     pub id: String,
     pub name: String,
     pub price: i64,
     pub category_id: String,
     pub available: bool,
+    /// Left unchanged when absent.
+    #[serde(default)]
+    pub age_restricted: Option<bool>,
+    /// Left unchanged when absent.
+    #[serde(default)]
+    pub unit: Option<QuantityUnit>,
+    /// Left unchanged when absent.
+    #[serde(default)]
+    pub translations: Option<Translations>,
+}
+
+/// A product edit proposed while catalog review is on, waiting for an admin.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingProductChange {
+    pub id: String,
+    /// The product as it is now.
+    pub current: Product,
+    pub proposed: UpdateProductPayload,
+    /// Name given by whoever proposed the change.
+    pub proposed_by: String,
+    pub created_at: String,
+}
+
+/// Optional filters and pagination of the product list, for catalogs too
+/// large to load at once.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct ProductFilter {
+    #[serde(default)]
+    pub category_id: Option<String>,
+    #[serde(default)]
+    pub available: Option<bool>,
+    /// Case-insensitive part of the name, or the exact barcode.
+    #[serde(default)]
+    pub search: Option<String>,
+    /// Maximum number of products to return; all products when absent.
+    #[serde(default)]
+    pub limit: Option<u32>,
+    /// Number of products to skip, in list order.
+    #[serde(default)]
+    pub offset: Option<u32>,
+    /// Local date (`YYYY-MM-DD`) the products must be offered on, see
+    /// `Product::available_from`. The sales screen uses today.
+    #[serde(default)]
+    pub available_on: Option<String>,
+}
+
+// ── Locale ───────────────────────────────────────────────────────────────────
+
+/// Interface languages, stored in the settings table. The sales screen and
+/// the customer-facing display can use different locales.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LocaleSettings {
+    pub sales_locale: String,
+    pub customer_display_locale: String,
+}
+
+impl Default for LocaleSettings {
+    fn default() -> Self {
+        LocaleSettings {
+            sales_locale: "fr".to_string(),
+            customer_display_locale: "fr".to_string(),
+        }
+    }
 }
 
 // ── Order ────────────────────────────────────────────────────────────────────
@@ -123,6 +401,41 @@ pub struct Order {
     pub total: i64,
     /// Payment method used for this order.
     pub payment_method: PaymentMethod,
+    /// Hash chaining this order to the previous one (see `verify_order_chain`).
+    /// `None` for orders recorded before the chain was introduced.
+    pub hash: Option<String>,
+    /// When the order was voided (see `undo_last_order`). Voided orders are
+    /// kept in the chain but excluded from sales figures.
+    #[serde(default)]
+    pub voided_at: Option<String>,
+    /// Order refunded by this one (see `refund_items`). Refund orders have
+    /// negative quantities and totals.
+    #[serde(default)]
+    pub refund_of: Option<String>,
+    /// Free-form labels (see `tag_order`), sorted. They can change after the
+    /// sale and are not part of the chain hash.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Flagged as a test sale (see `set_order_test_flag`), to be voided by
+    /// `purge_test_orders`. Not part of the chain hash either.
+    #[serde(default)]
+    pub is_test: bool,
+    /// Channel the order came through. Refunds keep the one of their
+    /// order. Not part of the chain hash.
+    #[serde(default)]
+    pub source: OrderSource,
+}
+
+/// A page of `list_orders`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct OrderPageQuery {
+    /// Id of the last order of the previous page; the first page when
+    /// absent.
+    #[serde(default)]
+    pub before: Option<String>,
+    /// Number of orders per page; all the remaining orders when absent.
+    #[serde(default)]
+    pub limit: Option<usize>,
 }
 
 /// A line item within an order.
@@ -135,11 +448,17 @@ pub struct OrderItem {
     pub product_id: String,
     /// Product name snapshot at sale time.
     pub product_name: String,
-    /// Unit price snapshot at sale time (cents).
+    /// Unit price snapshot at sale time (cents per piece, kg or litre).
     pub unit_price: i64,
-    pub quantity: i64,
-    /// unit_price * quantity (cents).
+    /// Number of pieces, or weight/volume for products sold by kg or litre.
+    pub quantity: f64,
+    /// Unit snapshot at sale time.
+    pub unit: QuantityUnit,
+    /// unit_price * quantity, rounded to the nearest cent.
     pub total: i64,
+    /// Line of the original order refunded by this (negative) line.
+    #[serde(default)]
+    pub refunded_item_id: Option<String>,
 }
 
 /// An order together with its line items, returned to the frontend.
@@ -156,6 +475,46 @@ pub struct CreateOrderPayload {
     pub items: Vec<CreateOrderItemPayload>,
     /// Payment method for this order.
     pub payment_method: PaymentMethod,
+    /// The cashier confirmed the customer's age. Required when the order
+    /// contains age-restricted products.
+    #[serde(default)]
+    pub age_confirmed: bool,
+    /// The cashier confirmed an order over the `OrderLimits`.
+    #[serde(default)]
+    pub limits_confirmed: bool,
+    /// Amounts taken off the order, e.g. a returned deposit.
+    #[serde(default)]
+    pub adjustments: Vec<OrderAdjustment>,
+    #[serde(default)]
+    pub source: OrderSource,
+}
+
+/// A negative line deducted from an order, such as a returned deposit
+/// redeemed against a new purchase. The order total cannot go below 0.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OrderAdjustment {
+    /// Printed as the line's product name.
+    pub label: String,
+    /// Negative amount in cents.
+    pub amount: i64,
+}
+
+/// Quantity of one order line to refund.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ItemRefund {
+    pub item_id: String,
+    /// Positive quantity to give back, at most what is left to refund.
+    pub quantity: f64,
+}
+
+/// Result of `exchange_order`: the refund of the returned items and the
+/// order of their replacements.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Exchange {
+    pub refund: OrderWithItems,
+    pub replacement: OrderWithItems,
+    /// Amount in cents the customer pays (positive) or gets back (negative).
+    pub balance: i64,
 }
 
 /// A single item within a new-order payload.
@@ -164,7 +523,73 @@ pub struct CreateOrderItemPayload {
     pub product_id: String,
     pub product_name: String,
     pub unit_price: i64,
-    pub quantity: i64,
+    /// Must be a whole number for products sold by the piece.
+    pub quantity: f64,
+}
+
+/// Payload of `split_order`: a cart shared by a group, each part being paid
+/// as an order of its own.
+#[derive(Debug, Deserialize)]
+pub struct SplitOrderPayload {
+    pub items: Vec<CreateOrderItemPayload>,
+    pub parts: Vec<SplitOrderPart>,
+    #[serde(default)]
+    pub age_confirmed: bool,
+    /// The cashier confirmed parts over the `OrderLimits`.
+    #[serde(default)]
+    pub limits_confirmed: bool,
+}
+
+/// What one person pays for, and how.
+#[derive(Debug, Deserialize)]
+pub struct SplitOrderPart {
+    pub payment_method: PaymentMethod,
+    pub items: Vec<SplitItemAssignment>,
+}
+
+/// Quantity of a cart line assigned to a part; a line can be shared between
+/// parts, e.g. a pitcher.
+#[derive(Debug, Deserialize)]
+pub struct SplitItemAssignment {
+    /// Index of the line in `SplitOrderPayload::items`.
+    pub item_index: usize,
+    pub quantity: f64,
+}
+
+// ── Tabs ─────────────────────────────────────────────────────────────────────
+
+/// An open tab: items served now and paid later, when it is settled (see
+/// `settle_tab`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Tab {
+    pub id: String,
+    /// Who the tab is for, e.g. a member's name or a table.
+    pub name: String,
+    pub opened_at: String,
+    pub items: Vec<TabItem>,
+    /// Total amount due in cents, before surcharges and rounding.
+    pub total: i64,
+}
+
+/// An item served on a tab.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TabItem {
+    pub id: String,
+    pub product_id: String,
+    pub product_name: String,
+    pub unit_price: i64,
+    pub quantity: f64,
+    pub added_at: String,
+}
+
+/// Items to put on a tab.
+#[derive(Debug, Deserialize)]
+pub struct AddToTabPayload {
+    pub items: Vec<CreateOrderItemPayload>,
+    /// Required when the items contain age-restricted products, as for an
+    /// order: the age is checked when serving, not when settling.
+    #[serde(default)]
+    pub age_confirmed: bool,
 }
 
 // ── Dashboard ────────────────────────────────────────────────────────────────
@@ -174,16 +599,79 @@ pub struct CreateOrderItemPayload {
 pub struct ProductSalesSummary {
     pub product_id: String,
     pub product_name: String,
-    pub total_quantity: i64,
+    pub total_quantity: f64,
+    pub total_revenue: i64,
+}
+
+/// Per-category sales summary row.
+/// Sales of products that have since been deleted cannot be traced back to a
+/// category and are grouped under `category_id: None`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CategorySalesSummary {
+    pub category_id: Option<String>,
+    pub category_label: Option<String>,
+    pub total_quantity: f64,
     pub total_revenue: i64,
 }
 
+/// Sort key for the per-product sales summary.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProductSortKey {
+    #[default]
+    Revenue,
+    Quantity,
+}
+
+/// Options for the per-product section of the dashboard, so the UI can ask
+/// for e.g. the top 5 products by volume.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ProductSummaryQuery {
+    #[serde(default)]
+    pub sort_by: ProductSortKey,
+    /// Maximum number of rows to return; all products when absent.
+    #[serde(default)]
+    pub limit: Option<u32>,
+}
+
 /// Breakdown of revenue by payment method.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct PaymentMethodBreakdown {
     pub payment_method: PaymentMethod,
     pub total_revenue: i64,
     pub transaction_count: i64,
+    /// Part of `total_revenue` made of payment surcharges.
+    #[serde(default)]
+    pub surcharge_revenue: i64,
+}
+
+/// Revenue and order count of the orders from one `OrderSource`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceBreakdown {
+    pub source: OrderSource,
+    pub total_revenue: i64,
+    pub transaction_count: i64,
+}
+
+/// Number of orders whose total falls within `[min, max)` (cents).
+/// The last bucket is open-ended (`max: None`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BasketHistogramBucket {
+    pub min: i64,
+    pub max: Option<i64>,
+    pub order_count: i64,
+}
+
+/// Distribution of order ("basket") values.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BasketStats {
+    /// Average order total in cents, rounded to the nearest cent.
+    pub average_total: i64,
+    /// Median order total in cents.
+    pub median_total: i64,
+    /// Average number of units per order.
+    pub average_items_per_order: f64,
+    pub histogram: Vec<BasketHistogramBucket>,
 }
 
 /// The complete dashboard summary returned to the frontend.
@@ -193,4 +681,1341 @@ pub struct DashboardSummary {
     pub total_transactions: i64,
     pub per_product: Vec<ProductSalesSummary>,
     pub per_payment_method: Vec<PaymentMethodBreakdown>,
+    pub per_category: Vec<CategorySalesSummary>,
+    #[serde(default)]
+    pub per_source: Vec<SourceBreakdown>,
+    pub basket: BasketStats,
+    /// `total_revenue` in the display currency, when one is configured.
+    #[serde(default)]
+    pub converted_total: Option<ConvertedAmount>,
+}
+
+/// Today's figures so far, cheap enough to be polled by a header widget.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TodayStats {
+    pub total_revenue: i64,
+    pub order_count: i64,
+    /// `created_at` of the last order of the day, if any.
+    pub last_order_at: Option<String>,
+    pub cash_revenue: i64,
+    pub card_revenue: i64,
+}
+
+/// Payload of the `sales-tick` event, sent after orders are recorded so
+/// that a secondary display can follow the day's takings live.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SalesTick {
+    /// Revenue of the day so far, in cents.
+    pub daily_total: i64,
+    pub order_count: i64,
+    /// Total of the orders just recorded; a split counts as one.
+    pub last_order_total: i64,
+}
+
+/// Revenue target of a fund-raising event held from `from` to `to`
+/// (inclusive local dates), stored in the settings table. Disabled while
+/// `target` is 0.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct RevenueGoal {
+    /// Cents to raise.
+    pub target: i64,
+    pub from: String,
+    pub to: String,
+}
+
+/// How far the sales of the event are from its `RevenueGoal`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GoalProgress {
+    pub target: i64,
+    /// Revenue of the event so far, in cents.
+    pub achieved: i64,
+    /// Revenue at the end of the event if sales keep the pace they had
+    /// since it started.
+    pub projected: i64,
+}
+
+// ── Reports ──────────────────────────────────────────────────────────────────
+
+/// Revenue and order count for one hour of a day (local time).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HourlySales {
+    /// Hour of the day, 0-23.
+    pub hour: i64,
+    pub total_revenue: i64,
+    pub order_count: i64,
+}
+
+/// An inclusive range of local calendar dates (`YYYY-MM-DD`).
+/// A missing bound leaves that side of the range open.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct DateRange {
+    pub from: Option<String>,
+    pub to: Option<String>,
+}
+
+/// Revenue and order count of the orders carrying a tag over a date range.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TagSales {
+    pub tag: String,
+    pub total_revenue: i64,
+    pub order_count: i64,
+}
+
+/// Revenue and order count for one day of the week over a date range.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeekdaySales {
+    /// ISO weekday, 1 = Monday ... 7 = Sunday.
+    pub weekday: i64,
+    pub total_revenue: i64,
+    pub order_count: i64,
+}
+
+/// Dashboard aggregates for two date ranges side by side.
+/// Deltas are expressed as `period_a - period_b`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeriodComparison {
+    pub period_a: DashboardSummary,
+    pub period_b: DashboardSummary,
+    pub revenue_delta: i64,
+    pub transactions_delta: i64,
+    /// Revenue change relative to `period_b`, or `None` when it had no sales.
+    pub revenue_change_percent: Option<f64>,
+}
+
+/// Granularity of a sales time series.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeBucket {
+    Hour,
+    Day,
+}
+
+/// One point of a sales time series, in local time.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SalesPoint {
+    /// Bucket start: `YYYY-MM-DD HH:00` for hourly series, `YYYY-MM-DD` for daily ones.
+    pub period: String,
+    pub total_revenue: i64,
+    pub order_count: i64,
+}
+
+/// Weekday x hour matrices of sales over a date range, in local time.
+/// Rows are ISO weekdays (index 0 = Monday), columns are hours 0-23.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SalesHeatmap {
+    pub order_counts: Vec<Vec<i64>>,
+    pub revenue: Vec<Vec<i64>>,
+}
+
+/// Two products bought within the same order, with how often that happened.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProductPair {
+    pub product_a_id: String,
+    pub product_a_name: String,
+    pub product_b_id: String,
+    pub product_b_name: String,
+    /// Number of orders containing both products.
+    pub order_count: i64,
+}
+
+// ── Export ───────────────────────────────────────────────────────────────────
+
+/// Journal and account codes used by the accounting exports.
+/// Stored in the settings table; the defaults follow the French chart of
+/// accounts (PCG).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LedgerAccounts {
+    pub journal_code: String,
+    /// Credited with the revenue of each order.
+    pub sales_account: String,
+    /// Debited for orders paid in cash.
+    pub cash_account: String,
+    /// Debited for orders paid by card.
+    pub card_account: String,
+}
+
+impl Default for LedgerAccounts {
+    fn default() -> Self {
+        LedgerAccounts {
+            journal_code: "VE".to_string(),
+            sales_account: "707000".to_string(),
+            cash_account: "530000".to_string(),
+            card_account: "511200".to_string(),
+        }
+    }
+}
+
+/// File format for report exports.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    Csv,
+    Xlsx,
+}
+
+// ── Email ────────────────────────────────────────────────────────────────────
+
+/// How the connection to the SMTP server is secured.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpSecurity {
+    /// Implicit TLS, usually on port 465.
+    Tls,
+    /// STARTTLS upgrade, usually on port 587.
+    #[default]
+    StartTls,
+    /// Unencrypted connection (local relays only).
+    None,
+}
+
+impl SmtpSecurity {
+    /// Parse a string from the settings table into an `SmtpSecurity`.
+    pub fn from_db_str(s: &str) -> Result<Self, String> {
+        match s {
+            "tls" => Ok(SmtpSecurity::Tls),
+            "starttls" => Ok(SmtpSecurity::StartTls),
+            "none" => Ok(SmtpSecurity::None),
+            other => Err(format!("Unknown SMTP security mode: {other}")),
+        }
+    }
+
+    /// Return the lowercase string representation stored in SQLite.
+    pub fn as_db_str(&self) -> &'static str {
+        match self {
+            SmtpSecurity::Tls => "tls",
+            SmtpSecurity::StartTls => "starttls",
+            SmtpSecurity::None => "none",
+        }
+    }
+}
+
+/// Outgoing mail server configuration, stored in the settings table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SmtpSettings {
+    pub host: String,
+    pub port: u16,
+    pub security: SmtpSecurity,
+    /// Leave empty for servers that do not require authentication.
+    pub username: String,
+    pub password: String,
+    /// Sender address, e.g. `Buvette <buvette@example.org>`.
+    pub from: String,
+}
+
+impl Default for SmtpSettings {
+    fn default() -> Self {
+        SmtpSettings {
+            host: String::new(),
+            port: 587,
+            security: SmtpSecurity::StartTls,
+            username: String::new(),
+            password: String::new(),
+            from: String::new(),
+        }
+    }
+}
+
+// ── Notifications ────────────────────────────────────────────────────────────
+
+/// Chat service receiving the summary notifications.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NotificationChannel {
+    #[default]
+    Disabled,
+    Slack,
+    Telegram,
+}
+
+impl NotificationChannel {
+    /// Parse a string from the settings table into a `NotificationChannel`.
+    pub fn from_db_str(s: &str) -> Result<Self, String> {
+        match s {
+            "disabled" => Ok(NotificationChannel::Disabled),
+            "slack" => Ok(NotificationChannel::Slack),
+            "telegram" => Ok(NotificationChannel::Telegram),
+            other => Err(format!("Unknown notification channel: {other}")),
+        }
+    }
+
+    /// Return the lowercase string representation stored in SQLite.
+    pub fn as_db_str(&self) -> &'static str {
+        match self {
+            NotificationChannel::Disabled => "disabled",
+            NotificationChannel::Slack => "slack",
+            NotificationChannel::Telegram => "telegram",
+        }
+    }
+}
+
+/// Chat notification configuration, stored in the settings table.
+/// Only the fields of the selected channel are used.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct NotificationSettings {
+    pub channel: NotificationChannel,
+    pub slack_webhook_url: String,
+    pub telegram_bot_token: String,
+    pub telegram_chat_id: String,
+}
+
+// ── Order chain ──────────────────────────────────────────────────────────────
+
+/// Result of re-computing the order and void hash chains.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChainVerification {
+    /// Whether every chained order and void matches its stored hash and
+    /// predecessor, and every voided order has its void.
+    pub valid: bool,
+    /// Number of chained orders checked.
+    pub checked_orders: i64,
+    /// Number of chained voids checked.
+    #[serde(default)]
+    pub checked_voids: i64,
+    /// First order whose content, link or void does not match, if any.
+    pub first_invalid_order_id: Option<String>,
+}
+
+/// The void of an order, chained to the previous void so that voids cannot
+/// be added or removed outside the app unnoticed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OrderVoid {
+    pub order_id: String,
+    pub voided_at: String,
+    /// Hash of the preceding void; `None`, like `hash`, for voids recorded
+    /// before voids were chained.
+    pub previous_hash: Option<String>,
+    pub hash: Option<String>,
+}
+
+/// One entry of the audit journal.
+///
+/// Orders and voids are journaled for now; other event types (closures) get
+/// their own variant so readers can dispatch on `type`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum JournalEntry {
+    Order {
+        /// 1-based position of the entry within this journal.
+        sequence: i64,
+        /// Hash of the preceding order in the chain; `order.hash` is computed
+        /// over this value and the order content (see `verify_order_chain`).
+        previous_hash: Option<String>,
+        order: OrderWithItems,
+    },
+    Void {
+        /// 1-based position of the entry within this journal.
+        sequence: i64,
+        void: OrderVoid,
+    },
+}
+
+/// Audit journal written by `export_journal` (JSON, format `pos-journal`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Journal {
+    /// Always `pos-journal`.
+    pub format: String,
+    /// Format version, incremented on incompatible changes.
+    pub version: i64,
+    pub app_version: String,
+    /// UTC timestamp of the export.
+    pub generated_at: String,
+    pub range: DateRange,
+    /// SHA-256 over the concatenated hashes of the orders and voids of
+    /// `entries`, in order.
+    pub journal_hash: String,
+    pub entries: Vec<JournalEntry>,
+}
+
+// ── Scale ────────────────────────────────────────────────────────────────────
+
+/// How the weighing scale is connected.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ScaleConnection {
+    #[default]
+    Disabled,
+    /// RS-232 or USB serial scale speaking the NCI/Toledo protocol.
+    Serial,
+    /// USB scale implementing the HID Point of Sale scale reports.
+    Hid,
+}
+
+impl ScaleConnection {
+    /// Parse a string from the settings table into a `ScaleConnection`.
+    pub fn from_db_str(s: &str) -> Result<Self, String> {
+        match s {
+            "disabled" => Ok(ScaleConnection::Disabled),
+            "serial" => Ok(ScaleConnection::Serial),
+            "hid" => Ok(ScaleConnection::Hid),
+            other => Err(format!("Unknown scale connection: {other}")),
+        }
+    }
+
+    /// Return the lowercase string representation stored in SQLite.
+    pub fn as_db_str(&self) -> &'static str {
+        match self {
+            ScaleConnection::Disabled => "disabled",
+            ScaleConnection::Serial => "serial",
+            ScaleConnection::Hid => "hid",
+        }
+    }
+}
+
+/// Weighing scale configuration, stored in the settings table.
+/// Only the fields of the selected connection are used.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScaleSettings {
+    pub connection: ScaleConnection,
+    /// Serial port name, e.g. `COM3` or `/dev/ttyUSB0`.
+    pub serial_port: String,
+    pub baud_rate: u32,
+    pub hid_vendor_id: u16,
+    pub hid_product_id: u16,
+}
+
+impl Default for ScaleSettings {
+    fn default() -> Self {
+        ScaleSettings {
+            connection: ScaleConnection::Disabled,
+            serial_port: String::new(),
+            baud_rate: 9600,
+            hid_vendor_id: 0,
+            hid_product_id: 0,
+        }
+    }
+}
+
+// ── Invoices ─────────────────────────────────────────────────────────────────
+
+/// Amount added to the orders paid by a payment method, e.g. +0.30 EUR for
+/// card payments below 5 EUR. Itemized as a "Surcharge" line.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SurchargeRule {
+    pub payment_method: PaymentMethod,
+    /// Cents.
+    pub amount: i64,
+    /// Only orders whose total is below this amount (cents) are charged;
+    /// all orders when absent.
+    pub below: Option<i64>,
+}
+
+/// Sends the preparation tickets of a category, e.g. drinks, to a station,
+/// e.g. "Bar". Subcategories follow their parent unless routed themselves.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StationRoute {
+    pub category_id: String,
+    pub station: String,
+}
+
+/// A preparation ticket written by `generate_station_tickets`, or when its
+/// order is created.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StationTicket {
+    pub station: String,
+    pub path: String,
+}
+
+/// How order totals are rounded, e.g. to the nearest 5 cents.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RoundingMode {
+    /// Totals are kept to the cent.
+    #[default]
+    None,
+    /// Each line total is rounded.
+    PerLine,
+    /// The order total is rounded, the difference being recorded as an extra
+    /// "Rounding" line so that product figures add up to the total.
+    OrderTotal,
+}
+
+impl RoundingMode {
+    /// Parse a string from the settings table into a `RoundingMode`.
+    pub fn from_db_str(s: &str) -> Result<Self, String> {
+        match s {
+            "none" => Ok(RoundingMode::None),
+            "per_line" => Ok(RoundingMode::PerLine),
+            "order_total" => Ok(RoundingMode::OrderTotal),
+            other => Err(format!("Unknown rounding mode: {other}")),
+        }
+    }
+
+    /// Return the snake_case string representation stored in SQLite.
+    pub fn as_db_str(&self) -> &'static str {
+        match self {
+            RoundingMode::None => "none",
+            RoundingMode::PerLine => "per_line",
+            RoundingMode::OrderTotal => "order_total",
+        }
+    }
+}
+
+/// Rounding policy of new orders, stored in the settings table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RoundingSettings {
+    pub mode: RoundingMode,
+    /// Cents amounts are rounded to, e.g. `5` or `10`.
+    pub increment: i64,
+}
+
+impl Default for RoundingSettings {
+    fn default() -> Self {
+        RoundingSettings {
+            mode: RoundingMode::None,
+            increment: 5,
+        }
+    }
+}
+
+/// Sanity limits of new orders, stored in the settings table, catching typos
+/// such as 999 beers. `0` disables a limit. Orders over a limit need an
+/// explicit confirmation.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct OrderLimits {
+    /// Cents the product lines may add up to, before surcharges and
+    /// rounding.
+    pub max_total: i64,
+    /// Quantity a single line may reach, in pieces, kg or litres.
+    pub max_quantity: f64,
+}
+
+/// Second currency amounts are also shown in, e.g. CHF for clubs near the
+/// Swiss border. Stored in the settings table; disabled while `code` is
+/// empty. Orders are always recorded in euros.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DisplayCurrencySettings {
+    /// ISO 4217 code, e.g. `CHF`.
+    pub code: String,
+    /// Units of the display currency per euro.
+    pub rate: f64,
+}
+
+impl Default for DisplayCurrencySettings {
+    fn default() -> Self {
+        DisplayCurrencySettings {
+            code: String::new(),
+            rate: 1.0,
+        }
+    }
+}
+
+impl DisplayCurrencySettings {
+    /// `cents` (euros) in the display currency, if one is configured.
+    pub fn convert(&self, cents: i64) -> Option<ConvertedAmount> {
+        if self.code.is_empty() {
+            return None;
+        }
+        Some(ConvertedAmount {
+            currency: self.code.clone(),
+            rate: self.rate,
+            amount: (cents as f64 * self.rate).round() as i64,
+        })
+    }
+}
+
+/// An amount converted to the display currency, for information only.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConvertedAmount {
+    pub currency: String,
+    /// Rate used, so that other figures can be converted the same way.
+    pub rate: f64,
+    /// Hundredths of the display currency.
+    pub amount: i64,
+}
+
+/// First-launch setup sent to `bootstrap_workspace`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BootstrapPayload {
+    pub business: BusinessSettings,
+    /// Admin PIN to set, if any.
+    #[serde(default)]
+    pub admin_pin: Option<String>,
+    #[serde(default)]
+    pub display_currency: Option<DisplayCurrencySettings>,
+    /// Whether to create the starter catalog of a French club bar.
+    #[serde(default)]
+    pub starter_catalog: bool,
+}
+
+/// Seller details printed on invoices, stored in the settings table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BusinessSettings {
+    pub name: String,
+    /// Postal address, one line per row.
+    pub address: String,
+    pub siret: String,
+    pub vat_number: String,
+    /// When enabled, invoices break the total down into amount excluding
+    /// VAT and VAT. Prices always include VAT.
+    pub vat_enabled: bool,
+    /// VAT rate in percent, e.g. `20.0` or `5.5`.
+    pub vat_rate: f64,
+}
+
+impl Default for BusinessSettings {
+    fn default() -> Self {
+        BusinessSettings {
+            name: String::new(),
+            address: String::new(),
+            siret: String::new(),
+            vat_number: String::new(),
+            vat_enabled: false,
+            vat_rate: 20.0,
+        }
+    }
+}
+
+/// Buyer details printed on an invoice.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomerDetails {
+    pub name: String,
+    /// Postal address, one line per row.
+    pub address: String,
+    #[serde(default)]
+    pub vat_number: String,
+}
+
+/// An invoice issued for an order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Invoice {
+    /// Sequential invoice number, e.g. `INV-000042`.
+    pub number: String,
+    pub order_id: String,
+    /// Local date the invoice was first issued (YYYY-MM-DD).
+    pub issued_at: String,
+    pub customer: CustomerDetails,
+    /// Business details and VAT settings at issue time, kept for reprints.
+    pub seller: BusinessSettings,
+    /// Amount including VAT, in cents.
+    pub total: i64,
+    /// Amount excluding VAT, in cents; equal to `total` without VAT.
+    pub total_excl_vat: i64,
+}
+
+/// Result of checking the QR code printed on a receipt.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceiptVerification {
+    /// Whether the order exists, still matches its chain hash and the hash
+    /// in the code.
+    pub authentic: bool,
+    /// The order the code refers to, when it exists.
+    pub order: Option<OrderWithItems>,
+}
+
+// ── Stock ────────────────────────────────────────────────────────────────────
+
+/// Why the stock of a product changed, sales aside.
+/// Serializes to/from lowercase strings ("adjustment", "waste", "receipt").
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StockMovementKind {
+    /// Correction made when committing a stocktake.
+    Adjustment,
+    /// Goods thrown away: spoiled, dropped, past their date...
+    Waste,
+    /// Goods put in stock as a `StockBatch`.
+    Receipt,
+}
+
+impl StockMovementKind {
+    /// Return the lowercase string representation stored in SQLite.
+    pub fn as_db_str(&self) -> &'static str {
+        match self {
+            StockMovementKind::Adjustment => "adjustment",
+            StockMovementKind::Waste => "waste",
+            StockMovementKind::Receipt => "receipt",
+        }
+    }
+}
+
+/// A change of the stock of a product, sales aside.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StockMovement {
+    pub id: String,
+    pub product_id: String,
+    pub kind: StockMovementKind,
+    /// Signed: negative when goods leave the stock.
+    pub quantity: f64,
+    pub reason: Option<String>,
+    pub created_at: String,
+}
+
+/// Wasted quantity of a product for one reason over a date range.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WasteSummary {
+    pub product_id: String,
+    pub product_name: String,
+    pub unit: QuantityUnit,
+    pub reason: String,
+    pub quantity: f64,
+    /// Wasted quantity valued at the current selling price, in cents.
+    pub value: i64,
+}
+
+/// Goods of a product put in stock together, e.g. the sandwiches made in
+/// the morning.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StockBatch {
+    /// Id of the `receipt` stock movement of the batch.
+    pub id: String,
+    pub product_id: String,
+    pub quantity: f64,
+    /// Best-before date, `YYYY-MM-DD`; `None` for goods that do not expire.
+    pub expires_on: Option<String>,
+    pub created_at: String,
+}
+
+/// Payload sent from the frontend when putting goods in stock.
+#[derive(Debug, Deserialize)]
+pub struct AddStockBatchPayload {
+    pub product_id: String,
+    pub quantity: f64,
+    #[serde(default)]
+    pub expires_on: Option<String>,
+}
+
+/// What is left of a batch close to or past its date. Sales are taken from
+/// the oldest batches first, so the remaining stock of a product is assumed
+/// to belong to its newest batches.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExpiringBatch {
+    pub batch_id: String,
+    pub product_id: String,
+    pub product_name: String,
+    pub unit: QuantityUnit,
+    pub expires_on: String,
+    pub remaining: f64,
+    /// Days until `expires_on`; negative once it has passed.
+    pub days_left: i64,
+}
+
+/// Current stock of a product whose stock is tracked.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StockLevel {
+    pub product_id: String,
+    pub product_name: String,
+    pub unit: QuantityUnit,
+    pub quantity: f64,
+}
+
+/// An inventory count, open until committed.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Stocktake {
+    pub id: String,
+    pub started_at: String,
+    pub committed_at: Option<String>,
+}
+
+/// Counted against expected stock of one product of a stocktake.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StocktakeLine {
+    pub product_id: String,
+    pub product_name: String,
+    pub unit: QuantityUnit,
+    /// Stock level according to the app: live while the stocktake is open,
+    /// as of the commit afterwards.
+    pub expected: f64,
+    pub counted: f64,
+    /// `counted - expected`: negative when items went missing.
+    pub variance: f64,
+    /// Variance valued at the current selling price, in cents.
+    pub variance_value: i64,
+}
+
+/// Variance report of a stocktake.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StocktakeReport {
+    pub stocktake: Stocktake,
+    pub lines: Vec<StocktakeLine>,
+    pub total_variance_value: i64,
+}
+
+/// Something products are made of, e.g. flour or eggs for a crêpe.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Ingredient {
+    pub id: String,
+    pub name: String,
+    pub unit: QuantityUnit,
+    /// Stock at or below which the ingredient is running low; `None`
+    /// disables the alert.
+    pub low_stock_threshold: Option<f64>,
+    pub stock: f64,
+    pub low_stock: bool,
+}
+
+/// Payload sent from the frontend when creating an ingredient.
+#[derive(Debug, Deserialize)]
+pub struct CreateIngredientPayload {
+    pub name: String,
+    #[serde(default)]
+    pub unit: QuantityUnit,
+    #[serde(default)]
+    pub low_stock_threshold: Option<f64>,
+}
+
+/// Payload sent from the frontend when updating an ingredient.
+#[derive(Debug, Deserialize)]
+pub struct UpdateIngredientPayload {
+    pub id: String,
+    pub name: String,
+    pub unit: QuantityUnit,
+    #[serde(default)]
+    pub low_stock_threshold: Option<f64>,
+}
+
+/// Quantity of an ingredient used per unit (piece, kg, litre) of a product.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecipeItem {
+    pub ingredient_id: String,
+    pub quantity: f64,
+}
+
+/// Reorder suggestion configuration, stored in the settings table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReorderSettings {
+    /// How many calendar days of past sales the usage is measured over.
+    pub lookback_days: u32,
+    /// How many sales days the stock should last, e.g. 2 for a weekend.
+    pub cover_days: u32,
+}
+
+impl Default for ReorderSettings {
+    fn default() -> Self {
+        ReorderSettings {
+            lookback_days: 30,
+            cover_days: 1,
+        }
+    }
+}
+
+/// A product or ingredient whose stock will not cover the next sales days.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReorderSuggestion {
+    /// Product or ingredient id, depending on `ingredient`.
+    pub id: String,
+    pub name: String,
+    pub ingredient: bool,
+    pub unit: QuantityUnit,
+    pub stock: f64,
+    /// Average quantity sold or consumed per sales day.
+    pub daily_usage: f64,
+    pub suggested_quantity: f64,
+}
+
+// ── Expenses ─────────────────────────────────────────────────────────────────
+
+/// Money spent for the stand, e.g. supplies or a pitch rental.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Expense {
+    pub id: String,
+    /// Local date of the expense (`YYYY-MM-DD`).
+    pub spent_on: String,
+    /// Cents, always positive.
+    pub amount: i64,
+    pub category: String,
+    pub note: String,
+    /// Photo or scan of the receipt.
+    pub receipt_path: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CreateExpensePayload {
+    pub spent_on: String,
+    pub amount: i64,
+    pub category: String,
+    #[serde(default)]
+    pub note: String,
+    #[serde(default)]
+    pub receipt_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UpdateExpensePayload {
+    pub id: String,
+    pub spent_on: String,
+    pub amount: i64,
+    pub category: String,
+    #[serde(default)]
+    pub note: String,
+    #[serde(default)]
+    pub receipt_path: Option<String>,
+}
+
+/// Expenses of one category over the range of a `NetResult`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ExpenseCategoryTotal {
+    pub category: String,
+    pub amount: i64,
+}
+
+/// Revenue minus expenses over a date range, in cents.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NetResult {
+    pub revenue: i64,
+    pub expenses: i64,
+    pub net: i64,
+    /// Sorted by decreasing amount.
+    pub per_category: Vec<ExpenseCategoryTotal>,
+}
+
+// ── Attachments ──────────────────────────────────────────────────────────────
+
+/// An image or PDF, e.g. a receipt photo or a supplier invoice, kept in the
+/// attachments folder of the app data dir.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    pub id: String,
+    pub expense_id: Option<String>,
+    /// Name of the file that was attached.
+    pub file_name: String,
+    pub content_type: String,
+    /// Bytes.
+    pub size: i64,
+    /// Absolute path of the stored copy.
+    pub path: String,
+    pub created_at: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AddAttachmentPayload {
+    /// File to copy into the attachments folder.
+    pub source_path: String,
+    #[serde(default)]
+    pub expense_id: Option<String>,
+}
+
+// ── Data dump ────────────────────────────────────────────────────────────────
+
+/// An order of a `DataDump`, with the chain link needed to restore it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DumpedOrder {
+    #[serde(flatten)]
+    pub order: OrderWithItems,
+    /// Hash of the preceding order in the chain, see `JournalEntry`.
+    pub previous_hash: Option<String>,
+    /// Missing from dumps written before age confirmations were dumped.
+    #[serde(default)]
+    pub age_confirmation: Option<AgeConfirmation>,
+}
+
+/// The cashier's confirmation of the customer's age for an order with
+/// age-restricted products.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AgeConfirmation {
+    pub confirmed_at: String,
+    /// Names of the restricted products, comma-separated.
+    pub restricted_products: String,
+}
+
+/// Complete copy of the data written by `export_all_data` (JSON, format
+/// `pos-dump`).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DataDump {
+    /// Always `pos-dump`.
+    pub format: String,
+    /// Format version, incremented on incompatible changes.
+    pub version: i64,
+    pub app_version: String,
+    /// Schema version of the exported database.
+    pub schema_version: i64,
+    /// UTC timestamp of the export.
+    pub generated_at: String,
+    pub categories: Vec<Category>,
+    pub products: Vec<Product>,
+    /// In chain order.
+    pub orders: Vec<DumpedOrder>,
+    /// In chain order. Missing from dumps written before voids were chained.
+    #[serde(default)]
+    pub voids: Vec<OrderVoid>,
+    /// Every setting except credentials.
+    pub settings: std::collections::BTreeMap<String, String>,
+}
+
+/// What `import_all_data` does with the records of a dump.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImportStrategy {
+    /// Only adds the records missing from the database.
+    Skip,
+    /// Adds missing records and replaces existing ones with the dump's.
+    Merge,
+    /// Like `Merge`, and also deletes the records missing from the dump, so
+    /// the database ends up holding exactly the dump.
+    Overwrite,
+}
+
+/// What happened to the records of one entity type during an import.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImportCounts {
+    pub inserted: u32,
+    pub updated: u32,
+    /// Already in the database and left unchanged.
+    pub skipped: u32,
+    pub deleted: u32,
+}
+
+/// Result of `import_all_data`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ImportReport {
+    pub categories: ImportCounts,
+    pub products: ImportCounts,
+    pub orders: ImportCounts,
+    pub settings: ImportCounts,
+    /// Records that could not be imported as they are, e.g. an order whose
+    /// id is already used by a different order.
+    pub warnings: Vec<String>,
+}
+
+// ── Report presets ───────────────────────────────────────────────────────────
+
+/// The file exports a report preset can run.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReportKind {
+    Dashboard,
+    Fec,
+    LedgerCsv,
+    Journal,
+}
+
+/// How the date range of a preset is computed, relative to the day it runs.
+/// Weeks start on Monday.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum DateRangeMode {
+    Today,
+    Yesterday,
+    /// The last `days` days, today included.
+    LastDays {
+        days: u32,
+    },
+    ThisWeek,
+    LastWeek,
+    ThisMonth,
+    LastMonth,
+    /// The same dates on every run; a missing bound leaves that side open.
+    Fixed {
+        from: Option<String>,
+        to: Option<String>,
+    },
+}
+
+/// Everything a preset runs, apart from its name.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportPresetConfig {
+    pub report: ReportKind,
+    pub range: DateRangeMode,
+    /// Format of the dashboard export; CSV when absent. The other reports
+    /// have a fixed format.
+    #[serde(default)]
+    pub format: Option<ExportFormat>,
+    /// Per-product options of the dashboard export.
+    #[serde(default)]
+    pub products: ProductSummaryQuery,
+}
+
+/// A named report configuration, e.g. "weekly treasurer export".
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ReportPreset {
+    pub id: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub config: ReportPresetConfig,
+}
+
+/// Payload sent from the frontend when creating a report preset.
+#[derive(Debug, Deserialize)]
+pub struct CreateReportPresetPayload {
+    pub name: String,
+    #[serde(flatten)]
+    pub config: ReportPresetConfig,
+}
+
+/// Payload sent from the frontend when updating a report preset.
+#[derive(Debug, Deserialize)]
+pub struct UpdateReportPresetPayload {
+    pub id: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub config: ReportPresetConfig,
+}
+
+// ── Custom reports ───────────────────────────────────────────────────────────
+
+/// Type of a custom report parameter, given as text by the frontend.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CustomReportParamKind {
+    /// `YYYY-MM-DD`.
+    Date,
+    Text,
+    Integer,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomReportParam {
+    pub name: String,
+    pub kind: CustomReportParamKind,
+    pub required: bool,
+}
+
+/// One of the curated report queries `run_custom_report` can run.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CustomReport {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+    pub params: Vec<CustomReportParam>,
+}
+
+// ── Scheduled jobs ───────────────────────────────────────────────────────────
+
+/// The recurring jobs the scheduler can run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduledJobKind {
+    /// Copies the database to the `backups` folder of the app data dir.
+    Backup,
+    /// Emails the day's sales report to the job's recipients.
+    ReportEmail,
+    /// Writes the stock levels as CSV to the `stock` folder.
+    StockSnapshot,
+    /// Posts the stock batches expiring by the next day to the chat channel.
+    ExpiryAlert,
+    /// Posts the day's sales summary to the chat channel.
+    SummaryNotification,
+}
+
+impl ScheduledJobKind {
+    pub const ALL: [ScheduledJobKind; 5] = [
+        ScheduledJobKind::Backup,
+        ScheduledJobKind::ReportEmail,
+        ScheduledJobKind::StockSnapshot,
+        ScheduledJobKind::ExpiryAlert,
+        ScheduledJobKind::SummaryNotification,
+    ];
+
+    /// Parse a string from the database into a `ScheduledJobKind`.
+    pub fn from_db_str(s: &str) -> Result<Self, String> {
+        match s {
+            "backup" => Ok(ScheduledJobKind::Backup),
+            "report_email" => Ok(ScheduledJobKind::ReportEmail),
+            "stock_snapshot" => Ok(ScheduledJobKind::StockSnapshot),
+            "expiry_alert" => Ok(ScheduledJobKind::ExpiryAlert),
+            "summary_notification" => Ok(ScheduledJobKind::SummaryNotification),
+            other => Err(format!("Unknown scheduled job: {other}")),
+        }
+    }
+
+    /// Return the snake_case string representation stored in SQLite.
+    pub fn as_db_str(&self) -> &'static str {
+        match self {
+            ScheduledJobKind::Backup => "backup",
+            ScheduledJobKind::ReportEmail => "report_email",
+            ScheduledJobKind::StockSnapshot => "stock_snapshot",
+            ScheduledJobKind::ExpiryAlert => "expiry_alert",
+            ScheduledJobKind::SummaryNotification => "summary_notification",
+        }
+    }
+}
+
+/// A recurring job, run once a day at `time` while enabled.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScheduledJob {
+    pub kind: ScheduledJobKind,
+    pub enabled: bool,
+    /// Local time of day, `HH:MM`.
+    pub time: String,
+    /// Who receives the report emails; unused by the other jobs.
+    pub recipients: Vec<String>,
+    pub last_run_at: Option<String>,
+    /// Why the last run failed; `None` when it succeeded or never ran.
+    pub last_error: Option<String>,
+}
+
+/// Payload sent from the frontend when changing the schedule of a job.
+#[derive(Debug, Deserialize)]
+pub struct UpdateSchedulePayload {
+    pub kind: ScheduledJobKind,
+    pub enabled: bool,
+    pub time: String,
+    #[serde(default)]
+    pub recipients: Vec<String>,
+}
+
+// ── Data retention ───────────────────────────────────────────────────────────
+
+/// How long orders must stay in the database before they can be purged,
+/// stored in the settings table.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RetentionSettings {
+    pub days: u32,
+}
+
+impl Default for RetentionSettings {
+    fn default() -> Self {
+        RetentionSettings { days: 365 }
+    }
+}
+
+/// Outcome of `purge_old_data`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PurgeReport {
+    /// Where the data was archived before being deleted.
+    pub archive_path: String,
+    pub deleted_orders: i64,
+    /// First old order that was kept, because it has an invoice or is
+    /// refunded by a newer order; the orders after it are kept too.
+    pub kept_from_order_id: Option<String>,
+}
+
+// ── Health ───────────────────────────────────────────────────────────────────
+
+/// Disk usage of the database, checked in the background by the storage
+/// monitor.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StorageStatus {
+    pub database_bytes: u64,
+    /// Size of the write-ahead log next to the database.
+    pub wal_bytes: u64,
+    /// Free space of the disk holding the database, when it can be read.
+    pub free_disk_bytes: Option<u64>,
+    /// Human-readable problems, e.g. the disk being almost full.
+    pub warnings: Vec<String>,
+}
+
+/// A row pointing to data that does not exist, e.g. the item of a deleted
+/// order, as early versions could leave behind.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DatabaseIssue {
+    pub table: String,
+    /// SQLite `rowid` of the row.
+    pub row_id: i64,
+    pub problem: String,
+    /// How `repair_database` fixes it; `None` for rows it never changes,
+    /// such as orders and invoices.
+    pub fix: Option<String>,
+}
+
+/// Result of `repair_database`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RepairReport {
+    pub issues: Vec<DatabaseIssue>,
+    /// Whether the fixes were applied, or only reported.
+    pub repaired: bool,
+}
+
+/// Result of `verify_database_schema`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SchemaVerification {
+    /// Whether nothing was found.
+    pub valid: bool,
+    /// Messages of `PRAGMA integrity_check`, such as
+    /// "CHECK constraint failed in products".
+    pub problems: Vec<String>,
+    pub issues: Vec<DatabaseIssue>,
+}
+
+/// State of the installation, for support and the settings page.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub schema_version: usize,
+    pub storage: StorageStatus,
+    /// Why the scheduler last failed to run the due jobs, if it still does.
+    pub scheduler_error: Option<String>,
+}
+
+// ── Feature flags ────────────────────────────────────────────────────────────
+
+/// Optional subsystems that can be switched off to keep the app simple. The
+/// commands of a disabled feature fail.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Feature {
+    /// Stock levels, stocktakes, waste, ingredients and reorder suggestions.
+    Stock,
+    /// Receipts, kitchen tickets, product labels and menus.
+    Printing,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 2] = [Feature::Stock, Feature::Printing];
+
+    /// Parse a string from the database into a `Feature`.
+    pub fn from_db_str(s: &str) -> Result<Self, String> {
+        match s {
+            "stock" => Ok(Feature::Stock),
+            "printing" => Ok(Feature::Printing),
+            other => Err(format!("Unknown feature: {other}")),
+        }
+    }
+
+    /// Return the lowercase string representation stored in SQLite.
+    pub fn as_db_str(&self) -> &'static str {
+        match self {
+            Feature::Stock => "stock",
+            Feature::Printing => "printing",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FeatureFlag {
+    pub feature: Feature,
+    pub enabled: bool,
+}
+
+// ── Settings export ──────────────────────────────────────────────────────────
+
+/// Configuration of a till written by `export_settings` (JSON, format
+/// `pos-settings`), to set up another till the same way.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SettingsExport {
+    /// Always `pos-settings`.
+    pub format: String,
+    /// Format version, incremented on incompatible changes.
+    pub version: i64,
+    pub app_version: String,
+    pub generated_at: String,
+    /// Raw settings table, without credentials.
+    pub settings: BTreeMap<String, String>,
+    #[serde(default)]
+    pub report_presets: Vec<ReportPreset>,
+    /// Schedules without their last-run status.
+    #[serde(default)]
+    pub scheduled_jobs: Vec<ScheduledJob>,
+    #[serde(default)]
+    pub features: Vec<FeatureFlag>,
+    #[serde(default)]
+    pub surcharge_rules: Vec<SurchargeRule>,
+    /// Routes of categories the importing till lacks are skipped.
+    #[serde(default)]
+    pub station_routes: Vec<StationRoute>,
+    /// Colors missing from the importing till are added after its own.
+    #[serde(default)]
+    pub palette_colors: Vec<PaletteColor>,
+}
+
+// ── Usage metrics ────────────────────────────────────────────────────────────
+
+/// Opt-in anonymous usage metrics, stored in the settings table. Nothing is
+/// recorded while disabled.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TelemetrySettings {
+    pub enabled: bool,
+    /// Where `upload_usage_report` posts the report.
+    pub endpoint: String,
+}
+
+/// What `upload_usage_report` sends: no names, amounts or identifiers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UsageReport {
+    pub app_version: String,
+    pub os: String,
+    pub arch: String,
+    /// Number of products, as a range such as `26-100`.
+    pub catalog_size: String,
+    /// Calls of each command since the last upload.
+    pub commands: BTreeMap<String, i64>,
 }
@@ -0,0 +1,34 @@
+use crate::models::{NotificationChannel, NotificationSettings};
+
+/// Posts a short text message to the configured chat channel.
+/// Blocks until the remote service has answered.
+pub fn send(settings: &NotificationSettings, text: &str) -> Result<(), String> {
+    let result = match settings.channel {
+        NotificationChannel::Disabled => {
+            return Err("Chat notifications are disabled".to_string());
+        }
+        NotificationChannel::Slack => {
+            if settings.slack_webhook_url.is_empty() {
+                return Err("Slack webhook URL is not configured".to_string());
+            }
+            ureq::post(&settings.slack_webhook_url).send_json(serde_json::json!({ "text": text }))
+        }
+        NotificationChannel::Telegram => {
+            if settings.telegram_bot_token.is_empty() || settings.telegram_chat_id.is_empty() {
+                return Err("Telegram bot token and chat id are required".to_string());
+            }
+            let url = format!(
+                "https://api.telegram.org/bot{}/sendMessage",
+                settings.telegram_bot_token
+            );
+            ureq::post(&url).send_json(serde_json::json!({
+                "chat_id": settings.telegram_chat_id,
+                "text": text,
+            }))
+        }
+    };
+
+    result
+        .map(|_| ())
+        .map_err(|e| format!("Failed to send notification: {e}"))
+}
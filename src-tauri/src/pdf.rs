@@ -0,0 +1,234 @@
+//! Minimal PDF writer: text in the standard Helvetica fonts, lines and filled
+//! rectangles. Enough for invoices, receipts, labels and menus without
+//! pulling in a full PDF library.
+
+/// Points per millimetre.
+pub const MM: f32 = 72.0 / 25.4;
+
+/// A4 page size, in points.
+pub const A4_WIDTH: f32 = 595.28;
+pub const A4_HEIGHT: f32 = 841.89;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Font {
+    Regular,
+    Bold,
+}
+
+impl Font {
+    fn resource_name(self) -> &'static str {
+        match self {
+            Font::Regular => "F1",
+            Font::Bold => "F2",
+        }
+    }
+}
+
+/// One page. Coordinates are in points, measured from the top-left corner.
+pub struct Page {
+    width: f32,
+    height: f32,
+    content: String,
+}
+
+impl Page {
+    pub fn new(width: f32, height: f32) -> Self {
+        Page {
+            width,
+            height,
+            content: String::new(),
+        }
+    }
+
+    pub fn width(&self) -> f32 {
+        self.width
+    }
+
+    /// Draws `text` with its baseline at `y`, starting at `x`.
+    pub fn text(&mut self, x: f32, y: f32, size: f32, font: Font, text: &str) {
+        let mut escaped = String::new();
+        for byte in encode_win_ansi(text) {
+            match byte {
+                b'(' | b')' | b'\\' => {
+                    escaped.push('\\');
+                    escaped.push(byte as char);
+                }
+                0x20..=0x7e => escaped.push(byte as char),
+                _ => escaped.push_str(&format!("\\{byte:03o}")),
+            }
+        }
+        self.content.push_str(&format!(
+            "BT /{} {size:.2} Tf {x:.2} {:.2} Td ({escaped}) Tj ET\n",
+            font.resource_name(),
+            self.height - y
+        ));
+    }
+
+    /// Draws `text` so that it ends at `right`.
+    pub fn text_right(&mut self, right: f32, y: f32, size: f32, font: Font, text: &str) {
+        let x = right - text_width(text, size, font);
+        self.text(x, y, size, font, text);
+    }
+
+    /// Draws `text` centred on `center`.
+    pub fn text_centered(&mut self, center: f32, y: f32, size: f32, font: Font, text: &str) {
+        let x = center - text_width(text, size, font) / 2.0;
+        self.text(x, y, size, font, text);
+    }
+
+    /// Draws a 0.5pt line.
+    pub fn line(&mut self, x1: f32, y1: f32, x2: f32, y2: f32) {
+        self.content.push_str(&format!(
+            "0.5 w {x1:.2} {:.2} m {x2:.2} {:.2} l S\n",
+            self.height - y1,
+            self.height - y2
+        ));
+    }
+
+    /// Fills a black rectangle whose top-left corner is at (`x`, `y`).
+    pub fn rect(&mut self, x: f32, y: f32, width: f32, height: f32) {
+        self.content.push_str(&format!(
+            "{x:.2} {:.2} {width:.2} {height:.2} re f\n",
+            self.height - y - height
+        ));
+    }
+
+    /// Fills a rectangle like `rect`, in the given RGB color.
+    pub fn color_rect(&mut self, x: f32, y: f32, width: f32, height: f32, rgb: [u8; 3]) {
+        let [r, g, b] = rgb.map(|c| f32::from(c) / 255.0);
+        self.content
+            .push_str(&format!("q {r:.3} {g:.3} {b:.3} rg\n"));
+        self.rect(x, y, width, height);
+        self.content.push_str("Q\n");
+    }
+}
+
+/// Serializes the pages into a PDF file.
+pub fn render(pages: &[Page]) -> Vec<u8> {
+    let mut out: Vec<u8> = b"%PDF-1.4\n".to_vec();
+    let mut offsets: Vec<usize> = Vec::new();
+
+    let mut object = |out: &mut Vec<u8>, body: &[u8]| {
+        offsets.push(out.len());
+        out.extend_from_slice(format!("{} 0 obj\n", offsets.len()).as_bytes());
+        out.extend_from_slice(body);
+        out.extend_from_slice(b"\nendobj\n");
+    };
+
+    // Objects 1-4 are fixed; each page then takes two objects (page and
+    // content stream), starting at 5.
+    let kids: Vec<String> = (0..pages.len())
+        .map(|i| format!("{} 0 R", 5 + 2 * i))
+        .collect();
+    object(&mut out, b"<< /Type /Catalog /Pages 2 0 R >>");
+    object(
+        &mut out,
+        format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            pages.len()
+        )
+        .as_bytes(),
+    );
+    object(
+        &mut out,
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>",
+    );
+    object(
+        &mut out,
+        b"<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>",
+    );
+
+    for (i, page) in pages.iter().enumerate() {
+        object(
+            &mut out,
+            format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {:.2} {:.2}] \
+                 /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+                page.width,
+                page.height,
+                6 + 2 * i
+            )
+            .as_bytes(),
+        );
+        let mut stream = format!("<< /Length {} >>\nstream\n", page.content.len()).into_bytes();
+        stream.extend_from_slice(page.content.as_bytes());
+        stream.extend_from_slice(b"\nendstream");
+        object(&mut out, &stream);
+    }
+
+    let xref_offset = out.len();
+    out.extend_from_slice(format!("xref\n0 {}\n", offsets.len() + 1).as_bytes());
+    out.extend_from_slice(b"0000000000 65535 f \n");
+    for offset in &offsets {
+        out.extend_from_slice(format!("{offset:010} 00000 n \n").as_bytes());
+    }
+    out.extend_from_slice(
+        format!(
+            "trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{xref_offset}\n%%EOF\n",
+            offsets.len() + 1
+        )
+        .as_bytes(),
+    );
+    out
+}
+
+/// Encodes text in WinAnsi (Windows-1252), the encoding of the standard
+/// fonts. Characters outside of it are replaced with `?`.
+fn encode_win_ansi(text: &str) -> Vec<u8> {
+    text.chars()
+        .map(|c| match c {
+            '\u{20}'..='\u{7e}' | '\u{a0}'..='\u{ff}' => c as u8,
+            '€' => 0x80,
+            '‚' => 0x82,
+            '„' => 0x84,
+            '…' => 0x85,
+            '‘' => 0x91,
+            '’' => 0x92,
+            '“' => 0x93,
+            '”' => 0x94,
+            '•' => 0x95,
+            '–' => 0x96,
+            '—' => 0x97,
+            'œ' => 0x9c,
+            'Œ' => 0x8c,
+            'Ÿ' => 0x9f,
+            _ => b'?',
+        })
+        .collect()
+}
+
+/// Helvetica advance widths (per 1000 units of font size) for ASCII 32..=126.
+const HELVETICA_WIDTHS: [u16; 95] = [
+    278, 278, 355, 556, 556, 889, 667, 191, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 278, 278, 584, 584, 584, 556, 1015, 667, 667, 722, 722, 667,
+    611, 778, 722, 278, 500, 667, 556, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 278, 278, 278, 469, 556, 333, 556, 556, 500, 556, 556, 278, 556, 556, 222, 222, 500,
+    222, 833, 556, 556, 556, 556, 333, 500, 278, 556, 500, 722, 500, 500, 500, 334, 260, 334, 584,
+];
+
+/// Helvetica-Bold advance widths for ASCII 32..=126.
+const HELVETICA_BOLD_WIDTHS: [u16; 95] = [
+    278, 333, 474, 556, 556, 889, 722, 238, 333, 333, 389, 584, 278, 333, 278, 278, 556, 556, 556,
+    556, 556, 556, 556, 556, 556, 556, 333, 333, 584, 584, 584, 611, 975, 722, 722, 722, 722, 667,
+    611, 778, 722, 278, 556, 722, 611, 833, 722, 778, 667, 778, 722, 667, 611, 722, 667, 944, 667,
+    667, 611, 333, 278, 333, 584, 556, 333, 556, 611, 556, 611, 556, 333, 611, 611, 278, 278, 556,
+    278, 889, 611, 611, 611, 611, 389, 556, 333, 611, 556, 778, 556, 556, 500, 389, 280, 389, 584,
+];
+
+/// Width of `text` in points. Non-ASCII characters are approximated by the
+/// width of a digit, which is close for accented letters.
+pub fn text_width(text: &str, size: f32, font: Font) -> f32 {
+    let widths = match font {
+        Font::Regular => &HELVETICA_WIDTHS,
+        Font::Bold => &HELVETICA_BOLD_WIDTHS,
+    };
+    let units: u32 = text
+        .chars()
+        .map(|c| match c {
+            ' '..='~' => u32::from(widths[c as usize - 32]),
+            _ => 556,
+        })
+        .sum();
+    units as f32 * size / 1000.0
+}
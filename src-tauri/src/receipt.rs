@@ -0,0 +1,241 @@
+use qrcode::{Color, QrCode};
+
+use crate::export::{format_cents, format_quantity};
+use crate::models::{BusinessSettings, DisplayCurrencySettings, Order, OrderWithItems};
+use crate::pdf::{self, Font, Page};
+
+/// Width of a thermal receipt roll.
+const RECEIPT_WIDTH: f32 = 80.0 * pdf::MM;
+const MARGIN: f32 = 4.0 * pdf::MM;
+const QR_SIZE: f32 = 32.0 * pdf::MM;
+const LINE_HEIGHT: f32 = 12.0;
+
+/// Prefix of the receipt verification codes; bump the version on any
+/// incompatible change.
+const QR_PREFIX: &str = "POS1";
+
+/// Content of the QR code printed on the receipt of `order`: the order id
+/// and its chain hash, which `verify_receipt_code` checks.
+pub fn qr_payload(order: &Order) -> String {
+    format!(
+        "{QR_PREFIX}:{}:{}",
+        order.id,
+        order.hash.as_deref().unwrap_or_default()
+    )
+}
+
+/// Splits a verification code into (order id, hash).
+pub fn parse_qr_payload(code: &str) -> Result<(String, String), String> {
+    let mut parts = code.trim().splitn(3, ':');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(QR_PREFIX), Some(order_id), Some(hash)) if !order_id.is_empty() => {
+            Ok((order_id.to_string(), hash.to_string()))
+        }
+        _ => Err("Not a receipt verification code".to_string()),
+    }
+}
+
+/// Draws `code` with its top-left corner at (`x`, `y`), `size` points wide.
+/// Dark modules of a row are merged into runs to keep the PDF small.
+fn draw_qr_code(page: &mut Page, code: &QrCode, x: f32, y: f32, size: f32) {
+    let width = code.width();
+    let module = size / width as f32;
+    let colors = code.to_colors();
+    for (row, modules) in colors.chunks(width).enumerate() {
+        let mut col = 0;
+        while col < width {
+            if modules[col] == Color::Dark {
+                let start = col;
+                while col < width && modules[col] == Color::Dark {
+                    col += 1;
+                }
+                page.rect(
+                    x + start as f32 * module,
+                    y + row as f32 * module,
+                    (col - start) as f32 * module,
+                    module,
+                );
+            } else {
+                col += 1;
+            }
+        }
+    }
+}
+
+/// Local date and time of a stored UTC timestamp.
+fn local_datetime(created_at: &str) -> String {
+    chrono::DateTime::parse_from_rfc3339(created_at)
+        .map(|d| {
+            d.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M")
+                .to_string()
+        })
+        .unwrap_or_else(|_| created_at.to_string())
+}
+
+/// Renders the receipt of `order` as a single 80mm-wide PDF page, with the
+/// verification QR code at the bottom. The total is also given in the
+/// display currency, if one is configured.
+pub fn render(
+    business: &BusinessSettings,
+    currency: &DisplayCurrencySettings,
+    order: &OrderWithItems,
+) -> Result<Vec<u8>, String> {
+    let code = QrCode::new(qr_payload(&order.order).as_bytes())
+        .map_err(|e| format!("QR code error: {e}"))?;
+
+    let header_lines = 1 + business.address.lines().count();
+    let converted = currency.convert(order.order.total);
+    let body_lines = 2 * order.items.len() + 6 + usize::from(converted.is_some());
+    let height = MARGIN * 2.0
+        + (header_lines + body_lines) as f32 * LINE_HEIGHT
+        + QR_SIZE
+        + 3.0 * LINE_HEIGHT;
+    let mut page = Page::new(RECEIPT_WIDTH, height);
+    let center = RECEIPT_WIDTH / 2.0;
+    let right = RECEIPT_WIDTH - MARGIN;
+
+    let mut y = MARGIN + LINE_HEIGHT;
+    if !business.name.is_empty() {
+        page.text_centered(center, y, 11.0, Font::Bold, &business.name);
+        y += LINE_HEIGHT;
+    }
+    for line in business.address.lines() {
+        page.text_centered(center, y, 8.0, Font::Regular, line);
+        y += LINE_HEIGHT;
+    }
+
+    page.text(
+        MARGIN,
+        y,
+        8.0,
+        Font::Regular,
+        &local_datetime(&order.order.created_at),
+    );
+    y += LINE_HEIGHT;
+    page.text(MARGIN, y, 7.0, Font::Regular, &order.order.id);
+    y += LINE_HEIGHT / 2.0;
+    page.line(MARGIN, y, right, y);
+    y += LINE_HEIGHT;
+
+    for item in &order.items {
+        page.text(MARGIN, y, 9.0, Font::Regular, &item.product_name);
+        y += LINE_HEIGHT;
+        page.text(
+            MARGIN + 8.0,
+            y,
+            8.0,
+            Font::Regular,
+            &format!(
+                "{} x {}",
+                format_quantity(item.quantity, item.unit),
+                format_cents(item.unit_price)
+            ),
+        );
+        page.text_right(right, y, 9.0, Font::Regular, &format_cents(item.total));
+        y += LINE_HEIGHT;
+    }
+
+    y -= LINE_HEIGHT / 2.0;
+    page.line(MARGIN, y, right, y);
+    y += LINE_HEIGHT + 2.0;
+    page.text(MARGIN, y, 11.0, Font::Bold, "TOTAL");
+    page.text_right(
+        right,
+        y,
+        11.0,
+        Font::Bold,
+        &format!("{} EUR", format_cents(order.order.total)),
+    );
+    y += LINE_HEIGHT;
+    if let Some(converted) = converted {
+        page.text_right(
+            right,
+            y,
+            8.0,
+            Font::Regular,
+            &format!(
+                "= {} {} (1 EUR = {} {})",
+                format_cents(converted.amount),
+                converted.currency,
+                converted.rate,
+                converted.currency
+            ),
+        );
+        y += LINE_HEIGHT;
+    }
+    page.text(
+        MARGIN,
+        y,
+        8.0,
+        Font::Regular,
+        &format!("Paid by {}", order.order.payment_method),
+    );
+    y += LINE_HEIGHT;
+
+    draw_qr_code(&mut page, &code, center - QR_SIZE / 2.0, y, QR_SIZE);
+    y += QR_SIZE + LINE_HEIGHT;
+    page.text_centered(center, y, 7.0, Font::Regular, "Scan to verify this receipt");
+
+    Ok(pdf::render(&[page]))
+}
+
+/// Renders the preparation ticket of `order` for the kitchen, or for
+/// `station` when routed (see `generate_station_tickets`): the order
+/// reference, time and items with their quantities, in large type and
+/// without prices. Lines that are not products (surcharges, rounding) are
+/// left out by the caller.
+pub fn render_kitchen_ticket(station: Option<&str>, order: &OrderWithItems) -> Vec<u8> {
+    let header_lines = 3 + usize::from(station.is_some());
+    let height = MARGIN * 2.0 + (header_lines + 2 * order.items.len()) as f32 * LINE_HEIGHT;
+    let mut page = Page::new(RECEIPT_WIDTH, height);
+    let right = RECEIPT_WIDTH - MARGIN;
+
+    let mut y = MARGIN + LINE_HEIGHT;
+    if let Some(station) = station {
+        page.text_centered(RECEIPT_WIDTH / 2.0, y, 11.0, Font::Bold, station);
+        y += LINE_HEIGHT * 1.5;
+    }
+    page.text(
+        MARGIN,
+        y,
+        14.0,
+        Font::Bold,
+        &format!("#{}", kitchen_reference(&order.order)),
+    );
+    page.text_right(
+        right,
+        y,
+        9.0,
+        Font::Regular,
+        &local_datetime(&order.order.created_at),
+    );
+    y += LINE_HEIGHT / 2.0;
+    page.line(MARGIN, y, right, y);
+    y += LINE_HEIGHT * 1.5;
+
+    for item in &order.items {
+        page.text(
+            MARGIN,
+            y,
+            12.0,
+            Font::Bold,
+            &format_quantity(item.quantity, item.unit),
+        );
+        page.text(
+            MARGIN + 14.0 * pdf::MM,
+            y,
+            12.0,
+            Font::Regular,
+            &item.product_name,
+        );
+        y += LINE_HEIGHT * 2.0;
+    }
+
+    pdf::render(&[page])
+}
+
+/// Short reference called out in the kitchen: the start of the order id.
+pub fn kitchen_reference(order: &Order) -> String {
+    order.id.chars().take(6).collect::<String>().to_uppercase()
+}
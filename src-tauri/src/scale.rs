@@ -0,0 +1,148 @@
+use std::io::{Read, Write};
+use std::time::Duration;
+
+use crate::models::{ScaleConnection, ScaleSettings};
+
+/// How long to wait for the scale to answer a weight request.
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+const GRAMS_PER_KG: f64 = 1000.0;
+const KG_PER_POUND: f64 = 0.453_592_37;
+const KG_PER_OUNCE: f64 = 0.028_349_523_125;
+
+/// Reads the current weight, in kilograms, from the configured scale.
+/// Blocks until the scale has answered or the read timed out.
+pub fn read_weight(settings: &ScaleSettings) -> Result<f64, String> {
+    match settings.connection {
+        ScaleConnection::Disabled => Err("No weighing scale is configured".to_string()),
+        ScaleConnection::Serial => read_serial(settings),
+        ScaleConnection::Hid => read_hid(settings),
+    }
+}
+
+fn read_serial(settings: &ScaleSettings) -> Result<f64, String> {
+    let mut port = serialport::new(&settings.serial_port, settings.baud_rate)
+        .timeout(READ_TIMEOUT)
+        .open()
+        .map_err(|e| format!("Failed to open scale port {}: {e}", settings.serial_port))?;
+
+    // "W" is the weight request of the NCI/Toledo protocol, which most
+    // serial POS scales understand.
+    port.write_all(b"W\r")
+        .map_err(|e| format!("Failed to write to scale: {e}"))?;
+
+    let mut response = Vec::new();
+    let mut buf = [0u8; 64];
+    while !is_complete_serial_response(&response) {
+        let n = port
+            .read(&mut buf)
+            .map_err(|e| format!("Failed to read from scale: {e}"))?;
+        if n == 0 {
+            break;
+        }
+        response.extend_from_slice(&buf[..n]);
+    }
+
+    parse_serial_weight(&String::from_utf8_lossy(&response))
+}
+
+/// A response is complete once a line terminator follows the weight.
+fn is_complete_serial_response(response: &[u8]) -> bool {
+    match response.iter().position(u8::is_ascii_digit) {
+        Some(start) => response[start..]
+            .iter()
+            .any(|b| matches!(b, b'\r' | b'\n' | 0x03)),
+        None => false,
+    }
+}
+
+/// Parses a weight line such as `"  0.350kg"`, `"350 g"` or `"0.77LB"` into
+/// kilograms. A missing unit is read as kilograms.
+pub fn parse_serial_weight(response: &str) -> Result<f64, String> {
+    let start = response
+        .find(|c: char| c.is_ascii_digit() || c == '-')
+        .ok_or_else(|| format!("Unexpected scale response: {response:?}"))?;
+    let rest = &response[start..];
+    let end = rest
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+        .unwrap_or(rest.len());
+
+    let value: f64 = rest[..end]
+        .parse()
+        .map_err(|_| format!("Unexpected scale response: {response:?}"))?;
+    let unit: String = rest[end..]
+        .trim_start()
+        .chars()
+        .take_while(char::is_ascii_alphabetic)
+        .collect::<String>()
+        .to_ascii_lowercase();
+
+    let kg = match unit.as_str() {
+        "" | "kg" => value,
+        "g" => value / GRAMS_PER_KG,
+        "lb" => value * KG_PER_POUND,
+        "oz" => value * KG_PER_OUNCE,
+        other => return Err(format!("Unsupported scale unit: {other}")),
+    };
+    checked_weight(kg)
+}
+
+fn read_hid(settings: &ScaleSettings) -> Result<f64, String> {
+    let api = hidapi::HidApi::new().map_err(|e| format!("HID error: {e}"))?;
+    let device = api
+        .open(settings.hid_vendor_id, settings.hid_product_id)
+        .map_err(|e| {
+            format!(
+                "Failed to open scale {:04x}:{:04x}: {e}",
+                settings.hid_vendor_id, settings.hid_product_id
+            )
+        })?;
+
+    let mut report = [0u8; 8];
+    let n = device
+        .read_timeout(&mut report, READ_TIMEOUT.as_millis() as i32)
+        .map_err(|e| format!("Failed to read from scale: {e}"))?;
+    if n == 0 {
+        return Err("The scale did not answer".to_string());
+    }
+
+    parse_hid_report(&report[..n])
+}
+
+/// Parses a scale data report as defined by the USB HID Point of Sale usage
+/// tables: report id, status, unit, signed power-of-ten exponent and
+/// little-endian weight.
+pub fn parse_hid_report(report: &[u8]) -> Result<f64, String> {
+    let [_report_id, status, unit, exponent, lsb, msb, ..] = *report else {
+        return Err(format!("Scale report too short: {report:?}"));
+    };
+
+    match status {
+        2 => return Ok(0.0),
+        4 => {}
+        3 => return Err("The scale is not stable yet".to_string()),
+        5 => return Err("The scale reports a negative weight".to_string()),
+        6 => return Err("The scale is overloaded".to_string()),
+        7 | 8 => return Err("The scale needs to be calibrated or zeroed".to_string()),
+        other => return Err(format!("The scale reports an error (status {other})")),
+    }
+
+    let raw = f64::from(u16::from_le_bytes([lsb, msb])) * 10f64.powi(i32::from(exponent as i8));
+    let kg = match unit {
+        2 => raw / GRAMS_PER_KG,
+        3 => raw,
+        11 => raw * KG_PER_OUNCE,
+        12 => raw * KG_PER_POUND,
+        other => return Err(format!("Unsupported scale unit code: {other}")),
+    };
+    checked_weight(kg)
+}
+
+/// Rejects negative weights and rounds to the gram, the precision kept on
+/// order lines.
+fn checked_weight(kg: f64) -> Result<f64, String> {
+    if kg < 0.0 {
+        return Err("The scale reports a negative weight".to_string());
+    }
+    Ok((kg * GRAMS_PER_KG).round() / GRAMS_PER_KG)
+}
@@ -0,0 +1,164 @@
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, Local, NaiveTime};
+use tauri::{AppHandle, Manager};
+
+use crate::commands::{
+    build_expiry_alert_inner, build_report_email_inner, build_summary_notification_inner,
+    get_stock_levels_inner, list_scheduled_jobs_inner, record_job_run, record_scheduler_error,
+};
+use crate::db::DbState;
+use crate::export::{self, Cell, Sheet};
+use crate::models::{DateRange, ExportFormat, ScheduledJob, ScheduledJobKind};
+
+/// How often the scheduler looks for due jobs.
+const TICK: Duration = Duration::from_secs(30);
+
+/// Backups and stock snapshots kept in their folder; older ones are deleted
+/// after each successful run.
+pub(crate) const KEPT_SCHEDULED_FILES: usize = 14;
+
+/// Starts the thread running the scheduled jobs. Their files go to the app
+/// data dir, next to the database. A tick that fails is recorded for
+/// `get_health` to report, and retried on the next one.
+pub fn spawn(app: AppHandle) {
+    std::thread::spawn(move || loop {
+        std::thread::sleep(TICK);
+        let db = app.state::<DbState>();
+        let result = app
+            .path()
+            .app_data_dir()
+            .map_err(|e| format!("No app data dir: {e}"))
+            .and_then(|data_dir| run_due_jobs(&db, Local::now(), &data_dir));
+        let _ = record_scheduler_error(db.inner(), result.err().as_deref());
+    });
+}
+
+/// A job runs once a day, on the first tick at or after its time. A failed
+/// run is not retried before the next day: its error is shown instead.
+fn is_due(job: &ScheduledJob, now: DateTime<Local>) -> bool {
+    let Ok(time) = NaiveTime::parse_from_str(&job.time, "%H:%M") else {
+        return false;
+    };
+    let ran_today = job
+        .last_run_at
+        .as_deref()
+        .and_then(|at| DateTime::parse_from_rfc3339(at).ok())
+        .is_some_and(|at| at.with_timezone(&Local).date_naive() >= now.date_naive());
+    job.enabled && now.time() >= time && !ran_today
+}
+
+/// Runs the jobs due at `now` and records their outcome. Returns the jobs
+/// that ran, whether they succeeded or not.
+pub(crate) fn run_due_jobs(
+    db: &DbState,
+    now: DateTime<Local>,
+    data_dir: &Path,
+) -> Result<Vec<ScheduledJobKind>, String> {
+    let mut ran = Vec::new();
+    for job in list_scheduled_jobs_inner(db)? {
+        if !is_due(&job, now) {
+            continue;
+        }
+        let error = run_job(db, &job, now, data_dir).err();
+        let ran_at = now
+            .with_timezone(&chrono::Utc)
+            .format("%Y-%m-%dT%H:%M:%SZ")
+            .to_string();
+        record_job_run(db, job.kind, &ran_at, error.as_deref())?;
+        ran.push(job.kind);
+    }
+    Ok(ran)
+}
+
+fn run_job(
+    db: &DbState,
+    job: &ScheduledJob,
+    now: DateTime<Local>,
+    data_dir: &Path,
+) -> Result<(), String> {
+    let stamp = now.format("%Y%m%d-%H%M%S");
+    match job.kind {
+        ScheduledJobKind::Backup => {
+            let path = output_path(data_dir, "backups", &format!("pos-{stamp}.db"))?;
+            crate::db::backup_to(&*db.read_conn()?, Path::new(&path))?;
+            prune_old_files(&data_dir.join("backups"), "pos-", ".db")?;
+        }
+        ScheduledJobKind::ReportEmail => {
+            let today = now.format("%Y-%m-%d").to_string();
+            let range = DateRange {
+                from: Some(today.clone()),
+                to: Some(today),
+            };
+            let (settings, message) = build_report_email_inner(db, range, job.recipients.clone())?;
+            crate::mail::send(&settings, &message)?;
+        }
+        ScheduledJobKind::StockSnapshot => {
+            let path = output_path(data_dir, "stock", &format!("stock-{stamp}.csv"))?;
+            let sheet = Sheet {
+                name: "Stock".to_string(),
+                header: vec![
+                    "Product".to_string(),
+                    "Unit".to_string(),
+                    "Quantity".to_string(),
+                ],
+                rows: get_stock_levels_inner(db)?
+                    .into_iter()
+                    .map(|level| {
+                        vec![
+                            Cell::Text(level.product_name),
+                            Cell::Text(level.unit.as_db_str().to_string()),
+                            Cell::Number(level.quantity),
+                        ]
+                    })
+                    .collect(),
+            };
+            export::write_sheets(&[sheet], ExportFormat::Csv, Path::new(&path))?;
+            prune_old_files(&data_dir.join("stock"), "stock-", ".csv")?;
+        }
+        ScheduledJobKind::ExpiryAlert => {
+            if let Some((settings, text)) = build_expiry_alert_inner(db, now.date_naive())? {
+                crate::notify::send(&settings, &text)?;
+            }
+        }
+        ScheduledJobKind::SummaryNotification => {
+            let today = now.format("%Y-%m-%d").to_string();
+            let (settings, text) = build_summary_notification_inner(db, Some(today))?;
+            crate::notify::send(&settings, &text)?;
+        }
+    }
+    Ok(())
+}
+
+/// `data_dir/folder/file_name`, creating the folder if needed.
+fn output_path(data_dir: &Path, folder: &str, file_name: &str) -> Result<String, String> {
+    let dir = data_dir.join(folder);
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| format!("Failed to create {}: {e}", dir.display()))?;
+    Ok(dir.join(file_name).to_string_lossy().into_owned())
+}
+
+/// Deletes the files of `dir` named `{prefix}{stamp}{suffix}` but the last
+/// `KEPT_SCHEDULED_FILES`, stamps sorting by date. Other files are left
+/// alone.
+fn prune_old_files(dir: &Path, prefix: &str, suffix: &str) -> Result<(), String> {
+    let entries =
+        std::fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {e}", dir.display()))?;
+    let mut names = Vec::new();
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read {}: {e}", dir.display()))?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if name.starts_with(prefix) && name.ends_with(suffix) {
+            names.push(name);
+        }
+    }
+    names.sort();
+    let excess = names.len().saturating_sub(KEPT_SCHEDULED_FILES);
+    for name in &names[..excess] {
+        let path = dir.join(name);
+        std::fs::remove_file(&path)
+            .map_err(|e| format!("Failed to delete {}: {e}", path.display()))?;
+    }
+    Ok(())
+}
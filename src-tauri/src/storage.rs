@@ -0,0 +1,83 @@
+use std::path::Path;
+use std::time::Duration;
+
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::db::DbState;
+use crate::models::StorageStatus;
+
+/// How often the storage monitor checks disk and database sizes.
+const CHECK_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Free disk space under which a warning is raised.
+const LOW_DISK_SPACE_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// WAL size over which a warning is raised: it is normally folded back into
+/// the database, so a large one means checkpoints are not keeping up.
+const LARGE_WAL_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Event emitted with a `StorageStatus` when its warnings change.
+pub const STORAGE_WARNING_EVENT: &str = "storage-warning";
+
+fn file_size(path: &str) -> u64 {
+    std::fs::metadata(path).map(|m| m.len()).unwrap_or(0)
+}
+
+/// Sizes of the database at `db_path` and of the disk holding it. All zero
+/// and unknown for an in-memory database.
+pub fn status(db_path: &str) -> StorageStatus {
+    if db_path == ":memory:" {
+        return StorageStatus {
+            database_bytes: 0,
+            wal_bytes: 0,
+            free_disk_bytes: None,
+            warnings: Vec::new(),
+        };
+    }
+
+    let database_bytes = file_size(db_path);
+    let wal_bytes = file_size(&format!("{db_path}-wal"));
+    let free_disk_bytes = Path::new(db_path)
+        .parent()
+        .and_then(|dir| fs4::available_space(dir).ok());
+
+    let mb = |bytes: u64| bytes / (1024 * 1024);
+    let mut warnings = Vec::new();
+    if let Some(free) = free_disk_bytes {
+        if free < LOW_DISK_SPACE_BYTES {
+            warnings.push(format!("Only {} MB of disk space left", mb(free)));
+        }
+        // Backups and migrations copy the whole database.
+        if free < database_bytes {
+            warnings.push("Not enough disk space left to back up the database".to_string());
+        }
+    }
+    if wal_bytes > LARGE_WAL_BYTES {
+        warnings.push(format!("The database journal is {} MB", mb(wal_bytes)));
+    }
+
+    StorageStatus {
+        database_bytes,
+        wal_bytes,
+        free_disk_bytes,
+        warnings,
+    }
+}
+
+/// Starts the thread checking the storage, emitting `STORAGE_WARNING_EVENT`
+/// whenever the warnings change and some remain.
+pub fn spawn(app: AppHandle) {
+    std::thread::spawn(move || {
+        let mut previous: Vec<String> = Vec::new();
+        loop {
+            let status = status(&app.state::<DbState>().db_path);
+            if status.warnings != previous {
+                previous = status.warnings.clone();
+                if !status.warnings.is_empty() {
+                    let _ = app.emit(STORAGE_WARNING_EVENT, &status);
+                }
+            }
+            std::thread::sleep(CHECK_INTERVAL);
+        }
+    });
+}
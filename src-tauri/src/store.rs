@@ -0,0 +1,307 @@
+use rusqlite::params;
+use rusqlite::Connection;
+use rusqlite::OptionalExtension;
+
+use crate::db::DbState;
+use crate::models::{Product, ProductFilter, QuantityUnit, Translations};
+
+/// Storage operations the command logic relies on, so that it can run
+/// against another backend than SQLite, or against an in-memory mock in
+/// tests.
+///
+/// Implemented by `Connection`, for helpers already holding the connection
+/// or a transaction, and by `DbState`, which locks its connection around
+/// every call.
+pub trait Store {
+    /// Reads a value from the key/value settings.
+    fn get_setting(&self, key: &str) -> Result<Option<String>, String>;
+
+    /// Writes several settings at once: either all of them are saved or
+    /// none is.
+    fn set_settings(&self, values: &[(&str, String)]) -> Result<(), String>;
+
+    /// Lists the products matching `filter`, ordered by category and name.
+    /// With a `catalog_id`, only the products of that catalog are returned,
+    /// and with a `register_id` only the products that register shows.
+    fn list_products(
+        &self,
+        catalog_id: Option<&str>,
+        register_id: Option<&str>,
+        filter: &ProductFilter,
+    ) -> Result<Vec<Product>, String>;
+
+    fn get_product(&self, product_id: &str) -> Result<Option<Product>, String>;
+
+    fn insert_product(&self, product: &Product) -> Result<(), String>;
+
+    /// Saves every field of an existing product; fails when it does not
+    /// exist.
+    fn update_product(&self, product: &Product) -> Result<(), String>;
+
+    /// Fails when the product does not exist.
+    fn delete_product(&self, product_id: &str) -> Result<(), String>;
+}
+
+/// Columns read by `product_from_row`, in order.
+pub(crate) const PRODUCT_COLUMNS: &str =
+    "id, name, price, category_id, available, age_restricted, unit, translations, barcode,
+     available_from, available_until";
+
+pub(crate) fn product_from_row(row: &rusqlite::Row) -> rusqlite::Result<Product> {
+    Ok(Product {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        price: row.get(2)?,
+        category_id: row.get(3)?,
+        available: row.get::<_, i64>(4)? != 0,
+        age_restricted: row.get(5)?,
+        unit: quantity_unit_from_row(row, 6)?,
+        translations: translations_from_row(row, 7)?,
+        barcode: row.get(8)?,
+        available_from: row.get(9)?,
+        available_until: row.get(10)?,
+    })
+}
+
+/// Reads a `QuantityUnit` stored as text in column `idx`.
+pub(crate) fn quantity_unit_from_row(
+    row: &rusqlite::Row,
+    idx: usize,
+) -> rusqlite::Result<QuantityUnit> {
+    let unit: String = row.get(idx)?;
+    QuantityUnit::from_db_str(&unit).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::from(e))
+    })
+}
+
+pub(crate) fn translations_json(translations: &Translations) -> String {
+    serde_json::to_string(translations).unwrap_or_else(|_| "{}".to_string())
+}
+
+/// Reads a `Translations` map stored as JSON text in column `idx`.
+pub(crate) fn translations_from_row(
+    row: &rusqlite::Row,
+    idx: usize,
+) -> rusqlite::Result<Translations> {
+    let json: String = row.get(idx)?;
+    serde_json::from_str(&json).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(idx, rusqlite::types::Type::Text, Box::new(e))
+    })
+}
+
+/// The SQLite backend. Writing several settings is not wrapped in a
+/// transaction here: callers holding a connection manage their own.
+impl Store for Connection {
+    fn get_setting(&self, key: &str) -> Result<Option<String>, String> {
+        crate::db::get_setting(self, key)
+    }
+
+    fn set_settings(&self, values: &[(&str, String)]) -> Result<(), String> {
+        for (key, value) in values {
+            crate::db::set_setting(self, key, value)?;
+        }
+        Ok(())
+    }
+
+    fn list_products(
+        &self,
+        catalog_id: Option<&str>,
+        register_id: Option<&str>,
+        filter: &ProductFilter,
+    ) -> Result<Vec<Product>, String> {
+        let search = filter
+            .search
+            .as_deref()
+            .map(|s| s.trim().to_lowercase())
+            .filter(|s| !s.is_empty());
+
+        let mut stmt = self
+            .prepare(&format!(
+                "SELECT {PRODUCT_COLUMNS}
+                 FROM products
+                 WHERE (?1 IS NULL
+                        OR id IN (SELECT product_id FROM catalog_products WHERE catalog_id = ?1))
+                   AND (?2 IS NULL OR category_id = ?2)
+                   AND (?3 IS NULL OR available = ?3)
+                   AND (?4 IS NULL OR instr(lower(name), ?4) > 0 OR barcode = ?4)
+                   AND (?7 IS NULL
+                        OR id IN (SELECT product_id FROM register_products WHERE register_id = ?7)
+                        OR category_id IN (
+                            WITH RECURSIVE shown(id) AS (
+                                SELECT category_id FROM register_categories WHERE register_id = ?7
+                                UNION
+                                SELECT c.id FROM categories c JOIN shown s ON c.parent_id = s.id
+                            )
+                            SELECT id FROM shown))
+                   AND (?8 IS NULL
+                        OR ((available_from IS NULL OR available_from <= ?8)
+                            AND (available_until IS NULL OR available_until >= ?8)))
+                 ORDER BY category_id, name
+                 LIMIT ?5 OFFSET ?6"
+            ))
+            .map_err(|e| format!("Query error: {e}"))?;
+
+        let products = stmt
+            .query_map(
+                params![
+                    catalog_id,
+                    filter.category_id,
+                    filter.available,
+                    search,
+                    // A negative LIMIT means no limit.
+                    filter.limit.map_or(-1, i64::from),
+                    filter.offset.unwrap_or(0),
+                    register_id,
+                    filter.available_on
+                ],
+                product_from_row,
+            )
+            .map_err(|e| format!("Query error: {e}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format!("Row mapping error: {e}"))?;
+
+        Ok(products)
+    }
+
+    fn get_product(&self, product_id: &str) -> Result<Option<Product>, String> {
+        self.query_row(
+            &format!("SELECT {PRODUCT_COLUMNS} FROM products WHERE id = ?1"),
+            params![product_id],
+            product_from_row,
+        )
+        .optional()
+        .map_err(|e| format!("Query error: {e}"))
+    }
+
+    fn insert_product(&self, product: &Product) -> Result<(), String> {
+        self.execute(
+            "INSERT INTO products (id, name, price, category_id, available, age_restricted, unit, translations, barcode,
+                                   available_from, available_until)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                product.id,
+                product.name,
+                product.price,
+                product.category_id,
+                product.available,
+                product.age_restricted,
+                product.unit.as_db_str(),
+                translations_json(&product.translations),
+                product.barcode,
+                product.available_from,
+                product.available_until
+            ],
+        )
+        .map_err(|e| format!("Insert error: {e}"))?;
+        Ok(())
+    }
+
+    fn update_product(&self, product: &Product) -> Result<(), String> {
+        let rows_affected = self
+            .execute(
+                "UPDATE products
+                 SET name = ?1, price = ?2, category_id = ?3, available = ?4,
+                     age_restricted = ?5, unit = ?6, translations = ?7, barcode = ?8,
+                     available_from = ?9, available_until = ?10
+                 WHERE id = ?11",
+                params![
+                    product.name,
+                    product.price,
+                    product.category_id,
+                    product.available,
+                    product.age_restricted,
+                    product.unit.as_db_str(),
+                    translations_json(&product.translations),
+                    product.barcode,
+                    product.available_from,
+                    product.available_until,
+                    product.id
+                ],
+            )
+            .map_err(|e| format!("Update error: {e}"))?;
+
+        if rows_affected == 0 {
+            return Err(format!("Product not found: {}", product.id));
+        }
+        Ok(())
+    }
+
+    fn delete_product(&self, product_id: &str) -> Result<(), String> {
+        let rows_affected = self
+            .execute("DELETE FROM products WHERE id = ?1", params![product_id])
+            .map_err(|e| format!("Delete error: {e}"))?;
+
+        if rows_affected == 0 {
+            return Err(format!("Product not found: {}", product_id));
+        }
+        Ok(())
+    }
+}
+
+impl Store for DbState {
+    fn get_setting(&self, key: &str) -> Result<Option<String>, String> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| format!("DB lock error: {e}"))?;
+        conn.get_setting(key)
+    }
+
+    fn set_settings(&self, values: &[(&str, String)]) -> Result<(), String> {
+        let mut conn = self
+            .conn
+            .lock()
+            .map_err(|e| format!("DB lock error: {e}"))?;
+        let tx = conn
+            .transaction()
+            .map_err(|e| format!("Transaction begin error: {e}"))?;
+        tx.set_settings(values)?;
+        tx.commit()
+            .map_err(|e| format!("Transaction commit error: {e}"))
+    }
+
+    fn list_products(
+        &self,
+        catalog_id: Option<&str>,
+        register_id: Option<&str>,
+        filter: &ProductFilter,
+    ) -> Result<Vec<Product>, String> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| format!("DB lock error: {e}"))?;
+        conn.list_products(catalog_id, register_id, filter)
+    }
+
+    fn get_product(&self, product_id: &str) -> Result<Option<Product>, String> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| format!("DB lock error: {e}"))?;
+        conn.get_product(product_id)
+    }
+
+    fn insert_product(&self, product: &Product) -> Result<(), String> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| format!("DB lock error: {e}"))?;
+        conn.insert_product(product)
+    }
+
+    fn update_product(&self, product: &Product) -> Result<(), String> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| format!("DB lock error: {e}"))?;
+        conn.update_product(product)
+    }
+
+    fn delete_product(&self, product_id: &str) -> Result<(), String> {
+        let conn = self
+            .conn
+            .lock()
+            .map_err(|e| format!("DB lock error: {e}"))?;
+        conn.delete_product(product_id)
+    }
+}
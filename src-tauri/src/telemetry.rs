@@ -0,0 +1,40 @@
+use tauri::ipc::Invoke;
+use tauri::{Manager, Runtime};
+
+use crate::db::DbState;
+use crate::models::UsageReport;
+
+/// Wraps the command handler so that every command call is counted by
+/// `record_usage_inner`, which does nothing unless usage metrics are enabled.
+pub fn count_commands<R: Runtime>(
+    handler: impl Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static {
+    move |invoke| {
+        if let Some(db) = invoke.message.webview().try_state::<DbState>() {
+            let _ = crate::commands::record_usage_inner(&db, invoke.message.command());
+        }
+        handler(invoke)
+    }
+}
+
+/// Rough size of a catalog, so that the exact number is not reported.
+pub fn catalog_size_bucket(products: i64) -> &'static str {
+    match products {
+        ..=25 => "0-25",
+        26..=100 => "26-100",
+        101..=500 => "101-500",
+        _ => "500+",
+    }
+}
+
+/// Posts the report as JSON to `endpoint`.
+/// Blocks until the remote service has answered.
+pub fn upload(endpoint: &str, report: &UsageReport) -> Result<(), String> {
+    if endpoint.trim().is_empty() {
+        return Err("Usage metrics endpoint is not configured".to_string());
+    }
+    ureq::post(endpoint.trim())
+        .send_json(report)
+        .map(|_| ())
+        .map_err(|e| format!("Failed to upload usage metrics: {e}"))
+}
@@ -1,4 +1,5 @@
 import { invoke } from '@tauri-apps/api/core';
+import { listen } from '@tauri-apps/api/event';
 
 export type AppTarget = 'tauri' | 'web' | 'static';
 
@@ -18,3 +19,17 @@ export async function api_call<T = void>(
 			throw new Error('Static backend not yet implemented');
 	}
 }
+
+/** Subscribes to a backend event; resolves to the function unsubscribing. */
+export async function api_listen<T>(
+	event: string,
+	handler: (payload: T) => void
+): Promise<() => void> {
+	switch (APP_TARGET) {
+		case 'tauri':
+			return listen<T>(event, (e) => handler(e.payload));
+		case 'web':
+		case 'static':
+			return () => {};
+	}
+}
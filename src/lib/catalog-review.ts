@@ -0,0 +1,14 @@
+import { get } from 'svelte/store';
+import { api_call } from '$lib/api';
+import { t } from '$lib/i18n';
+
+/**
+ * Admin PIN sent with a catalog change: asked for while catalog review is on,
+ * `null` otherwise, `undefined` when the user cancels.
+ */
+export async function catalogAdminPin(): Promise<string | null | undefined> {
+	if (!(await api_call<boolean>('get_catalog_review'))) {
+		return null;
+	}
+	return prompt(get(t)('catalogReview.adminPinPrompt')) ?? undefined;
+}
@@ -8,6 +8,9 @@ const locales: Record<string, Messages> = { fr, en };
 
 export const locale = writable<string>('fr');
 
+/** Locales the interface is translated into. */
+export const availableLocales = Object.keys(locales);
+
 function resolve(obj: unknown, path: string): string {
 	const keys = path.split('.');
 	let current: unknown = obj;
@@ -37,3 +40,9 @@ export const t = derived(locale, ($locale) => {
 		return value;
 	};
 });
+
+/** Picks a product or category label for the current locale, falling back to the default label. */
+export const tLabel = derived(locale, ($locale) => {
+	return (label: string, translations?: Record<string, string>): string =>
+		translations?.[$locale] ?? label;
+});
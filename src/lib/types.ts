@@ -1,21 +1,98 @@
+/** Labels keyed by locale code (e.g. "en"); missing locales use the default label. */
+export type Translations = Record<string, string>;
+
 export interface Category {
 	id: string;
 	label: string;
 	color: string;
+	translations: Translations;
+	/** Emoji or icon name. */
+	icon: string | null;
+	/** Null for top-level categories. */
+	parent_id: string | null;
+	/** Labels from the top-level category down, e.g. "Boissons > Chaudes". */
+	path: string;
+}
+
+/** A named selection of products offered on the sales screen. */
+export interface Catalog {
+	id: string;
+	name: string;
+	product_ids: string[];
+	/** Whether this catalog currently restricts the sales screen. */
+	active: boolean;
+}
+
+export interface CreateCatalogPayload {
+	name: string;
+	product_ids: string[];
+}
+
+export interface UpdateCatalogPayload {
+	id: string;
+	name: string;
+	product_ids?: string[];
+}
+
+/** A till of the event, showing only some products on its sales screen. */
+export interface Register {
+	id: string;
+	name: string;
+	product_ids: string[];
+	/** Categories whose products, subcategories included, are shown. */
+	category_ids: string[];
+	/** Whether this till is this register. */
+	current: boolean;
+}
+
+export interface CreateRegisterPayload {
+	name: string;
+	product_ids: string[];
+	category_ids: string[];
+}
+
+export interface UpdateRegisterPayload {
+	id: string;
+	name: string;
+	product_ids?: string[];
+	category_ids?: string[];
 }
 
 export interface CreateCategoryPayload {
 	id: string;
 	label: string;
 	color: string;
+	translations?: Translations;
+	icon?: string;
+	parent_id?: string;
+	/** Accept a color outside of the palette. */
+	allow_custom_color?: boolean;
 }
 
 export interface UpdateCategoryPayload {
 	id: string;
 	label: string;
 	color: string;
+	/** Left unchanged when omitted. */
+	translations?: Translations;
+	/** Left unchanged when omitted; an empty string removes the icon. */
+	icon?: string;
+	/** Left unchanged when omitted; an empty string makes it top-level. */
+	parent_id?: string;
+	/** Accept a color outside of the palette. */
+	allow_custom_color?: boolean;
 }
 
+/** An approved category color; an empty palette accepts any color. */
+export interface PaletteColor {
+	/** `#rrggbb`, lowercase. */
+	color: string;
+	name: string;
+}
+
+/** Unit a product is priced by. Weighed or measured products take decimal quantities. */
+export type QuantityUnit = 'piece' | 'kg' | 'litre';
+
 export interface Product {
 	id: string;
 	name: string;
@@ -24,6 +101,18 @@ export interface Product {
 	/** Foreign key referencing the categories table. */
 	category_id: string;
 	available: boolean;
+	/** Selling the product requires checking the customer's age. */
+	age_restricted: boolean;
+	/** The price applies per piece, per kg or per litre. */
+	unit: QuantityUnit;
+	/** Product name per locale. */
+	translations: Translations;
+	/** In-store EAN-13 code, assigned when its first label is printed. */
+	barcode: string | null;
+	/** First date (YYYY-MM-DD) a seasonal product is offered on; null when open. */
+	available_from: string | null;
+	/** Last date the product is offered on; null when open. */
+	available_until: string | null;
 }
 
 export interface Order {
@@ -31,6 +120,27 @@ export interface Order {
 	created_at: string;
 	total: number;
 	payment_method: 'cash' | 'card';
+	/** Tamper-evidence hash; null for orders recorded before it existed. */
+	hash: string | null;
+	/** Set when the order was voided; voided orders don't count as sales. */
+	voided_at: string | null;
+	/** Order refunded by this one; refunds have negative totals. */
+	refund_of: string | null;
+	/** Free-form labels, sorted. */
+	tags: string[];
+	/** Test sale, voided by `purge_test_orders`. */
+	is_test: boolean;
+	/** Channel the order came through; refunds keep the one of their order. */
+	source: OrderSource;
+}
+
+export type OrderSource = 'counter' | 'preorder' | 'phone' | 'online';
+
+/** A page of `list_orders`, most recent orders first. */
+export interface OrderPageQuery {
+	/** Id of the last order of the previous page; the first page when omitted. */
+	before?: string;
+	limit?: number;
 }
 
 export interface OrderItem {
@@ -39,8 +149,25 @@ export interface OrderItem {
 	product_id: string;
 	product_name: string;
 	unit_price: number;
+	/** Decimal for products sold by weight or volume. */
 	quantity: number;
+	unit: QuantityUnit;
+	/** unit_price * quantity, rounded to the cent. */
 	total: number;
+	/** Line refunded by this negative line. */
+	refunded_item_id: string | null;
+}
+
+export interface ItemRefund {
+	item_id: string;
+	quantity: number;
+}
+
+export interface Exchange {
+	refund: OrderWithItems;
+	replacement: OrderWithItems;
+	/** Cents the customer pays (positive) or gets back (negative). */
+	balance: number;
 }
 
 /** Flattened via #[serde(flatten)] on the Rust side. */
@@ -58,6 +185,60 @@ export interface CreateOrderItemPayload {
 export interface CreateOrderPayload {
 	items: CreateOrderItemPayload[];
 	payment_method: 'cash' | 'card';
+	/** Required when the order contains age-restricted products. */
+	age_confirmed?: boolean;
+	/** Required when the order is over the `OrderLimits`. */
+	limits_confirmed?: boolean;
+	/** Amounts taken off the order; its total cannot go below 0. */
+	adjustments?: OrderAdjustment[];
+	/** Defaults to `counter`. */
+	source?: OrderSource;
+}
+
+/** A negative line such as a returned deposit, stored with product id `adjustment`. */
+export interface OrderAdjustment {
+	label: string;
+	/** Negative amount in cents. */
+	amount: number;
+}
+
+/** A cart split between several people, each part paid as its own order. */
+export interface SplitOrderPayload {
+	items: CreateOrderItemPayload[];
+	parts: SplitOrderPart[];
+	age_confirmed?: boolean;
+	/** Required when a part is over the `OrderLimits`. */
+	limits_confirmed?: boolean;
+}
+
+export interface SplitOrderPart {
+	payment_method: 'cash' | 'card';
+	/** Every cart line must be assigned in full across the parts. */
+	items: { item_index: number; quantity: number }[];
+}
+
+/** Items served now and paid later, when the tab is settled. */
+export interface Tab {
+	id: string;
+	name: string;
+	opened_at: string;
+	items: TabItem[];
+	/** Cents, before surcharges and rounding. */
+	total: number;
+}
+
+export interface TabItem {
+	id: string;
+	product_id: string;
+	product_name: string;
+	unit_price: number;
+	quantity: number;
+	added_at: string;
+}
+
+export interface AddToTabPayload {
+	items: CreateOrderItemPayload[];
+	age_confirmed?: boolean;
 }
 
 export interface CreateProductPayload {
@@ -65,6 +246,9 @@ export interface CreateProductPayload {
 	/** Price in cents. */
 	price: number;
 	category_id: string;
+	age_restricted?: boolean;
+	unit?: QuantityUnit;
+	translations?: Translations;
 }
 
 export interface UpdateProductPayload {
@@ -74,6 +258,29 @@ export interface UpdateProductPayload {
 	price: number;
 	category_id: string;
 	available: boolean;
+	/** Left unchanged when omitted. */
+	age_restricted?: boolean;
+	/** Left unchanged when omitted. */
+	unit?: QuantityUnit;
+	/** Left unchanged when omitted. */
+	translations?: Translations;
+}
+
+/** A product edit proposed while catalog review is on, waiting for an admin. */
+export interface PendingProductChange {
+	id: string;
+	/** The product as it is now. */
+	current: Product;
+	proposed: UpdateProductPayload;
+	/** Name given by whoever proposed the change. */
+	proposed_by: string;
+	created_at: string;
+}
+
+/** Interface languages of the sales screen and the customer-facing display. */
+export interface LocaleSettings {
+	sales_locale: string;
+	customer_display_locale: string;
 }
 
 // ── App Version ──────────────────────────────────────────────────────────────
@@ -97,6 +304,37 @@ export interface PaymentMethodBreakdown {
 	payment_method: 'cash' | 'card';
 	total_revenue: number;
 	transaction_count: number;
+	/** Part of `total_revenue` made of payment surcharges. */
+	surcharge_revenue: number;
+}
+
+export interface SourceBreakdown {
+	source: OrderSource;
+	total_revenue: number;
+	transaction_count: number;
+}
+
+export interface CategorySalesSummary {
+	/** Null for sales of products that have since been deleted. */
+	category_id: string | null;
+	category_label: string | null;
+	total_quantity: number;
+	total_revenue: number;
+}
+
+export interface BasketHistogramBucket {
+	/** Lower bound in cents (inclusive). */
+	min: number;
+	/** Upper bound in cents (exclusive), null for the last bucket. */
+	max: number | null;
+	order_count: number;
+}
+
+export interface BasketStats {
+	average_total: number;
+	median_total: number;
+	average_items_per_order: number;
+	histogram: BasketHistogramBucket[];
 }
 
 export interface DashboardSummary {
@@ -104,6 +342,47 @@ export interface DashboardSummary {
 	total_transactions: number;
 	per_product: ProductSalesSummary[];
 	per_payment_method: PaymentMethodBreakdown[];
+	per_category: CategorySalesSummary[];
+	per_source: SourceBreakdown[];
+	basket: BasketStats;
+	/** `total_revenue` in the display currency, when one is configured. */
+	converted_total: ConvertedAmount | null;
+}
+
+/** An amount converted to the display currency, for information only. */
+export interface ConvertedAmount {
+	currency: string;
+	rate: number;
+	/** Hundredths of the display currency. */
+	amount: number;
+}
+
+/** Second currency amounts are also shown in; disabled while `code` is empty. */
+export interface DisplayCurrencySettings {
+	code: string;
+	/** Units of the display currency per euro. */
+	rate: number;
+}
+
+/** Seller details printed on invoices. */
+export interface BusinessSettings {
+	name: string;
+	/** Postal address, one line per row. */
+	address: string;
+	siret: string;
+	vat_number: string;
+	vat_enabled: boolean;
+	/** VAT rate in percent, e.g. 20 or 5.5. */
+	vat_rate: number;
+}
+
+/** First-launch setup sent to `bootstrap_workspace`. */
+export interface BootstrapPayload {
+	business: BusinessSettings;
+	admin_pin?: string | null;
+	display_currency?: DisplayCurrencySettings | null;
+	/** Create the starter catalog of a French club bar. */
+	starter_catalog: boolean;
 }
 
 /** Client-side cart item (product + chosen quantity). */
@@ -111,3 +390,493 @@ export interface CartItem {
 	product: Product;
 	quantity: number;
 }
+
+/** Current stock of a product whose stock is tracked. */
+export interface StockLevel {
+	product_id: string;
+	product_name: string;
+	unit: QuantityUnit;
+	quantity: number;
+}
+
+/** An inventory count, open until committed. */
+export interface Stocktake {
+	id: string;
+	started_at: string;
+	committed_at: string | null;
+}
+
+export interface StocktakeLine {
+	product_id: string;
+	product_name: string;
+	unit: QuantityUnit;
+	expected: number;
+	counted: number;
+	/** counted - expected: negative when items went missing. */
+	variance: number;
+	/** Variance valued at the selling price, in cents. */
+	variance_value: number;
+}
+
+export interface StocktakeReport {
+	stocktake: Stocktake;
+	lines: StocktakeLine[];
+	total_variance_value: number;
+}
+
+export type StockMovementKind = 'adjustment' | 'waste' | 'receipt';
+
+/** A change of the stock of a product, sales aside. */
+export interface StockMovement {
+	id: string;
+	product_id: string;
+	kind: StockMovementKind;
+	/** Negative when goods leave the stock. */
+	quantity: number;
+	reason: string | null;
+	created_at: string;
+}
+
+export interface WasteSummary {
+	product_id: string;
+	product_name: string;
+	unit: QuantityUnit;
+	reason: string;
+	quantity: number;
+	/** Valued at the selling price, in cents. */
+	value: number;
+}
+
+/** Goods of a product put in stock together. */
+export interface StockBatch {
+	/** Id of the `receipt` stock movement of the batch. */
+	id: string;
+	product_id: string;
+	quantity: number;
+	/** Best-before date, `YYYY-MM-DD`; null for goods that do not expire. */
+	expires_on: string | null;
+	created_at: string;
+}
+
+export interface AddStockBatchPayload {
+	product_id: string;
+	quantity: number;
+	expires_on?: string | null;
+}
+
+/** What is left of a batch close to or past its date, oldest batches being sold first. */
+export interface ExpiringBatch {
+	batch_id: string;
+	product_id: string;
+	product_name: string;
+	unit: QuantityUnit;
+	expires_on: string;
+	remaining: number;
+	/** Negative once the date has passed. */
+	days_left: number;
+}
+
+/** Something products are made of, e.g. flour or eggs for a crêpe. */
+export interface Ingredient {
+	id: string;
+	name: string;
+	unit: QuantityUnit;
+	/** Null disables the low-stock alert. */
+	low_stock_threshold: number | null;
+	stock: number;
+	low_stock: boolean;
+}
+
+export interface CreateIngredientPayload {
+	name: string;
+	unit?: QuantityUnit;
+	low_stock_threshold?: number | null;
+}
+
+export interface UpdateIngredientPayload {
+	id: string;
+	name: string;
+	unit: QuantityUnit;
+	low_stock_threshold?: number | null;
+}
+
+/** Quantity of an ingredient used per unit (piece, kg, litre) of a product. */
+export interface RecipeItem {
+	ingredient_id: string;
+	quantity: number;
+}
+
+export interface ReorderSettings {
+	/** Calendar days of past sales the usage is measured over. */
+	lookback_days: number;
+	/** Sales days the stock should last. */
+	cover_days: number;
+}
+
+/** A product or ingredient whose stock will not cover the next sales days. */
+export interface ReorderSuggestion {
+	id: string;
+	name: string;
+	ingredient: boolean;
+	unit: QuantityUnit;
+	stock: number;
+	daily_usage: number;
+	suggested_quantity: number;
+}
+
+/** Money spent for the stand, e.g. supplies or a pitch rental. */
+export interface Expense {
+	id: string;
+	/** Local date of the expense (YYYY-MM-DD). */
+	spent_on: string;
+	/** Cents, always positive. */
+	amount: number;
+	category: string;
+	note: string;
+	/** Photo or scan of the receipt. */
+	receipt_path: string | null;
+	created_at: string;
+}
+
+export interface CreateExpensePayload {
+	spent_on: string;
+	amount: number;
+	category: string;
+	note?: string;
+	receipt_path?: string | null;
+}
+
+export interface UpdateExpensePayload extends CreateExpensePayload {
+	id: string;
+}
+
+export interface ExpenseCategoryTotal {
+	category: string;
+	amount: number;
+}
+
+/** Revenue minus expenses over a date range, in cents. */
+export interface NetResult {
+	revenue: number;
+	expenses: number;
+	net: number;
+	/** Sorted by decreasing amount. */
+	per_category: ExpenseCategoryTotal[];
+}
+
+/** An image or PDF, e.g. a receipt photo or a supplier invoice. */
+export interface Attachment {
+	id: string;
+	expense_id: string | null;
+	/** Name of the file that was attached. */
+	file_name: string;
+	content_type: string;
+	/** Bytes. */
+	size: number;
+	/** Absolute path of the copy kept in the app data dir. */
+	path: string;
+	created_at: string;
+}
+
+export interface AddAttachmentPayload {
+	/** File to copy into the attachments folder. */
+	source_path: string;
+	expense_id?: string | null;
+}
+
+/** Schema migrations applied, or in a dry run to be applied, to a database. */
+export interface MigrationReport {
+	from_version: number;
+	to_version: number;
+	migrations: string[];
+	/** Copy of the database taken before migrating it, if any. */
+	backup_path: string | null;
+}
+
+/** Optional filters and pagination of `list_products`. */
+export interface ProductFilter {
+	category_id?: string | null;
+	available?: boolean | null;
+	/** Case-insensitive part of the name, or the exact barcode. */
+	search?: string | null;
+	/** Maximum number of products to return; all products when absent. */
+	limit?: number | null;
+	/** Number of products to skip, in list order. */
+	offset?: number | null;
+	/** Date (YYYY-MM-DD) the products must be offered on; the sales screen uses today. */
+	available_on?: string | null;
+}
+
+/** An order of a `DataDump`, with the chain link needed to restore it. */
+export interface DumpedOrder extends OrderWithItems {
+	previous_hash: string | null;
+}
+
+/** Complete copy of the data written by `export_all_data` (format `pos-dump`). */
+export interface DataDump {
+	format: 'pos-dump';
+	version: number;
+	app_version: string;
+	schema_version: number;
+	generated_at: string;
+	categories: Category[];
+	products: Product[];
+	orders: DumpedOrder[];
+	/** Every setting except credentials. */
+	settings: Record<string, string>;
+}
+
+/**
+ * What `import_all_data` does with the records of a dump: `skip` only adds
+ * missing records, `merge` also replaces existing ones and `overwrite` also
+ * deletes the records missing from the dump. Orders are only ever added.
+ */
+export type ImportStrategy = 'skip' | 'merge' | 'overwrite';
+
+/** What happened to the records of one entity type during an import. */
+export interface ImportCounts {
+	inserted: number;
+	updated: number;
+	skipped: number;
+	deleted: number;
+}
+
+/** Result of `import_all_data`. */
+export interface ImportReport {
+	categories: ImportCounts;
+	products: ImportCounts;
+	orders: ImportCounts;
+	settings: ImportCounts;
+	warnings: string[];
+}
+
+/** Today's figures so far, cheap enough to be polled by a header widget. */
+export interface TodayStats {
+	total_revenue: number;
+	order_count: number;
+	/** `created_at` of the last order of the day, if any. */
+	last_order_at: string | null;
+	cash_revenue: number;
+	card_revenue: number;
+}
+
+/** Revenue target of a fund-raising event; disabled while `target` is 0. */
+export interface RevenueGoal {
+	/** Cents to raise. */
+	target: number;
+	/** First and last day of the event, `YYYY-MM-DD`. */
+	from: string;
+	to: string;
+}
+
+export interface GoalProgress {
+	target: number;
+	achieved: number;
+	/** Revenue at the end of the event if sales keep their pace. */
+	projected: number;
+}
+
+/** Payload of the `sales-tick` event, sent after orders are recorded. */
+export interface SalesTick {
+	/** Revenue of the day so far, in cents. */
+	daily_total: number;
+	order_count: number;
+	/** Total of the orders just recorded; a split counts as one. */
+	last_order_total: number;
+}
+
+/** Revenue and order count of the orders carrying a tag over a date range. */
+export interface TagSales {
+	tag: string;
+	total_revenue: number;
+	order_count: number;
+}
+
+/** The file exports a report preset can run. */
+export type ReportKind = 'dashboard' | 'fec' | 'ledger_csv' | 'journal';
+
+/** How the date range of a preset is computed, relative to the day it runs. */
+export type DateRangeMode =
+	| { mode: 'today' | 'yesterday' | 'this_week' | 'last_week' | 'this_month' | 'last_month' }
+	| { mode: 'last_days'; days: number }
+	| { mode: 'fixed'; from: string | null; to: string | null };
+
+/** Everything a preset runs, apart from its name. */
+export interface ReportPresetConfig {
+	report: ReportKind;
+	range: DateRangeMode;
+	/** Format of the dashboard export; CSV when absent. */
+	format?: 'csv' | 'xlsx' | null;
+	/** Per-product options of the dashboard export. */
+	products?: { sort_by?: 'revenue' | 'quantity'; limit?: number | null };
+}
+
+/** A named report configuration, e.g. "weekly treasurer export". */
+export interface ReportPreset extends ReportPresetConfig {
+	id: string;
+	name: string;
+}
+
+/** A parameter of a custom report, given as text (`YYYY-MM-DD` for dates). */
+export interface CustomReportParam {
+	name: string;
+	kind: 'date' | 'text' | 'integer';
+	required: boolean;
+}
+
+/** One of the curated report queries `run_custom_report` can run. */
+export interface CustomReport {
+	id: string;
+	name: string;
+	description: string;
+	params: CustomReportParam[];
+}
+
+export type ScheduledJobKind =
+	| 'backup'
+	| 'report_email'
+	| 'stock_snapshot'
+	| 'expiry_alert'
+	| 'summary_notification';
+
+/** A recurring job, run once a day at `time` while enabled. */
+export interface ScheduledJob {
+	kind: ScheduledJobKind;
+	enabled: boolean;
+	/** Local time of day, `HH:MM`. */
+	time: string;
+	/** Who receives the report emails; unused by the other jobs. */
+	recipients: string[];
+	last_run_at: string | null;
+	/** Why the last run failed; null when it succeeded or never ran. */
+	last_error: string | null;
+}
+
+/** How long orders must stay in the database before they can be purged. */
+export interface RetentionSettings {
+	days: number;
+}
+
+/** Outcome of `purge_old_data`. */
+export interface PurgeReport {
+	archive_path: string;
+	deleted_orders: number;
+	/** First old order kept because it is invoiced or refunded by a newer order. */
+	kept_from_order_id: string | null;
+}
+
+/** Disk usage of the database, also sent with the `storage-warning` event. */
+export interface StorageStatus {
+	database_bytes: number;
+	wal_bytes: number;
+	free_disk_bytes: number | null;
+	warnings: string[];
+}
+
+/** A row pointing to data that does not exist, e.g. the item of a deleted order. */
+export interface DatabaseIssue {
+	table: string;
+	row_id: number;
+	problem: string;
+	/** How `repair_database` fixes it; null for orders and invoices, never changed. */
+	fix: string | null;
+}
+
+export interface RepairReport {
+	issues: DatabaseIssue[];
+	/** Whether the fixes were applied, or only reported. */
+	repaired: boolean;
+}
+
+/** Result of `verify_database_schema`. */
+export interface SchemaVerification {
+	/** Whether nothing was found. */
+	valid: boolean;
+	/** Messages of SQLite's integrity check, such as "CHECK constraint failed in products". */
+	problems: string[];
+	issues: DatabaseIssue[];
+}
+
+/** State of the installation, for support and the settings page. */
+export interface HealthReport {
+	schema_version: number;
+	storage: StorageStatus;
+	/** Why the scheduler last failed to run the due jobs, if it still does. */
+	scheduler_error: string | null;
+}
+
+/** Configuration of a till written by `export_settings`. */
+export interface SettingsExport {
+	format: 'pos-settings';
+	version: number;
+	app_version: string;
+	generated_at: string;
+	settings: Record<string, string>;
+	report_presets: ReportPreset[];
+	scheduled_jobs: ScheduledJob[];
+	features: FeatureFlag[];
+	surcharge_rules: SurchargeRule[];
+	station_routes: StationRoute[];
+	palette_colors: PaletteColor[];
+}
+
+/** Optional subsystems; the commands of a disabled feature fail. */
+export type Feature = 'stock' | 'printing';
+
+export interface FeatureFlag {
+	feature: Feature;
+	enabled: boolean;
+}
+
+/** Opt-in anonymous usage metrics; nothing is recorded while disabled. */
+export interface TelemetrySettings {
+	enabled: boolean;
+	endpoint: string;
+}
+
+/** What `upload_usage_report` sends: no names, amounts or identifiers. */
+export interface UsageReport {
+	app_version: string;
+	os: string;
+	arch: string;
+	catalog_size: string;
+	commands: Record<string, number>;
+}
+
+/**
+ * How order totals are rounded. With `order_total`, the difference is added
+ * as a "Rounding" line so that product figures add up to the total.
+ */
+export type RoundingMode = 'none' | 'per_line' | 'order_total';
+
+export interface RoundingSettings {
+	mode: RoundingMode;
+	/** Cents amounts are rounded to, e.g. 5 or 10. */
+	increment: number;
+}
+
+/** Sanity limits of new orders; 0 disables a limit. */
+export interface OrderLimits {
+	/** Cents the product lines may add up to, before surcharges and rounding. */
+	max_total: number;
+	max_quantity: number;
+}
+
+/** Amount added to the orders paid by a payment method, itemized as a line. */
+export interface SurchargeRule {
+	payment_method: 'cash' | 'card';
+	amount: number;
+	/** Only orders below this total (cents) are charged; all when null. */
+	below: number | null;
+}
+
+/** Sends the preparation tickets of a category and its subcategories to a station. */
+export interface StationRoute {
+	category_id: string;
+	station: string;
+}
+
+export interface StationTicket {
+	station: string;
+	path: string;
+}
@@ -1,6 +1,33 @@
+import type { QuantityUnit } from '$lib/types';
+
 /** Converts integer cents to a formatted euro string, e.g. 150 → "1,50 €". */
 export function formatPrice(cents: number): string {
 	const euros = Math.floor(cents / 100);
 	const remainder = Math.abs(cents % 100);
 	return `${euros},${remainder.toString().padStart(2, '0')} €`;
 }
+
+/** Formats a price with the unit it applies to, e.g. 1890 per kg → "18,90 € / kg". */
+export function formatUnitPrice(cents: number, unit: QuantityUnit): string {
+	switch (unit) {
+		case 'kg':
+			return `${formatPrice(cents)} / kg`;
+		case 'litre':
+			return `${formatPrice(cents)} / L`;
+		default:
+			return formatPrice(cents);
+	}
+}
+
+/** Formats a quantity with its unit, e.g. 2 → "2", 0.35 kg → "0,35 kg". */
+export function formatQuantity(quantity: number, unit: QuantityUnit): string {
+	const value = String(quantity).replace('.', ',');
+	switch (unit) {
+		case 'kg':
+			return `${value} kg`;
+		case 'litre':
+			return `${value} L`;
+		default:
+			return value;
+	}
+}